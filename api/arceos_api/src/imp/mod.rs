@@ -33,10 +33,79 @@ mod stdio {
     }
 }
 
+#[cfg(feature = "smp")]
+mod cpu {
+    use axerrno::{ax_err, AxResult};
+
+    #[cfg(feature = "multitask")]
+    pub fn ax_cpu_offline(cpu_id: usize) -> AxResult {
+        if !axhal::power::cpu_hotplug_supported() {
+            ax_err!(Unsupported)
+        } else if axtask::cpu_offline(cpu_id) {
+            Ok(())
+        } else {
+            ax_err!(InvalidInput)
+        }
+    }
+
+    #[cfg(feature = "multitask")]
+    pub fn ax_cpu_online(cpu_id: usize) -> AxResult {
+        if !axhal::power::cpu_hotplug_supported() {
+            ax_err!(Unsupported)
+        } else if axruntime::cpu_online(cpu_id) {
+            Ok(())
+        } else {
+            ax_err!(InvalidInput)
+        }
+    }
+
+    #[cfg(not(feature = "multitask"))]
+    pub fn ax_cpu_offline(_cpu_id: usize) -> AxResult {
+        ax_err!(Unsupported)
+    }
+
+    #[cfg(not(feature = "multitask"))]
+    pub fn ax_cpu_online(_cpu_id: usize) -> AxResult {
+        ax_err!(Unsupported)
+    }
+}
+
+#[cfg(feature = "profiler")]
+mod profiler {
+    use axerrno::{ax_err, AxResult};
+    use axhal::pmu::PmuError;
+    use core::fmt;
+
+    pub fn ax_profiler_start(period: u64, max_samples: usize) -> AxResult {
+        match axruntime::profiler::start(period, max_samples) {
+            Ok(()) => Ok(()),
+            Err(PmuError::InvalidPeriod) => ax_err!(InvalidInput),
+            Err(_) => ax_err!(Unsupported),
+        }
+    }
+
+    pub fn ax_profiler_stop() {
+        axruntime::profiler::stop()
+    }
+
+    pub fn ax_profiler_report(mut w: &mut dyn fmt::Write, folded: bool) -> fmt::Result {
+        if folded {
+            axruntime::profiler::report_folded(&mut w)
+        } else {
+            axruntime::profiler::report(&mut w)
+        }
+    }
+}
+
+#[cfg(feature = "smp")]
+pub use self::cpu::*;
 pub use self::mem::*;
+#[cfg(feature = "profiler")]
+pub use self::profiler::*;
 pub use self::stdio::*;
 pub use self::task::*;
 
 pub use axhal::misc::terminate as ax_terminate;
+pub use axhal::power::reboot as ax_reboot;
 pub use axhal::time::{current_time as ax_current_time, TimeValue as AxTimeValue};
 pub use axio::PollState as AxPollState;
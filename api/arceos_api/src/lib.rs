@@ -33,6 +33,38 @@ pub mod sys {
     define_api! {
         /// Shutdown the whole system and all CPUs.
         pub fn ax_terminate() -> !;
+        /// Reboots the whole system and all CPUs.
+        pub fn ax_reboot() -> !;
+    }
+
+    define_api! {
+        @cfg "smp";
+        /// Takes the given CPU offline, and waits until it is powered off.
+        ///
+        /// The tasks running on the CPU are migrated to other CPUs. The
+        /// primary CPU cannot be taken offline. Returns `Unsupported` on the
+        /// platforms that cannot power off CPUs, such as the ones booting
+        /// secondary CPUs from a spin table.
+        pub fn ax_cpu_offline(cpu_id: usize) -> crate::AxResult;
+        /// Brings the given CPU online again, after it is taken offline by
+        /// [`ax_cpu_offline`].
+        pub fn ax_cpu_online(cpu_id: usize) -> crate::AxResult;
+    }
+
+    define_api! {
+        @cfg "profiler";
+        /// Starts the sampling profiler on all CPUs, which samples the running
+        /// code every `period` CPU cycles, and keeps at most `max_samples`
+        /// samples.
+        pub fn ax_profiler_start(period: u64, max_samples: usize) -> crate::AxResult;
+        /// Stops the sampling profiler.
+        pub fn ax_profiler_stop();
+        /// Writes the samples of the profiler to `w`, as PC histograms per task,
+        /// or as folded stacks for flamegraphs if `folded` is true.
+        pub fn ax_profiler_report(
+            w: &mut dyn core::fmt::Write,
+            folded: bool,
+        ) -> core::fmt::Result;
     }
 }
 
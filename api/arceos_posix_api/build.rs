@@ -67,6 +67,7 @@ typedef struct {{
             "RLIMIT_.*",
             "EAI_.*",
             "MAXADDRS",
+            "CLOCK_.*",
         ];
 
         #[derive(Debug)]
@@ -1,5 +1,6 @@
 use alloc::sync::Arc;
 use core::ffi::{c_char, c_int};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use axerrno::{LinuxError, LinuxResult};
 use axfs::fops::OpenOptions;
@@ -11,12 +12,14 @@ use crate::{ctypes, utils::char_ptr_to_str};
 
 pub struct File {
     inner: Mutex<axfs::fops::File>,
+    nonblocking: AtomicBool,
 }
 
 impl File {
     fn new(inner: axfs::fops::File) -> Self {
         Self {
             inner: Mutex::new(inner),
+            nonblocking: AtomicBool::new(false),
         }
     }
 
@@ -34,7 +37,18 @@ impl File {
 
 impl FileLike for File {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
-        Ok(self.inner.lock().read(buf)?)
+        loop {
+            // Taken before the read, so that data queued in between is not missed.
+            let seq = axfs::fops::ready_seq();
+            // Device nodes (e.g. `/dev/input/*`) return `WouldBlock` when empty.
+            let res = self.inner.lock().read(buf);
+            match res {
+                Err(axerrno::AxError::WouldBlock) if !self.nonblocking.load(Ordering::Acquire) => {
+                    axfs::fops::wait_readable(seq);
+                }
+                res => return Ok(res?),
+            }
+        }
     }
 
     fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
@@ -64,13 +78,11 @@ impl FileLike for File {
     }
 
     fn poll(&self) -> LinuxResult<PollState> {
-        Ok(PollState {
-            readable: true,
-            writable: true,
-        })
+        Ok(self.inner.lock().poll()?)
     }
 
-    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
         Ok(())
     }
 }
@@ -111,8 +123,9 @@ pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) ->
     debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
     syscall_body!(sys_open, {
         let options = flags_to_options(flags, mode);
-        let file = axfs::fops::File::open(filename?, &options)?;
-        File::new(file).add_to_fd_table()
+        let file = File::new(axfs::fops::File::open(filename?, &options)?);
+        file.set_nonblocking(flags as u32 & ctypes::O_NONBLOCK != 0)?;
+        file.add_to_fd_table()
     })
 }
 
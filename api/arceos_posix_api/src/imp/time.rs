@@ -34,19 +34,50 @@ impl From<Duration> for ctypes::timeval {
     }
 }
 
-/// Get clock time since booting
-pub unsafe fn sys_clock_gettime(_clk: ctypes::clockid_t, ts: *mut ctypes::timespec) -> c_int {
+/// Get clock time
+///
+/// `CLOCK_REALTIME` is the wall-clock time since the Unix epoch. The other
+/// clocks, such as `CLOCK_MONOTONIC`, give the time since booting.
+pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespec) -> c_int {
     syscall_body!(sys_clock_gettime, {
         if ts.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let now = axhal::time::current_time().into();
+        let now = match clk as u32 {
+            ctypes::CLOCK_REALTIME => axhal::time::wall_time(),
+            _ => axhal::time::current_time(),
+        };
+        let now = now.into();
         unsafe { *ts = now };
         debug!("sys_clock_gettime: {}.{:09}s", now.tv_sec, now.tv_nsec);
         Ok(0)
     })
 }
 
+/// Set clock time
+///
+/// Only `CLOCK_REALTIME` can be set, which also updates the RTC.
+pub unsafe fn sys_clock_settime(clk: ctypes::clockid_t, ts: *const ctypes::timespec) -> c_int {
+    syscall_body!(sys_clock_settime, {
+        if ts.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let ts = unsafe { *ts };
+        debug!(
+            "sys_clock_settime <= {} {}.{:09}s",
+            clk, ts.tv_sec, ts.tv_nsec
+        );
+        if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+            return Err(LinuxError::EINVAL);
+        }
+        match clk as u32 {
+            ctypes::CLOCK_REALTIME => axhal::time::set_wall_time(ts.into()),
+            _ => return Err(LinuxError::EINVAL),
+        }
+        Ok(0)
+    })
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno
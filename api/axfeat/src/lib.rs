@@ -7,6 +7,8 @@
 //!     - `fp_simd`: Enable floating point and SIMD support.
 //! - Interrupts:
 //!     - `irq`: Enable interrupt handling support.
+//! - Time:
+//!     - `rtc`: Read the wall-clock time from the real-time clock.
 //! - Memory
 //!     - `alloc`: Enable dynamic memory allocation.
 //!     - `alloc-tlsf`: Use the TLSF allocator.
@@ -34,6 +36,10 @@
 //!     - `log-level-off`: Disable all logging.
 //!     - `log-level-error`, `log-level-warn`, `log-level-info`, `log-level-debug`,
 //!       `log-level-trace`: Keep logging only at the specified level or higher.
+//! - Debugging
+//!     - `backtrace`: Print a symbolized stack backtrace on panic.
+//!     - `profiler`: Enable the sampling profiler based on the hardware
+//!       performance counters.
 //!
 //! [ArceOS]: https://github.com/rcore-os/arceos
 
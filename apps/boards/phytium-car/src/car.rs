@@ -0,0 +1,60 @@
+//! Wheels of the four-wheel mecanum car, on top of the PCA9685 PWM controller.
+//!
+//! Each wheel has an L298N-style H-bridge: two PCA9685 channels select the
+//! direction and a third one sets the speed.
+
+use core::time::Duration;
+
+use axhal::time::busy_wait;
+use driver_i2c::{I2cBus, I2cConfig, I2cError};
+use driver_pca9685::{DcMotor, Error, Pca9685};
+use embedded_hal::delay::DelayNs;
+
+/// MIO controller the PCA9685 is wired to.
+const PCA9685_MIO: usize = 1;
+/// Address of the PCA9685 on the car, with the A5 pin high.
+const PCA9685_ADDRESS: u8 = 0x60;
+/// PWM frequency of the motor bridges.
+const PWM_FREQUENCY_HZ: u32 = 50;
+
+/// Front-left, front-right, rear-left and rear-right wheels, with their
+/// direction and enable channels.
+const WHEELS: [DcMotor; 4] = [
+    DcMotor::new(2, 1).with_enable(0),
+    DcMotor::new(4, 3).with_enable(5),
+    DcMotor::new(8, 7).with_enable(6),
+    DcMotor::new(10, 9).with_enable(11),
+];
+
+/// Delays of the PCA9685 driver, by busy waiting.
+pub struct BusyDelay;
+
+impl DelayNs for BusyDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        busy_wait(Duration::from_nanos(ns as u64));
+    }
+}
+
+/// The car, owning its motor controller.
+pub struct Car {
+    pca: Pca9685<I2cBus, BusyDelay>,
+}
+
+impl Car {
+    /// Initializes the motor controller, with all wheels stopped.
+    pub fn new() -> Result<Self, Error<I2cError>> {
+        let bus = I2cBus::new(PCA9685_MIO, I2cConfig::default()).map_err(Error::I2c)?;
+        let mut pca = Pca9685::new(bus, BusyDelay, PCA9685_ADDRESS);
+        pca.init(PWM_FREQUENCY_HZ)?;
+        Ok(Self { pca })
+    }
+
+    /// Sets the signed duty of the front-left, front-right, rear-left and
+    /// rear-right wheels, up to [`DcMotor::MAX_SPEED`].
+    pub fn set_wheels(&mut self, duties: [i16; 4]) -> Result<(), Error<I2cError>> {
+        for (wheel, duty) in WHEELS.iter().zip(duties) {
+            wheel.set_speed(&mut self.pca, duty)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+//! Control laws of the car, free of the hardware and the OS.
+//!
+//! Commands are arbitrated by [`State`], velocities are mixed into wheel
+//! speeds by [`Velocity::wheel_speeds`], and each wheel follows its speed
+//! through [`WheelLoop`]: a ramp bounding the acceleration, and a PI loop on
+//! the measured speed when the wheels have encoders.
+//!
+//! Speeds are fractions of full speed, i.e., the speed of an unloaded wheel
+//! at the full duty, and duties are fractions of the full duty.
+
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+/// Velocity of the car, each component in `-1.0..=1.0` of full speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Velocity {
+    /// Forward speed.
+    pub vx: f32,
+    /// Speed to the left.
+    pub vy: f32,
+    /// Counter-clockwise rotation speed.
+    pub omega: f32,
+}
+
+impl Velocity {
+    /// Standing still.
+    pub const ZERO: Self = Self::new(0.0, 0.0, 0.0);
+
+    pub const fn new(vx: f32, vy: f32, omega: f32) -> Self {
+        Self { vx, vy, omega }
+    }
+
+    /// Speeds of the front-left, front-right, rear-left and rear-right wheels,
+    /// scaled down together if any exceeds full speed.
+    pub fn wheel_speeds(&self) -> [f32; 4] {
+        let Self { vx, vy, omega } = *self;
+        let speeds = [
+            vx - vy - omega,
+            vx + vy + omega,
+            vx + vy - omega,
+            vx - vy + omega,
+        ];
+        let max = speeds.iter().fold(1.0f32, |max, s| max.max(s.abs()));
+        speeds.map(|s| s / max)
+    }
+}
+
+/// A velocity held for a fixed time.
+#[derive(Debug, Clone, Copy)]
+pub struct Maneuver {
+    pub velocity: Velocity,
+    pub duration: Duration,
+}
+
+impl Maneuver {
+    pub const fn new(velocity: Velocity, duration: Duration) -> Self {
+        Self { velocity, duration }
+    }
+}
+
+/// Pending commands. Times are since boot.
+#[derive(Default)]
+pub struct State {
+    /// Streamed velocity, and when it was last refreshed.
+    pub streamed: Option<(Velocity, Duration)>,
+    pub queue: VecDeque<Maneuver>,
+    /// Maneuver in progress, and when it started.
+    pub running: Option<(Maneuver, Duration)>,
+    /// Cut the motors instead of ramping down.
+    pub halt: bool,
+}
+
+impl State {
+    /// Returns the velocity to reach at `now`, and whether to cut the motors.
+    ///
+    /// A streamed velocity not refreshed for `watchdog` is dropped, and cuts
+    /// the motors once.
+    pub fn poll(&mut self, now: Duration, watchdog: Duration) -> (Velocity, bool) {
+        loop {
+            if let Some((m, start)) = self.running {
+                if now.saturating_sub(start) < m.duration {
+                    return (m.velocity, false);
+                }
+                self.running = None;
+            }
+            match self.queue.pop_front() {
+                Some(m) => self.running = Some((m, now)),
+                None => break,
+            }
+        }
+        if let Some((velocity, fed)) = self.streamed {
+            if now.saturating_sub(fed) < watchdog {
+                return (velocity, false);
+            }
+            self.streamed = None;
+            self.halt = true;
+        }
+        (Velocity::ZERO, core::mem::take(&mut self.halt))
+    }
+}
+
+/// Gains of the PI loop of each wheel, on speed errors in fractions of full
+/// speed.
+#[derive(Debug, Clone, Copy)]
+pub struct Gains {
+    /// Duty added per unit of speed error.
+    pub kp: f32,
+    /// Duty added per unit of speed error and second.
+    pub ki: f32,
+}
+
+/// Speed control of the four wheels.
+pub struct WheelLoop {
+    /// Largest change of a setpoint per second.
+    accel: f32,
+    gains: Gains,
+    setpoints: [f32; 4],
+    integrals: [f32; 4],
+}
+
+impl WheelLoop {
+    /// A loop with the wheels stopped.
+    pub const fn new(accel: f32, gains: Gains) -> Self {
+        Self {
+            accel,
+            gains,
+            setpoints: [0.0; 4],
+            integrals: [0.0; 4],
+        }
+    }
+
+    /// Advances the loop by `dt` seconds towards the wheel speeds `goal`, and
+    /// returns the wheel duties.
+    ///
+    /// Setpoints ramp towards `goal`, or drop to zero at once if `halt`. With
+    /// the `measured` wheel speeds, each duty is the setpoint corrected by the
+    /// PI loop. Otherwise, it is the setpoint itself (open loop).
+    pub fn update(
+        &mut self,
+        goal: [f32; 4],
+        halt: bool,
+        measured: Option<[f32; 4]>,
+        dt: f32,
+    ) -> [f32; 4] {
+        let step = self.accel * dt;
+        let mut duties = [0.0; 4];
+        for i in 0..4 {
+            let setpoint = &mut self.setpoints[i];
+            *setpoint = if halt {
+                0.0
+            } else {
+                *setpoint + (goal[i] - *setpoint).clamp(-step, step)
+            };
+            duties[i] = match measured {
+                // A stopped wheel is not driven, whatever it measures.
+                Some(measured) if *setpoint != 0.0 => {
+                    let error = *setpoint - measured[i];
+                    let integral = self.integrals[i] + self.gains.ki * error * dt;
+                    self.integrals[i] = integral.clamp(-1.0, 1.0);
+                    *setpoint + self.gains.kp * error + self.integrals[i]
+                }
+                _ => {
+                    self.integrals[i] = 0.0;
+                    *setpoint
+                }
+            };
+        }
+        duties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATCHDOG: Duration = Duration::from_millis(500);
+    const GAINS: Gains = Gains { kp: 0.5, ki: 2.0 };
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_mecanum_mixing() {
+        let speeds = |vx, vy, omega| Velocity::new(vx, vy, omega).wheel_speeds();
+        assert_close(speeds(0.5, 0.0, 0.0), [0.5; 4]);
+        // Strafing left: the front-left and rear-right wheels turn backwards.
+        assert_close(speeds(0.0, 0.5, 0.0), [-0.5, 0.5, 0.5, -0.5]);
+        // Turning counter-clockwise: the left wheels turn backwards.
+        assert_close(speeds(0.0, 0.0, 0.5), [-0.5, 0.5, -0.5, 0.5]);
+        assert_close(speeds(0.5, 0.25, 0.0), [0.25, 0.75, 0.75, 0.25]);
+        assert_close(Velocity::ZERO.wheel_speeds(), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_mixing_saturation() {
+        // Full speed forward and left: scaled down by the fastest wheel (2.0).
+        let speeds = Velocity::new(1.0, 1.0, 0.0).wheel_speeds();
+        assert_close(speeds, [0.0, 1.0, 1.0, 0.0]);
+        let speeds = Velocity::new(1.0, 0.5, -1.0).wheel_speeds();
+        assert_close(speeds, [0.6, 0.2, 1.0, -0.2]);
+        // Speeds within full speed are not scaled up.
+        assert_close(
+            Velocity::new(0.1, 0.1, 0.1).wheel_speeds(),
+            [-0.1, 0.3, 0.1, 0.1],
+        );
+        for v in [Velocity::new(-1.0, 1.0, 1.0), Velocity::new(1.0, -1.0, 1.0)] {
+            assert!(v.wheel_speeds().iter().all(|s| s.abs() <= 1.0));
+        }
+    }
+
+    #[test]
+    fn test_watchdog_stop() {
+        let forward = Velocity::new(1.0, 0.0, 0.0);
+        let mut state = State {
+            streamed: Some((forward, ms(1000))),
+            ..State::default()
+        };
+        assert_eq!(state.poll(ms(1499), WATCHDOG), (forward, false));
+        // The motors are cut once when the watchdog expires.
+        assert_eq!(state.poll(ms(1500), WATCHDOG), (Velocity::ZERO, true));
+        assert!(state.streamed.is_none());
+        assert_eq!(state.poll(ms(1520), WATCHDOG), (Velocity::ZERO, false));
+    }
+
+    #[test]
+    fn test_maneuver_queue() {
+        let forward = Velocity::new(1.0, 0.0, 0.0);
+        let left = Velocity::new(0.0, 1.0, 0.0);
+        let mut state = State::default();
+        state.queue.push_back(Maneuver::new(forward, ms(100)));
+        state.queue.push_back(Maneuver::new(left, ms(50)));
+        assert_eq!(state.poll(ms(0), WATCHDOG), (forward, false));
+        assert_eq!(state.poll(ms(99), WATCHDOG), (forward, false));
+        assert_eq!(state.poll(ms(100), WATCHDOG), (left, false));
+        // Maneuvers are not subject to the watchdog.
+        assert_eq!(state.poll(ms(149), WATCHDOG), (left, false));
+        assert_eq!(state.poll(ms(150), WATCHDOG), (Velocity::ZERO, false));
+        assert!(state.running.is_none());
+    }
+
+    #[test]
+    fn test_ramp_and_halt() {
+        let mut wheels = WheelLoop::new(2.0, GAINS);
+        let goal = [1.0, -1.0, 0.1, 0.0];
+        assert_close(wheels.update(goal, false, None, 0.1), [0.2, -0.2, 0.1, 0.0]);
+        assert_close(wheels.update(goal, false, None, 0.1), [0.4, -0.4, 0.1, 0.0]);
+        // Ramping down is bounded as well.
+        assert_close(
+            wheels.update([0.0; 4], false, None, 0.1),
+            [0.2, -0.2, 0.0, 0.0],
+        );
+        assert_close(wheels.update(goal, true, None, 0.1), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_speed_feedback() {
+        let mut wheels = WheelLoop::new(10.0, GAINS);
+        let goal = [0.5; 4];
+        // The wheels are slower than their setpoints: the duties go up.
+        let duties = wheels.update(goal, false, Some([0.3, 0.5, 0.7, 0.5]), 0.1);
+        assert_close(duties, [0.64, 0.5, 0.36, 0.5]);
+        let duties = wheels.update(goal, false, Some([0.3, 0.5, 0.7, 0.5]), 0.1);
+        assert_close(duties, [0.68, 0.5, 0.32, 0.5]);
+        // The integral term is bounded, and dropped when the wheels stop.
+        for _ in 0..100 {
+            wheels.update(goal, false, Some([0.0; 4]), 0.1);
+        }
+        assert_close(wheels.update(goal, false, Some([0.0; 4]), 0.1), [1.75; 4]);
+        assert_close(wheels.update(goal, true, Some([0.5; 4]), 0.1), [0.0; 4]);
+        assert_close(wheels.update(goal, false, Some([0.5; 4]), 0.1), [0.5; 4]);
+    }
+}
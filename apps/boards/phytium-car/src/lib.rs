@@ -0,0 +1,7 @@
+//! Hardware-independent parts of the car application, tested on the host.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod control;
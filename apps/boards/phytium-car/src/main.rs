@@ -8,18 +8,23 @@ use core::time::Duration;
 
 use alloc::sync::Arc;
 use ax_event_bus::events::mouse::MouseEvent;
-use ax_event_bus::events::{EventData, EventHandler, Events};
+use ax_event_bus::events::{EventHandler, Propagation};
 use axalloc::GlobalNoCacheAllocator;
 use axhal::paging::PageSize;
 use axhal::{mem::VirtAddr, time::busy_wait};
-use driver_pca9685::{car_run_task, Quest};
 use driver_usb::abstractions::event::USBSystemEvent;
 use driver_usb::{USBSystem, USBSystemConfig};
 
+use self::car::Car;
+use self::motion::{MotionConfig, MotionController, Quest};
+
 extern crate alloc;
 #[macro_use]
 extern crate axstd as std;
 
+mod car;
+mod motion;
+
 #[derive(Clone)]
 struct PlatformAbstraction;
 
@@ -43,17 +48,14 @@ impl driver_usb::abstractions::OSAbstractions for PlatformAbstraction {
                 middle,
                 wheel,
             }) => {
-                ax_event_bus::post_event(
-                    Events::MouseEvent,
-                    EventData::MouseEvent(MouseEvent {
-                        dx,
-                        dy,
-                        left,
-                        right,
-                        middle,
-                        wheel,
-                    }),
-                );
+                ax_event_bus::post(MouseEvent {
+                    dx,
+                    dy,
+                    left,
+                    right,
+                    middle,
+                    wheel,
+                });
             }
             _ => {}
         };
@@ -64,55 +66,60 @@ impl driver_usb::abstractions::HALAbstractions for PlatformAbstraction {
     fn force_sync_cache() {}
 }
 
-struct MouseEventHandler;
+struct MouseEventHandler {
+    motion: MotionController,
+}
 
-impl EventHandler for MouseEventHandler {
-    fn handle(&self, event: &mut ax_event_bus::events::EventData) -> bool {
-        if let EventData::MouseEvent(data) = event {
-            let mut flag = false;
-            println!("{:?}", data);
-            match (&data.dx, &data.dy, &data.left) {
-                (x, y, _) if (-10..=10).contains(x) && (-10..=10).contains(y) => {
-                    car_run_task(Quest::Stop)
-                }
-                (x, y, _) if y.abs() > x.abs() => {
-                    // car_run_task(if *y < 0 { Quest::Advance } else { Quest::Back });
-                    if *y < 0 {
-                        car_run_task(Quest::Advance)
-                    } else {
-                        car_run_task(Quest::Back)
-                    };
+impl MouseEventHandler {
+    fn car_run_task(&self, quest: Quest) {
+        self.motion.set_velocity(quest.velocity());
+    }
+}
+
+impl EventHandler<MouseEvent> for MouseEventHandler {
+    fn handle(&self, data: &mut MouseEvent) -> Propagation {
+        let mut flag = false;
+        println!("{:?}", data);
+        match (&data.dx, &data.dy, &data.left) {
+            (x, y, _) if (-10..=10).contains(x) && (-10..=10).contains(y) => {
+                self.car_run_task(Quest::Stop)
+            }
+            (x, y, _) if y.abs() > x.abs() => {
+                // car_run_task(if *y < 0 { Quest::Advance } else { Quest::Back });
+                if *y < 0 {
+                    self.car_run_task(Quest::Advance)
+                } else {
+                    self.car_run_task(Quest::Back)
+                };
+            }
+            (x, y, false) if x.abs() > y.abs() => {
+                // car_run_task(
+                if *x > 0 {
+                    self.car_run_task(Quest::RotateLeft)
+                } else {
+                    // Quest::RotateLeft
+                    self.car_run_task(Quest::RotateRight)
                 }
-                (x, y, false) if x.abs() > y.abs() => {
-                    // car_run_task(
-                    if *x > 0 {
-                        car_run_task(Quest::RotateLeft)
+                // );
+            }
+            (x, y, true) if x.abs() > 10 && y.abs() > 10 => {
+                if *x > 0 {
+                    if *y > 0 {
+                        self.car_run_task(Quest::BackRight)
                     } else {
-                        // Quest::RotateLeft
-                        car_run_task(Quest::RotateRight)
+                        self.car_run_task(Quest::AdvanceRight)
                     }
-                    // );
-                }
-                (x, y, true) if x.abs() > 10 && y.abs() > 10 => {
-                    if *x > 0 {
-                        if *y > 0 {
-                            car_run_task(Quest::BackRight)
-                        } else {
-                            car_run_task(Quest::AdvanceRight)
-                        }
+                } else {
+                    if *y > 0 {
+                        self.car_run_task(Quest::BackLeft)
                     } else {
-                        if *y > 0 {
-                            car_run_task(Quest::BackLeft)
-                        } else {
-                            car_run_task(Quest::AdvanceLeft)
-                        }
+                        self.car_run_task(Quest::AdvanceLeft)
                     }
                 }
-                _ => {}
             }
-            return true;
+            _ => {}
         }
-        false
+        Propagation::Consume
     }
 }
 
@@ -125,12 +132,18 @@ fn main() {
     .init_probe();
     println!("usb initialized");
 
-    driver_pca9685::pca_init(2500, 2500, 2500, 2500);
+    let car = match Car::new() {
+        Ok(car) => car,
+        Err(e) => {
+            println!("car init failed: {}", e);
+            return;
+        }
+    };
     println!("i2c init completed");
 
-    let handler: Arc<dyn EventHandler> = Arc::new(MouseEventHandler);
-
-    ax_event_bus::register_handler(Events::MouseEvent, &handler);
+    // The car stops unless the mouse keeps moving.
+    let motion = MotionController::spawn(car, MotionConfig::default());
+    let _subscription = ax_event_bus::subscribe(MouseEventHandler { motion });
     println!("handler registered");
 
     usbsystem.drive_all();
@@ -0,0 +1,211 @@
+//! Velocity control of the mecanum car.
+//!
+//! A [`MotionController`] owns the [`Car`] and drives it from a control
+//! thread. Commands are either a streamed velocity, which must be refreshed
+//! before the watchdog expires, or a queue of timed [`Maneuver`]s. Wheel speeds
+//! ramp towards their targets at a bounded acceleration, except on an explicit
+//! stop or a watchdog expiry, which cut the motors at once.
+//!
+//! Given [`WheelEncoders`], the loop is closed: each wheel duty is corrected by
+//! a PI loop on the measured wheel speed, so that the speed holds under load
+//! and battery drain. The stock car has no encoders, and its duties are
+//! proportional to the target speeds. The control laws are in
+//! [`phytium_car::control`].
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use axhal::time::current_time;
+use driver_pca9685::DcMotor;
+use phytium_car::control::{Gains, State, WheelLoop};
+pub use phytium_car::control::{Maneuver, Velocity};
+
+use crate::car::Car;
+
+/// Wheel encoders of the car.
+pub trait WheelEncoders: Send {
+    /// Speeds of the front-left, front-right, rear-left and rear-right wheels
+    /// in fractions of full speed, i.e., of the speed at
+    /// [`MotionConfig::max_duty`] without load. Returns `None` if they cannot
+    /// be measured, and the duties are not corrected then.
+    fn wheel_speeds(&mut self) -> Option<[f32; 4]>;
+}
+
+/// Canned movements of the car.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quest {
+    Stop,
+    Advance,
+    Back,
+    MoveLeft,
+    MoveRight,
+    TurnLeft,
+    TurnRight,
+    AdvanceLeft,
+    AdvanceRight,
+    BackLeft,
+    BackRight,
+    RotateLeft,
+    RotateRight,
+}
+
+impl Quest {
+    /// Velocity of the movement, at full speed.
+    pub const fn velocity(self) -> Velocity {
+        match self {
+            Self::Stop => Velocity::ZERO,
+            Self::Advance => Velocity::new(1.0, 0.0, 0.0),
+            Self::Back => Velocity::new(-1.0, 0.0, 0.0),
+            Self::MoveLeft => Velocity::new(0.0, 1.0, 0.0),
+            Self::MoveRight => Velocity::new(0.0, -1.0, 0.0),
+            Self::TurnLeft => Velocity::new(0.5, 0.0, 0.5),
+            Self::TurnRight => Velocity::new(0.5, 0.0, -0.5),
+            Self::AdvanceLeft => Velocity::new(0.5, 0.5, 0.0),
+            Self::AdvanceRight => Velocity::new(0.5, -0.5, 0.0),
+            Self::BackLeft => Velocity::new(-0.5, 0.5, 0.0),
+            Self::BackRight => Velocity::new(-0.5, -0.5, 0.0),
+            Self::RotateLeft => Velocity::new(0.0, 0.0, 1.0),
+            Self::RotateRight => Velocity::new(0.0, 0.0, -1.0),
+        }
+    }
+}
+
+/// Tuning of a [`MotionController`].
+#[derive(Debug, Clone, Copy)]
+pub struct MotionConfig {
+    /// Wheel duty at full speed, up to `DcMotor::MAX_SPEED`.
+    pub max_duty: i16,
+    /// Largest change of a wheel duty per second.
+    pub accel: u32,
+    /// Gains of the wheel speed loops, with [`WheelEncoders`].
+    pub gains: Gains,
+    /// Period of the control loop.
+    pub period: Duration,
+    /// The car stops when no velocity command comes for this long.
+    pub watchdog: Duration,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            max_duty: 2500,
+            accel: 10_000,
+            gains: Gains { kp: 0.5, ki: 2.0 },
+            period: Duration::from_millis(20),
+            watchdog: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Handle to the control thread of the car.
+#[derive(Clone)]
+pub struct MotionController {
+    state: Arc<Mutex<State>>,
+}
+
+impl MotionController {
+    /// Starts the control thread, which owns `car` from now on. The duties
+    /// are not corrected, as the car has no encoders.
+    pub fn spawn(car: Car, config: MotionConfig) -> Self {
+        Self::spawn_inner(car, None, config)
+    }
+
+    /// Starts the control thread, which owns `car` and its `encoders` from now
+    /// on, with the wheel speeds in closed loop.
+    pub fn spawn_with_encoders(
+        car: Car,
+        encoders: impl WheelEncoders + 'static,
+        config: MotionConfig,
+    ) -> Self {
+        Self::spawn_inner(car, Some(Box::new(encoders)), config)
+    }
+
+    fn spawn_inner(
+        car: Car,
+        encoders: Option<Box<dyn WheelEncoders>>,
+        config: MotionConfig,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let shared = state.clone();
+        thread::spawn(move || control_loop(car, encoders, config, shared));
+        Self { state }
+    }
+
+    /// Moves at `velocity` until the next command, or until the watchdog
+    /// expires. Cancels pending maneuvers.
+    pub fn set_velocity(&self, velocity: Velocity) {
+        let mut state = self.state.lock();
+        state.queue.clear();
+        state.running = None;
+        state.streamed = Some((velocity, current_time()));
+    }
+
+    /// Keeps the streamed velocity for another watchdog period.
+    pub fn feed(&self) {
+        if let Some((_, fed)) = self.state.lock().streamed.as_mut() {
+            *fed = current_time();
+        }
+    }
+
+    /// Queues `maneuver` after the pending ones. The car stops once the queue
+    /// is empty.
+    pub fn push(&self, maneuver: Maneuver) {
+        let mut state = self.state.lock();
+        state.streamed = None;
+        state.queue.push_back(maneuver);
+    }
+
+    /// Cancels all commands and cuts the motors.
+    pub fn stop(&self) {
+        let mut state = self.state.lock();
+        state.streamed = None;
+        state.queue.clear();
+        state.running = None;
+        state.halt = true;
+    }
+
+    /// Whether no maneuver is pending or running.
+    pub fn is_idle(&self) -> bool {
+        let state = self.state.lock();
+        state.running.is_none() && state.queue.is_empty()
+    }
+}
+
+fn control_loop(
+    mut car: Car,
+    mut encoders: Option<Box<dyn WheelEncoders>>,
+    config: MotionConfig,
+    state: Arc<Mutex<State>>,
+) {
+    let max_duty = config.max_duty as f32;
+    let dt = config.period.as_secs_f32();
+    let mut wheels = WheelLoop::new(config.accel as f32 / max_duty, config.gains);
+    let mut applied = [0i16; 4];
+    loop {
+        let (velocity, halt) = {
+            let mut state = state.lock();
+            let streaming = state.streamed.is_some();
+            let command = state.poll(current_time(), config.watchdog);
+            if streaming && state.streamed.is_none() {
+                println!("motion: no command for {:?}, stopping", config.watchdog);
+            }
+            command
+        };
+        let measured = encoders.as_mut().and_then(|e| e.wheel_speeds());
+        let duties = wheels
+            .update(velocity.wheel_speeds(), halt, measured, dt)
+            .map(|d| {
+                (d * max_duty).clamp(-DcMotor::MAX_SPEED as f32, DcMotor::MAX_SPEED as f32) as i16
+            });
+        if duties != applied {
+            match car.set_wheels(duties) {
+                Ok(()) => applied = duties,
+                Err(e) => println!("motion: cannot drive wheels: {}", e),
+            }
+        }
+        thread::sleep(config.period);
+    }
+}
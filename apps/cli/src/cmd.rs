@@ -18,11 +18,17 @@ type CmdHandler = fn(&str);
 const CMD_TABLE: &[(&str, CmdHandler)] = &[
     ("exit", do_exit),
     ("help", do_help),
+    ("reboot", do_reboot),
+    ("poweroff", do_poweroff),
     ("uname", do_uname),
     ("ldr", do_ldr),
     ("str", do_str),
     ("iic", do_iic),
     ("pca", do_pca),
+    ("i2cdetect", do_i2cdetect),
+    ("i2cget", do_i2cget),
+    ("i2cset", do_i2cset),
+    ("i2cdump", do_i2cdump),
     // ("test_xhci", test_xhci),
 ];
 
@@ -31,8 +37,321 @@ fn do_iic(_args: &str) {
 }
 
 fn do_pca(_args: &str) {
-    driver_pca9685::test_pca();
-    println!("this command has been deprecated because of change of code structure")
+    println!("this command has been deprecated because of change of code structure, use i2cset")
+}
+
+/// Largest transfer of the `i` (I2C block) mode, as in i2c-tools.
+const I2C_BLOCK_MAX: usize = 32;
+
+/// Parses a number the way i2c-tools does: hexadecimal with a `0x` prefix,
+/// decimal otherwise.
+fn parse_num(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_arg(cmd: &str, what: &str, arg: Option<&str>, max: u32) -> Option<u32> {
+    let Some(arg) = arg else {
+        println!("{}: missing {}", cmd, what);
+        return None;
+    };
+    match parse_num(arg) {
+        Some(n) if n <= max => Some(n),
+        _ => {
+            println!("{}: invalid {}: {}", cmd, what, arg);
+            None
+        }
+    }
+}
+
+/// Opens the bus and checks the chip address, from the first two arguments.
+fn open_i2c<'a>(
+    cmd: &str,
+    args: &mut impl Iterator<Item = &'a str>,
+) -> Option<(driver_i2c::I2cBus, u8)> {
+    let bus = parse_arg(cmd, "bus", args.next(), u32::MAX)? as usize;
+    let addr = parse_arg(cmd, "chip address", args.next(), 0x77)? as u8;
+    if addr < 0x03 {
+        println!("{}: invalid chip address: {:#04x}", cmd, addr);
+        return None;
+    }
+    match driver_i2c::I2cBus::new(bus, driver_i2c::I2cConfig::default()) {
+        Ok(i2c) => Some((i2c, addr)),
+        Err(e) => {
+            println!("{}: cannot open bus {}: {:?}", cmd, bus, e);
+            None
+        }
+    }
+}
+
+fn is_nack(e: &driver_i2c::I2cError) -> bool {
+    use embedded_hal::i2c::{Error, ErrorKind};
+    matches!(e.kind(), ErrorKind::NoAcknowledge(_))
+}
+
+fn do_i2cdetect(args: &str) {
+    let mut args = args.split_whitespace();
+    let Some(bus) = parse_arg("i2cdetect", "bus", args.next(), u32::MAX) else {
+        println!("usage: i2cdetect BUS [FIRST LAST]");
+        return;
+    };
+    let first = match args.next() {
+        Some(s) => match parse_arg("i2cdetect", "first address", Some(s), 0x7f) {
+            Some(n) => n as u8,
+            None => return,
+        },
+        None => 0x03,
+    };
+    let last = match args.next() {
+        Some(s) => match parse_arg("i2cdetect", "last address", Some(s), 0x7f) {
+            Some(n) => n as u8,
+            None => return,
+        },
+        None => 0x77,
+    };
+    let i2c = match driver_i2c::I2cBus::new(bus as usize, driver_i2c::I2cConfig::default()) {
+        Ok(i2c) => i2c,
+        Err(e) => {
+            println!("i2cdetect: cannot open bus {}: {:?}", bus, e);
+            return;
+        }
+    };
+
+    // Zero-length transfers are not supported by the controller, so devices
+    // are probed by reading one byte, like `i2cdetect -r`.
+    println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f");
+    for row in (0..0x80u8).step_by(16) {
+        print!("{:02x}:", row);
+        for addr in row..row + 16 {
+            if addr < first || addr > last {
+                print!("   ");
+                continue;
+            }
+            let mut byte = [0u8];
+            match i2c.read(addr, &mut byte) {
+                Ok(()) => print!(" {:02x}", addr),
+                Err(e) if is_nack(&e) => print!(" --"),
+                Err(_) => print!(" XX"),
+            }
+        }
+        println!();
+    }
+}
+
+/// Access size of `i2cget`, `i2cset` and `i2cdump`.
+#[derive(Clone, Copy, PartialEq)]
+enum I2cMode {
+    Byte,
+    Word,
+    Block,
+}
+
+impl I2cMode {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "b" => Some(Self::Byte),
+            "w" => Some(Self::Word),
+            "i" => Some(Self::Block),
+            _ => None,
+        }
+    }
+}
+
+fn do_i2cget(args: &str) {
+    const USAGE: &str = "usage: i2cget BUS CHIP [REG [MODE [LEN]]], MODE is b (default), w or i";
+    let mut args = args.split_whitespace();
+    let Some((i2c, addr)) = open_i2c("i2cget", &mut args) else {
+        println!("{}", USAGE);
+        return;
+    };
+    let reg = match args.next() {
+        Some(s) => match parse_arg("i2cget", "register", Some(s), 0xff) {
+            Some(n) => Some(n as u8),
+            None => return,
+        },
+        None => None,
+    };
+    let mode = match args.next().map(I2cMode::parse) {
+        None => I2cMode::Byte,
+        Some(Some(mode)) => mode,
+        Some(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+    let len = match (mode, args.next()) {
+        (I2cMode::Block, Some(s)) => {
+            match parse_arg("i2cget", "length", Some(s), I2C_BLOCK_MAX as u32) {
+                Some(n) if n > 0 => n as usize,
+                _ => return,
+            }
+        }
+        (I2cMode::Block, None) => I2C_BLOCK_MAX,
+        (I2cMode::Word, _) => 2,
+        (I2cMode::Byte, _) => 1,
+    };
+
+    let mut buf = [0u8; I2C_BLOCK_MAX];
+    let res = match reg {
+        Some(reg) => i2c.write_read(addr, &[reg], &mut buf[..len]),
+        None => i2c.read(addr, &mut buf[..len]),
+    };
+    if let Err(e) = res {
+        println!("i2cget: read failed: {:?}", e);
+        return;
+    }
+    match mode {
+        I2cMode::Byte => println!("0x{:02x}", buf[0]),
+        I2cMode::Word => println!("0x{:04x}", u16::from_le_bytes([buf[0], buf[1]])),
+        I2cMode::Block => {
+            for byte in &buf[..len] {
+                print!("0x{:02x} ", byte);
+            }
+            println!();
+        }
+    }
+}
+
+fn do_i2cset(args: &str) {
+    const USAGE: &str = "usage: i2cset BUS CHIP REG VALUE... [MODE], MODE is b (default), w or i";
+    let mut args = args.split_whitespace();
+    let Some((i2c, addr)) = open_i2c("i2cset", &mut args) else {
+        println!("{}", USAGE);
+        return;
+    };
+    let Some(reg) = parse_arg("i2cset", "register", args.next(), 0xff) else {
+        println!("{}", USAGE);
+        return;
+    };
+    // Values and the optional mode, without requiring an allocator.
+    let mut words = [""; I2C_BLOCK_MAX + 1];
+    let mut nwords = 0;
+    for arg in args {
+        if nwords == words.len() {
+            println!("{}", USAGE);
+            return;
+        }
+        words[nwords] = arg;
+        nwords += 1;
+    }
+    let (mode, values) = match words[..nwords].split_last() {
+        Some((last, rest)) if I2cMode::parse(last).is_some() => {
+            (I2cMode::parse(last).unwrap(), rest)
+        }
+        _ => (I2cMode::Byte, &words[..nwords]),
+    };
+    let count = match mode {
+        I2cMode::Block => 1..=I2C_BLOCK_MAX,
+        _ => 1..=1,
+    };
+    if !count.contains(&values.len()) {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let mut buf = [0u8; I2C_BLOCK_MAX + 1];
+    buf[0] = reg as u8;
+    let len = match mode {
+        I2cMode::Word => {
+            let Some(value) = parse_arg("i2cset", "value", Some(values[0]), 0xffff) else {
+                return;
+            };
+            buf[1..3].copy_from_slice(&(value as u16).to_le_bytes());
+            3
+        }
+        _ => {
+            for (dst, s) in buf[1..].iter_mut().zip(values) {
+                match parse_arg("i2cset", "value", Some(s), 0xff) {
+                    Some(value) => *dst = value as u8,
+                    None => return,
+                }
+            }
+            1 + values.len()
+        }
+    };
+    if let Err(e) = i2c.write(addr, &buf[..len]) {
+        println!("i2cset: write failed: {:?}", e);
+    }
+}
+
+fn do_i2cdump(args: &str) {
+    const USAGE: &str = "usage: i2cdump BUS CHIP [MODE [FIRST LAST]], MODE is b (default) or i";
+    let mut args = args.split_whitespace();
+    let Some((i2c, addr)) = open_i2c("i2cdump", &mut args) else {
+        println!("{}", USAGE);
+        return;
+    };
+    let mode = match args.next().map(I2cMode::parse) {
+        None => I2cMode::Byte,
+        Some(Some(mode)) if mode != I2cMode::Word => mode,
+        _ => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+    let first = match args.next() {
+        Some(s) => match parse_arg("i2cdump", "first register", Some(s), 0xff) {
+            Some(n) => n as usize,
+            None => return,
+        },
+        None => 0,
+    };
+    let last = match args.next() {
+        Some(s) => match parse_arg("i2cdump", "last register", Some(s), 0xff) {
+            Some(n) if n as usize >= first => n as usize,
+            _ => return,
+        },
+        None => 0xff,
+    };
+
+    // `None` marks registers that could not be read.
+    let mut regs = [None; 256];
+    let mut reg = first;
+    while reg <= last {
+        let len = match mode {
+            I2cMode::Block => (last + 1 - reg).min(I2C_BLOCK_MAX),
+            _ => 1,
+        };
+        let mut buf = [0u8; I2C_BLOCK_MAX];
+        match i2c.write_read(addr, &[reg as u8], &mut buf[..len]) {
+            Ok(()) => {
+                for (i, byte) in buf[..len].iter().enumerate() {
+                    regs[reg + i] = Some(*byte);
+                }
+            }
+            Err(e) if reg == first => {
+                println!("i2cdump: read failed: {:?}", e);
+                return;
+            }
+            Err(_) => {}
+        }
+        reg += len;
+    }
+
+    println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f    0123456789abcdef");
+    for row in (first & !0xf..=last).step_by(16) {
+        print!("{:02x}: ", row);
+        for reg in row..row + 16 {
+            match regs[reg] {
+                _ if reg < first || reg > last => print!("   "),
+                Some(byte) => print!("{:02x} ", byte),
+                None => print!("XX "),
+            }
+        }
+        print!("   ");
+        for reg in row..row + 16 {
+            let c = match regs[reg] {
+                _ if reg < first || reg > last => ' ',
+                Some(byte @ 0x20..=0x7e) => byte as char,
+                Some(_) => '.',
+                None => 'X',
+            };
+            print!("{}", c);
+        }
+        println!();
+    }
 }
 
 // fn test_xhci(_args: &str) {
@@ -76,6 +395,20 @@ fn do_exit(_args: &str) {
     std::process::exit(0);
 }
 
+fn do_reboot(_args: &str) {
+    #[cfg(feature = "axstd")]
+    std::os::arceos::api::sys::ax_reboot();
+    #[cfg(not(feature = "axstd"))]
+    println!("reboot: not supported");
+}
+
+fn do_poweroff(_args: &str) {
+    #[cfg(feature = "axstd")]
+    std::os::arceos::api::sys::ax_terminate();
+    #[cfg(not(feature = "axstd"))]
+    println!("poweroff: not supported");
+}
+
 fn do_ldr(args: &str) {
     println!("ldr");
     if args.is_empty() {
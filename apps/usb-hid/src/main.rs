@@ -4,6 +4,7 @@
 
 use axalloc::GlobalNoCacheAllocator;
 use axhal::{mem::VirtAddr, paging::PageSize};
+use driver_usb::abstractions::event::{MouseEvent, USBSystemEvent};
 use driver_usb::{USBSystem, USBSystemConfig};
 
 #[macro_use]
@@ -23,7 +24,25 @@ impl driver_usb::abstractions::OSAbstractions for PlatformAbstraction {
     }
 
     fn send_event(&self, event: driver_usb::abstractions::event::USBSystemEvent) {
-        //println!("event:{:#?}",event);
+        match event {
+            USBSystemEvent::MouseEvent(MouseEvent {
+                dx,
+                dy,
+                left,
+                right,
+                middle,
+                wheel,
+            }) => {
+                ax_event_bus::post(ax_event_bus::events::mouse::MouseEvent {
+                    dx,
+                    dy,
+                    left,
+                    right,
+                    middle,
+                    wheel,
+                });
+            }
+        }
     }
 }
 
@@ -34,10 +53,20 @@ impl driver_usb::abstractions::HALAbstractions for PlatformAbstraction {
 #[no_mangle]
 fn main() {
     //comm
-    let mut usbsystem = driver_usb::USBSystem::new({
-        USBSystemConfig::new(0xffff_0000_31a0_8000, 48, 0, PlatformAbstraction)
-    })
-    .init()
-    .init_probe()
-    .drive_all();
+    #[cfg(target_arch = "x86_64")]
+    let config =
+        USBSystemConfig::probe_pci(0, PlatformAbstraction).expect("no xHCI controller found");
+    #[cfg(not(target_arch = "x86_64"))]
+    let config = USBSystemConfig::new(0xffff_0000_31a0_8000, 48, 0, PlatformAbstraction);
+
+    // record enumeration, and print it as a pcap file once done
+    #[cfg(feature = "usbmon")]
+    driver_usb::host::usbmon::start(1024, 256);
+
+    let mut usbsystem = driver_usb::USBSystem::new(config).init().init_probe();
+
+    #[cfg(feature = "usbmon")]
+    driver_usb::host::usbmon::dump_pcap_to_console();
+
+    usbsystem.drive_all();
 }
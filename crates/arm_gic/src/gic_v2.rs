@@ -4,7 +4,7 @@
 
 use core::ptr::NonNull;
 
-use crate::{TriggerMode, GIC_MAX_IRQ, SPI_RANGE};
+use crate::{TriggerMode, GIC_MAX_IRQ, SGI_RANGE, SPI_RANGE};
 use tock_registers::interfaces::{Readable, Writeable};
 use tock_registers::register_structs;
 use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
@@ -115,6 +115,15 @@ pub struct GicCpuInterface {
     base: NonNull<GicCpuInterfaceRegs>,
 }
 
+/// The CPUs an SGI is sent to.
+#[derive(Debug, Clone, Copy)]
+pub enum SgiTarget {
+    /// The CPU with the given CPU interface number (0-7).
+    Cpu(usize),
+    /// All CPUs except the current one.
+    AllOthers,
+}
+
 unsafe impl Send for GicDistributor {}
 unsafe impl Sync for GicDistributor {}
 
@@ -177,6 +186,21 @@ impl GicDistributor {
         }
     }
 
+    /// Sends the software-generated interrupt `sgi` (0-15). (write GICD_SGIR)
+    pub fn send_sgi(&self, target: SgiTarget, sgi: usize) {
+        if sgi >= SGI_RANGE.end {
+            return;
+        }
+        let value = match target {
+            // CPUTargetList, with TargetListFilter = 0b00
+            SgiTarget::Cpu(cpu) if cpu < 8 => 1 << (16 + cpu),
+            SgiTarget::Cpu(_) => return,
+            // TargetListFilter = 0b01
+            SgiTarget::AllOthers => 1 << 24,
+        };
+        self.regs().SGIR.set(value | sgi as u32);
+    }
+
     /// Initializes the GIC distributor.
     ///
     /// It disables all interrupts, sets the target of all SPIs to CPU 0,
@@ -0,0 +1,516 @@
+//! Types and definitions for GICv3.
+//!
+//! Unlike GICv2, the CPU interface of GICv3 is accessed through the `ICC_*`
+//! system registers, and the private interrupts (SGIs and PPIs) of each CPU
+//! are configured in its redistributor instead of the distributor. SPIs are
+//! routed by affinity (affinity routing is always enabled).
+//!
+//! The official documentation: <https://developer.arm.com/documentation/ihi0069/latest/>
+
+use core::arch::asm;
+use core::ptr::NonNull;
+
+use crate::{TriggerMode, GIC_MAX_IRQ, PPI_RANGE, SGI_RANGE, SPI_RANGE};
+use tock_registers::interfaces::{Readable, Writeable};
+use tock_registers::register_structs;
+use tock_registers::registers::{ReadOnly, ReadWrite};
+
+/// Priority given to all interrupts, in the middle of the range so that the
+/// priority mask can be raised above it.
+const DEFAULT_PRIORITY: u32 = 0xa0;
+
+/// Size of the `RD_base` and `SGI_base` frames of a redistributor.
+const GICR_FRAME_SIZE: usize = 0x1_0000;
+
+// GICD_CTLR bits (accessed with a single security state, or non-secure).
+const GICD_CTLR_ENABLE_GRP1: u32 = 1 << 0;
+const GICD_CTLR_ENABLE_GRP1A: u32 = 1 << 1;
+const GICD_CTLR_ARE: u32 = 1 << 4;
+const GICD_CTLR_RWP: u32 = 1 << 31;
+
+// GICR_TYPER bits.
+const GICR_TYPER_VLPIS: u64 = 1 << 1;
+const GICR_TYPER_LAST: u64 = 1 << 4;
+
+// GICR_WAKER bits.
+const GICR_WAKER_PROCESSOR_SLEEP: u32 = 1 << 1;
+const GICR_WAKER_CHILDREN_ASLEEP: u32 = 1 << 2;
+
+/// INTIDs 1020-1023 are special, 1023 means no pending interrupt.
+const INTID_SPECIAL_START: u32 = 1020;
+
+register_structs! {
+    /// GIC Distributor registers.
+    #[allow(non_snake_case)]
+    GicDistributorRegs {
+        /// Distributor Control Register.
+        (0x0000 => CTLR: ReadWrite<u32>),
+        /// Interrupt Controller Type Register.
+        (0x0004 => TYPER: ReadOnly<u32>),
+        /// Distributor Implementer Identification Register.
+        (0x0008 => IIDR: ReadOnly<u32>),
+        (0x000c => _reserved_0),
+        /// Interrupt Group Registers.
+        (0x0080 => IGROUPR: [ReadWrite<u32>; 0x20]),
+        /// Interrupt Set-Enable Registers.
+        (0x0100 => ISENABLER: [ReadWrite<u32>; 0x20]),
+        /// Interrupt Clear-Enable Registers.
+        (0x0180 => ICENABLER: [ReadWrite<u32>; 0x20]),
+        /// Interrupt Set-Pending Registers.
+        (0x0200 => ISPENDR: [ReadWrite<u32>; 0x20]),
+        /// Interrupt Clear-Pending Registers.
+        (0x0280 => ICPENDR: [ReadWrite<u32>; 0x20]),
+        /// Interrupt Set-Active Registers.
+        (0x0300 => ISACTIVER: [ReadWrite<u32>; 0x20]),
+        /// Interrupt Clear-Active Registers.
+        (0x0380 => ICACTIVER: [ReadWrite<u32>; 0x20]),
+        /// Interrupt Priority Registers.
+        (0x0400 => IPRIORITYR: [ReadWrite<u32>; 0x100]),
+        (0x0800 => _reserved_1),
+        /// Interrupt Configuration Registers.
+        (0x0c00 => ICFGR: [ReadWrite<u32>; 0x40]),
+        /// Interrupt Group Modifier Registers.
+        (0x0d00 => IGRPMODR: [ReadWrite<u32>; 0x20]),
+        (0x0d80 => _reserved_2),
+        /// Interrupt Routing Registers, for INTIDs 32-1019.
+        (0x6100 => IROUTER: [ReadWrite<u64>; 988]),
+        (0x7fe0 => _reserved_3),
+        (0x10000 => @END),
+    }
+}
+
+register_structs! {
+    /// GIC Redistributor registers of the `RD_base` frame.
+    #[allow(non_snake_case)]
+    GicRedistributorRegs {
+        /// Redistributor Control Register.
+        (0x0000 => CTLR: ReadWrite<u32>),
+        /// Redistributor Implementer Identification Register.
+        (0x0004 => IIDR: ReadOnly<u32>),
+        /// Redistributor Type Register.
+        (0x0008 => TYPER: ReadOnly<u64>),
+        (0x0010 => _reserved_0),
+        /// Redistributor Wake Register.
+        (0x0014 => WAKER: ReadWrite<u32>),
+        (0x0018 => _reserved_1),
+        (0x10000 => @END),
+    }
+}
+
+register_structs! {
+    /// GIC Redistributor registers of the `SGI_base` frame.
+    #[allow(non_snake_case)]
+    GicSgiRegs {
+        (0x0000 => _reserved_0),
+        /// Interrupt Group Register 0.
+        (0x0080 => IGROUPR0: ReadWrite<u32>),
+        (0x0084 => _reserved_1),
+        /// Interrupt Set-Enable Register 0.
+        (0x0100 => ISENABLER0: ReadWrite<u32>),
+        (0x0104 => _reserved_2),
+        /// Interrupt Clear-Enable Register 0.
+        (0x0180 => ICENABLER0: ReadWrite<u32>),
+        (0x0184 => _reserved_3),
+        /// Interrupt Set-Pending Register 0.
+        (0x0200 => ISPENDR0: ReadWrite<u32>),
+        (0x0204 => _reserved_4),
+        /// Interrupt Clear-Pending Register 0.
+        (0x0280 => ICPENDR0: ReadWrite<u32>),
+        (0x0284 => _reserved_5),
+        /// Interrupt Priority Registers.
+        (0x0400 => IPRIORITYR: [ReadWrite<u32>; 8]),
+        (0x0420 => _reserved_6),
+        /// SGI Configuration Register (`ICFGR[0]`) and PPI Configuration
+        /// Register (`ICFGR[1]`).
+        (0x0c00 => ICFGR: [ReadWrite<u32>; 2]),
+        (0x0c08 => _reserved_7),
+        /// Interrupt Group Modifier Register 0.
+        (0x0d00 => IGRPMODR0: ReadWrite<u32>),
+        (0x0d04 => _reserved_8),
+        (0x10000 => @END),
+    }
+}
+
+/// Encodings of the system registers, so that no GIC feature has to be
+/// enabled in the assembler.
+macro_rules! sysreg {
+    (MPIDR_EL1) => {
+        "mpidr_el1"
+    };
+    (ICC_PMR_EL1) => {
+        "S3_0_C4_C6_0"
+    };
+    (ICC_SGI1R_EL1) => {
+        "S3_0_C12_C11_5"
+    };
+    (ICC_IAR1_EL1) => {
+        "S3_0_C12_C12_0"
+    };
+    (ICC_EOIR1_EL1) => {
+        "S3_0_C12_C12_1"
+    };
+    (ICC_BPR1_EL1) => {
+        "S3_0_C12_C12_3"
+    };
+    (ICC_CTLR_EL1) => {
+        "S3_0_C12_C12_4"
+    };
+    (ICC_SRE_EL1) => {
+        "S3_0_C12_C12_5"
+    };
+    (ICC_IGRPEN1_EL1) => {
+        "S3_0_C12_C12_7"
+    };
+}
+
+macro_rules! read_sysreg {
+    ($name:ident) => {{
+        let value: u64;
+        unsafe { asm!(concat!("mrs {}, ", sysreg!($name)), out(reg) value) };
+        value
+    }};
+}
+
+macro_rules! write_sysreg {
+    ($name:ident, $value:expr) => {{
+        let value: u64 = $value;
+        unsafe { asm!(concat!("msr ", sysreg!($name), ", {}"), "isb", in(reg) value) };
+    }};
+}
+
+/// Returns the affinity fields of `MPIDR_EL1`, in the layout of `GICD_IROUTER`
+/// (`Aff3` in bits \[39:32\], `Aff2..Aff0` in bits \[23:0\]).
+fn current_affinity() -> u64 {
+    read_sysreg!(MPIDR_EL1) & 0xff_00ff_ffff
+}
+
+/// Converts an affinity in the layout of `GICD_IROUTER` to that of
+/// `GICR_TYPER[63:32]` (`Aff3.Aff2.Aff1.Aff0`).
+const fn affinity_to_typer(aff: u64) -> u64 {
+    ((aff >> 32) & 0xff) << 24 | (aff & 0xff_ffff)
+}
+
+/// The GIC distributor.
+///
+/// In GICv3, the distributor only handles SPIs: it sets their group,
+/// priority, trigger mode and enable state, and routes each of them to a
+/// CPU by its affinity.
+pub struct GicDistributor {
+    base: NonNull<GicDistributorRegs>,
+    max_irqs: usize,
+}
+
+/// The GIC redistributor of a CPU.
+///
+/// Each CPU has a redistributor, which handles its SGIs and PPIs and connects
+/// its CPU interface to the distributor.
+pub struct GicRedistributor {
+    rd: NonNull<GicRedistributorRegs>,
+    sgi: NonNull<GicSgiRegs>,
+}
+
+/// The GIC CPU interface of the current CPU, accessed through system
+/// registers.
+pub struct GicCpuInterface;
+
+/// The CPUs an SGI is sent to.
+#[derive(Debug, Clone, Copy)]
+pub enum SgiTarget {
+    /// The CPU with the given `MPIDR_EL1` affinity.
+    Cpu(u64),
+    /// All CPUs except the current one.
+    AllOthers,
+}
+
+unsafe impl Send for GicDistributor {}
+unsafe impl Sync for GicDistributor {}
+
+unsafe impl Send for GicRedistributor {}
+unsafe impl Sync for GicRedistributor {}
+
+impl GicDistributor {
+    /// Construct a new GIC distributor instance from the base address.
+    pub const fn new(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new(base).unwrap().cast(),
+            max_irqs: GIC_MAX_IRQ,
+        }
+    }
+
+    const fn regs(&self) -> &GicDistributorRegs {
+        unsafe { self.base.as_ref() }
+    }
+
+    /// The maximum number of interrupts that the GIC supports
+    pub fn max_irqs(&self) -> usize {
+        ((self.regs().TYPER.get() as usize & 0b11111) + 1) * 32
+    }
+
+    /// Waits until the writes to `GICD_CTLR` have taken effect.
+    fn wait_for_rwp(&self) {
+        while self.regs().CTLR.get() & GICD_CTLR_RWP != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Configures the trigger mode for the given SPI.
+    pub fn configure_interrupt(&mut self, vector: usize, tm: TriggerMode) {
+        if vector >= self.max_irqs || !SPI_RANGE.contains(&vector) {
+            return;
+        }
+        let reg_idx = vector >> 4;
+        let bit_shift = ((vector & 0xf) << 1) + 1;
+        let mut reg_val = self.regs().ICFGR[reg_idx].get();
+        match tm {
+            TriggerMode::Edge => reg_val |= 1 << bit_shift,
+            TriggerMode::Level => reg_val &= !(1 << bit_shift),
+        }
+        self.regs().ICFGR[reg_idx].set(reg_val);
+    }
+
+    /// Enables or disables the given SPI.
+    pub fn set_enable(&mut self, vector: usize, enable: bool) {
+        if vector >= self.max_irqs || !SPI_RANGE.contains(&vector) {
+            return;
+        }
+        let reg = vector / 32;
+        let mask = 1 << (vector % 32);
+        if enable {
+            self.regs().ISENABLER[reg].set(mask);
+        } else {
+            self.regs().ICENABLER[reg].set(mask);
+        }
+    }
+
+    /// Routes the given SPI to the CPU with the given `MPIDR_EL1` affinity.
+    pub fn set_route(&mut self, vector: usize, affinity: u64) {
+        if vector >= self.max_irqs || !SPI_RANGE.contains(&vector) {
+            return;
+        }
+        self.regs().IROUTER[vector - SPI_RANGE.start].set(affinity & 0xff_00ff_ffff);
+    }
+
+    /// Initializes the GIC distributor.
+    ///
+    /// It disables all SPIs, puts them in non-secure group 1 with the same
+    /// priority, routes them to the current CPU, configures them to be
+    /// edge-triggered, and finally enables the GICD with affinity routing.
+    ///
+    /// This function should be called only once.
+    pub fn init(&mut self) {
+        let max_irqs = self.max_irqs();
+        assert!(max_irqs <= GIC_MAX_IRQ);
+        self.max_irqs = max_irqs;
+        // INTIDs 1020..1023 are reserved, even if `GICD_TYPER` reports 1024.
+        let spi_end = max_irqs.min(SPI_RANGE.end);
+
+        // Disable the distributor while reconfiguring it.
+        self.regs().CTLR.set(0);
+        self.wait_for_rwp();
+
+        let affinity = current_affinity();
+        for i in (SPI_RANGE.start..spi_end).step_by(32) {
+            self.regs().ICENABLER[i / 32].set(u32::MAX);
+            self.regs().ICPENDR[i / 32].set(u32::MAX);
+            self.regs().IGROUPR[i / 32].set(u32::MAX);
+            self.regs().IGRPMODR[i / 32].set(0);
+        }
+        for i in (SPI_RANGE.start..spi_end).step_by(4) {
+            self.regs().IPRIORITYR[i / 4].set(DEFAULT_PRIORITY * 0x01_01_01_01);
+        }
+        for i in SPI_RANGE.start..spi_end {
+            self.set_route(i, affinity);
+            self.configure_interrupt(i, TriggerMode::Edge);
+        }
+        self.wait_for_rwp();
+
+        self.regs()
+            .CTLR
+            .set(GICD_CTLR_ARE | GICD_CTLR_ENABLE_GRP1A | GICD_CTLR_ENABLE_GRP1);
+        self.wait_for_rwp();
+    }
+}
+
+impl GicRedistributor {
+    /// Finds the redistributor of the current CPU among the contiguous
+    /// redistributors starting at `base`.
+    ///
+    /// Returns `None` if the last redistributor is reached without finding
+    /// the one whose affinity matches `MPIDR_EL1`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to the mapped redistributor region.
+    pub unsafe fn current(base: *mut u8) -> Option<Self> {
+        let target = affinity_to_typer(current_affinity());
+        let mut frame = base;
+        loop {
+            let rd = NonNull::new(frame)?.cast::<GicRedistributorRegs>();
+            let typer = rd.as_ref().TYPER.get();
+            // Each redistributor has `RD_base` and `SGI_base` frames, plus two
+            // more frames if it supports virtual LPIs (GICv4).
+            let frames = if typer & GICR_TYPER_VLPIS != 0 { 4 } else { 2 };
+            if typer >> 32 == target {
+                let sgi = NonNull::new(frame.add(GICR_FRAME_SIZE))?.cast();
+                return Some(Self { rd, sgi });
+            }
+            if typer & GICR_TYPER_LAST != 0 {
+                return None;
+            }
+            frame = frame.add(frames * GICR_FRAME_SIZE);
+        }
+    }
+
+    const fn rd_regs(&self) -> &GicRedistributorRegs {
+        unsafe { self.rd.as_ref() }
+    }
+
+    const fn sgi_regs(&self) -> &GicSgiRegs {
+        unsafe { self.sgi.as_ref() }
+    }
+
+    /// Configures the trigger mode for the given PPI.
+    pub fn configure_interrupt(&mut self, vector: usize, tm: TriggerMode) {
+        // SGIs are always edge-triggered
+        if !PPI_RANGE.contains(&vector) {
+            return;
+        }
+        let bit_shift = ((vector & 0xf) << 1) + 1;
+        let mut reg_val = self.sgi_regs().ICFGR[1].get();
+        match tm {
+            TriggerMode::Edge => reg_val |= 1 << bit_shift,
+            TriggerMode::Level => reg_val &= !(1 << bit_shift),
+        }
+        self.sgi_regs().ICFGR[1].set(reg_val);
+    }
+
+    /// Enables or disables the given SGI or PPI.
+    pub fn set_enable(&mut self, vector: usize, enable: bool) {
+        if vector >= SPI_RANGE.start {
+            return;
+        }
+        let mask = 1 << vector;
+        if enable {
+            self.sgi_regs().ISENABLER0.set(mask);
+        } else {
+            self.sgi_regs().ICENABLER0.set(mask);
+        }
+    }
+
+    /// Initializes the redistributor.
+    ///
+    /// It wakes the redistributor up, then disables all SGIs and PPIs and
+    /// puts them in non-secure group 1 with the same priority.
+    ///
+    /// This function should be called once on each CPU.
+    pub fn init(&mut self) {
+        let waker = self.rd_regs().WAKER.get();
+        self.rd_regs()
+            .WAKER
+            .set(waker & !GICR_WAKER_PROCESSOR_SLEEP);
+        while self.rd_regs().WAKER.get() & GICR_WAKER_CHILDREN_ASLEEP != 0 {
+            core::hint::spin_loop();
+        }
+
+        self.sgi_regs().ICENABLER0.set(u32::MAX);
+        self.sgi_regs().ICPENDR0.set(u32::MAX);
+        self.sgi_regs().IGROUPR0.set(u32::MAX);
+        self.sgi_regs().IGRPMODR0.set(0);
+        for reg in self.sgi_regs().IPRIORITYR.iter() {
+            reg.set(DEFAULT_PRIORITY * 0x01_01_01_01);
+        }
+    }
+}
+
+impl GicCpuInterface {
+    /// Construct a new GIC CPU interface instance.
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Returns the interrupt ID of the highest priority pending group 1
+    /// interrupt. (read ICC_IAR1_EL1)
+    ///
+    /// The read returns a spurious interrupt ID of `1023` if there is no
+    /// pending interrupt.
+    pub fn iar(&self) -> u32 {
+        read_sysreg!(ICC_IAR1_EL1) as u32
+    }
+
+    /// Informs the CPU interface that it has completed the processing of the
+    /// specified interrupt. (write ICC_EOIR1_EL1)
+    ///
+    /// The value written must be the value returns from [`Self::iar`].
+    pub fn eoi(&self, iar: u32) {
+        write_sysreg!(ICC_EOIR1_EL1, iar as u64);
+    }
+
+    /// handles the signaled interrupt.
+    ///
+    /// It first reads ICC_IAR1_EL1 to obtain the pending interrupt ID and
+    /// then calls the given handler. After the handler returns, it writes
+    /// ICC_EOIR1_EL1 to acknowledge the interrupt.
+    ///
+    /// If the interrupt ID is a special one (spurious), it does nothing.
+    pub fn handle_irq<F>(&self, handler: F)
+    where
+        F: FnOnce(u32),
+    {
+        let iar = self.iar();
+        let vector = iar & 0xff_ffff;
+        if vector < INTID_SPECIAL_START {
+            handler(vector);
+            self.eoi(iar);
+        } else {
+            // spurious
+        }
+    }
+
+    /// Sends the software-generated interrupt `sgi` (0-15). (write
+    /// ICC_SGI1R_EL1)
+    pub fn send_sgi(&self, target: SgiTarget, sgi: usize) {
+        if sgi >= SGI_RANGE.end {
+            return;
+        }
+        let intid = (sgi as u64) << 24;
+        let value = match target {
+            SgiTarget::Cpu(mpidr) => {
+                let aff0 = mpidr & 0xff;
+                let aff1 = (mpidr >> 8) & 0xff;
+                let aff2 = (mpidr >> 16) & 0xff;
+                let aff3 = (mpidr >> 32) & 0xff;
+                // The target list covers 16 CPUs, `RS` selects the range.
+                (aff3 << 48)
+                    | (aff2 << 32)
+                    | ((aff0 >> 4) << 44)
+                    | (aff1 << 16)
+                    | (1 << (aff0 & 0xf))
+            }
+            SgiTarget::AllOthers => 1 << 40,
+        };
+        write_sysreg!(ICC_SGI1R_EL1, value | intid);
+    }
+
+    /// Initializes the GIC CPU interface.
+    ///
+    /// It enables the system register interface, unmask interrupts at all
+    /// priority levels and enables group 1 interrupts.
+    ///
+    /// This function should be called once on each CPU.
+    pub fn init(&self) {
+        write_sysreg!(ICC_SRE_EL1, read_sysreg!(ICC_SRE_EL1) | 1);
+        // unmask interrupts at all priority levels
+        write_sysreg!(ICC_PMR_EL1, 0xff);
+        // no preemption grouping, EOI also deactivates
+        write_sysreg!(ICC_BPR1_EL1, 0);
+        write_sysreg!(ICC_CTLR_EL1, 0);
+        // enable group 1 interrupts
+        write_sysreg!(ICC_IGRPEN1_EL1, 1);
+    }
+}
+
+impl Default for GicCpuInterface {
+    fn default() -> Self {
+        Self::new()
+    }
+}
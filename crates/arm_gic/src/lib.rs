@@ -1,5 +1,9 @@
 //! ARM Generic Interrupt Controller (GIC) register definitions and basic
 //! operations.
+//!
+//! [`gic_v2`] supports GICv2 with memory-mapped CPU interfaces, and [`gic_v3`]
+//! (on AArch64 only) supports GICv3 with redistributors and system register
+//! CPU interfaces.
 
 #![no_std]
 #![feature(const_ptr_as_ref)]
@@ -7,6 +11,8 @@
 #![feature(const_nonnull_new)]
 
 pub mod gic_v2;
+#[cfg(target_arch = "aarch64")]
+pub mod gic_v3;
 
 use core::ops::Range;
 
@@ -0,0 +1,8 @@
+//! Definitions for PL031 RTC.
+
+#![no_std]
+#![feature(const_ptr_as_ref)]
+#![feature(const_option)]
+#![feature(const_nonnull_new)]
+
+pub mod pl031;
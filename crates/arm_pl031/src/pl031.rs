@@ -0,0 +1,80 @@
+//! Types and definitions for PL031 RTC.
+//!
+//! The official documentation: <https://developer.arm.com/documentation/ddi0224/latest>
+
+use core::ptr::NonNull;
+
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
+};
+
+register_structs! {
+    /// Pl031 registers.
+    Pl031RtcRegs {
+        /// Data Register.
+        (0x00 => dr: ReadOnly<u32>),
+        /// Match Register.
+        (0x04 => mr: ReadWrite<u32>),
+        /// Load Register.
+        (0x08 => lr: ReadWrite<u32>),
+        /// Control Register.
+        (0x0c => cr: ReadWrite<u32>),
+        /// Interrupt Mask Set or Clear Register.
+        (0x10 => imsc: ReadWrite<u32>),
+        /// Raw Interrupt Status Register.
+        (0x14 => ris: ReadOnly<u32>),
+        /// Masked Interrupt Status Register.
+        (0x18 => mis: ReadOnly<u32>),
+        /// Interrupt Clear Register.
+        (0x1c => icr: WriteOnly<u32>),
+        (0x20 => @END),
+    }
+}
+
+/// The Pl031 RTC
+///
+/// The counter of the RTC counts seconds, and is usually set to the seconds
+/// since the Unix epoch (1970-01-01 00:00:00 UTC).
+pub struct Pl031Rtc {
+    base: NonNull<Pl031RtcRegs>,
+}
+
+unsafe impl Send for Pl031Rtc {}
+unsafe impl Sync for Pl031Rtc {}
+
+impl Pl031Rtc {
+    /// Construct a new PL031 RTC instance from the base address.
+    pub const fn new(base: *mut u8) -> Self {
+        Self {
+            base: NonNull::new(base).unwrap().cast(),
+        }
+    }
+
+    const fn regs(&self) -> &Pl031RtcRegs {
+        unsafe { self.base.as_ref() }
+    }
+
+    /// Initializes the Pl031 RTC.
+    ///
+    /// It masks and clears the match interrupt, and starts the counter if it
+    /// is stopped.
+    pub fn init(&mut self) {
+        self.regs().imsc.set(0);
+        self.regs().icr.set(1);
+        if self.regs().cr.get() & 1 == 0 {
+            self.regs().cr.set(1);
+        }
+    }
+
+    /// Returns the current value of the counter, in seconds.
+    pub fn get_unix_timestamp(&self) -> u32 {
+        self.regs().dr.get()
+    }
+
+    /// Sets the counter to the given value, in seconds.
+    pub fn set_unix_timestamp(&mut self, secs: u32) {
+        self.regs().lr.set(secs);
+    }
+}
@@ -1,15 +1,42 @@
-use mouse::MouseEvent;
+use core::any::Any;
 
 pub mod mouse;
-pub enum EventData {
-    MouseEvent(MouseEvent),
+
+/// Anything that can be posted on the bus, app-defined types included.
+pub trait Event: Any + Send {}
+
+impl<T: Any + Send> Event for T {}
+
+/// What a handler wants done with the event it just handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Propagation {
+    /// Pass the event on to the handlers of lower priority.
+    Continue,
+    /// Stop here, the remaining handlers do not see the event.
+    Consume,
+}
+
+/// Outcome of posting an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dispatch {
+    /// Nobody subscribed to this event type.
+    Unhandled,
+    /// Every handler let the event through.
+    Propagated,
+    /// A handler consumed the event.
+    Consumed,
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
-pub enum Events {
-    MouseEvent,
+pub trait EventHandler<E: Event>: Send + Sync {
+    fn handle(&self, event: &mut E) -> Propagation;
 }
 
-pub trait EventHandler: Send + Sync {
-    fn handle(&self, event: &mut EventData) -> bool;
+impl<E, F> EventHandler<E> for F
+where
+    E: Event,
+    F: Fn(&mut E) -> Propagation + Send + Sync,
+{
+    fn handle(&self, event: &mut E) -> Propagation {
+        self(event)
+    }
 }
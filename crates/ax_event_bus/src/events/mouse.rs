@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct MouseEvent {
     pub dx: isize,
     pub dy: isize,
@@ -1,8 +1,25 @@
-#![no_std]
-#![feature(allocator_api)]
+//! A typed publish/subscribe bus.
+//!
+//! Handlers subscribe to an event type and are called in priority order
+//! when an event of that type is posted, until one of them consumes it.
+//! Subscriptions end when their [`Subscription`] is dropped.
+//!
+//! [`post`] delivers right away, in the caller's context. Contexts that must
+//! not run handlers, such as IRQ handlers, use [`post_deferred`] instead: the
+//! event is queued and delivered by [`dispatch_pending`]. With the feature
+//! `multitask`, a dispatcher task calls it whenever events are queued. The
+//! task is started by the first subscription, or by `start_dispatcher`.
 
-use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec, vec::Vec};
-use events::{EventData, EventHandler, Events};
+#![cfg_attr(not(test), no_std)]
+
+use alloc::{
+    boxed::Box,
+    collections::{btree_map::BTreeMap, VecDeque},
+    sync::Arc,
+    vec::Vec,
+};
+use core::any::{Any, TypeId};
+use events::{Dispatch, Event, EventHandler, Propagation};
 use lazy_static::lazy_static;
 use spinlock::SpinNoIrq;
 
@@ -10,36 +27,189 @@ extern crate alloc;
 
 pub mod events;
 
+#[cfg(test)]
+mod tests;
+
 lazy_static! {
     static ref EVENT_BUS: SpinNoIrq<EventBus> = SpinNoIrq::new(EventBus::new());
+    static ref PENDING: SpinNoIrq<VecDeque<PendingEvent>> = SpinNoIrq::new(VecDeque::new());
+}
+
+type ErasedHandler = Box<dyn Fn(&mut dyn Any) -> Propagation + Send + Sync>;
+
+struct Entry {
+    id: u64,
+    priority: i32,
+    handler: ErasedHandler,
+}
+
+struct PendingEvent {
+    type_id: TypeId,
+    data: Box<dyn Any + Send>,
 }
 
 struct EventBus {
-    bus: BTreeMap<Events, Vec<Arc<dyn EventHandler>>>,
+    bus: BTreeMap<TypeId, Vec<Arc<Entry>>>,
+    next_id: u64,
 }
 
 impl EventBus {
     fn new() -> Self {
         Self {
             bus: BTreeMap::new(),
+            next_id: 0,
         }
     }
 }
 
-pub fn post_event(event: Events, mut data: EventData) -> bool {
-    EVENT_BUS
-        .lock()
-        .bus
-        .get(&event)
-        .map(|handlers| !handlers.iter().any(|handler| !handler.handle(&mut data)))
-        .unwrap_or(false)
+/// Keeps a handler subscribed, it is unsubscribed on drop.
+#[must_use = "the handler is unsubscribed when the subscription is dropped"]
+pub struct Subscription {
+    type_id: TypeId,
+    id: u64,
+}
+
+impl Subscription {
+    /// Keeps the handler subscribed for good.
+    pub fn detach(self) {
+        core::mem::forget(self)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut bus = EVENT_BUS.lock();
+        if let Some(handlers) = bus.bus.get_mut(&self.type_id) {
+            handlers.retain(|entry| entry.id != self.id);
+            if handlers.is_empty() {
+                bus.bus.remove(&self.type_id);
+            }
+        }
+    }
+}
+
+/// Subscribes `handler` to events of type `E`, with priority 0.
+pub fn subscribe<E, H>(handler: H) -> Subscription
+where
+    E: Event,
+    H: EventHandler<E> + 'static,
+{
+    subscribe_with_priority(0, handler)
+}
+
+/// Subscribes `handler` to events of type `E`. Handlers of higher priority
+/// are called first, those of equal priority in subscription order.
+pub fn subscribe_with_priority<E, H>(priority: i32, handler: H) -> Subscription
+where
+    E: Event,
+    H: EventHandler<E> + 'static,
+{
+    let handler: ErasedHandler =
+        Box::new(move |data: &mut dyn Any| match data.downcast_mut::<E>() {
+            Some(event) => handler.handle(event),
+            None => Propagation::Continue,
+        });
+
+    // Events are only worth dispatching once somebody listens.
+    #[cfg(feature = "multitask")]
+    start_dispatcher();
+
+    let type_id = TypeId::of::<E>();
+    let mut bus = EVENT_BUS.lock();
+    let id = bus.next_id;
+    bus.next_id += 1;
+    let handlers = bus.bus.entry(type_id).or_default();
+    let at = handlers.partition_point(|entry| entry.priority >= priority);
+    handlers.insert(
+        at,
+        Arc::new(Entry {
+            id,
+            priority,
+            handler,
+        }),
+    );
+    Subscription { type_id, id }
+}
+
+/// Delivers `event` to its handlers now, in the caller's context.
+///
+/// Handlers run without the bus locked, so they may post, subscribe or
+/// drop subscriptions themselves.
+pub fn post<E: Event>(mut event: E) -> Dispatch {
+    dispatch(TypeId::of::<E>(), &mut event)
+}
+
+/// Queues `event` to be delivered later by [`dispatch_pending`]. Safe to call
+/// from IRQ context.
+pub fn post_deferred<E: Event>(event: E) {
+    PENDING.lock().push_back(PendingEvent {
+        type_id: TypeId::of::<E>(),
+        data: Box::new(event),
+    });
+    #[cfg(feature = "multitask")]
+    dispatcher::notify();
+}
+
+/// Delivers the events queued by [`post_deferred`], returns how many were
+/// delivered.
+pub fn dispatch_pending() -> usize {
+    let mut count = 0;
+    while let Some(mut pending) = next_pending() {
+        dispatch(pending.type_id, pending.data.as_mut());
+        count += 1;
+    }
+    count
+}
+
+/// Takes one event at a time, the queue must not stay locked while handlers
+/// run as they may queue more events.
+fn next_pending() -> Option<PendingEvent> {
+    PENDING.lock().pop_front()
 }
 
-pub fn register_handler(event: Events, handler: &Arc<dyn EventHandler>) {
-    EVENT_BUS
-        .lock()
-        .bus
-        .entry(event)
-        .and_modify(|v| v.push(handler.clone()))
-        .or_insert(vec![handler.clone()]);
+#[cfg(feature = "multitask")]
+fn has_pending() -> bool {
+    !PENDING.lock().is_empty()
+}
+
+fn dispatch(type_id: TypeId, data: &mut dyn Any) -> Dispatch {
+    let handlers = match EVENT_BUS.lock().bus.get(&type_id) {
+        Some(handlers) => handlers.clone(),
+        None => return Dispatch::Unhandled,
+    };
+    for entry in handlers.iter() {
+        if (entry.handler)(data) == Propagation::Consume {
+            return Dispatch::Consumed;
+        }
+    }
+    Dispatch::Propagated
+}
+
+#[cfg(feature = "multitask")]
+pub use dispatcher::start_dispatcher;
+
+#[cfg(feature = "multitask")]
+mod dispatcher {
+    use axtask::WaitQueue;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    static WAKER: WaitQueue = WaitQueue::new();
+    static STARTED: AtomicBool = AtomicBool::new(false);
+
+    /// Spawns the task delivering deferred events, once. It is called by the
+    /// first subscription.
+    pub fn start_dispatcher() {
+        if STARTED.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        axtask::spawn(|| loop {
+            WAKER.wait_until(super::has_pending);
+            super::dispatch_pending();
+        });
+    }
+
+    pub(super) fn notify() {
+        // may run in IRQ context, do not reschedule here
+        WAKER.notify_one(false);
+    }
 }
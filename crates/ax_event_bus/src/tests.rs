@@ -0,0 +1,137 @@
+use std::sync::{Arc, Mutex};
+
+use crate::events::{Dispatch, Propagation};
+use crate::*;
+
+// The bus is global and tests run in parallel, so each test posts its own
+// event types.
+
+/// Subscribes a handler logging `name` to the events of type `E`.
+fn log_handler<E: Event>(
+    log: &Arc<Mutex<Vec<&'static str>>>,
+    priority: i32,
+    name: &'static str,
+    propagation: Propagation,
+) -> Subscription {
+    let log = log.clone();
+    subscribe_with_priority(priority, move |_: &mut E| {
+        log.lock().unwrap().push(name);
+        propagation
+    })
+}
+
+#[test]
+fn unhandled_without_subscribers() {
+    struct Nobody;
+    assert_eq!(post(Nobody), Dispatch::Unhandled);
+}
+
+#[test]
+fn priority_order() {
+    struct Ordered;
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let _low = log_handler::<Ordered>(&log, -1, "low", Propagation::Continue);
+    let _first = log_handler::<Ordered>(&log, 0, "first", Propagation::Continue);
+    let _high = log_handler::<Ordered>(&log, 5, "high", Propagation::Continue);
+    let _second = log_handler::<Ordered>(&log, 0, "second", Propagation::Continue);
+
+    assert_eq!(post(Ordered), Dispatch::Propagated);
+    assert_eq!(*log.lock().unwrap(), ["high", "first", "second", "low"]);
+}
+
+#[test]
+fn consume_stops_propagation() {
+    struct Consumed;
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let _high = log_handler::<Consumed>(&log, 1, "high", Propagation::Consume);
+    let _low = log_handler::<Consumed>(&log, 0, "low", Propagation::Continue);
+
+    assert_eq!(post(Consumed), Dispatch::Consumed);
+    assert_eq!(*log.lock().unwrap(), ["high"]);
+}
+
+#[test]
+fn handlers_see_the_event() {
+    struct Counter(u32);
+    let _double = subscribe_with_priority(1, |event: &mut Counter| {
+        event.0 *= 2;
+        Propagation::Continue
+    });
+    let seen = Arc::new(Mutex::new(0));
+    let _check = {
+        let seen = seen.clone();
+        subscribe(move |event: &mut Counter| {
+            *seen.lock().unwrap() = event.0;
+            Propagation::Continue
+        })
+    };
+
+    post(Counter(21));
+    assert_eq!(*seen.lock().unwrap(), 42);
+}
+
+#[test]
+fn unsubscribe_on_drop() {
+    struct Dropped;
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let kept = log_handler::<Dropped>(&log, 0, "kept", Propagation::Continue);
+    let dropped = log_handler::<Dropped>(&log, 0, "dropped", Propagation::Continue);
+
+    drop(dropped);
+    assert_eq!(post(Dropped), Dispatch::Propagated);
+    assert_eq!(*log.lock().unwrap(), ["kept"]);
+
+    drop(kept);
+    assert_eq!(post(Dropped), Dispatch::Unhandled);
+}
+
+#[test]
+fn detach_keeps_the_handler() {
+    struct Detached;
+    let log = Arc::new(Mutex::new(Vec::new()));
+    log_handler::<Detached>(&log, 0, "detached", Propagation::Continue).detach();
+
+    assert_eq!(post(Detached), Dispatch::Propagated);
+    assert_eq!(*log.lock().unwrap(), ["detached"]);
+}
+
+#[test]
+fn handlers_may_post() {
+    struct Outer;
+    struct Inner;
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let _inner = log_handler::<Inner>(&log, 0, "inner", Propagation::Continue);
+    let _outer = {
+        let log = log.clone();
+        subscribe(move |_: &mut Outer| {
+            log.lock().unwrap().push("outer");
+            post(Inner);
+            Propagation::Continue
+        })
+    };
+
+    assert_eq!(post(Outer), Dispatch::Propagated);
+    assert_eq!(*log.lock().unwrap(), ["outer", "inner"]);
+}
+
+#[test]
+fn deferred_delivery() {
+    struct Deferred(u32);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let _sub = {
+        let seen = seen.clone();
+        subscribe(move |event: &mut Deferred| {
+            seen.lock().unwrap().push(event.0);
+            Propagation::Continue
+        })
+    };
+
+    post_deferred(Deferred(1));
+    post_deferred(Deferred(2));
+    assert!(seen.lock().unwrap().is_empty());
+
+    // This is the only test deferring events, nothing else is queued.
+    assert_eq!(dispatch_pending(), 2);
+    assert_eq!(*seen.lock().unwrap(), [1, 2]);
+    assert_eq!(dispatch_pending(), 0);
+}
@@ -0,0 +1,357 @@
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType};
+use axfs_vfs::{VfsPollState, VfsResult};
+use spin::Mutex;
+
+/// Event type: synchronization marker.
+pub const EV_SYN: u16 = 0x00;
+/// Event type: key or button state change.
+pub const EV_KEY: u16 = 0x01;
+/// Event type: relative axis movement.
+pub const EV_REL: u16 = 0x02;
+
+/// `EV_SYN` code: end of a packet of events.
+pub const SYN_REPORT: u16 = 0;
+/// `EV_SYN` code: the queue overflowed and events were lost.
+pub const SYN_DROPPED: u16 = 3;
+
+/// `EV_REL` code: horizontal movement.
+pub const REL_X: u16 = 0x00;
+/// `EV_REL` code: vertical movement.
+pub const REL_Y: u16 = 0x01;
+/// `EV_REL` code: vertical wheel.
+pub const REL_WHEEL: u16 = 0x08;
+
+/// `EV_KEY` code: left mouse button.
+pub const BTN_LEFT: u16 = 0x110;
+/// `EV_KEY` code: right mouse button.
+pub const BTN_RIGHT: u16 = 0x111;
+/// `EV_KEY` code: middle mouse button.
+pub const BTN_MIDDLE: u16 = 0x112;
+
+/// Maximum number of events buffered by an [`EvdevDev`].
+const EVDEV_QUEUE_LEN: usize = 256;
+/// Maximum number of packets buffered by a [`MiceDev`].
+const MICE_QUEUE_LEN: usize = 64;
+
+/// A Linux `struct input_event`.
+///
+/// Its binary form is `struct timeval` (two native `long`s) followed by
+/// `type: u16`, `code: u16` and `value: i32`, i.e. 24 bytes on 64-bit targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// Time of the event since boot.
+    pub time: Duration,
+    /// Event type (`EV_*`).
+    pub type_: u16,
+    /// Event code (`REL_*`, `BTN_*`, ...).
+    pub code: u16,
+    /// Event value.
+    pub value: i32,
+}
+
+impl InputEvent {
+    /// Size of the binary form of an event.
+    pub const SIZE: usize = 2 * core::mem::size_of::<isize>() + 8;
+
+    /// Creates a new event.
+    pub const fn new(time: Duration, type_: u16, code: u16, value: i32) -> Self {
+        Self {
+            time,
+            type_,
+            code,
+            value,
+        }
+    }
+
+    /// Encodes the event into `buf`, which must be [`SIZE`](Self::SIZE) bytes.
+    pub fn to_bytes(&self, buf: &mut [u8]) {
+        const L: usize = core::mem::size_of::<isize>();
+        buf[..L].copy_from_slice(&(self.time.as_secs() as isize).to_ne_bytes());
+        buf[L..2 * L].copy_from_slice(&(self.time.subsec_micros() as isize).to_ne_bytes());
+        buf[2 * L..2 * L + 2].copy_from_slice(&self.type_.to_ne_bytes());
+        buf[2 * L + 2..2 * L + 4].copy_from_slice(&self.code.to_ne_bytes());
+        buf[2 * L + 4..2 * L + 8].copy_from_slice(&self.value.to_ne_bytes());
+    }
+
+    /// Decodes an event from `buf`, which must be [`SIZE`](Self::SIZE) bytes.
+    pub fn from_bytes(buf: &[u8]) -> Self {
+        const L: usize = core::mem::size_of::<isize>();
+        let sec = isize::from_ne_bytes(buf[..L].try_into().unwrap());
+        let usec = isize::from_ne_bytes(buf[L..2 * L].try_into().unwrap());
+        Self {
+            time: Duration::new(sec as u64, (usec as u32).saturating_mul(1000)),
+            type_: u16::from_ne_bytes(buf[2 * L..2 * L + 2].try_into().unwrap()),
+            code: u16::from_ne_bytes(buf[2 * L + 2..2 * L + 4].try_into().unwrap()),
+            value: i32::from_ne_bytes(buf[2 * L + 4..2 * L + 8].try_into().unwrap()),
+        }
+    }
+}
+
+/// A relative pointer report, as produced by a HID mouse.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MouseReport {
+    /// Horizontal movement, positive to the right.
+    pub dx: i32,
+    /// Vertical movement, positive downwards.
+    pub dy: i32,
+    /// Wheel movement, positive away from the user.
+    pub wheel: i32,
+    /// Left button is pressed.
+    pub left: bool,
+    /// Right button is pressed.
+    pub right: bool,
+    /// Middle button is pressed.
+    pub middle: bool,
+}
+
+struct EvdevInner {
+    queue: VecDeque<InputEvent>,
+    buttons: [bool; 3],
+}
+
+/// An evdev character device behaves like `/dev/input/eventX`.
+///
+/// Reports are queued as [`InputEvent`]s terminated by `SYN_REPORT`. Reads
+/// return whole events only, and fail with [`VfsError::WouldBlock`] when the
+/// queue is empty. When the queue overflows, pending events are discarded
+/// and a `SYN_DROPPED` is queued instead, as Linux does.
+pub struct EvdevDev {
+    inner: Mutex<EvdevInner>,
+    notify: fn(),
+}
+
+impl EvdevDev {
+    /// Creates a new device with an empty queue.
+    pub const fn new() -> Self {
+        Self::with_notify(|| {})
+    }
+
+    /// Creates a new device with an empty queue, calling `notify` whenever
+    /// events are queued, e.g. to wake up the blocked readers.
+    pub const fn with_notify(notify: fn()) -> Self {
+        Self {
+            inner: Mutex::new(EvdevInner {
+                queue: VecDeque::new(),
+                buttons: [false; 3],
+            }),
+            notify,
+        }
+    }
+
+    /// Queues a single event.
+    pub fn push(&self, event: InputEvent) {
+        Self::push_locked(&mut self.inner.lock(), event);
+        (self.notify)();
+    }
+
+    /// Queues the events of a mouse report, followed by `SYN_REPORT`.
+    ///
+    /// Button events are only generated when a button changes state.
+    pub fn report_mouse(&self, time: Duration, report: &MouseReport) {
+        let mut inner = self.inner.lock();
+        for (code, value) in [
+            (REL_X, report.dx),
+            (REL_Y, report.dy),
+            (REL_WHEEL, report.wheel),
+        ] {
+            if value != 0 {
+                Self::push_locked(&mut inner, InputEvent::new(time, EV_REL, code, value));
+            }
+        }
+        let buttons = [report.left, report.right, report.middle];
+        for (i, code) in [BTN_LEFT, BTN_RIGHT, BTN_MIDDLE].into_iter().enumerate() {
+            if inner.buttons[i] != buttons[i] {
+                let event = InputEvent::new(time, EV_KEY, code, buttons[i] as i32);
+                Self::push_locked(&mut inner, event);
+            }
+        }
+        inner.buttons = buttons;
+        Self::push_locked(&mut inner, InputEvent::new(time, EV_SYN, SYN_REPORT, 0));
+        drop(inner);
+        (self.notify)();
+    }
+
+    fn push_locked(inner: &mut EvdevInner, event: InputEvent) {
+        if inner.queue.len() >= EVDEV_QUEUE_LEN {
+            log::warn!(
+                "evdev queue overflow, dropping {} events",
+                inner.queue.len()
+            );
+            inner.queue.clear();
+            inner
+                .queue
+                .push_back(InputEvent::new(event.time, EV_SYN, SYN_DROPPED, 0));
+        }
+        inner.queue.push_back(event);
+    }
+}
+
+impl Default for EvdevDev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsNodeOps for EvdevDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o660),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        if buf.len() < InputEvent::SIZE {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut inner = self.inner.lock();
+        if inner.queue.is_empty() {
+            return Err(VfsError::WouldBlock);
+        }
+        let mut read_len = 0;
+        for chunk in buf.chunks_exact_mut(InputEvent::SIZE) {
+            match inner.queue.pop_front() {
+                Some(event) => event.to_bytes(chunk),
+                None => break,
+            }
+            read_len += InputEvent::SIZE;
+        }
+        Ok(read_len)
+    }
+
+    /// Injects events, like writing to an evdev node on Linux.
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if buf.len() < InputEvent::SIZE {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut inner = self.inner.lock();
+        let mut write_len = 0;
+        for chunk in buf.chunks_exact(InputEvent::SIZE) {
+            Self::push_locked(&mut inner, InputEvent::from_bytes(chunk));
+            write_len += InputEvent::SIZE;
+        }
+        drop(inner);
+        (self.notify)();
+        Ok(write_len)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    fn poll(&self) -> VfsResult<VfsPollState> {
+        Ok(VfsPollState {
+            readable: !self.inner.lock().queue.is_empty(),
+            writable: true,
+        })
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// A PS/2 mouse device behaves like `/dev/input/mice`.
+///
+/// Each report is encoded as a 3-byte PS/2 packet: buttons and sign bits,
+/// then X and Y movement clamped to `-127..=127`, with Y positive upwards.
+/// Reads fail with [`VfsError::WouldBlock`] when no packet is pending.
+pub struct MiceDev {
+    queue: Mutex<VecDeque<u8>>,
+    notify: fn(),
+}
+
+impl MiceDev {
+    /// Size of a PS/2 packet.
+    pub const PACKET_SIZE: usize = 3;
+
+    /// Creates a new device with an empty queue.
+    pub const fn new() -> Self {
+        Self::with_notify(|| {})
+    }
+
+    /// Creates a new device with an empty queue, calling `notify` whenever
+    /// packets are queued, e.g. to wake up the blocked readers.
+    pub const fn with_notify(notify: fn()) -> Self {
+        Self {
+            queue: Mutex::new(VecDeque::new()),
+            notify,
+        }
+    }
+
+    /// Queues the PS/2 packet of a mouse report.
+    ///
+    /// The oldest packet is dropped when the queue is full.
+    pub fn report_mouse(&self, report: &MouseReport) {
+        let dx = report.dx.clamp(-127, 127);
+        let dy = (-report.dy).clamp(-127, 127);
+        let mut head = 0x08;
+        head |= report.left as u8;
+        head |= (report.right as u8) << 1;
+        head |= (report.middle as u8) << 2;
+        if dx < 0 {
+            head |= 0x10;
+        }
+        if dy < 0 {
+            head |= 0x20;
+        }
+
+        let mut queue = self.queue.lock();
+        if queue.len() >= MICE_QUEUE_LEN * Self::PACKET_SIZE {
+            queue.drain(..Self::PACKET_SIZE);
+        }
+        queue.extend([head, dx as u8, dy as u8]);
+        drop(queue);
+        (self.notify)();
+    }
+}
+
+impl Default for MiceDev {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VfsNodeOps for MiceDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o660),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut queue = self.queue.lock();
+        if queue.is_empty() {
+            return Err(VfsError::WouldBlock);
+        }
+        let read_len = buf.len().min(queue.len());
+        for (dst, src) in buf.iter_mut().zip(queue.drain(..read_len)) {
+            *dst = src;
+        }
+        Ok(read_len)
+    }
+
+    /// PS/2 commands are accepted but ignored.
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    fn poll(&self) -> VfsResult<VfsPollState> {
+        Ok(VfsPollState {
+            readable: !self.queue.lock().is_empty(),
+            writable: true,
+        })
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
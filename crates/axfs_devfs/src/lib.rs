@@ -7,6 +7,7 @@
 extern crate alloc;
 
 mod dir;
+mod input;
 mod null;
 mod zero;
 
@@ -14,6 +15,9 @@ mod zero;
 mod tests;
 
 pub use self::dir::DirNode;
+pub use self::input::{EvdevDev, InputEvent, MiceDev, MouseReport};
+pub use self::input::{BTN_LEFT, BTN_MIDDLE, BTN_RIGHT, EV_KEY, EV_REL, EV_SYN};
+pub use self::input::{REL_WHEEL, REL_X, REL_Y, SYN_DROPPED, SYN_REPORT};
 pub use self::null::NullDev;
 pub use self::zero::ZeroDev;
 
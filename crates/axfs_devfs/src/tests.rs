@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsError, VfsNodeOps, VfsNodeType, VfsResult};
 
 use crate::*;
 
@@ -113,3 +113,96 @@ fn test_devfs() {
     test_devfs_ops(&devfs).unwrap();
     test_get_parent(&devfs).unwrap();
 }
+
+#[test]
+fn test_input_devs() {
+    use core::time::Duration;
+
+    let devfs = DeviceFileSystem::new();
+    let evdev = Arc::new(EvdevDev::new());
+    let mice = Arc::new(MiceDev::new());
+    let dir_input = devfs.mkdir("input");
+    dir_input.add("event0", evdev.clone());
+    dir_input.add("mice", mice.clone());
+
+    let event0 = devfs.root_dir().lookup("input/event0").unwrap();
+    let mut buf = [0; 8 * InputEvent::SIZE];
+    assert!(!event0.poll().unwrap().readable);
+    assert_eq!(
+        event0.read_at(0, &mut buf).err(),
+        Some(VfsError::WouldBlock)
+    );
+
+    let report = MouseReport {
+        dx: 3,
+        dy: -200,
+        left: true,
+        ..Default::default()
+    };
+    let time = Duration::from_micros(1_500_001);
+    evdev.report_mouse(time, &report);
+    mice.report_mouse(&report);
+    assert!(event0.poll().unwrap().readable);
+    assert_eq!(
+        event0.read_at(0, &mut buf[..InputEvent::SIZE - 1]).err(),
+        Some(VfsError::InvalidInput)
+    );
+
+    assert_eq!(event0.read_at(0, &mut buf).unwrap(), 4 * InputEvent::SIZE);
+    let events: Vec<_> = buf
+        .chunks_exact(InputEvent::SIZE)
+        .take(4)
+        .map(InputEvent::from_bytes)
+        .collect();
+    assert_eq!(
+        events,
+        [
+            InputEvent::new(time, EV_REL, REL_X, 3),
+            InputEvent::new(time, EV_REL, REL_Y, -200),
+            InputEvent::new(time, EV_KEY, BTN_LEFT, 1),
+            InputEvent::new(time, EV_SYN, SYN_REPORT, 0),
+        ]
+    );
+    assert!(!event0.poll().unwrap().readable);
+
+    // Buttons are only reported when they change.
+    evdev.report_mouse(time, &report);
+    assert_eq!(event0.read_at(0, &mut buf).unwrap(), 3 * InputEvent::SIZE);
+
+    let node = devfs.root_dir().lookup("input/mice").unwrap();
+    let mut packet = [0; 4];
+    assert_eq!(node.read_at(0, &mut packet).unwrap(), 3);
+    assert_eq!(packet[..3], [0x09, 3, 127]);
+    assert_eq!(
+        node.read_at(0, &mut packet).err(),
+        Some(VfsError::WouldBlock)
+    );
+}
+
+#[test]
+fn test_input_notify() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::time::Duration;
+
+    static NOTIFIED: AtomicUsize = AtomicUsize::new(0);
+    fn notify() {
+        NOTIFIED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let evdev = EvdevDev::with_notify(notify);
+    let mice = MiceDev::with_notify(notify);
+    let report = MouseReport {
+        dx: 1,
+        ..Default::default()
+    };
+    evdev.report_mouse(Duration::ZERO, &report);
+    assert_eq!(NOTIFIED.load(Ordering::Relaxed), 1);
+    mice.report_mouse(&report);
+    assert_eq!(NOTIFIED.load(Ordering::Relaxed), 2);
+
+    // Injected events wake up the readers as well.
+    let mut buf = [0; InputEvent::SIZE];
+    InputEvent::new(Duration::ZERO, EV_SYN, SYN_REPORT, 0).to_bytes(&mut buf);
+    assert_eq!(evdev.write_at(0, &buf).unwrap(), InputEvent::SIZE);
+    assert_eq!(NOTIFIED.load(Ordering::Relaxed), 3);
+}
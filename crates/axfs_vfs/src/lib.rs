@@ -26,6 +26,7 @@
 //! | [`write_at()`](VfsNodeOps::write_at) | Write data to the file | file |
 //! | [`fsync()`](VfsNodeOps::fsync) | Synchronize the file data to disk | file |
 //! | [`truncate()`](VfsNodeOps::truncate) | Truncate the file | file |
+//! | [`poll()`](VfsNodeOps::poll) | Query the I/O readiness of the file | file |
 //! | [`parent()`](VfsNodeOps::parent) | Get the parent directory | directory |
 //! | [`lookup()`](VfsNodeOps::lookup) | Lookup the node with the given path | directory |
 //! | [`create()`](VfsNodeOps::create) | Create a new node with the given path | directory |
@@ -46,7 +47,9 @@ pub mod path;
 use alloc::sync::Arc;
 use axerrno::{ax_err, AxError, AxResult};
 
-pub use self::structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType};
+pub use self::structs::{
+    FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsPollState,
+};
 
 /// A wrapper of [`Arc<dyn VfsNodeOps>`].
 pub type VfsNodeRef = Arc<dyn VfsNodeOps>;
@@ -122,6 +125,17 @@ pub trait VfsNodeOps: Send + Sync {
         ax_err!(InvalidInput)
     }
 
+    /// Query whether the file can be read or written without blocking.
+    ///
+    /// Regular files are always ready. Nodes whose `read_at` may fail with
+    /// [`WouldBlock`](axerrno::AxError::WouldBlock) should override it.
+    fn poll(&self) -> VfsResult<VfsPollState> {
+        Ok(VfsPollState {
+            readable: true,
+            writable: true,
+        })
+    }
+
     // directory operations:
 
     /// Get the parent directory of this directory.
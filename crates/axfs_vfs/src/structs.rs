@@ -65,6 +65,17 @@ pub enum VfsNodeType {
     Socket = 0o14,
 }
 
+/// I/O readiness of a node, returned by [`VfsNodeOps::poll`].
+///
+/// [`VfsNodeOps::poll`]: crate::VfsNodeOps::poll
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VfsPollState {
+    /// Data can be read without blocking.
+    pub readable: bool,
+    /// Data can be written without blocking.
+    pub writable: bool,
+}
+
 /// Directory entry.
 pub struct VfsDirEntry {
     d_type: VfsNodeType,
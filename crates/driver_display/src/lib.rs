@@ -2,6 +2,12 @@
 
 #![no_std]
 
+#[cfg(feature = "ssd1306")]
+extern crate alloc;
+
+#[cfg(feature = "ssd1306")]
+pub mod ssd1306;
+
 #[doc(no_inline)]
 pub use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
 
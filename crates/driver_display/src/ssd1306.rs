@@ -0,0 +1,217 @@
+//! Driver for SSD1306 128x64 monochrome OLED panels on an I2C bus.
+//!
+//! The framebuffer uses the same 32-bit BGRA layout as the other display
+//! devices, so applications render to it unchanged. On [`flush`], each pixel
+//! is lit if its luminance is at least half the full scale, the pixels are
+//! packed into the eight 128-byte pages of the panel RAM, and only the columns
+//! that changed since the previous flush are sent.
+//!
+//! [`flush`]: DisplayDriverOps::flush
+
+use alloc::boxed::Box;
+use alloc::vec;
+
+use embedded_hal::i2c::I2c;
+
+use crate::{BaseDriverOps, DevError, DevResult, DeviceType};
+use crate::{DisplayDriverOps, DisplayInfo, FrameBuffer};
+
+/// Default 7-bit address of the panel (`SA0` low).
+pub const SSD1306_ADDRESS: u8 = 0x3C;
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+const PAGES: usize = HEIGHT / 8;
+const BYTES_PER_PIXEL: usize = 4;
+const FB_SIZE: usize = WIDTH * HEIGHT * BYTES_PER_PIXEL;
+
+// Control bytes: the rest of the transfer is commands or display data.
+const CTRL_CMD: u8 = 0x00;
+const CTRL_DATA: u8 = 0x40;
+
+// Commands.
+const CMD_SET_CONTRAST: u8 = 0x81;
+const CMD_DISPLAY_RAM: u8 = 0xA4;
+const CMD_NORMAL: u8 = 0xA6;
+const CMD_INVERT: u8 = 0xA7;
+const CMD_DISPLAY_OFF: u8 = 0xAE;
+const CMD_DISPLAY_ON: u8 = 0xAF;
+const CMD_PAGE_START: u8 = 0xB0;
+const CMD_COLUMN_LOW: u8 = 0x00;
+const CMD_COLUMN_HIGH: u8 = 0x10;
+
+/// Power-on sequence for a 128x64 panel with the internal charge pump, in
+/// page addressing mode.
+#[rustfmt::skip]
+const INIT_SEQUENCE: &[u8] = &[
+    CMD_DISPLAY_OFF,
+    0xD5, 0x80, // clock divide ratio and oscillator frequency
+    0xA8, 0x3F, // multiplex ratio: 64
+    0xD3, 0x00, // display offset: 0
+    0x40,       // display start line: 0
+    0x8D, 0x14, // enable the charge pump
+    0x20, 0x02, // page addressing mode
+    0xA1,       // column 127 is mapped to SEG0
+    0xC8,       // scan from COM63 to COM0
+    0xDA, 0x12, // alternative COM pin configuration
+    CMD_SET_CONTRAST, 0xCF,
+    0xD9, 0xF1, // pre-charge period
+    0xDB, 0x40, // VCOMH deselect level
+    CMD_DISPLAY_RAM,
+    CMD_NORMAL,
+    CMD_DISPLAY_ON,
+];
+
+/// An SSD1306 panel on the I2C bus `I`.
+pub struct Ssd1306<I> {
+    i2c: I,
+    address: u8,
+    info: DisplayInfo,
+    /// The page data shown on the panel, `None` if unknown.
+    shown: Option<[[u8; WIDTH]; PAGES]>,
+}
+
+impl<I: I2c> Ssd1306<I> {
+    /// Initializes the panel at `address` and clears it.
+    pub fn new(i2c: I, address: u8) -> DevResult<Self> {
+        let fb: &'static mut [u8] = Box::leak(vec![0u8; FB_SIZE].into_boxed_slice());
+        let mut dev = Self {
+            i2c,
+            address,
+            info: DisplayInfo {
+                width: WIDTH as u32,
+                height: HEIGHT as u32,
+                fb_base_vaddr: fb.as_mut_ptr() as usize,
+                fb_size: FB_SIZE,
+            },
+            shown: None,
+        };
+        dev.command(INIT_SEQUENCE)?;
+        dev.flush_pages()?;
+        Ok(dev)
+    }
+
+    /// Sets the contrast, from 0 (dimmest) to 255.
+    pub fn set_contrast(&mut self, contrast: u8) -> DevResult {
+        self.command(&[CMD_SET_CONTRAST, contrast])
+    }
+
+    /// Shows lit pixels dark and unlit pixels bright, or back to normal.
+    pub fn set_invert(&mut self, invert: bool) -> DevResult {
+        self.command(&[if invert { CMD_INVERT } else { CMD_NORMAL }])
+    }
+
+    /// Turns the panel on or off (sleep mode). The RAM content is kept.
+    pub fn set_display_on(&mut self, on: bool) -> DevResult {
+        self.command(&[if on { CMD_DISPLAY_ON } else { CMD_DISPLAY_OFF }])
+    }
+
+    /// Forgets what the panel shows, so that the next flush sends all pages.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    fn command(&mut self, cmds: &[u8]) -> DevResult {
+        let mut buf = [0u8; 32];
+        buf[0] = CTRL_CMD;
+        buf[1..=cmds.len()].copy_from_slice(cmds);
+        self.i2c
+            .write(self.address, &buf[..=cmds.len()])
+            .map_err(|_| DevError::Io)
+    }
+
+    fn data(&mut self, data: &[u8]) -> DevResult {
+        let mut buf = [0u8; WIDTH + 1];
+        buf[0] = CTRL_DATA;
+        buf[1..=data.len()].copy_from_slice(data);
+        self.i2c
+            .write(self.address, &buf[..=data.len()])
+            .map_err(|_| DevError::Io)
+    }
+
+    /// Packs the framebuffer into panel pages: bit `n` of byte `x` of page
+    /// `p` is the pixel at `(x, 8 * p + n)`.
+    fn pack(&self) -> [[u8; WIDTH]; PAGES] {
+        let fb = unsafe {
+            core::slice::from_raw_parts(self.info.fb_base_vaddr as *const u8, self.info.fb_size)
+        };
+        let mut pages = [[0u8; WIDTH]; PAGES];
+        for (y, row) in fb.chunks_exact(WIDTH * BYTES_PER_PIXEL).enumerate() {
+            for (x, px) in row.chunks_exact(BYTES_PER_PIXEL).enumerate() {
+                let (b, g, r) = (px[0] as u32, px[1] as u32, px[2] as u32);
+                if (r * 77 + g * 150 + b * 29) >> 8 >= 0x80 {
+                    pages[y / 8][x] |= 1 << (y % 8);
+                }
+            }
+        }
+        pages
+    }
+
+    /// Sends the columns that changed in each page.
+    fn flush_pages(&mut self) -> DevResult {
+        let pages = self.pack();
+        for (page, data) in pages.iter().enumerate() {
+            let (first, last) = match &self.shown {
+                Some(shown) => {
+                    let changed = |x: &usize| data[*x] != shown[page][*x];
+                    match (0..WIDTH).find(changed) {
+                        Some(first) => (first, (0..WIDTH).rfind(changed).unwrap()),
+                        None => continue,
+                    }
+                }
+                None => (0, WIDTH - 1),
+            };
+            self.command(&[
+                CMD_PAGE_START | page as u8,
+                CMD_COLUMN_LOW | (first & 0xF) as u8,
+                CMD_COLUMN_HIGH | (first >> 4) as u8,
+            ])?;
+            if let Err(e) = self.data(&data[first..=last]) {
+                self.shown = None;
+                return Err(e);
+            }
+        }
+        self.shown = Some(pages);
+        Ok(())
+    }
+}
+
+impl<I> Drop for Ssd1306<I> {
+    fn drop(&mut self) {
+        let fb = core::ptr::slice_from_raw_parts_mut(
+            self.info.fb_base_vaddr as *mut u8,
+            self.info.fb_size,
+        );
+        drop(unsafe { Box::from_raw(fb) });
+    }
+}
+
+impl<I: I2c + Send + Sync> BaseDriverOps for Ssd1306<I> {
+    fn device_name(&self) -> &str {
+        "ssd1306"
+    }
+
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Display
+    }
+}
+
+impl<I: I2c + Send + Sync> DisplayDriverOps for Ssd1306<I> {
+    fn info(&self) -> DisplayInfo {
+        self.info
+    }
+
+    fn fb(&self) -> FrameBuffer<'_> {
+        unsafe {
+            FrameBuffer::from_raw_parts_mut(self.info.fb_base_vaddr as *mut u8, self.info.fb_size)
+        }
+    }
+
+    fn need_flush(&self) -> bool {
+        true
+    }
+
+    fn flush(&mut self) -> DevResult {
+        self.flush_pages()
+    }
+}
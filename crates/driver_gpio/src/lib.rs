@@ -0,0 +1,221 @@
+//! GPIO pin drivers.
+//!
+//! A controller hands out its pins one at a time; each pin implements
+//! [`GpioPin`]: direction, level, pull resistor and interrupts. Pin
+//! interrupts are delivered through `axhal::irq` to a [`GpioIrqHandler`],
+//! called in interrupt context with the index of the pin.
+//!
+//! Controllers:
+//!
+//! - [`phytium::PhytiumGpio`] (feature `phytium`): GPIO0–GPIO5 of the Phytium
+//!   SoCs. Pins are routed to their pads and pulled through the IOPAD.
+//! - [`pl061::Pl061`] (feature `pl061`): the ARM PrimeCell PL061, as found on
+//!   QEMU `virt`.
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(all(feature = "phytium", feature = "pl061")), allow(dead_code))]
+
+#[cfg(feature = "phytium")]
+pub mod phytium;
+#[cfg(feature = "pl061")]
+pub mod pl061;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use axsync::spin::SpinNoIrq;
+
+/// Direction of a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+/// Pull resistor of a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+/// Condition raising a pin interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    RisingEdge,
+    FallingEdge,
+    BothEdges,
+    /// Raised as long as the pin is high.
+    HighLevel,
+    /// Raised as long as the pin is low.
+    LowLevel,
+}
+
+impl Trigger {
+    fn is_level(self) -> bool {
+        matches!(self, Self::HighLevel | Self::LowLevel)
+    }
+}
+
+/// Handler of a pin interrupt, called in interrupt context with the index of
+/// the pin on its controller.
+///
+/// The handler of a level-triggered interrupt must clear the condition on the
+/// pin, or disable the interrupt, before returning.
+pub type GpioIrqHandler = fn(pin: u32);
+
+/// Errors of GPIO operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioError {
+    /// There is no controller with this index.
+    InvalidController(usize),
+    /// The controller has no pin with this index.
+    InvalidPin(u32),
+    /// The pin is already owned.
+    InUse,
+    /// The operation is not supported by the controller.
+    Unsupported,
+    /// The controller interrupt could not be registered.
+    IrqUnavailable,
+}
+
+/// Result type of GPIO operations.
+pub type GpioResult<T = ()> = Result<T, GpioError>;
+
+/// A GPIO pin owned by its user.
+pub trait GpioPin {
+    /// Index of the pin on its controller.
+    fn index(&self) -> u32;
+
+    /// Makes the pin an input or an output.
+    fn set_direction(&mut self, dir: Direction) -> GpioResult;
+
+    /// The current direction of the pin.
+    fn direction(&self) -> Direction;
+
+    /// Reads the level of the pin, `true` if high.
+    fn read(&self) -> bool;
+
+    /// Sets the level driven by the pin, `true` for high. On an input, the
+    /// level is driven once the pin becomes an output.
+    fn write(&mut self, high: bool) -> GpioResult;
+
+    /// Inverts the level driven by the pin.
+    fn toggle(&mut self) -> GpioResult;
+
+    /// Sets the pull resistor of the pin.
+    fn set_pull(&mut self, pull: Pull) -> GpioResult;
+
+    /// Calls `handler` whenever `trigger` occurs on the pin, replacing any
+    /// previous handler. The pin should be an input.
+    fn enable_irq(&mut self, trigger: Trigger, handler: GpioIrqHandler) -> GpioResult;
+
+    /// Stops the interrupts of the pin.
+    fn disable_irq(&mut self);
+}
+
+/// Ownership and interrupt handlers of the pins of one controller.
+struct PinTable<const N: usize> {
+    owned: SpinNoIrq<u32>,
+    handlers: SpinNoIrq<[Option<GpioIrqHandler>; N]>,
+    /// Pins interrupting on both edges, for controllers that emulate it.
+    both_edges: AtomicU32,
+    /// Serializes read-modify-write cycles on the registers shared by pins.
+    regs_lock: SpinNoIrq<()>,
+}
+
+impl<const N: usize> PinTable<N> {
+    const fn new() -> Self {
+        Self {
+            owned: SpinNoIrq::new(0),
+            both_edges: AtomicU32::new(0),
+            regs_lock: SpinNoIrq::new(()),
+            handlers: SpinNoIrq::new([None; N]),
+        }
+    }
+
+    fn claim(&self, pin: u32) -> GpioResult {
+        if pin as usize >= N {
+            return Err(GpioError::InvalidPin(pin));
+        }
+        let mut owned = self.owned.lock();
+        if *owned & (1 << pin) != 0 {
+            return Err(GpioError::InUse);
+        }
+        *owned |= 1 << pin;
+        Ok(())
+    }
+
+    fn release(&self, pin: u32) {
+        self.handlers.lock()[pin as usize] = None;
+        *self.owned.lock() &= !(1 << pin);
+    }
+
+    fn set_handler(&self, pin: u32, handler: Option<GpioIrqHandler>) {
+        self.handlers.lock()[pin as usize] = handler;
+    }
+
+    fn both_edges(&self) -> u32 {
+        self.both_edges.load(Ordering::Relaxed)
+    }
+
+    fn set_both_edges(&self, pin: u32, set: bool) {
+        if set {
+            self.both_edges.fetch_or(1 << pin, Ordering::Relaxed);
+        } else {
+            self.both_edges.fetch_and(!(1 << pin), Ordering::Relaxed);
+        }
+    }
+
+    /// Calls the handlers of the pins set in `pending`.
+    fn dispatch(&self, pending: u32) {
+        let handlers = *self.handlers.lock();
+        for (pin, handler) in handlers.iter().enumerate() {
+            if pending & (1 << pin) != 0 {
+                match handler {
+                    Some(handler) => handler(pin as u32),
+                    None => log::trace!("spurious interrupt on GPIO pin {}", pin),
+                }
+            }
+        }
+    }
+}
+
+struct Regs {
+    base: usize,
+}
+
+impl Regs {
+    #[cfg(not(test))]
+    fn new(paddr: usize) -> Self {
+        use axhal::mem::{phys_to_virt, PhysAddr};
+        Self {
+            base: phys_to_virt(PhysAddr::from(paddr)).as_usize(),
+        }
+    }
+
+    /// Tests pass the address of a register block in memory.
+    #[cfg(test)]
+    fn new(addr: usize) -> Self {
+        Self { base: addr }
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.base + offset) as *mut u32, value) }
+    }
+
+    /// Sets or clears bit `bit` of the register at `offset`.
+    fn set_bit(&self, offset: usize, bit: u32, set: bool) {
+        let value = self.read(offset);
+        let value = if set {
+            value | (1 << bit)
+        } else {
+            value & !(1 << bit)
+        };
+        self.write(offset, value);
+    }
+}
@@ -0,0 +1,286 @@
+//! GPIO controllers of the Phytium SoCs.
+//!
+//! GPIO0–GPIO5 are DesignWare APB GPIO blocks with 16 pins on port A. A pin
+//! reaches the outside through a pad of the IOPAD, which must select the GPIO
+//! function of the pad and holds its pull resistor: give the pad of a pin with
+//! [`PhytiumGpio::pin_with_pad`] to configure it, or use [`PhytiumGpio::pin`]
+//! if the firmware already routed the pin.
+//!
+//! The controller cannot interrupt on both edges: the polarity of such pins is
+//! flipped after every edge.
+
+use axsync::spin::SpinNoIrq;
+use driver_i2c::driver_iic::io::{fiopad_select_func, fiopad_select_pull, FIOPadPull};
+
+use crate::{Direction, GpioError, GpioIrqHandler, GpioPin, GpioResult, PinTable, Pull};
+use crate::{Regs, Trigger};
+
+/// Number of GPIO controllers.
+pub const CONTROLLER_COUNT: usize = 6;
+/// Number of pins of a controller.
+pub const PIN_COUNT: u32 = 16;
+
+// Registers.
+const SWPORTA_DR: usize = 0x00;
+const SWPORTA_DDR: usize = 0x04;
+const EXT_PORTA: usize = 0x08;
+const INTEN: usize = 0x18;
+const INTMASK: usize = 0x1C;
+const INTTYPE_LEVEL: usize = 0x20;
+const INT_POLARITY: usize = 0x24;
+const INTSTATUS: usize = 0x28;
+const PORTA_EOI: usize = 0x38;
+
+struct GpioInfo {
+    base: usize,
+    /// First interrupt line, and whether each pin has its own line following
+    /// it rather than all pins sharing it.
+    irq: (usize, bool),
+}
+
+const GPIO_TABLE: [GpioInfo; CONTROLLER_COUNT] = [
+    GpioInfo {
+        base: 0x2803_4000,
+        irq: (140, true),
+    },
+    GpioInfo {
+        base: 0x2803_5000,
+        irq: (156, true),
+    },
+    GpioInfo {
+        base: 0x2803_6000,
+        irq: (172, true),
+    },
+    GpioInfo {
+        base: 0x2803_7000,
+        irq: (188, false),
+    },
+    GpioInfo {
+        base: 0x2803_8000,
+        irq: (189, false),
+    },
+    GpioInfo {
+        base: 0x2803_9000,
+        irq: (190, false),
+    },
+];
+
+static PINS: [PinTable<{ PIN_COUNT as usize }>; CONTROLLER_COUNT] =
+    [const { PinTable::new() }; CONTROLLER_COUNT];
+/// Interrupt lines of each controller with the handler installed, as bits
+/// of their offsets from the first line.
+static IRQ_LINES: [SpinNoIrq<u32>; CONTROLLER_COUNT] =
+    [const { SpinNoIrq::new(0) }; CONTROLLER_COUNT];
+
+const IRQ_HANDLERS: [fn(); CONTROLLER_COUNT] = [
+    || handle_irq(0),
+    || handle_irq(1),
+    || handle_irq(2),
+    || handle_irq(3),
+    || handle_irq(4),
+    || handle_irq(5),
+];
+
+fn regs_of(id: usize) -> Regs {
+    Regs::new(GPIO_TABLE[id].base)
+}
+
+/// Installs the interrupt handlers of controller `id`, once.
+///
+/// `axhal` cannot remove a handler: the lines installed before a failure
+/// stay installed, and are skipped on the next call.
+fn register_irq(id: usize) -> bool {
+    let (first, per_pin) = GPIO_TABLE[id].irq;
+    let lines = if per_pin { PIN_COUNT as usize } else { 1 };
+    let mut registered = IRQ_LINES[id].lock();
+    for line in 0..lines {
+        if *registered & (1 << line) != 0 {
+            continue;
+        }
+        if !axhal::irq::register_handler(first + line, IRQ_HANDLERS[id]) {
+            log::warn!("GPIO{}: IRQ {} unavailable", id, first + line);
+            return false;
+        }
+        *registered |= 1 << line;
+    }
+    true
+}
+
+fn handle_irq(id: usize) {
+    let regs = regs_of(id);
+    let pending = {
+        let _guard = PINS[id].regs_lock.lock();
+        let pending = regs.read(INTSTATUS);
+        // Only edge interrupts are latched.
+        regs.write(PORTA_EOI, pending & regs.read(INTTYPE_LEVEL));
+        let both = pending & PINS[id].both_edges();
+        if both != 0 {
+            // Wait for the opposite edge of the level just seen.
+            let level = regs.read(EXT_PORTA);
+            let polarity = regs.read(INT_POLARITY);
+            regs.write(INT_POLARITY, (polarity & !both) | (!level & both));
+        }
+        pending
+    };
+    PINS[id].dispatch(pending);
+}
+
+/// The IOPAD pad of a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pad {
+    /// Offset of the pad registers from the IOPAD base.
+    pub offset: usize,
+    /// Function of the pad selecting the GPIO pin.
+    pub func: u32,
+}
+
+/// One of the GPIO0–GPIO5 controllers.
+#[derive(Debug, Clone, Copy)]
+pub struct PhytiumGpio {
+    id: usize,
+}
+
+impl PhytiumGpio {
+    /// The controller GPIO`id`.
+    pub fn new(id: usize) -> GpioResult<Self> {
+        if id >= CONTROLLER_COUNT {
+            return Err(GpioError::InvalidController(id));
+        }
+        Ok(Self { id })
+    }
+
+    /// Index of the controller.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Takes pin `index`, whose pad is already routed to it.
+    pub fn pin(&self, index: u32) -> GpioResult<PhytiumPin> {
+        PINS[self.id].claim(index)?;
+        Ok(PhytiumPin {
+            id: self.id,
+            index,
+            pad: None,
+            regs: regs_of(self.id),
+        })
+    }
+
+    /// Takes pin `index` and routes `pad` to it.
+    pub fn pin_with_pad(&self, index: u32, pad: Pad) -> GpioResult<PhytiumPin> {
+        let mut pin = self.pin(index)?;
+        fiopad_select_func(pad.offset, pad.func);
+        pin.pad = Some(pad);
+        Ok(pin)
+    }
+}
+
+/// A pin of a [`PhytiumGpio`] controller, released when dropped.
+pub struct PhytiumPin {
+    id: usize,
+    index: u32,
+    pad: Option<Pad>,
+    regs: Regs,
+}
+
+impl PhytiumPin {
+    /// Index of the controller of the pin.
+    pub fn controller(&self) -> usize {
+        self.id
+    }
+
+    /// The pad routed to the pin, if known.
+    pub fn pad(&self) -> Option<Pad> {
+        self.pad
+    }
+
+    fn set_bit(&self, offset: usize, set: bool) {
+        let _guard = PINS[self.id].regs_lock.lock();
+        self.regs.set_bit(offset, self.index, set);
+    }
+}
+
+impl GpioPin for PhytiumPin {
+    fn index(&self) -> u32 {
+        self.index
+    }
+
+    fn set_direction(&mut self, dir: Direction) -> GpioResult {
+        self.set_bit(SWPORTA_DDR, dir == Direction::Output);
+        Ok(())
+    }
+
+    fn direction(&self) -> Direction {
+        match self.regs.read(SWPORTA_DDR) & (1 << self.index) {
+            0 => Direction::Input,
+            _ => Direction::Output,
+        }
+    }
+
+    fn read(&self) -> bool {
+        self.regs.read(EXT_PORTA) & (1 << self.index) != 0
+    }
+
+    fn write(&mut self, high: bool) -> GpioResult {
+        self.set_bit(SWPORTA_DR, high);
+        Ok(())
+    }
+
+    fn toggle(&mut self) -> GpioResult {
+        let _guard = PINS[self.id].regs_lock.lock();
+        let value = self.regs.read(SWPORTA_DR);
+        self.regs.write(SWPORTA_DR, value ^ (1 << self.index));
+        Ok(())
+    }
+
+    fn set_pull(&mut self, pull: Pull) -> GpioResult {
+        let pad = self.pad.ok_or(GpioError::Unsupported)?;
+        let pull = match pull {
+            Pull::None => FIOPadPull::None,
+            Pull::Up => FIOPadPull::Up,
+            Pull::Down => FIOPadPull::Down,
+        };
+        fiopad_select_pull(pad.offset, pull);
+        Ok(())
+    }
+
+    fn enable_irq(&mut self, trigger: Trigger, handler: GpioIrqHandler) -> GpioResult {
+        self.disable_irq();
+        if !register_irq(self.id) {
+            return Err(GpioError::IrqUnavailable);
+        }
+        let table = &PINS[self.id];
+        table.set_handler(self.index, Some(handler));
+        let _guard = table.regs_lock.lock();
+        let (bit, regs) = (self.index, &self.regs);
+        let rising = match trigger {
+            Trigger::BothEdges => !self.read(),
+            Trigger::RisingEdge | Trigger::HighLevel => true,
+            Trigger::FallingEdge | Trigger::LowLevel => false,
+        };
+        table.set_both_edges(bit, trigger == Trigger::BothEdges);
+        regs.set_bit(INTTYPE_LEVEL, bit, !trigger.is_level());
+        regs.set_bit(INT_POLARITY, bit, rising);
+        regs.write(PORTA_EOI, 1 << bit);
+        regs.set_bit(INTEN, bit, true);
+        regs.set_bit(INTMASK, bit, false);
+        Ok(())
+    }
+
+    fn disable_irq(&mut self) {
+        let table = &PINS[self.id];
+        {
+            let _guard = table.regs_lock.lock();
+            self.regs.set_bit(INTMASK, self.index, true);
+            self.regs.set_bit(INTEN, self.index, false);
+            table.set_both_edges(self.index, false);
+        }
+        table.set_handler(self.index, None);
+    }
+}
+
+impl Drop for PhytiumPin {
+    fn drop(&mut self) {
+        self.disable_irq();
+        PINS[self.id].release(self.index);
+    }
+}
@@ -0,0 +1,314 @@
+//! ARM PrimeCell PL061 GPIO controller.
+//!
+//! A PL061 has 8 pins and no pull resistors. On QEMU `virt`, pin 3 of the
+//! controller at [`QEMU_VIRT_BASE`] is the power button: it rises on
+//! `system_powerdown` in the QEMU monitor.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axsync::spin::SpinNoIrq;
+
+use crate::{Direction, GpioError, GpioIrqHandler, GpioPin, GpioResult, PinTable, Pull};
+use crate::{Regs, Trigger};
+
+/// Number of pins of a controller.
+pub const PIN_COUNT: u32 = 8;
+/// Base address of the PL061 of QEMU `virt`.
+pub const QEMU_VIRT_BASE: usize = 0x0903_0000;
+/// Interrupt of the PL061 of QEMU `virt` (SPI 7).
+pub const QEMU_VIRT_IRQ: usize = 39;
+
+/// Maximum number of PL061 controllers in use.
+const MAX_CONTROLLERS: usize = 4;
+
+// Registers. The data register is mirrored over 0x000–0x3FC: bits 9:2 of
+// the offset select the pins accessed.
+const GPIODIR: usize = 0x400;
+const GPIOIS: usize = 0x404;
+const GPIOIBE: usize = 0x408;
+const GPIOIEV: usize = 0x40C;
+const GPIOIE: usize = 0x410;
+const GPIOMIS: usize = 0x418;
+const GPIOIC: usize = 0x41C;
+
+/// Base addresses of the controllers in use, 0 for a free slot.
+static BASES: [AtomicUsize; MAX_CONTROLLERS] = [const { AtomicUsize::new(0) }; MAX_CONTROLLERS];
+/// Interrupt of each slot, once registered.
+static IRQS: [SpinNoIrq<Option<usize>>; MAX_CONTROLLERS] =
+    [const { SpinNoIrq::new(None) }; MAX_CONTROLLERS];
+static PINS: [PinTable<{ PIN_COUNT as usize }>; MAX_CONTROLLERS] =
+    [const { PinTable::new() }; MAX_CONTROLLERS];
+
+const IRQ_HANDLERS: [fn(); MAX_CONTROLLERS] = [
+    || handle_irq(0),
+    || handle_irq(1),
+    || handle_irq(2),
+    || handle_irq(3),
+];
+
+fn handle_irq(slot: usize) {
+    let regs = Regs::new(BASES[slot].load(Ordering::Acquire));
+    let pending = regs.read(GPIOMIS);
+    // Level interrupts are not latched: clearing them has no effect.
+    regs.write(GPIOIC, pending);
+    PINS[slot].dispatch(pending);
+}
+
+/// A PL061 controller.
+#[derive(Debug, Clone, Copy)]
+pub struct Pl061 {
+    slot: usize,
+    irq: usize,
+}
+
+impl Pl061 {
+    /// The controller at physical address `base`, raising interrupt `irq`.
+    ///
+    /// Calls with the same `base` return the same controller.
+    pub fn new(base: usize, irq: usize) -> GpioResult<Self> {
+        for (slot, slot_base) in BASES.iter().enumerate() {
+            match slot_base.compare_exchange(0, base, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => {
+                    // Mask everything left enabled by a previous user.
+                    Regs::new(base).write(GPIOIE, 0);
+                    return Ok(Self { slot, irq });
+                }
+                Err(current) if current == base => return Ok(Self { slot, irq }),
+                Err(_) => {}
+            }
+        }
+        Err(GpioError::InUse)
+    }
+
+    /// The controller of QEMU `virt`.
+    pub fn qemu_virt() -> GpioResult<Self> {
+        Self::new(QEMU_VIRT_BASE, QEMU_VIRT_IRQ)
+    }
+
+    /// Takes pin `index`.
+    pub fn pin(&self, index: u32) -> GpioResult<Pl061Pin> {
+        PINS[self.slot].claim(index)?;
+        Ok(Pl061Pin {
+            slot: self.slot,
+            irq: self.irq,
+            index,
+            regs: Regs::new(BASES[self.slot].load(Ordering::Acquire)),
+        })
+    }
+
+    /// Installs the interrupt handler of the controller, once.
+    fn register_irq(slot: usize, irq: usize) -> bool {
+        let mut registered = IRQS[slot].lock();
+        if registered.is_some() {
+            return true;
+        }
+        if !axhal::irq::register_handler(irq, IRQ_HANDLERS[slot]) {
+            log::warn!("PL061: IRQ {} unavailable", irq);
+            return false;
+        }
+        *registered = Some(irq);
+        true
+    }
+}
+
+/// A pin of a [`Pl061`] controller, released when dropped.
+pub struct Pl061Pin {
+    slot: usize,
+    irq: usize,
+    index: u32,
+    regs: Regs,
+}
+
+impl Pl061Pin {
+    /// Offset of the data register giving access to this pin only.
+    fn data(&self) -> usize {
+        1 << (self.index + 2)
+    }
+
+    fn set_bit(&self, offset: usize, set: bool) {
+        let _guard = PINS[self.slot].regs_lock.lock();
+        self.regs.set_bit(offset, self.index, set);
+    }
+}
+
+impl GpioPin for Pl061Pin {
+    fn index(&self) -> u32 {
+        self.index
+    }
+
+    fn set_direction(&mut self, dir: Direction) -> GpioResult {
+        self.set_bit(GPIODIR, dir == Direction::Output);
+        Ok(())
+    }
+
+    fn direction(&self) -> Direction {
+        match self.regs.read(GPIODIR) & (1 << self.index) {
+            0 => Direction::Input,
+            _ => Direction::Output,
+        }
+    }
+
+    fn read(&self) -> bool {
+        self.regs.read(self.data()) != 0
+    }
+
+    fn write(&mut self, high: bool) -> GpioResult {
+        // The masked data register needs no read-modify-write.
+        self.regs.write(self.data(), if high { 0xFF } else { 0 });
+        Ok(())
+    }
+
+    fn toggle(&mut self) -> GpioResult {
+        let _guard = PINS[self.slot].regs_lock.lock();
+        let value = self.regs.read(self.data());
+        self.regs.write(self.data(), !value & 0xFF);
+        Ok(())
+    }
+
+    fn set_pull(&mut self, _pull: Pull) -> GpioResult {
+        Err(GpioError::Unsupported)
+    }
+
+    fn enable_irq(&mut self, trigger: Trigger, handler: GpioIrqHandler) -> GpioResult {
+        self.disable_irq();
+        if !Pl061::register_irq(self.slot, self.irq) {
+            return Err(GpioError::IrqUnavailable);
+        }
+        let table = &PINS[self.slot];
+        table.set_handler(self.index, Some(handler));
+        let _guard = table.regs_lock.lock();
+        let (bit, regs) = (self.index, &self.regs);
+        let high = matches!(trigger, Trigger::RisingEdge | Trigger::HighLevel);
+        regs.set_bit(GPIOIS, bit, trigger.is_level());
+        regs.set_bit(GPIOIBE, bit, trigger == Trigger::BothEdges);
+        regs.set_bit(GPIOIEV, bit, high);
+        regs.write(GPIOIC, 1 << bit);
+        regs.set_bit(GPIOIE, bit, true);
+        Ok(())
+    }
+
+    fn disable_irq(&mut self) {
+        self.set_bit(GPIOIE, false);
+        PINS[self.slot].set_handler(self.index, None);
+    }
+}
+
+impl Drop for Pl061Pin {
+    fn drop(&mut self) {
+        self.disable_irq();
+        PINS[self.slot].release(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicU32;
+
+    use super::*;
+
+    /// Registers of a controller, backed by memory. Data reads return the
+    /// last value written at the same offset.
+    struct MockRegs(Box<[u32; GPIOIC / 4 + 1]>);
+
+    impl MockRegs {
+        fn new() -> Self {
+            Self(Box::new([0; GPIOIC / 4 + 1]))
+        }
+
+        fn base(&self) -> usize {
+            self.0.as_ptr() as usize
+        }
+
+        fn get(&self, offset: usize) -> u32 {
+            Regs::new(self.base()).read(offset)
+        }
+
+        fn set(&self, offset: usize, value: u32) {
+            Regs::new(self.base()).write(offset, value)
+        }
+    }
+
+    #[test]
+    fn test_pins() {
+        let regs = MockRegs::new();
+        regs.set(GPIOIE, 0xFF);
+        let gpio = Pl061::new(regs.base(), QEMU_VIRT_IRQ).unwrap();
+        // Interrupts left enabled are masked.
+        assert_eq!(regs.get(GPIOIE), 0);
+        assert_eq!(
+            Pl061::new(regs.base(), QEMU_VIRT_IRQ).unwrap().slot,
+            gpio.slot
+        );
+
+        let mut pin = gpio.pin(3).unwrap();
+        assert_eq!(gpio.pin(3).err(), Some(GpioError::InUse));
+        assert_eq!(
+            gpio.pin(PIN_COUNT).err(),
+            Some(GpioError::InvalidPin(PIN_COUNT))
+        );
+        let mut other = gpio.pin(0).unwrap();
+
+        pin.set_direction(Direction::Output).unwrap();
+        other.set_direction(Direction::Output).unwrap();
+        other.set_direction(Direction::Input).unwrap();
+        assert_eq!(regs.get(GPIODIR), 1 << 3);
+        assert_eq!(pin.direction(), Direction::Output);
+        assert_eq!(other.direction(), Direction::Input);
+
+        // Each pin is accessed through its own mask of the data register.
+        pin.write(true).unwrap();
+        assert_eq!(regs.get(1 << 5), 0xFF);
+        assert!(pin.read());
+        assert!(!other.read());
+        pin.toggle().unwrap();
+        assert_eq!(regs.get(1 << 5), 0);
+        assert!(!pin.read());
+        other.write(true).unwrap();
+        assert_eq!(regs.get(1 << 2), 0xFF);
+        assert_eq!(regs.get(1 << 5), 0);
+
+        assert_eq!(pin.set_pull(Pull::Up), Err(GpioError::Unsupported));
+        drop(pin);
+        assert!(gpio.pin(3).is_ok());
+    }
+
+    #[test]
+    fn test_irq() {
+        static FIRED: AtomicU32 = AtomicU32::new(0);
+        fn handler(pin: u32) {
+            FIRED.fetch_or(1 << pin, Ordering::Relaxed);
+        }
+
+        let regs = MockRegs::new();
+        let gpio = Pl061::new(regs.base(), QEMU_VIRT_IRQ).unwrap();
+        // The dummy platform cannot install handlers: pretend it is done.
+        *IRQS[gpio.slot].lock() = Some(QEMU_VIRT_IRQ);
+        let mut pin = gpio.pin(6).unwrap();
+
+        pin.enable_irq(Trigger::RisingEdge, handler).unwrap();
+        let bit = 1 << 6;
+        assert_eq!(regs.get(GPIOIS) & bit, 0);
+        assert_eq!(regs.get(GPIOIBE) & bit, 0);
+        assert_eq!(regs.get(GPIOIEV) & bit, bit);
+        assert_eq!(regs.get(GPIOIC), bit);
+        assert_eq!(regs.get(GPIOIE), bit);
+
+        pin.enable_irq(Trigger::BothEdges, handler).unwrap();
+        assert_eq!(regs.get(GPIOIBE) & bit, bit);
+        pin.enable_irq(Trigger::LowLevel, handler).unwrap();
+        assert_eq!(regs.get(GPIOIS) & bit, bit);
+        assert_eq!(regs.get(GPIOIBE) & bit, 0);
+        assert_eq!(regs.get(GPIOIEV) & bit, 0);
+
+        // Pending interrupts are cleared and dispatched to their pins.
+        regs.set(GPIOMIS, bit | 1);
+        handle_irq(gpio.slot);
+        assert_eq!(regs.get(GPIOIC), bit | 1);
+        assert_eq!(FIRED.load(Ordering::Relaxed), bit);
+
+        pin.disable_irq();
+        assert_eq!(regs.get(GPIOIE), 0);
+        handle_irq(gpio.slot);
+        assert_eq!(FIRED.load(Ordering::Relaxed), bit);
+    }
+}
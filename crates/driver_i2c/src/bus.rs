@@ -0,0 +1,567 @@
+//! Safe, instance-based I2C master API over the Phytium MIO controllers.
+//!
+//! Each MIO block (MIO0–MIO4) hosts a DesignWare I2C controller. An
+//! [`I2cBus`] owns one of them: it selects the I2C function of the MIO,
+//! configures the pads and the bus speed, and implements
+//! [`embedded_hal::i2c::I2c`]. The target address is given per transaction,
+//! and the controller is locked for the whole transaction, so drivers of
+//! several devices can share one bus through `&I2cBus`.
+//!
+//! With the `irq` feature, transfers are driven by the controller interrupt:
+//! the handler fills and drains the FIFOs while the calling task sleeps on a
+//! wait queue. Otherwise the FIFOs are polled.
+//!
+//! A controller can also act as a target (slave) with [`I2cTarget`], which
+//! answers at a fixed address and reports the bus events of each transfer to
+//! an [`I2cTargetHandler`] from the controller interrupt.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axhal::mem::{phys_to_virt, PhysAddr};
+use axhal::time::{busy_wait, current_time};
+use axsync::Mutex;
+use embedded_hal::i2c::{self, ErrorKind, NoAcknowledgeSource, Operation, SevenBitAddress};
+use log::*;
+
+use crate::driver_iic::i2c_hw::*;
+use crate::driver_iic::io::fiopad_select_func;
+use crate::driver_mio::mio_hw::{
+    fmio_func_state_mask, FMIO_FUNC_I2C, FMIO_FUNC_SEL, FMIO_FUNC_STATE,
+};
+use crate::driver_mio::mio_sinit::{fmio_lookup_config, FMIO_NUM};
+
+#[cfg(feature = "irq")]
+mod irq;
+mod target;
+
+pub use self::target::{I2cRegisterFile, I2cTarget, I2cTargetHandler};
+
+/// Number of MIO controllers usable as I2C buses.
+pub const MIO_COUNT: usize = FMIO_NUM;
+
+/// Reference clock of the I2C controllers.
+const REF_CLK_HZ: u32 = 50_000_000;
+/// Depth of the TX and RX FIFOs.
+const FIFO_DEPTH: usize = 8;
+
+/// SCL and SDA pad register offsets of each MIO, and the pad function
+/// selecting I2C. `None` if the pads are expected to be configured by the
+/// firmware.
+const MIO_PADS: [Option<(usize, usize, u32)>; MIO_COUNT] =
+    [None, Some((0x00D0, 0x00D4, 5)), None, None, None];
+
+static TAKEN: [AtomicBool; MIO_COUNT] = [const { AtomicBool::new(false) }; MIO_COUNT];
+static IRQ_REGISTERED: [AtomicBool; MIO_COUNT] = [const { AtomicBool::new(false) }; MIO_COUNT];
+
+const IRQ_HANDLERS: [fn(); MIO_COUNT] = [
+    || handle_irq(0),
+    || handle_irq(1),
+    || handle_irq(2),
+    || handle_irq(3),
+    || handle_irq(4),
+];
+
+fn release_mio(mio: usize) {
+    TAKEN[mio].store(false, Ordering::Release);
+}
+
+/// Controller registers of `mio`.
+fn mio_regs_of(mio: usize) -> Regs {
+    Regs::map(fmio_lookup_config(mio as u32).unwrap().func_base_addr)
+}
+
+/// Interrupt number of `mio`.
+fn mio_irq_of(mio: usize) -> u32 {
+    fmio_lookup_config(mio as u32).unwrap().irq_num
+}
+
+/// Installs the interrupt handler of `mio`, once. Returns `false` if the
+/// interrupt is unavailable.
+fn register_irq(mio: usize) -> bool {
+    if IRQ_REGISTERED[mio].load(Ordering::Acquire) {
+        return true;
+    }
+    let irq_num = mio_irq_of(mio);
+    if !axhal::irq::register_handler(irq_num as usize, IRQ_HANDLERS[mio]) {
+        warn!("I2C on MIO{}: IRQ {} unavailable", mio, irq_num);
+        return false;
+    }
+    IRQ_REGISTERED[mio].store(true, Ordering::Release);
+    true
+}
+
+/// Routes the interrupt of `mio` to whoever owns the controller.
+fn handle_irq(mio: usize) {
+    if target::handle_irq(mio) {
+        return;
+    }
+    #[cfg(feature = "irq")]
+    irq::handle_irq(mio);
+    #[cfg(not(feature = "irq"))]
+    mio_regs_of(mio).write(IC_INTR_MASK, 0);
+}
+
+/// Errors of I2C bus operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cError {
+    /// There is no MIO controller with this index.
+    InvalidMio(usize),
+    /// The MIO controller is already owned by another [`I2cBus`] or [`I2cTarget`].
+    InUse,
+    /// The requested bus speed cannot be configured.
+    UnsupportedSpeed(u32),
+    /// The address is not a 7-bit address.
+    InvalidAddress(u8),
+    /// The controller cannot issue a transaction without data bytes.
+    ZeroLength,
+    /// The controller did not make progress in time.
+    Timeout,
+    /// The controller aborted the transfer, with the `IC_TX_ABRT_SOURCE` bits.
+    Abort(u32),
+    /// The controller interrupt could not be registered.
+    IrqUnavailable,
+}
+
+impl i2c::Error for I2cError {
+    fn kind(&self) -> ErrorKind {
+        match *self {
+            Self::Abort(source) if source & ABRT_ARB_LOST != 0 => ErrorKind::ArbitrationLoss,
+            Self::Abort(source) if source & ABRT_ADDR_NOACK != 0 => {
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)
+            }
+            Self::Abort(source) if source & ABRT_TXDATA_NOACK != 0 => {
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)
+            }
+            Self::Abort(_) => ErrorKind::Bus,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Configuration of an [`I2cBus`].
+#[derive(Debug, Clone, Copy)]
+pub struct I2cConfig {
+    /// Bus speed in Hz, up to 1M. The SCL timing is computed for this rate,
+    /// in standard mode up to 100k, fast mode up to 400k, high speed above.
+    pub speed_hz: u32,
+    /// Maximum time to wait for the controller to make progress.
+    pub timeout: Duration,
+}
+
+impl Default for I2cConfig {
+    fn default() -> Self {
+        Self {
+            speed_hz: 100_000,
+            timeout: Duration::from_millis(100),
+        }
+    }
+}
+
+struct Regs {
+    base: usize,
+}
+
+impl Regs {
+    fn map(paddr: usize) -> Self {
+        Self {
+            base: phys_to_virt(PhysAddr::from(paddr)).as_usize(),
+        }
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.base + offset) as *mut u32, value) }
+    }
+}
+
+struct Controller {
+    mio: usize,
+    regs: Regs,
+    speed_hz: u32,
+    timeout: Duration,
+    /// Whether the MIO interrupt handler is installed.
+    use_irq: bool,
+}
+
+impl Controller {
+    /// Takes the MIO controller `mio` and selects its I2C function.
+    ///
+    /// The caller must release it with [`release_mio`].
+    fn take(mio: usize, config: &I2cConfig) -> Result<Self, I2cError> {
+        if mio >= MIO_COUNT {
+            return Err(I2cError::InvalidMio(mio));
+        }
+        if TAKEN[mio].swap(true, Ordering::Acquire) {
+            return Err(I2cError::InUse);
+        }
+
+        if let Some((scl, sda, func)) = MIO_PADS[mio] {
+            fiopad_select_func(scl, func);
+            fiopad_select_func(sda, func);
+        }
+        let mio_regs = Regs::map(fmio_lookup_config(mio as u32).unwrap().mio_base_addr);
+        if mio_regs.read(FMIO_FUNC_STATE) & fmio_func_state_mask() != FMIO_FUNC_I2C {
+            mio_regs.write(FMIO_FUNC_SEL, FMIO_FUNC_I2C);
+        }
+
+        Ok(Self {
+            mio,
+            regs: mio_regs_of(mio),
+            speed_hz: config.speed_hz,
+            timeout: config.timeout,
+            use_irq: false,
+        })
+    }
+
+    fn deadline(&self) -> Duration {
+        current_time() + self.timeout
+    }
+
+    fn set_enable(&self, enable: bool) -> Result<(), I2cError> {
+        let deadline = self.deadline();
+        loop {
+            self.regs.write(IC_ENABLE, enable as u32);
+            if self.regs.read(IC_ENABLE_STATUS) & 1 == enable as u32 {
+                return Ok(());
+            }
+            if current_time() > deadline {
+                return Err(I2cError::Timeout);
+            }
+            busy_wait(Duration::from_micros(10));
+        }
+    }
+
+    fn set_speed(&mut self, speed_hz: u32) -> Result<(), I2cError> {
+        let speed_mode = fi2c_speed_mode(speed_hz).ok_or(I2cError::UnsupportedSpeed(speed_hz))?;
+        let mut cfg = FI2cSpeedCfg {
+            speed_mode,
+            ..Default::default()
+        };
+        let spk_cnt = match speed_mode {
+            2 => self.regs.read(IC_HS_SPKLEN),
+            _ => self.regs.read(IC_FS_SPKLEN),
+        };
+        if !fi2c_calc_timing(REF_CLK_HZ, speed_hz, spk_cnt, &mut cfg) {
+            return Err(I2cError::UnsupportedSpeed(speed_hz));
+        }
+
+        let (hcnt, lcnt) = match cfg.speed_mode {
+            0 => (IC_SS_SCL_HCNT, IC_SS_SCL_LCNT),
+            1 => (IC_FS_SCL_HCNT, IC_FS_SCL_LCNT),
+            _ => (IC_HS_SCL_HCNT, IC_HS_SCL_LCNT),
+        };
+        self.regs.write(hcnt, cfg.scl_hcnt);
+        self.regs.write(lcnt, cfg.scl_lcnt);
+        let con = self.regs.read(IC_CON) & !CON_SPEED_MASK;
+        self.regs
+            .write(IC_CON, con | ((cfg.speed_mode + 1) << CON_SPEED_SHIFT));
+        if cfg.sda_hold != 0 {
+            self.regs.write(IC_SDA_HOLD, cfg.sda_hold);
+        }
+        self.speed_hz = speed_hz;
+        Ok(())
+    }
+
+    fn init_master(&mut self, speed_hz: u32) -> Result<(), I2cError> {
+        self.set_enable(false)?;
+        self.regs.write(
+            IC_CON,
+            CON_MASTER_MODE | CON_RESTART_EN | CON_SLAVE_DISABLE | (1 << CON_SPEED_SHIFT),
+        );
+        self.regs.write(IC_RX_TL, 0);
+        self.regs.write(IC_TX_TL, 0);
+        self.regs.write(IC_INTR_MASK, 0);
+        self.set_speed(speed_hz)?;
+        self.set_enable(true)
+    }
+
+    fn set_target(&self, address: u8) -> Result<(), I2cError> {
+        self.set_enable(false)?;
+        self.regs.write(IC_TAR, address as u32);
+        self.set_enable(true)
+    }
+
+    fn check_abort(&self) -> Result<(), I2cError> {
+        if self.regs.read(IC_RAW_INTR_STAT) & INTR_TX_ABRT == 0 {
+            return Ok(());
+        }
+        let source = self.regs.read(IC_TX_ABRT_SOURCE);
+        self.regs.read(IC_CLR_TX_ABRT);
+        Err(I2cError::Abort(source))
+    }
+
+    fn wait_idle(&self) -> Result<(), I2cError> {
+        let deadline = self.deadline();
+        while self.regs.read(IC_STATUS) & STATUS_MST_ACTIVITY != 0 {
+            if current_time() > deadline {
+                return Err(I2cError::Timeout);
+            }
+            busy_wait(Duration::from_micros(10));
+        }
+        Ok(())
+    }
+
+    fn push_cmd(&self, cmd: u32) -> Result<(), I2cError> {
+        let deadline = self.deadline();
+        while self.regs.read(IC_STATUS) & STATUS_TFNF == 0 {
+            self.check_abort()?;
+            if current_time() > deadline {
+                return Err(I2cError::Timeout);
+            }
+        }
+        self.regs.write(IC_DATA_CMD, cmd);
+        Ok(())
+    }
+
+    fn write_bytes(&self, buf: &[u8], restart: bool, stop: bool) -> Result<(), I2cError> {
+        for (i, &byte) in buf.iter().enumerate() {
+            let mut cmd = byte as u32;
+            if restart && i == 0 {
+                cmd |= CMD_RESTART;
+            }
+            if stop && i == buf.len() - 1 {
+                cmd |= CMD_STOP;
+            }
+            self.push_cmd(cmd)?;
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], restart: bool, stop: bool) -> Result<(), I2cError> {
+        let (mut issued, mut received) = (0, 0);
+        let mut deadline = self.deadline();
+        while received < buf.len() {
+            self.check_abort()?;
+            // Never request more bytes than the RX FIFO can hold.
+            while issued < buf.len()
+                && issued - received < FIFO_DEPTH
+                && self.regs.read(IC_STATUS) & STATUS_TFNF != 0
+            {
+                let mut cmd = CMD_READ;
+                if restart && issued == 0 {
+                    cmd |= CMD_RESTART;
+                }
+                if stop && issued == buf.len() - 1 {
+                    cmd |= CMD_STOP;
+                }
+                self.regs.write(IC_DATA_CMD, cmd);
+                issued += 1;
+                deadline = self.deadline();
+            }
+            while received < issued && self.regs.read(IC_STATUS) & STATUS_RFNE != 0 {
+                buf[received] = self.regs.read(IC_DATA_CMD) as u8;
+                received += 1;
+                deadline = self.deadline();
+            }
+            if current_time() > deadline {
+                return Err(I2cError::Timeout);
+            }
+        }
+        Ok(())
+    }
+
+    fn wait_stop(&self) -> Result<(), I2cError> {
+        let deadline = self.deadline();
+        loop {
+            let raw = self.regs.read(IC_RAW_INTR_STAT);
+            self.check_abort()?;
+            if raw & INTR_STOP_DET != 0 {
+                self.regs.read(IC_CLR_STOP_DET);
+                return Ok(());
+            }
+            if current_time() > deadline {
+                return Err(I2cError::Timeout);
+            }
+        }
+    }
+
+    /// Drops whatever a failed transfer left behind: commands in the TX FIFO,
+    /// a transfer in progress on the bus, and data in the RX FIFO.
+    fn recover(&self) {
+        let busy = self.regs.read(IC_STATUS) & STATUS_MST_ACTIVITY != 0
+            || self.regs.read(IC_STATUS) & STATUS_TFE == 0;
+        if busy && self.regs.read(IC_ENABLE_STATUS) & 1 != 0 {
+            // The abort flushes the TX FIFO and ends the transfer with a STOP.
+            self.regs.write(IC_ENABLE, 1 | ENABLE_ABORT);
+            let deadline = self.deadline();
+            while self.regs.read(IC_ENABLE) & ENABLE_ABORT != 0 {
+                if current_time() > deadline {
+                    // Disabling the controller flushes both FIFOs as well.
+                    warn!(
+                        "I2C{}: abort timed out, restarting the controller",
+                        self.mio
+                    );
+                    if self
+                        .set_enable(false)
+                        .and_then(|_| self.set_enable(true))
+                        .is_err()
+                    {
+                        warn!("I2C{}: controller restart timed out", self.mio);
+                    }
+                    break;
+                }
+                busy_wait(Duration::from_micros(10));
+            }
+        }
+        while self.regs.read(IC_STATUS) & STATUS_RFNE != 0 {
+            self.regs.read(IC_DATA_CMD);
+        }
+        self.regs.read(IC_CLR_INTR);
+    }
+
+    fn transaction(&self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), I2cError> {
+        let len = |op: &Operation<'_>| match op {
+            Operation::Read(buf) => buf.len(),
+            Operation::Write(buf) => buf.len(),
+        };
+        let Some(last) = operations.iter().rposition(|op| len(op) != 0) else {
+            return Err(I2cError::ZeroLength);
+        };
+
+        self.wait_idle()?;
+        self.set_target(address)?;
+        self.regs.read(IC_CLR_INTR);
+        #[cfg(feature = "irq")]
+        if self.use_irq {
+            return irq::transfer(self, operations, last);
+        }
+
+        // A repeated start is needed whenever the direction changes.
+        let mut prev_read = None;
+        for (i, op) in operations[..=last].iter_mut().enumerate() {
+            let stop = i == last;
+            match op {
+                Operation::Write(buf) if !buf.is_empty() => {
+                    self.write_bytes(buf, prev_read == Some(true), stop)?;
+                    prev_read = Some(false);
+                }
+                Operation::Read(buf) if !buf.is_empty() => {
+                    self.read_bytes(buf, prev_read == Some(false), stop)?;
+                    prev_read = Some(true);
+                }
+                _ => {}
+            }
+        }
+        self.wait_stop()
+    }
+}
+
+/// An I2C master bus on one of the Phytium MIO controllers.
+///
+/// At most one `I2cBus` exists per MIO at a time; the controller is released
+/// when the bus is dropped.
+pub struct I2cBus {
+    mio: usize,
+    inner: Mutex<Controller>,
+}
+
+impl I2cBus {
+    /// Takes the MIO controller `mio` (0–4) and initializes it as an I2C
+    /// master with the given configuration.
+    pub fn new(mio: usize, config: I2cConfig) -> Result<Self, I2cError> {
+        let bus = Self {
+            mio,
+            inner: Mutex::new(Controller::take(mio, &config)?),
+        };
+        // On failure, dropping `bus` releases the MIO.
+        let mut inner = bus.inner.lock();
+        inner.init_master(config.speed_hz)?;
+        #[cfg(feature = "irq")]
+        {
+            inner.use_irq = register_irq(mio);
+        }
+        drop(inner);
+        debug!("I2C bus on MIO{} ready at {} Hz", mio, config.speed_hz);
+        Ok(bus)
+    }
+
+    /// Index of the MIO controller of this bus.
+    pub fn mio(&self) -> usize {
+        self.mio
+    }
+
+    /// Interrupt number of the controller.
+    pub fn irq_num(&self) -> u32 {
+        mio_irq_of(self.mio)
+    }
+
+    /// Changes the bus speed.
+    pub fn set_speed(&self, speed_hz: u32) -> Result<(), I2cError> {
+        let mut inner = self.inner.lock();
+        inner.set_enable(false)?;
+        inner.set_speed(speed_hz)?;
+        inner.set_enable(true)
+    }
+
+    /// Performs a transaction with the device at `address`, holding the bus
+    /// for its whole duration. See [`i2c::I2c::transaction`].
+    pub fn transfer(&self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), I2cError> {
+        if address > 0x7F {
+            return Err(I2cError::InvalidAddress(address));
+        }
+        let inner = self.inner.lock();
+        let res = inner.transaction(address, operations);
+        if let Err(err) = res {
+            trace!("I2C transfer to {:#04x} failed: {:?}", address, err);
+            inner.recover();
+        }
+        res
+    }
+
+    /// Writes `bytes` to the device at `address`.
+    pub fn write(&self, address: u8, bytes: &[u8]) -> Result<(), I2cError> {
+        self.transfer(address, &mut [Operation::Write(bytes)])
+    }
+
+    /// Reads `buffer.len()` bytes from the device at `address`.
+    pub fn read(&self, address: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.transfer(address, &mut [Operation::Read(buffer)])
+    }
+
+    /// Writes `bytes` then reads into `buffer` after a repeated start.
+    pub fn write_read(&self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.transfer(
+            address,
+            &mut [Operation::Write(bytes), Operation::Read(buffer)],
+        )
+    }
+}
+
+impl Drop for I2cBus {
+    fn drop(&mut self) {
+        let _ = self.inner.lock().set_enable(false);
+        release_mio(self.mio);
+    }
+}
+
+impl i2c::ErrorType for I2cBus {
+    type Error = I2cError;
+}
+
+impl i2c::I2c<SevenBitAddress> for I2cBus {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transfer(address, operations)
+    }
+}
+
+impl i2c::ErrorType for &I2cBus {
+    type Error = I2cError;
+}
+
+impl i2c::I2c<SevenBitAddress> for &I2cBus {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transfer(address, operations)
+    }
+}
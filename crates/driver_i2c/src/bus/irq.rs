@@ -0,0 +1,219 @@
+//! Interrupt-driven transfers.
+//!
+//! The task starting a transfer publishes it in the per-MIO [`XFERS`] slot,
+//! unmasks the controller interrupts and sleeps on the per-MIO wait queue.
+//! The handler pushes commands while the TX FIFO has room, drains the RX
+//! FIFO, and completes the transfer on `STOP_DET` or `TX_ABRT`.
+
+use core::time::Duration;
+
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+use embedded_hal::i2c::Operation;
+
+use super::*;
+
+/// Interrupts used while a transfer is in flight.
+const XFER_INTR_MASK: u32 = INTR_RX_FULL | INTR_TX_EMPTY | INTR_TX_ABRT | INTR_STOP_DET;
+
+/// A transfer in flight, shared between the waiting task and the handler.
+struct Xfer {
+    /// The operations of the transaction, up to the last non-empty one. They
+    /// outlive the transfer: the task does not return before the slot is
+    /// cleared.
+    ops: *mut Operation<'static>,
+    count: usize,
+    /// Next command to push: operation index and byte index.
+    tx: (usize, usize),
+    /// Next byte to receive: operation index and byte index.
+    rx: (usize, usize),
+    /// Read commands pushed but not yet received.
+    pending_reads: usize,
+    result: Option<Result<(), I2cError>>,
+}
+
+// SAFETY: the operations are only accessed under the slot lock, and the task
+// owning them waits until the slot is cleared.
+unsafe impl Send for Xfer {}
+
+impl Xfer {
+    fn op(&mut self, idx: usize) -> &mut Operation<'static> {
+        unsafe { &mut *self.ops.add(idx) }
+    }
+
+    fn op_len(&mut self, idx: usize) -> usize {
+        match self.op(idx) {
+            Operation::Read(buf) => buf.len(),
+            Operation::Write(buf) => buf.len(),
+        }
+    }
+
+    /// Whether the operation at `idx` needs a repeated start before it.
+    fn needs_restart(&mut self, idx: usize) -> bool {
+        let is_read = matches!(self.op(idx), Operation::Read(_));
+        (0..idx)
+            .rev()
+            .find(|&i| self.op_len(i) != 0)
+            .is_some_and(|i| matches!(self.op(i), Operation::Read(_)) != is_read)
+    }
+
+    /// Moves `pos` past the end of the current operation and any empty one.
+    fn skip_done(&mut self, pos: &mut (usize, usize)) {
+        while pos.0 < self.count && pos.1 >= self.op_len(pos.0) {
+            *pos = (pos.0 + 1, 0);
+        }
+    }
+
+    /// Pushes commands while the TX FIFO has room. Returns `true` once all
+    /// commands are pushed.
+    fn fill(&mut self, regs: &Regs) -> bool {
+        loop {
+            let mut tx = self.tx;
+            self.skip_done(&mut tx);
+            self.tx = tx;
+            if tx.0 >= self.count {
+                return true;
+            }
+            if regs.read(IC_STATUS) & STATUS_TFNF == 0 {
+                return false;
+            }
+
+            let mut cmd = 0;
+            if tx.1 == 0 && self.needs_restart(tx.0) {
+                cmd |= CMD_RESTART;
+            }
+            if tx.0 == self.count - 1 && tx.1 == self.op_len(tx.0) - 1 {
+                cmd |= CMD_STOP;
+            }
+            match self.op(tx.0) {
+                Operation::Write(buf) => cmd |= buf[tx.1] as u32,
+                Operation::Read(_) => {
+                    // Never request more bytes than the RX FIFO can hold.
+                    if self.pending_reads >= FIFO_DEPTH {
+                        return false;
+                    }
+                    self.pending_reads += 1;
+                    cmd |= CMD_READ;
+                }
+            }
+            regs.write(IC_DATA_CMD, cmd);
+            self.tx.1 += 1;
+        }
+    }
+
+    /// Moves received bytes from the RX FIFO into the read buffers.
+    fn drain(&mut self, regs: &Regs) {
+        while self.pending_reads > 0 && regs.read(IC_STATUS) & STATUS_RFNE != 0 {
+            let mut rx = self.rx;
+            loop {
+                self.skip_done(&mut rx);
+                if matches!(self.op(rx.0), Operation::Read(_)) {
+                    break;
+                }
+                rx = (rx.0 + 1, 0);
+            }
+            let byte = regs.read(IC_DATA_CMD) as u8;
+            if let Operation::Read(buf) = self.op(rx.0) {
+                buf[rx.1] = byte;
+            }
+            self.rx = (rx.0, rx.1 + 1);
+            self.pending_reads -= 1;
+        }
+    }
+}
+
+static XFERS: [SpinNoIrq<Option<Xfer>>; MIO_COUNT] = [const { SpinNoIrq::new(None) }; MIO_COUNT];
+static WAITERS: [WaitQueue; MIO_COUNT] = [const { WaitQueue::new() }; MIO_COUNT];
+
+pub(super) fn handle_irq(mio: usize) {
+    let regs = mio_regs_of(mio);
+    let stat = regs.read(IC_INTR_STAT);
+    let mut slot = XFERS[mio].lock();
+    let Some(xfer) = slot.as_mut().filter(|xfer| xfer.result.is_none()) else {
+        // Spurious, or a late interrupt of a finished transfer.
+        regs.write(IC_INTR_MASK, 0);
+        regs.read(IC_CLR_INTR);
+        return;
+    };
+
+    let result = if stat & INTR_TX_ABRT != 0 {
+        let source = regs.read(IC_TX_ABRT_SOURCE);
+        regs.read(IC_CLR_TX_ABRT);
+        Some(Err(I2cError::Abort(source)))
+    } else {
+        xfer.drain(&regs);
+        let pushed_all = xfer.fill(&regs);
+        // TX_EMPTY is level-triggered: mask it while there is nothing we can
+        // push, RX_FULL brings us back once reads complete.
+        if pushed_all || xfer.pending_reads >= FIFO_DEPTH {
+            regs.write(IC_INTR_MASK, XFER_INTR_MASK & !INTR_TX_EMPTY);
+        } else {
+            regs.write(IC_INTR_MASK, XFER_INTR_MASK);
+        }
+        if stat & INTR_STOP_DET != 0 {
+            regs.read(IC_CLR_STOP_DET);
+            xfer.drain(&regs);
+            Some(match xfer.pending_reads {
+                0 => Ok(()),
+                _ => Err(I2cError::Timeout),
+            })
+        } else {
+            None
+        }
+    };
+
+    if result.is_some() {
+        regs.write(IC_INTR_MASK, 0);
+        xfer.result = result;
+        drop(slot);
+        WAITERS[mio].notify_one(false);
+    }
+}
+
+/// Runs the transaction on an idle, addressed controller, sleeping until the
+/// interrupt handler completes it.
+pub(super) fn transfer(
+    ctrl: &Controller,
+    operations: &mut [Operation<'_>],
+    last: usize,
+) -> Result<(), I2cError> {
+    let mio = ctrl.mio;
+    let bytes: usize = operations[..=last]
+        .iter()
+        .map(|op| match op {
+            Operation::Read(buf) => buf.len(),
+            Operation::Write(buf) => buf.len(),
+        })
+        .sum();
+    // Generous bound: 20 bit times per byte on top of the progress timeout.
+    let timeout = ctrl.timeout
+        + Duration::from_nanos(bytes as u64 * 20 * 1_000_000_000 / ctrl.speed_hz as u64);
+
+    *XFERS[mio].lock() = Some(Xfer {
+        ops: operations.as_mut_ptr().cast(),
+        count: last + 1,
+        tx: (0, 0),
+        rx: (0, 0),
+        pending_reads: 0,
+        result: None,
+    });
+    ctrl.regs.write(IC_TX_TL, (FIFO_DEPTH / 2) as u32);
+    ctrl.regs.write(IC_INTR_MASK, XFER_INTR_MASK);
+
+    WAITERS[mio].wait_timeout_until(timeout, || {
+        XFERS[mio]
+            .lock()
+            .as_ref()
+            .map_or(true, |xfer| xfer.result.is_some())
+    });
+
+    let xfer = {
+        let mut slot = XFERS[mio].lock();
+        ctrl.regs.write(IC_INTR_MASK, 0);
+        slot.take()
+    };
+    match xfer.and_then(|xfer| xfer.result) {
+        Some(res) => res,
+        None => Err(I2cError::Timeout),
+    }
+}
@@ -0,0 +1,231 @@
+//! I2C target (slave) mode.
+//!
+//! An [`I2cTarget`] owns a MIO controller and answers at a 7-bit address.
+//! Bus events are delivered from the controller interrupt to an
+//! [`I2cTargetHandler`].
+
+use axsync::spin::SpinNoIrq;
+
+use super::*;
+
+const TARGET_INTR_MASK: u32 =
+    INTR_RX_FULL | INTR_RD_REQ | INTR_TX_ABRT | INTR_RX_DONE | INTR_STOP_DET;
+
+/// Receiver of the bus events of an [`I2cTarget`].
+///
+/// The methods are called in interrupt context and must not block.
+pub trait I2cTargetHandler: Send + Sync {
+    /// The controller addressed us for a write, before the first byte.
+    fn write_requested(&self) {}
+
+    /// The controller wrote `byte`.
+    fn write_received(&self, byte: u8);
+
+    /// The controller reads a byte. Returns the byte to send.
+    fn read_requested(&self) -> u8;
+
+    /// The controller did not acknowledge the last byte sent, ending the read.
+    fn read_processed(&self) {}
+
+    /// A STOP condition ended the transfer addressed to us.
+    fn stop(&self) {}
+}
+
+struct TargetSlot {
+    handler: &'static dyn I2cTargetHandler,
+    writing: bool,
+}
+
+static TARGETS: [SpinNoIrq<Option<TargetSlot>>; MIO_COUNT] =
+    [const { SpinNoIrq::new(None) }; MIO_COUNT];
+
+/// A MIO controller acting as an I2C target at a fixed address.
+///
+/// The controller is released when the target is dropped.
+pub struct I2cTarget {
+    ctrl: Controller,
+    address: u8,
+}
+
+impl I2cTarget {
+    /// Takes the MIO controller `mio` (0–4) and makes it answer at `address`,
+    /// delivering bus events to `handler`.
+    pub fn new(
+        mio: usize,
+        address: u8,
+        config: I2cConfig,
+        handler: &'static dyn I2cTargetHandler,
+    ) -> Result<Self, I2cError> {
+        if address > 0x7F {
+            return Err(I2cError::InvalidAddress(address));
+        }
+        let mut target = Self {
+            ctrl: Controller::take(mio, &config)?,
+            address,
+        };
+        // From here on, dropping `target` releases the MIO.
+        target.init(config.speed_hz)?;
+        *TARGETS[mio].lock() = Some(TargetSlot {
+            handler,
+            writing: false,
+        });
+        if !register_irq(mio) {
+            return Err(I2cError::IrqUnavailable);
+        }
+        target.ctrl.regs.write(IC_INTR_MASK, TARGET_INTR_MASK);
+        debug!("I2C target on MIO{} at {:#04x}", mio, address);
+        Ok(target)
+    }
+
+    fn init(&mut self, speed_hz: u32) -> Result<(), I2cError> {
+        self.ctrl.set_enable(false)?;
+        let regs = &self.ctrl.regs;
+        regs.write(IC_CON, CON_STOP_DET_IFADDRESSED | (1 << CON_SPEED_SHIFT));
+        regs.write(IC_SAR, self.address as u32);
+        regs.write(IC_RX_TL, 0);
+        regs.write(IC_TX_TL, 0);
+        regs.write(IC_INTR_MASK, 0);
+        regs.read(IC_CLR_INTR);
+        self.ctrl.set_speed(speed_hz)?;
+        self.ctrl.set_enable(true)
+    }
+
+    /// Index of the MIO controller.
+    pub fn mio(&self) -> usize {
+        self.ctrl.mio
+    }
+
+    /// The address we answer at.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+}
+
+impl Drop for I2cTarget {
+    fn drop(&mut self) {
+        self.ctrl.regs.write(IC_INTR_MASK, 0);
+        let _ = self.ctrl.set_enable(false);
+        TARGETS[self.ctrl.mio].lock().take();
+        release_mio(self.ctrl.mio);
+    }
+}
+
+/// Handles the interrupt of `mio` if it is in target mode.
+pub(super) fn handle_irq(mio: usize) -> bool {
+    let mut slot = TARGETS[mio].lock();
+    let Some(target) = slot.as_mut() else {
+        return false;
+    };
+    let regs = mio_regs_of(mio);
+    let stat = regs.read(IC_INTR_STAT);
+
+    if stat & INTR_RX_FULL != 0 {
+        while regs.read(IC_STATUS) & STATUS_RFNE != 0 {
+            if !target.writing {
+                target.writing = true;
+                target.handler.write_requested();
+            }
+            let byte = regs.read(IC_DATA_CMD) as u8;
+            target.handler.write_received(byte);
+        }
+    }
+    if stat & INTR_RD_REQ != 0 {
+        regs.read(IC_CLR_RD_REQ);
+        target.writing = false;
+        let byte = target.handler.read_requested();
+        regs.write(IC_DATA_CMD, byte as u32);
+    }
+    if stat & INTR_TX_ABRT != 0 {
+        // The TX FIFO was flushed, e.g. after a NACK from the controller.
+        trace!("I2C target abort: {:#x}", regs.read(IC_TX_ABRT_SOURCE));
+        regs.read(IC_CLR_TX_ABRT);
+    }
+    if stat & INTR_RX_DONE != 0 {
+        regs.read(IC_CLR_RX_DONE);
+        target.handler.read_processed();
+    }
+    if stat & INTR_STOP_DET != 0 {
+        regs.read(IC_CLR_STOP_DET);
+        target.writing = false;
+        target.handler.stop();
+    }
+    true
+}
+
+struct RegisterFileInner<const N: usize> {
+    regs: [u8; N],
+    index: usize,
+    expect_index: bool,
+}
+
+/// An [`I2cTargetHandler`] emulating a register file or a small EEPROM.
+///
+/// The first byte of a write selects the register index, following bytes are
+/// stored at increasing indexes. Reads return bytes from the current index
+/// onwards. The index wraps at `N`.
+pub struct I2cRegisterFile<const N: usize> {
+    inner: SpinNoIrq<RegisterFileInner<N>>,
+}
+
+impl<const N: usize> I2cRegisterFile<N> {
+    /// Creates a register file filled with zeros.
+    pub const fn new() -> Self {
+        Self {
+            inner: SpinNoIrq::new(RegisterFileInner {
+                regs: [0; N],
+                index: 0,
+                expect_index: true,
+            }),
+        }
+    }
+
+    /// Reads the register at `index`.
+    pub fn get(&self, index: usize) -> u8 {
+        self.inner.lock().regs[index % N]
+    }
+
+    /// Writes the register at `index`.
+    pub fn set(&self, index: usize, value: u8) {
+        self.inner.lock().regs[index % N] = value;
+    }
+
+    /// Copies all registers into `buf`.
+    pub fn snapshot(&self, buf: &mut [u8; N]) {
+        buf.copy_from_slice(&self.inner.lock().regs);
+    }
+}
+
+impl<const N: usize> Default for I2cRegisterFile<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> I2cTargetHandler for I2cRegisterFile<N> {
+    fn write_requested(&self) {
+        self.inner.lock().expect_index = true;
+    }
+
+    fn write_received(&self, byte: u8) {
+        let mut inner = self.inner.lock();
+        if inner.expect_index {
+            inner.index = byte as usize % N;
+            inner.expect_index = false;
+        } else {
+            let index = inner.index;
+            inner.regs[index] = byte;
+            inner.index = (index + 1) % N;
+        }
+    }
+
+    fn read_requested(&self) -> u8 {
+        let mut inner = self.inner.lock();
+        let byte = inner.regs[inner.index];
+        inner.index = (inner.index + 1) % N;
+        byte
+    }
+
+    fn stop(&self) {
+        self.inner.lock().expect_index = true;
+    }
+}
@@ -4,13 +4,83 @@ use log::*;
 
 use crate::driver_iic::io::*;
 
+// Controller registers.
+pub const IC_CON: usize = 0x00;
+pub const IC_TAR: usize = 0x04;
+pub const IC_SAR: usize = 0x08;
+pub const IC_DATA_CMD: usize = 0x10;
+pub const IC_SS_SCL_HCNT: usize = 0x14;
+pub const IC_SS_SCL_LCNT: usize = 0x18;
+pub const IC_FS_SCL_HCNT: usize = 0x1C;
+pub const IC_FS_SCL_LCNT: usize = 0x20;
+pub const IC_HS_SCL_HCNT: usize = 0x24;
+pub const IC_HS_SCL_LCNT: usize = 0x28;
+pub const IC_INTR_STAT: usize = 0x2C;
+pub const IC_INTR_MASK: usize = 0x30;
+pub const IC_RAW_INTR_STAT: usize = 0x34;
+pub const IC_RX_TL: usize = 0x38;
+pub const IC_TX_TL: usize = 0x3C;
+pub const IC_CLR_INTR: usize = 0x40;
+pub const IC_CLR_RX_UNDER: usize = 0x44;
+pub const IC_CLR_RX_OVER: usize = 0x48;
+pub const IC_CLR_TX_OVER: usize = 0x4C;
+pub const IC_CLR_RD_REQ: usize = 0x50;
+pub const IC_CLR_TX_ABRT: usize = 0x54;
+pub const IC_CLR_RX_DONE: usize = 0x58;
+pub const IC_CLR_ACTIVITY: usize = 0x5C;
+pub const IC_CLR_STOP_DET: usize = 0x60;
+pub const IC_CLR_START_DET: usize = 0x64;
+pub const IC_CLR_GEN_CALL: usize = 0x68;
+pub const IC_ENABLE: usize = 0x6C;
+pub const IC_STATUS: usize = 0x70;
+pub const IC_SDA_HOLD: usize = 0x7C;
+pub const IC_TX_ABRT_SOURCE: usize = 0x80;
+pub const IC_ENABLE_STATUS: usize = 0x9C;
+pub const IC_FS_SPKLEN: usize = 0xA0;
+pub const IC_HS_SPKLEN: usize = 0xA4;
+
+// IC_CON bits.
+pub const CON_MASTER_MODE: u32 = 1 << 0;
+pub const CON_SPEED_SHIFT: u32 = 1;
+pub const CON_SPEED_MASK: u32 = 0b11 << CON_SPEED_SHIFT;
+pub const CON_RESTART_EN: u32 = 1 << 5;
+pub const CON_SLAVE_DISABLE: u32 = 1 << 6;
+pub const CON_STOP_DET_IFADDRESSED: u32 = 1 << 9;
+
+// IC_DATA_CMD bits.
+pub const CMD_READ: u32 = 1 << 8;
+pub const CMD_STOP: u32 = 1 << 9;
+pub const CMD_RESTART: u32 = 1 << 10;
+
+// IC_ENABLE bits.
+pub const ENABLE_ABORT: u32 = 1 << 1;
+
+// IC_STATUS bits.
+pub const STATUS_TFNF: u32 = 1 << 1;
+pub const STATUS_TFE: u32 = 1 << 2;
+pub const STATUS_RFNE: u32 = 1 << 3;
+pub const STATUS_MST_ACTIVITY: u32 = 1 << 5;
+
+// IC_INTR_* bits.
+pub const INTR_RX_FULL: u32 = 1 << 2;
+pub const INTR_TX_EMPTY: u32 = 1 << 4;
+pub const INTR_RD_REQ: u32 = 1 << 5;
+pub const INTR_TX_ABRT: u32 = 1 << 6;
+pub const INTR_RX_DONE: u32 = 1 << 7;
+pub const INTR_STOP_DET: u32 = 1 << 9;
+
+// IC_TX_ABRT_SOURCE bits.
+pub const ABRT_ADDR_NOACK: u32 = 0b111;
+pub const ABRT_TXDATA_NOACK: u32 = 1 << 3;
+pub const ABRT_ARB_LOST: u32 = 1 << 12;
+
 // 定义速度配置相关的结构体
 #[derive(Debug, Clone, Copy, Default)]
 pub struct FI2cSpeedCfg {
-    speed_mode: u32,
-    scl_lcnt: u32,
-    scl_hcnt: u32,
-    sda_hold: u32,
+    pub speed_mode: u32,
+    pub scl_lcnt: u32,
+    pub scl_hcnt: u32,
+    pub sda_hold: u32,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -70,8 +140,8 @@ pub fn fi2c_set_enable(addr: u32, enable: bool) -> bool {
     };
     let mut timeout: u32 = 50000;
     while 0 != timeout {
-        output_32(addr, 0x6C, status);
-        if ((input_32(addr, 0x9C)) & (0x1 << 0)) == status {
+        output_32(addr, IC_ENABLE, status);
+        if ((input_32(addr, IC_ENABLE_STATUS)) & (0x1 << 0)) == status {
             return true;
         }
         timeout -= 1;
@@ -80,8 +150,13 @@ pub fn fi2c_set_enable(addr: u32, enable: bool) -> bool {
     return false;
 }
 
-//计算I2C的上升沿下降沿配置
-pub fn fi2c_calc_timing(bus_clk_hz: u32, spk_cnt: u32, speed_cfg_p: &mut FI2cSpeedCfg) -> bool {
+//计算I2C的上升沿下降沿配置，SCL 周期按 speed_hz 计算
+pub fn fi2c_calc_timing(
+    bus_clk_hz: u32,
+    speed_hz: u32,
+    spk_cnt: u32,
+    speed_cfg_p: &mut FI2cSpeedCfg,
+) -> bool {
     // 确保 speed_cfg_p 不为空
     assert!(Some(speed_cfg_p.clone()).is_some());
 
@@ -100,7 +175,11 @@ pub fn fi2c_calc_timing(bus_clk_hz: u32, spk_cnt: u32, speed_cfg_p: &mut FI2cSpe
     let scl_rise_time_ns: i32;
     let scl_fall_time_ns: i32;
 
-    period_cnt = (bus_clk_hz / info_p.speed) as i32;
+    if speed_hz == 0 || speed_hz > info_p.speed {
+        trace!("i2c: speed {} out of mode {}", speed_hz, speed_mode);
+        return false;
+    }
+    period_cnt = (bus_clk_hz / speed_hz) as i32;
     scl_rise_time_ns = (info_p.def_risetime_ns) as i32;
     scl_fall_time_ns = (info_p.def_falltime_ns) as i32;
 
@@ -115,7 +194,7 @@ pub fn fi2c_calc_timing(bus_clk_hz: u32, spk_cnt: u32, speed_cfg_p: &mut FI2cSpe
         "i2c: mode {}, bus_clk {}, speed {}, period {} rise {} fall {} tlow {} thigh {} spk {}",
         speed_mode,
         bus_clk_hz,
-        info_p.speed,
+        speed_hz,
         period_cnt,
         rise_cnt,
         fall_cnt,
@@ -144,6 +223,26 @@ pub fn fi2c_calc_timing(bus_clk_hz: u32, spk_cnt: u32, speed_cfg_p: &mut FI2cSpe
         lcnt += diff;
         tot = hcnt + lcnt + 7 + spk_cnt as i32 + rise_cnt + 1;
         lcnt += period_cnt - tot;
+    } else if tot > period_cnt {
+        // 最短的 SCL 周期仍比 speed_hz 的周期长
+        trace!(
+            "i2c: speed {} too high, period {} < {}",
+            speed_hz,
+            period_cnt,
+            tot
+        );
+        return false;
+    }
+
+    // HCNT 和 LCNT 寄存器只有 16 位
+    if hcnt > 0xFFFF || lcnt > 0xFFFF {
+        trace!(
+            "i2c: speed {} too low. hcnt = {} lcnt = {}",
+            speed_hz,
+            hcnt,
+            lcnt
+        );
+        return false;
     }
 
     speed_cfg_p.scl_lcnt = lcnt as u32;
@@ -161,6 +260,18 @@ pub fn fi2c_calc_timing(bus_clk_hz: u32, spk_cnt: u32, speed_cfg_p: &mut FI2cSpe
     true
 }
 
+/// Returns the slowest speed mode able to run the bus at `speed_hz`, or
+/// `None` if no mode can.
+pub fn fi2c_speed_mode(speed_hz: u32) -> Option<u32> {
+    match speed_hz {
+        0 => None,
+        _ => I2C_SPEED_CFG
+            .iter()
+            .position(|info| speed_hz <= info.speed)
+            .map(|mode| mode as u32),
+    }
+}
+
 //计算I2C的速度配置
 // enum
 // {
@@ -176,22 +287,16 @@ pub fn fi2c_calc_speed_cfg(
     speed_cfg_p: &mut FI2cSpeedCfg,
 ) -> bool {
     assert!(Some(speed_cfg_p.clone()).is_some()); // 确保 speed_cfg_p 不为空
-    let spk_cnt: u32;
-
-    if speed >= 1000000 {
-        speed_cfg_p.speed_mode = 2;
-        spk_cnt = input_32(addr, 0xa4);
-    } else if speed >= 400000 {
-        speed_cfg_p.speed_mode = 1;
-        spk_cnt = input_32(addr, 0xa0);
-    } else if speed >= 100000 {
-        speed_cfg_p.speed_mode = 0;
-        spk_cnt = input_32(addr, 0xa0);
-    } else {
+    let Some(speed_mode) = fi2c_speed_mode(speed) else {
         return false;
-    }
+    };
+    speed_cfg_p.speed_mode = speed_mode;
+    let spk_cnt = match speed_mode {
+        2 => input_32(addr, IC_HS_SPKLEN),
+        _ => input_32(addr, IC_FS_SPKLEN),
+    };
 
-    fi2c_calc_timing(bus_clk_hz, spk_cnt, speed_cfg_p)
+    fi2c_calc_timing(bus_clk_hz, speed, spk_cnt, speed_cfg_p)
 }
 
 //设置I2C控制器的速率
@@ -210,36 +315,36 @@ pub fn fi2c_set_speed(addr: u32, speed_rate: u32) -> bool {
     }
 
     // 获取启用状态
-    enable_status = input_32(addr, 0x9C);
+    enable_status = input_32(addr, IC_ENABLE_STATUS);
 
     // 重置速率模式位
-    reg_val = input_32(addr, 0x00) & !fi2c_con_speed_mask();
+    reg_val = input_32(addr, IC_CON) & !fi2c_con_speed_mask();
     match speed_cfg.speed_mode {
         0 => {
             reg_val |= 0x1 << 1;
-            output_32(addr, 0x14, speed_cfg.scl_hcnt);
-            output_32(addr, 0x18, speed_cfg.scl_lcnt);
+            output_32(addr, IC_SS_SCL_HCNT, speed_cfg.scl_hcnt);
+            output_32(addr, IC_SS_SCL_LCNT, speed_cfg.scl_lcnt);
         }
         1 => {
             reg_val |= 0x2 << 1;
-            output_32(addr, 0x1C, speed_cfg.scl_hcnt);
-            output_32(addr, 0x20, speed_cfg.scl_lcnt);
+            output_32(addr, IC_FS_SCL_HCNT, speed_cfg.scl_hcnt);
+            output_32(addr, IC_FS_SCL_LCNT, speed_cfg.scl_lcnt);
         }
         2 => {
             reg_val |= 0x3 << 1;
-            output_32(addr, 0x24, speed_cfg.scl_hcnt);
-            output_32(addr, 0x28, speed_cfg.scl_lcnt);
+            output_32(addr, IC_HS_SCL_HCNT, speed_cfg.scl_hcnt);
+            output_32(addr, IC_HS_SCL_LCNT, speed_cfg.scl_lcnt);
         }
         _ => {
             return false;
         }
     }
 
-    output_32(addr, 0x00, reg_val);
+    output_32(addr, IC_CON, reg_val);
 
     // 配置 SDA 保持时间（如果需要）
     if speed_cfg.sda_hold != 0 {
-        output_32(addr, 0x7c, speed_cfg.sda_hold);
+        output_32(addr, IC_SDA_HOLD, speed_cfg.sda_hold);
     }
 
     // 恢复 I2C 状态
@@ -255,7 +360,7 @@ pub fn fi2c_wait_status(addr: u32, stat_bit: u32) -> bool {
     let mut timeout: u32 = 0;
 
     // 等待状态位设置或超时
-    while !((input_32(addr, 0x70) & stat_bit) != 0) && (50000 > timeout) {
+    while !((input_32(addr, IC_STATUS) & stat_bit) != 0) && (50000 > timeout) {
         busy_wait(Duration::from_millis(1)); // 等待 1 微秒
         timeout += 1;
     }
@@ -272,7 +377,7 @@ pub fn fi2c_wait_status(addr: u32, stat_bit: u32) -> bool {
 pub fn fi2c_wait_bus_busy(addr: u32) -> bool {
     let mut ret = true;
 
-    if (input_32(addr, 0x70) & (0x1 << 5)) != 0
+    if (input_32(addr, IC_STATUS) & (0x1 << 5)) != 0
         && (true != fi2c_wait_status(addr, 0x1 << 2)) != true
     {
         ret = false;
@@ -284,7 +389,7 @@ pub fn fi2c_wait_bus_busy(addr: u32) -> bool {
 
 //设置与I2C主机通信的从机地址
 pub fn fi2c_set_tar(addr: u32, tar_addr: u32) -> bool {
-    let enable_status = input_32(addr, 0x9C);
+    let enable_status = input_32(addr, IC_ENABLE_STATUS);
     let mut ret = true;
 
     if enable_status == (0x1 << 0) {
@@ -292,7 +397,7 @@ pub fn fi2c_set_tar(addr: u32, tar_addr: u32) -> bool {
     }
 
     if ret == true {
-        output_32(addr, 0x04, tar_addr & fi2c_ic_tar_mask());
+        output_32(addr, IC_TAR, tar_addr & fi2c_ic_tar_mask());
     }
 
     if enable_status == (0x1 << 0) {
@@ -304,7 +409,7 @@ pub fn fi2c_set_tar(addr: u32, tar_addr: u32) -> bool {
 
 //从机模式下，设置I2C地址
 pub fn fi2c_set_sar(addr: u32, sar_addr: u32) -> bool {
-    let enable_status = input_32(addr, 0x9C);
+    let enable_status = input_32(addr, IC_ENABLE_STATUS);
     let mut ret = true;
 
     if enable_status == (0x1 << 0) {
@@ -312,7 +417,7 @@ pub fn fi2c_set_sar(addr: u32, sar_addr: u32) -> bool {
     }
 
     if ret == true {
-        output_32(addr, 0x08, sar_addr & fi2c_ic_sar_mask());
+        output_32(addr, IC_SAR, sar_addr & fi2c_ic_sar_mask());
     }
 
     if enable_status == (0x1 << 0) {
@@ -329,8 +434,8 @@ pub fn fi2c_flush_rx_fifo(addr: u32) -> bool {
     let mut ret = true;
 
     // 读取数据直到 FIFO 为空
-    while (input_32(addr, 0x70) & (0x1 << 3)) != 0 {
-        data = input_32(addr, 0x10) as u8;
+    while (input_32(addr, IC_STATUS) & (0x1 << 3)) != 0 {
+        data = input_32(addr, IC_DATA_CMD) as u8;
 
         if timeout >= 50000 {
             ret = false;
@@ -349,44 +454,44 @@ pub fn fi2c_flush_rx_fifo(addr: u32) -> bool {
 pub fn fi2c_clear_intr_bits(addr: u32, last_err_p: &mut u32) -> u32 {
     assert!(Some(last_err_p.clone()).is_some());
 
-    let stat: u32 = input_32(addr, 0x2C);
+    let stat: u32 = input_32(addr, IC_INTR_STAT);
 
     // 读取以清除中断状态位
     if (stat & (0x1 << 6)) != 0 {
-        *last_err_p = input_32(addr, 0x80); // 读取中止源
-        input_32(addr, 0x54); // 清除 TX_ABRT 中断
+        *last_err_p = input_32(addr, IC_TX_ABRT_SOURCE); // 读取中止源
+        input_32(addr, IC_CLR_TX_ABRT); // 清除 TX_ABRT 中断
     }
 
     if (stat & (0x1 << 0)) != 0 {
-        input_32(addr, 0x44); // 清除 RX_UNDER 中断
+        input_32(addr, IC_CLR_RX_UNDER); // 清除 RX_UNDER 中断
     }
 
     if (stat & (0x1 << 1)) != 0 {
-        input_32(addr, 0x48); // 清除 RX_OVER 中断
+        input_32(addr, IC_CLR_RX_OVER); // 清除 RX_OVER 中断
     }
 
     if (stat & (0x1 << 3)) != 0 {
-        input_32(addr, 0x4C); // 清除 TX_OVER 中断
+        input_32(addr, IC_CLR_TX_OVER); // 清除 TX_OVER 中断
     }
 
     if (stat & (0x1 << 7)) != 0 {
-        input_32(addr, 0x58); // 清除 RX_DONE 中断
+        input_32(addr, IC_CLR_RX_DONE); // 清除 RX_DONE 中断
     }
 
     if (stat & (0x1 << 8)) != 0 {
-        input_32(addr, 0x5c); // 清除 ACTIVITY 中断
+        input_32(addr, IC_CLR_ACTIVITY); // 清除 ACTIVITY 中断
     }
 
     if (stat & (0x1 << 9)) != 0 {
-        input_32(addr, 0x60); // 清除 STOP_DET 中断
+        input_32(addr, IC_CLR_STOP_DET); // 清除 STOP_DET 中断
     }
 
     if (stat & (0x1 << 10)) != 0 {
-        input_32(addr, 0x64); // 清除 START_DET 中断
+        input_32(addr, IC_CLR_START_DET); // 清除 START_DET 中断
     }
 
     if (stat & (0x1 << 11)) != 0 {
-        input_32(addr, 0x68); // 清除 GEN_CALL 中断
+        input_32(addr, IC_CLR_GEN_CALL); // 清除 GEN_CALL 中断
     }
 
     stat
@@ -399,8 +504,8 @@ pub fn fi2c_clear_abort(addr: u32) {
 
     loop {
         // 清除中断状态
-        input_32(addr, 0x40);
-        reg_val = input_32(addr, 0x80);
+        input_32(addr, IC_CLR_INTR);
+        reg_val = input_32(addr, IC_TX_ABRT_SOURCE);
 
         if reg_val == 0 {
             return;
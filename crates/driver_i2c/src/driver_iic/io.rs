@@ -1,3 +1,4 @@
+use axhal::mem::{phys_to_virt, PhysAddr};
 use log::*;
 
 pub fn write_reg(addr: u32, value: u32) {
@@ -109,3 +110,41 @@ pub fn fiopad_lookup_config(instance_id: u32) -> Option<FIOPadConfig> {
 
     None
 }
+
+// Fields of the first register (`x_reg0`) of a pad.
+const PAD_FUNC_MASK: u32 = 0b111;
+const PAD_DRIVE_SHIFT: u32 = 4;
+const PAD_DRIVE_MASK: u32 = 0b1111 << PAD_DRIVE_SHIFT;
+const PAD_PULL_SHIFT: u32 = 8;
+const PAD_PULL_MASK: u32 = 0b11 << PAD_PULL_SHIFT;
+
+/// Pull resistor of a pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FIOPadPull {
+    None = 0,
+    Down = 1,
+    Up = 2,
+}
+
+/// Updates the `mask` bits of the first register of the pad at `pad_off`
+/// from the IOPAD base.
+fn fiopad_update(pad_off: usize, mask: u32, value: u32) {
+    let base = FIO_PAD_CONFIG_TBL[0].base_address;
+    let reg = phys_to_virt(PhysAddr::from(base + pad_off)).as_usize() as *mut u32;
+    unsafe { reg.write_volatile((reg.read_volatile() & !mask) | (value & mask)) };
+}
+
+/// Selects the function `func` (0–7) of the pad at `pad_off`.
+pub fn fiopad_select_func(pad_off: usize, func: u32) {
+    fiopad_update(pad_off, PAD_FUNC_MASK, func);
+}
+
+/// Sets the pull resistor of the pad at `pad_off`.
+pub fn fiopad_select_pull(pad_off: usize, pull: FIOPadPull) {
+    fiopad_update(pad_off, PAD_PULL_MASK, (pull as u32) << PAD_PULL_SHIFT);
+}
+
+/// Sets the drive strength (0–15) of the pad at `pad_off`.
+pub fn fiopad_select_drive(pad_off: usize, strength: u32) {
+    fiopad_update(pad_off, PAD_DRIVE_MASK, strength << PAD_DRIVE_SHIFT);
+}
@@ -1,5 +1,14 @@
 use crate::driver_iic::io::*;
 
+// MIO control registers.
+pub const FMIO_FUNC_SEL: usize = 0x00;
+pub const FMIO_FUNC_STATE: usize = 0x04;
+pub const FMIO_VERSION: usize = 0x100;
+
+// Functions of a MIO.
+pub const FMIO_FUNC_I2C: u32 = 0b00;
+pub const FMIO_FUNC_UART: u32 = 0b01;
+
 pub fn fmio_func_state_mask() -> u32 {
     ((!0u32) - (1u32 << (0)) + 1) & (!0u32 >> (32 - 1 - (1)))
 }
@@ -8,13 +17,13 @@ pub fn fmio_select_func(addr: usize, mio_type: u32) -> bool {
     assert!(mio_type < 2);
     assert!(addr != 0);
 
-    let reg_val = input_32(addr as u32, 0x04) & fmio_func_state_mask();
+    let reg_val = input_32(addr as u32, FMIO_FUNC_STATE) & fmio_func_state_mask();
 
     if mio_type == reg_val {
         return true;
     }
 
-    output_32(addr as u32, 0x00, mio_type);
+    output_32(addr as u32, FMIO_FUNC_SEL, mio_type);
 
     true
 }
@@ -22,11 +31,12 @@ pub fn fmio_select_func(addr: usize, mio_type: u32) -> bool {
 pub fn fmio_get_func(addr: usize) -> u32 {
     assert!(addr != 0);
 
-    input_32(addr as u32, 0x04) & fmio_func_state_mask()
+    input_32(addr as u32, FMIO_FUNC_STATE) & fmio_func_state_mask()
 }
 
 pub fn fmio_get_version(addr: usize) -> u32 {
     assert!(addr != 0);
 
-    input_32(addr as u32, 0x100) & (((!0u32) - (1u32 << (0)) + 1) & (!0u32 >> (32 - 1 - (31))))
+    input_32(addr as u32, FMIO_VERSION)
+        & (((!0u32) - (1u32 << (0)) + 1) & (!0u32 >> (32 - 1 - (31))))
 }
@@ -1,6 +1,9 @@
 use crate::driver_mio::mio::*;
 
-pub static FMIO_CONFIG_TBL: [FMioConfig; 3] = [
+/// Number of MIO controllers.
+pub const FMIO_NUM: usize = 5;
+
+pub static FMIO_CONFIG_TBL: [FMioConfig; FMIO_NUM] = [
     FMioConfig {
         instance_id: 0,
         func_base_addr: 0x28014000,
@@ -19,6 +22,18 @@ pub static FMIO_CONFIG_TBL: [FMioConfig; 3] = [
         irq_num: 126,
         mio_base_addr: 0x28019000,
     },
+    FMioConfig {
+        instance_id: 3,
+        func_base_addr: 0x2801A000,
+        irq_num: 127,
+        mio_base_addr: 0x2801B000,
+    },
+    FMioConfig {
+        instance_id: 4,
+        func_base_addr: 0x2801C000,
+        irq_num: 128,
+        mio_base_addr: 0x2801D000,
+    },
 ];
 
 pub fn fmio_lookup_config(instance_id: u32) -> Option<FMioConfig> {
@@ -7,13 +7,15 @@
 #![allow(unused_attributes)]
 #![allow(unused_variables)]
 use log::*;
+pub mod bus;
 pub mod driver_iic;
 pub mod driver_mio;
 pub mod example;
 
-use crate::driver_iic::io::*;
+pub use bus::{I2cBus, I2cConfig, I2cError};
 
-use crate::example::*;
+use axhal::time::busy_wait;
+use core::time::Duration;
 
 const OLED_INIT_CMDS: [u8; 24] = [
     0xAE, // Display off
@@ -42,49 +44,37 @@ const OLED_INIT_CMDS: [u8; 24] = [
     0xAF, // Display ON
 ];
 
-pub unsafe fn oled_init() -> bool {
-    let mut ret: bool;
-    (0..1000000).for_each(|_i| {
-        // 上电延时
-    });
-    let cmd = OLED_INIT_CMDS.clone();
-    for i in 0..24 {
-        ret = fi2c_master_write(&mut [cmd[i]], 1, 0);
-        if ret != true {
-            return ret;
-        }
+/// Address of the SSD1306 OLED used by [`run_iicoled`].
+const OLED_ADDRESS: u8 = 0x3c;
+
+pub fn oled_init(bus: &I2cBus) -> Result<(), I2cError> {
+    busy_wait(Duration::from_millis(100)); // 上电延时
+    for cmd in OLED_INIT_CMDS {
+        bus.write(OLED_ADDRESS, &[0x00, cmd])?;
     }
-    return true;
+    Ok(())
 }
 
-pub unsafe fn oled_display_on() -> bool {
-    let mut ret: bool;
-    let display_data = [0xFF; 128];
+pub fn oled_display_on(bus: &I2cBus) -> Result<(), I2cError> {
+    let mut display_data = [0xFF; 129];
+    display_data[0] = 0x40; // data stream
 
     for _ in 0..8 {
         // SSD1306有8页
-        for i in 0..128 {
-            ret = fi2c_master_write(&mut [display_data[i]], 1, 0);
-            if ret != true {
-                trace!("failed");
-                return ret;
-            }
-        }
+        bus.write(OLED_ADDRESS, &display_data)?;
     }
-    return true;
+    Ok(())
 }
 
 pub fn run_iicoled() {
-    unsafe {
-        let mut ret: bool = true;
-        let address: u32 = 0x3c;
-        let speed_rate: u32 = 100000; /*kb/s*/
-        fiopad_cfg_initialize(&mut IOPAD_CTRL, &fiopad_lookup_config(0).unwrap());
-        ret = fi2c_mio_master_init(address, speed_rate);
-        if ret != true {
-            trace!("FI2cMioMasterInit mio_id {:?} is error!", 1);
+    let bus = match I2cBus::new(1, I2cConfig::default()) {
+        Ok(bus) => bus,
+        Err(e) => {
+            warn!("failed to init I2C bus on MIO1: {:?}", e);
+            return;
         }
-        ret = oled_init();
-        ret = oled_display_on();
+    };
+    if let Err(e) = oled_init(&bus).and_then(|_| oled_display_on(&bus)) {
+        warn!("OLED at {:#x} not responding: {:?}", OLED_ADDRESS, e);
     }
 }
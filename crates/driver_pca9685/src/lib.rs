@@ -1,364 +1,257 @@
-#![no_std]
-#![no_main]
-#![allow(dead_code)]
-#![allow(static_mut_refs)]
-use axhal::time::busy_wait;
-use core::time::Duration;
-use log::*;
-
-use driver_i2c::driver_iic::io::*;
-use driver_i2c::example::*;
-
-const PCA9685_ADDRESS: u8 = 0x60;
+//! Driver for the PCA9685 16-channel, 12-bit PWM controller.
+//!
+//! The driver works on any [`embedded_hal::i2c::I2c`] bus. Besides raw
+//! per-channel control, [`Servo`] and [`DcMotor`] describe what is wired to
+//! the channels and translate angles and signed speeds into duty cycles.
+
+#![cfg_attr(not(test), no_std)]
+
+mod motor;
+mod servo;
+
+#[cfg(test)]
+mod tests;
+
+use core::fmt;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+pub use self::motor::DcMotor;
+pub use self::servo::Servo;
+
+/// Default 7-bit address, with all address pins low.
+pub const DEFAULT_ADDRESS: u8 = 0x40;
+/// Default all-call address.
+pub const DEFAULT_ALL_CALL_ADDRESS: u8 = 0x70;
+/// Number of PWM channels.
+pub const CHANNEL_COUNT: u8 = 16;
+/// Resolution of a PWM period, in counter ticks.
+pub const PWM_STEPS: u16 = 4096;
+
+/// Frequency of the internal oscillator.
+const OSC_CLOCK_HZ: u32 = 25_000_000;
+
+// Registers.
 const MODE1: u8 = 0x00;
-const PRE_SCALE: u8 = 0xFE;
+const MODE2: u8 = 0x01;
+const ALLCALLADR: u8 = 0x05;
 const LED0_ON_L: u8 = 0x06;
+const ALL_LED_ON_L: u8 = 0xFA;
+const PRE_SCALE: u8 = 0xFE;
+
+// MODE1 bits.
+const MODE1_RESTART: u8 = 1 << 7;
+const MODE1_AI: u8 = 1 << 5;
+const MODE1_SLEEP: u8 = 1 << 4;
+const MODE1_ALLCALL: u8 = 1 << 0;
+
+// MODE2 bits.
+const MODE2_OUTDRV: u8 = 1 << 2;
+
+// Bit 4 of LEDn_ON_H and LEDn_OFF_H: the output is fully on or fully off.
+const LED_FULL: u16 = 1 << 12;
+
+/// Errors of PCA9685 operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The I2C bus failed.
+    I2c(E),
+    /// There is no channel with this index.
+    InvalidChannel(u8),
+    /// The PWM frequency is out of the 24–1526 Hz range of the prescaler.
+    InvalidFrequency(u32),
+}
 
-// ##################################################################
-
-pub fn car_run_task(proposal: Quest) {
-    unsafe {
-        match proposal {
-            Quest::Stop => stop(),
-            Quest::Advance => advance(),
-            Quest::Back => back(),
-            Quest::MoveLeft => move_left(),
-            Quest::MoveRight => move_right(),
-            Quest::TrunLeft => trun_left(),
-            Quest::TrunRight => trun_right(),
-            Quest::AdvanceLeft => advance_left(),
-            Quest::AdvanceRight => advance_right(),
-            Quest::BackLeft => back_left(),
-            Quest::BackRight => back_right(),
-            Quest::RotateLeft => rotate_left(),
-            Quest::RotateRight => rotate_right(),
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::I2c(e) => write!(f, "I2C error: {:?}", e),
+            Self::InvalidChannel(ch) => write!(f, "invalid channel {}", ch),
+            Self::InvalidFrequency(hz) => write!(f, "unsupported PWM frequency {} Hz", hz),
         }
     }
 }
 
-pub enum Quest {
-    Stop,
-    Advance,
-    Back,
-    MoveLeft,
-    MoveRight,
-    TrunLeft,
-    TrunRight,
-    AdvanceLeft,
-    AdvanceRight,
-    BackLeft,
-    BackRight,
-    RotateLeft,
-    RotateRight,
+/// A PCA9685 at a given address on the I2C bus `I`.
+///
+/// `D` provides the oscillator start-up delays.
+pub struct Pca9685<I, D> {
+    i2c: I,
+    delay: D,
+    address: u8,
+    prescale: u8,
 }
 
-unsafe fn stop() {
-    //停止
-    status_control(0, 0, 0, 0);
-}
-unsafe fn advance() {
-    //前进
-    status_control(1, 1, 1, 1);
-}
-unsafe fn back() {
-    //后退
-    status_control(-1, -1, -1, -1);
-}
-unsafe fn move_left() {
-    //平移向左
-    status_control(-1, 1, 1, -1);
-}
-unsafe fn move_right() {
-    //平移向右
-    status_control(1, -1, -1, 1);
-}
-unsafe fn trun_left() {
-    //左转
-    status_control(0, 1, 1, 1);
-}
-unsafe fn trun_right() {
-    //右转
-    status_control(1, 0, 1, 1);
-}
-unsafe fn advance_left() {
-    //左前
-    status_control(0, 1, 1, 0);
-}
-unsafe fn advance_right() {
-    //右前
-    status_control(1, 0, 0, 1);
-}
-unsafe fn back_left() {
-    //左后
-    status_control(-1, 0, 0, -1);
-}
-unsafe fn back_right() {
-    //右后
-    status_control(0, -1, -1, 0);
-}
-unsafe fn rotate_right() {
-    //左旋转
-    status_control(1, -1, 1, -1);
-}
-unsafe fn rotate_left() {
-    //右旋转
-    status_control(-1, 1, -1, 1);
-}
+impl<I: I2c, D: DelayNs> Pca9685<I, D> {
+    /// Creates a driver for the chip at `address`, without touching it.
+    pub fn new(i2c: I, delay: D, address: u8) -> Self {
+        Self {
+            i2c,
+            delay,
+            address,
+            // Power-on value: 200 Hz.
+            prescale: 0x1E,
+        }
+    }
 
-unsafe fn write_byte_data(_address: u8, offset: u8, value: u16) {
-    let high_byte = (value >> 8) as u8; // 高8位
-    let low_byte = (value & 0xFF) as u8; // 低8位
-    fi2c_master_write(&mut [high_byte], 1, offset as u32);
-    fi2c_master_write(&mut [low_byte], 1, offset as u32);
-}
+    /// Releases the bus and the delay.
+    pub fn release(self) -> (I, D) {
+        (self.i2c, self.delay)
+    }
 
-unsafe fn read_byte_data(_address: u8, offset: u8) -> u16 {
-    let high_byte: u8 = 0x00; // 高8位
-    let low_byte: u8 = 0x00;
-    fi2c_master_read(&mut [high_byte], 1, offset as u32);
-    fi2c_master_read(&mut [low_byte], 1, offset as u32);
-    ((high_byte as u16) << 8) | (low_byte as u16)
-}
+    /// Address of the chip.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Resets the modes, turns all outputs off, sets the PWM frequency and
+    /// starts the oscillator.
+    ///
+    /// Outputs are totem-pole, as needed to drive H-bridges and servos.
+    pub fn init(&mut self, freq_hz: u32) -> Result<(), Error<I::Error>> {
+        self.write_reg(MODE1, MODE1_AI | MODE1_ALLCALL | MODE1_SLEEP)?;
+        self.write_reg(MODE2, MODE2_OUTDRV)?;
+        self.set_all_off()?;
+        self.set_pwm_frequency(freq_hz)?;
+        self.wake()
+    }
 
-pub fn pca_init(d1: u16, d2: u16, d3: u16, d4: u16) {
-    unsafe {
-        let address: u32 = PCA9685_ADDRESS as u32;
-        let speed_rate: u32 = 100000; /*kb/s*/
-        fiopad_cfg_initialize(&mut IOPAD_CTRL, &fiopad_lookup_config(0).unwrap());
-        if fi2c_mio_master_init(address, speed_rate) != true {
-            trace!("FI2cMioMasterInit mio_id {:?} is error!", 1);
+    /// Sets the PWM frequency of all channels.
+    ///
+    /// The prescaler can only be written in sleep mode: the chip is put to
+    /// sleep and restored to its previous state afterwards.
+    pub fn set_pwm_frequency(&mut self, freq_hz: u32) -> Result<(), Error<I::Error>> {
+        // round(OSC_CLOCK_HZ / (4096 * freq_hz)) - 1
+        let prescale = freq_hz
+            .checked_mul(4096)
+            .and_then(|div| (OSC_CLOCK_HZ + div / 2).checked_div(div))
+            .and_then(|prescale| prescale.checked_sub(1))
+            .filter(|prescale| (3..=255).contains(prescale))
+            .ok_or(Error::InvalidFrequency(freq_hz))?;
+        let mode1 = self.read_reg(MODE1)?;
+        self.write_reg(MODE1, (mode1 & !MODE1_RESTART) | MODE1_SLEEP)?;
+        self.write_reg(PRE_SCALE, prescale as u8)?;
+        self.prescale = prescale as u8;
+        if mode1 & MODE1_SLEEP == 0 {
+            self.wake()?;
         }
-        set_pwm_frequency(50);
-        set_pwm(d1, d2, d3, d4);
-        stop();
-        // traffic_light_release();
+        Ok(())
     }
-}
 
-unsafe fn set_pwm_frequency(freq: u16) {
-    let prescale_val = (25000000.0 / ((4096 * freq) as f64) - 1.0) as u16; //计算预分频器值 prescale_val
-    let old_mode = read_byte_data(PCA9685_ADDRESS, MODE1);
-    let new_mode = (old_mode & 0x7F) | 0x10;
-    write_byte_data(PCA9685_ADDRESS, MODE1, new_mode); //休眠模式
-    write_byte_data(PCA9685_ADDRESS, PRE_SCALE, prescale_val); //设置PWM频率
-    write_byte_data(PCA9685_ADDRESS, MODE1, old_mode); //退出休眠模式
-    busy_wait(Duration::from_millis(5)); //等待至少500us，以确保OSC稳定
-    write_byte_data(PCA9685_ADDRESS, MODE1, old_mode | 0x80); //重启pca
-    write_byte_data(PCA9685_ADDRESS, MODE1, 0x00); //
-}
+    /// The actual PWM frequency, in Hz.
+    pub fn pwm_frequency(&self) -> u32 {
+        OSC_CLOCK_HZ / (4096 * (self.prescale as u32 + 1))
+    }
 
-unsafe fn set_pwm(
-    duty_channel1_pwm: u16,
-    duty_channel2_pwm: u16,
-    duty_channel3_pwm: u16,
-    duty_channel4_pwm: u16,
-) {
-    let duty_channel1 = duty_channel1_pwm.max(0).min(4095); //限制off_time在0-4095之间
-    let duty_channel2 = duty_channel2_pwm.max(0).min(4095);
-    let duty_channel3 = duty_channel3_pwm.max(0).min(4095);
-    let duty_channel4 = duty_channel4_pwm.max(0).min(4095);
-
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 0, 0 & 0xFF);
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 0 + 1, 0 >> 8);
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 0 + 2,
-        (duty_channel1 & 0xFF) as u16,
-    );
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 0 + 3,
-        (duty_channel1 >> 8) as u16,
-    );
-
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 5, 0 & 0xFF);
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 5 + 1, 0 >> 8);
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 5 + 2,
-        (duty_channel2 & 0xFF) as u16,
-    );
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 5 + 3,
-        (duty_channel2 >> 8) as u16,
-    );
-
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 6, 0 & 0xFF);
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 6 + 1, 0 >> 8);
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 6 + 2,
-        (duty_channel3 & 0xFF) as u16,
-    );
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 6 + 3,
-        (duty_channel3 >> 8) as u16,
-    );
-
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 11, 0 & 0xFF);
-    write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 11 + 1, 0 >> 8);
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 11 + 2,
-        (duty_channel4 & 0xFF) as u16,
-    );
-    write_byte_data(
-        PCA9685_ADDRESS,
-        LED0_ON_L + 4 * 11 + 3,
-        (duty_channel4 >> 8) as u16,
-    );
-}
+    /// Converts a pulse width to counter ticks at the current frequency.
+    pub fn pulse_to_ticks(&self, pulse_us: u32) -> u16 {
+        let period_us = 4096 * (self.prescale as u64 + 1) * 1_000_000 / OSC_CLOCK_HZ as u64;
+        (pulse_us as u64 * PWM_STEPS as u64 / period_us).min(PWM_STEPS as u64) as u16
+    }
 
-unsafe fn status_control(m1: i16, m2: i16, m3: i16, m4: i16) {
-    match m1 {
-        -1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 3, 4095 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 3, 0 >> 8);
-        }
-        0 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 3, 0 >> 8);
-        }
-        1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 1 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 2 + 3, 4095 >> 8);
-        }
-        _ => (),
+    /// Stops the oscillator. All outputs stop, their settings are kept.
+    pub fn sleep(&mut self) -> Result<(), Error<I::Error>> {
+        let mode1 = self.read_reg(MODE1)?;
+        self.write_reg(MODE1, (mode1 & !MODE1_RESTART) | MODE1_SLEEP)
     }
 
-    match m2 {
-        -1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 3, 4095 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 3, 0 >> 8);
-        }
-        0 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 3, 0 >> 8);
+    /// Starts the oscillator and, if the chip was put to sleep with active
+    /// outputs, restarts them with their previous settings.
+    pub fn wake(&mut self) -> Result<(), Error<I::Error>> {
+        let mode1 = self.read_reg(MODE1)?;
+        self.write_reg(MODE1, mode1 & !(MODE1_SLEEP | MODE1_RESTART))?;
+        // The oscillator needs 500 us to stabilize.
+        self.delay.delay_us(500);
+        if mode1 & MODE1_RESTART != 0 {
+            self.write_reg(MODE1, (mode1 & !MODE1_SLEEP) | MODE1_RESTART)?;
         }
-        1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 3 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 4 + 3, 4095 >> 8);
-        }
-        _ => (),
+        Ok(())
     }
 
-    match m3 {
-        -1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 3, 4095 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 3, 0 >> 8);
-        }
-        0 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 3, 0 >> 8);
+    /// Makes the chip also respond at the all-call `address`, or stops it
+    /// responding at its all-call address.
+    pub fn set_all_call(&mut self, address: Option<u8>) -> Result<(), Error<I::Error>> {
+        let mode1 = self.read_reg(MODE1)? & !MODE1_RESTART;
+        match address {
+            Some(address) => {
+                self.write_reg(ALLCALLADR, address << 1)?;
+                self.write_reg(MODE1, mode1 | MODE1_ALLCALL)
+            }
+            None => self.write_reg(MODE1, mode1 & !MODE1_ALLCALL),
         }
-        1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 7 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 8 + 3, 4095 >> 8);
-        }
-        _ => (),
     }
 
-    match m4 {
-        -1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 3, 4095 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 3, 0 >> 8);
-        }
-        0 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 3, 0 >> 8);
+    /// Sets the counter values at which `channel` turns on and off, both in
+    /// `0..4096`.
+    pub fn set_pwm(&mut self, channel: u8, on: u16, off: u16) -> Result<(), Error<I::Error>> {
+        let reg = Self::channel_reg(channel)?;
+        self.write_led(reg, on & 0xFFF, off & 0xFFF)
+    }
+
+    /// Sets the duty cycle of `channel`, in `0..=4096` ticks of 4096.
+    ///
+    /// 0 and 4096 use the full-off and full-on modes, free of glitches.
+    pub fn set_duty(&mut self, channel: u8, duty: u16) -> Result<(), Error<I::Error>> {
+        let reg = Self::channel_reg(channel)?;
+        self.write_duty(reg, duty)
+    }
+
+    /// Turns `channel` fully on.
+    pub fn set_full_on(&mut self, channel: u8) -> Result<(), Error<I::Error>> {
+        self.set_duty(channel, PWM_STEPS)
+    }
+
+    /// Turns `channel` fully off.
+    pub fn set_full_off(&mut self, channel: u8) -> Result<(), Error<I::Error>> {
+        self.set_duty(channel, 0)
+    }
+
+    /// Sets the duty cycle of all channels at once.
+    pub fn set_all_duty(&mut self, duty: u16) -> Result<(), Error<I::Error>> {
+        self.write_duty(ALL_LED_ON_L, duty)
+    }
+
+    /// Turns all channels fully off.
+    pub fn set_all_off(&mut self) -> Result<(), Error<I::Error>> {
+        self.set_all_duty(0)
+    }
+
+    fn channel_reg(channel: u8) -> Result<u8, Error<I::Error>> {
+        match channel {
+            0..CHANNEL_COUNT => Ok(LED0_ON_L + 4 * channel),
+            _ => Err(Error::InvalidChannel(channel)),
         }
-        1 => {
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 2, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 9 + 3, 0 >> 8);
-
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10, 0 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 1, 0 >> 8);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 2, 4095 & 0xFF);
-            write_byte_data(PCA9685_ADDRESS, LED0_ON_L + 4 * 10 + 3, 4095 >> 8);
+    }
+
+    fn write_duty(&mut self, reg: u8, duty: u16) -> Result<(), Error<I::Error>> {
+        match duty {
+            0 => self.write_led(reg, 0, LED_FULL),
+            PWM_STEPS.. => self.write_led(reg, LED_FULL, 0),
+            _ => self.write_led(reg, 0, duty),
         }
-        _ => (),
     }
-}
 
-pub fn test_pca() {}
+    fn write_led(&mut self, reg: u8, on: u16, off: u16) -> Result<(), Error<I::Error>> {
+        let [on_l, on_h] = on.to_le_bytes();
+        let [off_l, off_h] = off.to_le_bytes();
+        // Relies on register auto-increment.
+        self.i2c
+            .write(self.address, &[reg, on_l, on_h, off_l, off_h])
+            .map_err(Error::I2c)
+    }
+
+    fn read_reg(&mut self, reg: u8) -> Result<u8, Error<I::Error>> {
+        let mut value = [0];
+        self.i2c
+            .write_read(self.address, &[reg], &mut value)
+            .map_err(Error::I2c)?;
+        Ok(value[0])
+    }
+
+    fn write_reg(&mut self, reg: u8, value: u8) -> Result<(), Error<I::Error>> {
+        self.i2c
+            .write(self.address, &[reg, value])
+            .map_err(Error::I2c)
+    }
+}
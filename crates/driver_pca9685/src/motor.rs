@@ -0,0 +1,85 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::{Error, Pca9685, PWM_STEPS};
+
+/// A DC motor behind an H-bridge driven by PCA9685 channels.
+///
+/// The two inputs of the bridge select the direction. Without an enable
+/// channel, the speed is the PWM duty of the active input. With one, as on
+/// L298N-style bridges, the inputs are switched fully on or off and the speed
+/// is the duty of the enable channel.
+#[derive(Debug, Clone, Copy)]
+pub struct DcMotor {
+    /// Input pulsed or held high to turn forwards.
+    pub forward: u8,
+    /// Input pulsed or held high to turn backwards.
+    pub backward: u8,
+    /// Enable input of the bridge, if driven by the PCA9685.
+    pub enable: Option<u8>,
+}
+
+impl DcMotor {
+    /// Full speed, in either direction.
+    pub const MAX_SPEED: i16 = PWM_STEPS as i16;
+
+    /// A motor whose bridge inputs are the `forward` and `backward` channels.
+    pub const fn new(forward: u8, backward: u8) -> Self {
+        Self {
+            forward,
+            backward,
+            enable: None,
+        }
+    }
+
+    /// Drives the bridge enable input from `channel`.
+    pub const fn with_enable(mut self, channel: u8) -> Self {
+        self.enable = Some(channel);
+        self
+    }
+
+    /// Turns at `speed`, from `-MAX_SPEED` (full backwards) to `MAX_SPEED`
+    /// (full forwards). 0 lets the motor coast.
+    pub fn set_speed<I: I2c, D: DelayNs>(
+        &self,
+        pca: &mut Pca9685<I, D>,
+        speed: i16,
+    ) -> Result<(), Error<I::Error>> {
+        let duty = speed.unsigned_abs().min(PWM_STEPS);
+        let (active, idle) = match speed {
+            0 => return self.coast(pca),
+            1.. => (self.forward, self.backward),
+            _ => (self.backward, self.forward),
+        };
+        pca.set_full_off(idle)?;
+        match self.enable {
+            Some(enable) => {
+                pca.set_full_on(active)?;
+                pca.set_duty(enable, duty)
+            }
+            None => pca.set_duty(active, duty),
+        }
+    }
+
+    /// Lets the motor spin freely: both inputs low.
+    pub fn coast<I: I2c, D: DelayNs>(
+        &self,
+        pca: &mut Pca9685<I, D>,
+    ) -> Result<(), Error<I::Error>> {
+        pca.set_full_off(self.forward)?;
+        pca.set_full_off(self.backward)
+    }
+
+    /// Stops the motor actively: both inputs high, shorting its terminals.
+    pub fn brake<I: I2c, D: DelayNs>(
+        &self,
+        pca: &mut Pca9685<I, D>,
+    ) -> Result<(), Error<I::Error>> {
+        pca.set_full_on(self.forward)?;
+        pca.set_full_on(self.backward)?;
+        match self.enable {
+            Some(enable) => pca.set_full_on(enable),
+            None => Ok(()),
+        }
+    }
+}
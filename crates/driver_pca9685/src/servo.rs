@@ -0,0 +1,63 @@
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::{Error, Pca9685};
+
+/// A hobby servo on one PCA9685 channel.
+///
+/// The servo expects a pulse every PWM period, whose width sets the angle:
+/// `min_pulse_us` for 0 degrees up to `max_pulse_us` for `range_deg`. The
+/// chip should run at the servo frame rate, usually 50 Hz.
+#[derive(Debug, Clone, Copy)]
+pub struct Servo {
+    /// PWM channel.
+    pub channel: u8,
+    /// Pulse width at 0 degrees, in microseconds.
+    pub min_pulse_us: u32,
+    /// Pulse width at `range_deg`, in microseconds.
+    pub max_pulse_us: u32,
+    /// Travel of the servo, in degrees.
+    pub range_deg: u32,
+}
+
+impl Servo {
+    /// A standard 180-degree servo driven with 500–2500 us pulses.
+    pub const fn new(channel: u8) -> Self {
+        Self {
+            channel,
+            min_pulse_us: 500,
+            max_pulse_us: 2500,
+            range_deg: 180,
+        }
+    }
+
+    /// Moves to `angle_deg`, clamped to the travel of the servo.
+    pub fn set_angle<I: I2c, D: DelayNs>(
+        &self,
+        pca: &mut Pca9685<I, D>,
+        angle_deg: f32,
+    ) -> Result<(), Error<I::Error>> {
+        let angle = angle_deg.clamp(0.0, self.range_deg as f32);
+        let span = self.max_pulse_us.saturating_sub(self.min_pulse_us) as f32;
+        let pulse_us = self.min_pulse_us + (span * angle / self.range_deg as f32) as u32;
+        self.set_pulse(pca, pulse_us)
+    }
+
+    /// Sends pulses of `pulse_us` microseconds.
+    pub fn set_pulse<I: I2c, D: DelayNs>(
+        &self,
+        pca: &mut Pca9685<I, D>,
+        pulse_us: u32,
+    ) -> Result<(), Error<I::Error>> {
+        let ticks = pca.pulse_to_ticks(pulse_us);
+        pca.set_duty(self.channel, ticks)
+    }
+
+    /// Stops sending pulses, so that the servo no longer holds its position.
+    pub fn release<I: I2c, D: DelayNs>(
+        &self,
+        pca: &mut Pca9685<I, D>,
+    ) -> Result<(), Error<I::Error>> {
+        pca.set_full_off(self.channel)
+    }
+}
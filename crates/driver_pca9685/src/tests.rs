@@ -0,0 +1,122 @@
+use core::convert::Infallible;
+
+use embedded_hal::i2c::{ErrorType, Operation};
+
+use crate::*;
+
+/// A PCA9685 register file, with register auto-increment.
+struct MockChip {
+    regs: [u8; 256],
+}
+
+impl MockChip {
+    fn new() -> Self {
+        let mut regs = [0; 256];
+        regs[MODE1 as usize] = MODE1_SLEEP | MODE1_ALLCALL;
+        regs[PRE_SCALE as usize] = 0x1E;
+        Self { regs }
+    }
+}
+
+impl ErrorType for MockChip {
+    type Error = Infallible;
+}
+
+impl I2c for MockChip {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Infallible> {
+        assert_eq!(address, DEFAULT_ADDRESS);
+        let mut reg = 0;
+        for op in operations {
+            match op {
+                Operation::Write(buf) => {
+                    let (&first, data) = buf.split_first().unwrap();
+                    reg = first as usize;
+                    for &byte in data {
+                        self.regs[reg] = byte;
+                        reg += 1;
+                    }
+                }
+                Operation::Read(buf) => {
+                    for byte in buf.iter_mut() {
+                        *byte = self.regs[reg];
+                        reg += 1;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+fn new_pca() -> Pca9685<MockChip, NoDelay> {
+    Pca9685::new(MockChip::new(), NoDelay, DEFAULT_ADDRESS)
+}
+
+fn led(chip: &MockChip, channel: u8) -> (u16, u16) {
+    let reg = (LED0_ON_L + 4 * channel) as usize;
+    let r = &chip.regs[reg..reg + 4];
+    (
+        u16::from_le_bytes([r[0], r[1]]),
+        u16::from_le_bytes([r[2], r[3]]),
+    )
+}
+
+#[test]
+fn init() {
+    let mut pca = new_pca();
+    pca.init(50).unwrap();
+    assert_eq!(pca.pwm_frequency(), 50);
+    let (chip, _) = pca.release();
+    assert_eq!(chip.regs[PRE_SCALE as usize], 121);
+    assert_eq!(chip.regs[MODE1 as usize] & MODE1_SLEEP, 0);
+    assert_eq!(chip.regs[MODE2 as usize], MODE2_OUTDRV);
+    let all = ALL_LED_ON_L as usize;
+    assert_eq!(chip.regs[all..all + 4], [0, 0, 0, (LED_FULL >> 8) as u8]);
+}
+
+#[test]
+fn pwm_frequency_range() {
+    let mut pca = new_pca();
+    pca.set_pwm_frequency(24).unwrap();
+    assert_eq!(pca.pwm_frequency(), 24);
+    pca.set_pwm_frequency(1526).unwrap();
+    assert_eq!(pca.pwm_frequency(), 1525);
+
+    // Out of the prescaler range, down to the overflows of the computation.
+    for freq_hz in [0, 23, 2_000, 12_500, 1_000_000, 2_000_000, u32::MAX] {
+        assert_eq!(
+            pca.set_pwm_frequency(freq_hz),
+            Err(Error::InvalidFrequency(freq_hz))
+        );
+    }
+    // Rejected frequencies leave the chip alone.
+    assert_eq!(pca.pwm_frequency(), 1525);
+    let (chip, _) = pca.release();
+    assert_eq!(chip.regs[PRE_SCALE as usize], 3);
+}
+
+#[test]
+fn duty() {
+    let mut pca = new_pca();
+    pca.set_duty(1, 1024).unwrap();
+    pca.set_full_on(2).unwrap();
+    pca.set_pwm(3, 100, 4200).unwrap();
+    assert_eq!(
+        pca.set_duty(CHANNEL_COUNT, 0),
+        Err(Error::InvalidChannel(16))
+    );
+    let (chip, _) = pca.release();
+    assert_eq!(led(&chip, 1), (0, 1024));
+    assert_eq!(led(&chip, 2), (LED_FULL, 0));
+    assert_eq!(led(&chip, 3), (100, 4200 & 0xFFF));
+}
@@ -0,0 +1,121 @@
+//! PCI capability list and MSI configuration.
+
+use bit_field::BitField;
+
+/// Capability ID of Message Signaled Interrupts.
+pub const CAP_ID_MSI: u8 = 0x05;
+/// Capability ID of PCI Express.
+pub const CAP_ID_PCIE: u8 = 0x10;
+/// Capability ID of MSI-X.
+pub const CAP_ID_MSIX: u8 = 0x11;
+
+const CAP_POINTER_OFFSET: usize = 0x34;
+
+/// An entry of the capability list in the configuration space.
+#[derive(Clone, Copy, Debug)]
+pub struct PciCapability {
+    /// The capability ID.
+    pub id: u8,
+    /// Offset of the capability structure in the configuration space.
+    pub offset: usize,
+}
+
+/// An iterator over the capability list of a device function.
+pub struct CapabilityIterator {
+    cfg_addr: usize,
+    next: usize,
+}
+
+impl CapabilityIterator {
+    /// The caller must make sure the capability list bit in the status
+    /// register is set.
+    pub(crate) fn new(cfg_addr: usize) -> Self {
+        let next = unsafe { ((cfg_addr + CAP_POINTER_OFFSET) as *const u8).read_volatile() };
+        Self {
+            cfg_addr,
+            next: (next & 0xfc) as usize,
+        }
+    }
+
+    pub(crate) fn empty() -> Self {
+        Self {
+            cfg_addr: 0,
+            next: 0,
+        }
+    }
+}
+
+impl Iterator for CapabilityIterator {
+    type Item = PciCapability;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // offsets below 0x40 point into the standard header, treat as end of list
+        if self.next < 0x40 {
+            return None;
+        }
+        let offset = self.next;
+        let header = unsafe { ((self.cfg_addr + offset) as *const u16).read_volatile() };
+        self.next = (header.get_bits(8..16) & 0xfc) as usize;
+        Some(PciCapability {
+            id: header.get_bits(0..8) as u8,
+            offset,
+        })
+    }
+}
+
+/// The MSI capability structure of a device function.
+pub struct MsiCapability {
+    base: usize,
+}
+
+impl MsiCapability {
+    pub(crate) fn new(cfg_addr: usize, cap: &PciCapability) -> Self {
+        Self {
+            base: cfg_addr + cap.offset,
+        }
+    }
+
+    fn control(&self) -> u16 {
+        unsafe { ((self.base + 2) as *const u16).read_volatile() }
+    }
+
+    fn set_control(&self, value: u16) {
+        unsafe { ((self.base + 2) as *mut u16).write_volatile(value) }
+    }
+
+    /// Whether the function can generate 64-bit message addresses.
+    pub fn is_64bit(&self) -> bool {
+        self.control().get_bit(7)
+    }
+
+    /// Whether MSI is currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.control().get_bit(0)
+    }
+
+    /// Programs a single message with the given address and data, then
+    /// enables MSI.
+    pub fn enable(&self, address: u64, data: u16) {
+        unsafe {
+            ((self.base + 4) as *mut u32).write_volatile(address as u32);
+            if self.is_64bit() {
+                ((self.base + 8) as *mut u32).write_volatile((address >> 32) as u32);
+                ((self.base + 12) as *mut u16).write_volatile(data);
+            } else {
+                ((self.base + 8) as *mut u16).write_volatile(data);
+            }
+        }
+        let mut control = self.control();
+        // only one vector is requested
+        control.set_bits(4..7, 0);
+        control.set_bit(0, true);
+        self.set_control(control);
+    }
+
+    /// Disables MSI, the function falls back to INTx.
+    pub fn disable(&self) {
+        let mut control = self.control();
+        control.set_bit(0, false);
+        self.set_control(control);
+    }
+}
@@ -0,0 +1,18 @@
+//! Standard PCIe enhanced configuration access mechanism (ECAM), used by
+//! QEMU `q35` and `virt` machines.
+
+use crate::types::ConifgPciPciBridge;
+use crate::{Access, PciAddress};
+
+#[derive(Clone)]
+pub struct GenericEcam;
+
+impl Access for GenericEcam {
+    fn setup(_mmio_base: usize) {}
+
+    fn probe_bridge(_mmio_base: usize, _bridge_header: &ConifgPciPciBridge) {}
+
+    fn map_conf(mmio_base: usize, addr: PciAddress) -> Option<usize> {
+        Some(mmio_base + (addr.bus << 20 | addr.device << 15 | addr.function << 12))
+    }
+}
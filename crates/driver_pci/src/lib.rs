@@ -13,6 +13,8 @@
 #[cfg(feature = "bcm2711")]
 mod bcm2711;
 extern crate alloc;
+pub mod capability;
+mod ecam;
 pub mod err;
 mod root_complex;
 pub mod types;
@@ -39,17 +41,7 @@ cfg_match! {
         pub type RootComplex = PciRootComplex<bcm2711::BCM2711>;
     }
     _=>{
-        struct DummyPciRoot;
-        pub type RootComplex = PciRootComplex<DummyPciRoot>;
-        impl Access for DummyPciRoot {
-            fn setup(mmio_base: usize) {}
-
-            fn probe_bridge(mmio_base: usize, bridge_header: &ConifgPciPciBridge) {}
-
-            fn map_conf(mmio_base: usize, addr: PciAddress) -> Option<usize> {
-                None
-            }
-        }
+        pub type RootComplex = PciRootComplex<ecam::GenericEcam>;
     }
 }
 
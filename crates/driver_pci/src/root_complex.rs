@@ -56,6 +56,14 @@ impl<A: Access> PciRootComplex<A> {
         }
     }
 
+    /// Returns the common header of the given function.
+    ///
+    /// Some root complexes access the configuration space through a shared
+    /// window, so the header is only valid until another function is mapped.
+    pub fn header(&self, bdf: PciAddress) -> Option<PciHeader> {
+        A::map_conf(self.mmio_base, bdf).map(PciHeader::new)
+    }
+
     pub fn bar_info(&self, bdf: PciAddress, slot: u8) -> Option<Bar> {
         let cfg_addr = A::map_conf(self.mmio_base, bdf).unwrap();
         let mut ep = ConifgEndpoint::new(cfg_addr);
@@ -218,10 +226,14 @@ fn config_ep(cfg_addr: usize, allocator: &mut PciRangeAllocator) -> ConifgEndpoi
                     size,
                     prefetchable,
                 } => {
-                    let addr = allocator.alloc(size).unwrap();
-                    unsafe {
-                        ep.write_bar64(slot, addr);
-                    }
+                    // keep the firmware assignment if there is no range to allocate from
+                    let addr = match allocator.alloc(size) {
+                        Some(addr) => {
+                            ep.write_bar64(slot, addr);
+                            addr
+                        }
+                        None => address,
+                    };
                     debug!(
                         "  BAR {}: MEM [{:#x}, {:#x}){}{}",
                         slot,
@@ -238,10 +250,13 @@ fn config_ep(cfg_addr: usize, allocator: &mut PciRangeAllocator) -> ConifgEndpoi
                     size,
                     prefetchable,
                 } => {
-                    let addr = allocator.alloc(size as u64).unwrap() as u32;
-                    unsafe {
-                        ep.write_bar32(slot, addr);
-                    }
+                    let addr = match allocator.alloc(size as u64) {
+                        Some(addr) => {
+                            ep.write_bar32(slot, addr as u32);
+                            addr as u32
+                        }
+                        None => address,
+                    };
                     debug!(
                         "  BAR {}: MEM [{:#x}, {:#x}){}{}",
                         slot,
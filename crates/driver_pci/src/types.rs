@@ -1,3 +1,4 @@
+use crate::capability::{CapabilityIterator, MsiCapability, CAP_ID_MSI};
 use crate::PciAddress;
 use bit_field::BitField;
 use tock_registers::interfaces::ReadWriteable;
@@ -172,6 +173,47 @@ impl PciHeader {
             .fold(0u16, |acc, a| acc + a.clone() as u16);
         self.regs().command.set(cmd)
     }
+
+    pub fn command(&self) -> u16 {
+        self.regs().command.get()
+    }
+
+    /// Sets the given command bits, keeping the others untouched.
+    pub fn enable_command(&self, command: impl IntoIterator<Item = ConfigCommand>) {
+        let cmd = command
+            .into_iter()
+            .fold(self.command(), |acc, a| acc | a.clone() as u16);
+        self.regs().command.set(cmd)
+    }
+
+    /// Clears the given command bits, keeping the others untouched.
+    pub fn disable_command(&self, command: impl IntoIterator<Item = ConfigCommand>) {
+        let cmd = command
+            .into_iter()
+            .fold(self.command(), |acc, a| acc & !(a.clone() as u16));
+        self.regs().command.set(cmd)
+    }
+
+    /// Returns the interrupt pin (1 = INTA# .. 4 = INTD#, 0 = none) and the
+    /// interrupt line assigned by firmware.
+    pub fn interrupt_pin_and_line(&self) -> (u8, u8) {
+        let reg = unsafe { ((self.cfg_base + 0x3c) as *const u32).read_volatile() };
+        (reg.get_bits(8..16) as u8, reg.get_bits(0..8) as u8)
+    }
+
+    pub fn capabilities(&self) -> CapabilityIterator {
+        if self.regs().status.is_set(RC_CFG_STATUS::CAPABILITIES_LIST) {
+            CapabilityIterator::new(self.cfg_base)
+        } else {
+            CapabilityIterator::empty()
+        }
+    }
+
+    pub fn msi(&self) -> Option<MsiCapability> {
+        self.capabilities()
+            .find(|cap| cap.id == CAP_ID_MSI)
+            .map(|cap| MsiCapability::new(self.cfg_base, &cap))
+    }
 }
 
 pub type Revision = u8;
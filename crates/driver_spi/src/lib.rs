@@ -0,0 +1,312 @@
+//! SPI master drivers.
+//!
+//! A controller backend implements [`SpiController`]. A [`SpiMaster`] owns a
+//! backend and implements [`embedded_hal::spi::SpiBus`] for exclusive use.
+//! Several devices share it through [`SpiDev`]s, which implement
+//! [`embedded_hal::spi::SpiDevice`]: a transaction locks the bus, applies the
+//! [`SpiConfig`] of the device and drives its [`ChipSelect`] around the
+//! operations.
+//!
+//! Backends:
+//!
+//! - [`phytium::PhytiumSpi`] (feature `phytium`): the FSPIM controllers of the
+//!   Phytium SoCs, with optional DMA through a [`DmaChannel`].
+//! - [`mock::MockSpi`] (feature `mock`, and in tests): records the bus
+//!   traffic and answers from a script or by looping MOSI back to MISO, so
+//!   that device drivers can be tested on the host.
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(any(feature = "phytium", feature = "mock", test))]
+extern crate alloc;
+
+#[cfg(any(feature = "mock", test))]
+pub mod mock;
+#[cfg(feature = "phytium")]
+pub mod phytium;
+
+#[cfg(test)]
+mod tests;
+
+use core::convert::Infallible;
+use core::fmt;
+
+use axsync::Mutex;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::{self, ErrorKind, ErrorType, Mode, Operation, MODE_0};
+
+/// Errors of SPI operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiError {
+    /// There is no controller with this index.
+    InvalidController(usize),
+    /// The controller is already owned.
+    InUse,
+    /// The clock frequency cannot be configured.
+    UnsupportedFrequency(u32),
+    /// The controller has no chip-select line with this index.
+    InvalidChipSelect(u8),
+    /// The controller did not make progress in time.
+    Timeout,
+    /// The receive FIFO overflowed.
+    Overrun,
+    /// The DMA channel failed.
+    Dma,
+    /// The chip-select pin could not be driven.
+    ChipSelect,
+}
+
+impl fmt::Display for SpiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidController(id) => write!(f, "no SPI controller {}", id),
+            Self::InUse => write!(f, "SPI controller in use"),
+            Self::UnsupportedFrequency(hz) => write!(f, "unsupported SPI clock {} Hz", hz),
+            Self::InvalidChipSelect(cs) => write!(f, "no chip select {}", cs),
+            Self::Timeout => write!(f, "SPI timeout"),
+            Self::Overrun => write!(f, "SPI receive overrun"),
+            Self::Dma => write!(f, "SPI DMA error"),
+            Self::ChipSelect => write!(f, "SPI chip select error"),
+        }
+    }
+}
+
+impl spi::Error for SpiError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Overrun => ErrorKind::Overrun,
+            Self::ChipSelect => ErrorKind::ChipSelectFault,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Clock settings of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiConfig {
+    /// Clock polarity and phase.
+    pub mode: Mode,
+    /// Highest clock frequency of the device, in Hz.
+    pub frequency_hz: u32,
+}
+
+impl Default for SpiConfig {
+    fn default() -> Self {
+        Self {
+            mode: MODE_0,
+            frequency_hz: 1_000_000,
+        }
+    }
+}
+
+/// A SPI controller in master mode, transferring 8-bit words MSB first.
+pub trait SpiController: Send {
+    /// Applies the clock settings to the following transfers.
+    fn configure(&mut self, config: &SpiConfig) -> Result<(), SpiError>;
+
+    /// Asserts or deasserts the native chip-select line `cs`.
+    fn set_cs(&mut self, cs: u8, active: bool) -> Result<(), SpiError>;
+
+    /// Clocks `max(read.len(), write.len())` words, sending `write` padded
+    /// with zeros and keeping the first `read.len()` words received.
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError>;
+
+    /// Sends `words` and replaces them with the words received.
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), SpiError>;
+
+    /// Waits until all words have left the controller.
+    fn flush(&mut self) -> Result<(), SpiError>;
+
+    /// Waits for at least `ns` nanoseconds.
+    fn delay_ns(&mut self, ns: u32);
+}
+
+/// Direction of a DMA transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaDirection {
+    MemToDevice,
+    DeviceToMemory,
+}
+
+/// A DMA channel serving a controller FIFO.
+///
+/// The channel follows the handshake of the controller and keeps the CPU
+/// caches coherent with the memory it accesses.
+pub trait DmaChannel: Send {
+    /// Starts moving `len` bytes between the memory at physical address `mem`
+    /// and the data register at physical address `dev`.
+    fn start(
+        &mut self,
+        dir: DmaDirection,
+        mem: usize,
+        dev: usize,
+        len: usize,
+    ) -> Result<(), SpiError>;
+
+    /// Waits for the transfer started last to complete.
+    fn wait(&mut self) -> Result<(), SpiError>;
+}
+
+/// A chip-select pin that is never driven, for [`ChipSelect`]s without GPIO.
+pub struct NoPin;
+
+impl embedded_hal::digital::ErrorType for NoPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// How the chip select of a device is driven.
+pub enum ChipSelect<P = NoPin> {
+    /// The device has no chip select, or it is tied active.
+    None,
+    /// A chip-select line of the controller.
+    Native(u8),
+    /// A GPIO pin, active low.
+    Gpio(P),
+}
+
+impl<P: OutputPin> ChipSelect<P> {
+    fn set<C: SpiController>(&mut self, ctrl: &mut C, active: bool) -> Result<(), SpiError> {
+        match self {
+            Self::None => Ok(()),
+            Self::Native(cs) => ctrl.set_cs(*cs, active),
+            Self::Gpio(pin) => pin
+                .set_state((!active).into())
+                .map_err(|_| SpiError::ChipSelect),
+        }
+    }
+}
+
+struct Inner<C> {
+    ctrl: C,
+    /// The settings applied last.
+    config: Option<SpiConfig>,
+}
+
+impl<C: SpiController> Inner<C> {
+    fn configure(&mut self, config: &SpiConfig) -> Result<(), SpiError> {
+        if self.config.as_ref() != Some(config) {
+            self.config = None;
+            self.ctrl.configure(config)?;
+            self.config = Some(*config);
+        }
+        Ok(())
+    }
+}
+
+/// A SPI bus: a controller shared by the devices on it.
+pub struct SpiMaster<C> {
+    inner: Mutex<Inner<C>>,
+}
+
+impl<C: SpiController> SpiMaster<C> {
+    /// Creates a bus over `ctrl`.
+    pub fn new(ctrl: C) -> Self {
+        Self {
+            inner: Mutex::new(Inner { ctrl, config: None }),
+        }
+    }
+
+    /// Returns the controller.
+    pub fn into_inner(self) -> C {
+        self.inner.into_inner().ctrl
+    }
+
+    /// Applies `config` to the transfers made through [`SpiBus`].
+    ///
+    /// [`SpiBus`]: embedded_hal::spi::SpiBus
+    pub fn configure(&self, config: &SpiConfig) -> Result<(), SpiError> {
+        self.inner.lock().configure(config)
+    }
+
+    /// A device on the bus, selected with `cs` and clocked with `config`.
+    pub fn device<P: OutputPin>(&self, cs: ChipSelect<P>, config: SpiConfig) -> SpiDev<'_, C, P> {
+        SpiDev {
+            bus: self,
+            cs,
+            config,
+        }
+    }
+}
+
+impl<C> ErrorType for SpiMaster<C> {
+    type Error = SpiError;
+}
+
+impl<C: SpiController> spi::SpiBus for SpiMaster<C> {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), SpiError> {
+        self.inner.get_mut().ctrl.transfer(words, &[])
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), SpiError> {
+        self.inner.get_mut().ctrl.transfer(&mut [], words)
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError> {
+        self.inner.get_mut().ctrl.transfer(read, write)
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), SpiError> {
+        self.inner.get_mut().ctrl.transfer_in_place(words)
+    }
+
+    fn flush(&mut self) -> Result<(), SpiError> {
+        self.inner.get_mut().ctrl.flush()
+    }
+}
+
+/// A device on a [`SpiMaster`].
+pub struct SpiDev<'a, C, P = NoPin> {
+    bus: &'a SpiMaster<C>,
+    cs: ChipSelect<P>,
+    config: SpiConfig,
+}
+
+impl<C, P> SpiDev<'_, C, P> {
+    /// The clock settings of the device.
+    pub fn config(&self) -> &SpiConfig {
+        &self.config
+    }
+
+    /// Changes the clock settings of the device.
+    pub fn set_config(&mut self, config: SpiConfig) {
+        self.config = config;
+    }
+}
+
+impl<C, P> ErrorType for SpiDev<'_, C, P> {
+    type Error = SpiError;
+}
+
+impl<C: SpiController, P: OutputPin> spi::SpiDevice for SpiDev<'_, C, P> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), SpiError> {
+        let mut inner = self.bus.inner.lock();
+        inner.configure(&self.config)?;
+        let ctrl = &mut inner.ctrl;
+        self.cs.set(ctrl, true)?;
+        let result = operations.iter_mut().try_for_each(|op| match op {
+            Operation::Read(words) => ctrl.transfer(words, &[]),
+            Operation::Write(words) => ctrl.transfer(&mut [], words),
+            Operation::Transfer(read, write) => ctrl.transfer(read, write),
+            Operation::TransferInPlace(words) => ctrl.transfer_in_place(words),
+            Operation::DelayNs(ns) => {
+                ctrl.flush()?;
+                ctrl.delay_ns(*ns);
+                Ok(())
+            }
+        });
+        // Deassert the chip select even if an operation failed.
+        let flushed = ctrl.flush();
+        let deselected = self.cs.set(ctrl, false);
+        result.and(flushed).and(deselected)
+    }
+}
@@ -0,0 +1,151 @@
+//! An in-memory SPI controller for testing device drivers.
+//!
+//! [`MockSpi`] records every call as a [`MockEvent`]. Received words come
+//! from a queue of scripted responses, or are the words sent when the mock is
+//! in loopback mode, as if MOSI were wired to MISO.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axsync::Mutex;
+
+use crate::{SpiConfig, SpiController, SpiError};
+
+/// Word received when nothing drives MISO.
+pub const IDLE_WORD: u8 = 0xFF;
+
+/// A call made to a [`MockSpi`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockEvent {
+    Configure(SpiConfig),
+    /// A native chip-select line was asserted (`true`) or deasserted.
+    ChipSelect(u8, bool),
+    /// Words clocked out, and words clocked in.
+    Transfer {
+        mosi: Vec<u8>,
+        miso: Vec<u8>,
+    },
+    Delay(u32),
+}
+
+#[derive(Default)]
+struct State {
+    events: Vec<MockEvent>,
+    responses: VecDeque<u8>,
+}
+
+/// An in-memory [`SpiController`].
+///
+/// The mock is a handle: clones share the recorded events, so that a test
+/// can keep one while the driver under test owns another.
+#[derive(Clone)]
+pub struct MockSpi {
+    state: Arc<Mutex<State>>,
+    loopback: bool,
+}
+
+impl MockSpi {
+    /// A mock answering with the scripted responses, then [`IDLE_WORD`]s.
+    pub fn new() -> Self {
+        Self {
+            state: Default::default(),
+            loopback: false,
+        }
+    }
+
+    /// A mock receiving the words it sends.
+    pub fn loopback() -> Self {
+        Self {
+            loopback: true,
+            ..Self::new()
+        }
+    }
+
+    /// Queues `words` to be received by the following transfers.
+    pub fn respond(&self, words: &[u8]) {
+        self.state.lock().responses.extend(words);
+    }
+
+    /// The events recorded so far.
+    pub fn events(&self) -> Vec<MockEvent> {
+        self.state.lock().events.clone()
+    }
+
+    /// Returns the events recorded so far and forgets them.
+    pub fn take_events(&self) -> Vec<MockEvent> {
+        core::mem::take(&mut self.state.lock().events)
+    }
+
+    /// All words sent so far, across transfers.
+    pub fn sent(&self) -> Vec<u8> {
+        let state = self.state.lock();
+        let transfers = state.events.iter().filter_map(|e| match e {
+            MockEvent::Transfer { mosi, .. } => Some(mosi.iter().copied()),
+            _ => None,
+        });
+        transfers.flatten().collect()
+    }
+
+    fn record(&self, event: MockEvent) {
+        self.state.lock().events.push(event);
+    }
+
+    /// Clocks `mosi` out and returns the words clocked in.
+    fn clock(&mut self, mosi: Vec<u8>) -> Vec<u8> {
+        let mut state = self.state.lock();
+        let miso: Vec<u8> = if self.loopback {
+            mosi.clone()
+        } else {
+            let responses = &mut state.responses;
+            mosi.iter()
+                .map(|_| responses.pop_front().unwrap_or(IDLE_WORD))
+                .collect()
+        };
+        state.events.push(MockEvent::Transfer {
+            mosi,
+            miso: miso.clone(),
+        });
+        miso
+    }
+}
+
+impl Default for MockSpi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpiController for MockSpi {
+    fn configure(&mut self, config: &SpiConfig) -> Result<(), SpiError> {
+        self.record(MockEvent::Configure(*config));
+        Ok(())
+    }
+
+    fn set_cs(&mut self, cs: u8, active: bool) -> Result<(), SpiError> {
+        self.record(MockEvent::ChipSelect(cs, active));
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError> {
+        let mut mosi = write.to_vec();
+        mosi.resize(read.len().max(write.len()), 0);
+        let miso = self.clock(mosi);
+        read.copy_from_slice(&miso[..read.len()]);
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), SpiError> {
+        let miso = self.clock(words.to_vec());
+        words.copy_from_slice(&miso);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), SpiError> {
+        Ok(())
+    }
+
+    fn delay_ns(&mut self, ns: u32) {
+        self.record(MockEvent::Delay(ns));
+    }
+}
@@ -0,0 +1,479 @@
+//! FSPIM controllers of the Phytium SoCs.
+//!
+//! SPI0–SPI3 are DesignWare SSI blocks with a chip-select register added by
+//! Phytium, which holds a native chip select asserted across transfers. Words
+//! are moved through the FIFOs by polling, or by DMA for long transfers once
+//! channels are attached with [`PhytiumSpi::set_dma`].
+//!
+//! The controller only clocks while a chip select is enabled: devices with a
+//! GPIO chip select, or none, run their transfers with native line 0 enabled,
+//! which must then be left unconnected.
+
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axhal::mem::{phys_to_virt, virt_to_phys, PhysAddr, VirtAddr};
+use axhal::time::{busy_wait, current_time};
+use embedded_hal::spi::{Phase, Polarity};
+use log::*;
+
+use crate::{DmaChannel, DmaDirection, SpiConfig, SpiController, SpiError};
+
+/// Number of FSPIM controllers.
+pub const SPI_COUNT: usize = 4;
+/// Number of native chip-select lines of a controller.
+pub const CS_COUNT: u8 = 4;
+
+/// Reference clock of the controllers.
+const REF_CLK_HZ: u32 = 50_000_000;
+/// Shortest transfer worth setting up DMA for.
+const DMA_MIN_LEN: usize = 64;
+/// Longest time a word may take to go through the FIFOs.
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+const SPI_BASES: [usize; SPI_COUNT] = [0x2803_A000, 0x2803_B000, 0x2803_C000, 0x2803_D000];
+
+// Registers.
+const CTRLR0: usize = 0x00;
+const SSIENR: usize = 0x08;
+const SER: usize = 0x10;
+const BAUDR: usize = 0x14;
+const TXFTLR: usize = 0x18;
+const TXFLR: usize = 0x20;
+const SR: usize = 0x28;
+const IMR: usize = 0x2C;
+const RISR: usize = 0x34;
+const ICR: usize = 0x48;
+const DMACR: usize = 0x4C;
+const DMATDLR: usize = 0x50;
+const DMARDLR: usize = 0x54;
+const DR: usize = 0x60;
+const CS: usize = 0x100;
+
+// CTRLR0 fields.
+const CTRLR0_DFS_8BIT: u32 = 7;
+const CTRLR0_SCPH: u32 = 1 << 6;
+const CTRLR0_SCPOL: u32 = 1 << 7;
+const CTRLR0_TMOD_SHIFT: u32 = 8;
+const CTRLR0_TMOD_MASK: u32 = 0b11 << CTRLR0_TMOD_SHIFT;
+const TMOD_TX_RX: u32 = 0;
+const TMOD_TX_ONLY: u32 = 1;
+
+// SR bits.
+const SR_BUSY: u32 = 1 << 0;
+const SR_TFE: u32 = 1 << 2;
+const SR_RFNE: u32 = 1 << 3;
+
+// RISR bits.
+const INTR_RXOI: u32 = 1 << 3;
+
+// DMACR bits.
+const DMACR_RDMAE: u32 = 1 << 0;
+const DMACR_TDMAE: u32 = 1 << 1;
+
+/// CS bits: software control of line `cs`, and its level.
+const fn cs_enable(cs: u8) -> u32 {
+    1 << (cs + 8)
+}
+
+const fn cs_level(cs: u8) -> u32 {
+    1 << cs
+}
+
+static TAKEN: [AtomicBool; SPI_COUNT] = [const { AtomicBool::new(false) }; SPI_COUNT];
+
+struct Regs {
+    base: usize,
+}
+
+impl Regs {
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.base + offset) as *mut u32, value) }
+    }
+}
+
+/// A pair of DMA channels serving the TX and RX FIFOs.
+struct Dma {
+    tx: Box<dyn DmaChannel>,
+    rx: Box<dyn DmaChannel>,
+}
+
+/// One of the SPI0–SPI3 controllers, in master mode.
+///
+/// The controller is released when dropped.
+pub struct PhytiumSpi {
+    id: usize,
+    regs: Regs,
+    fifo_depth: u32,
+    ctrlr0: u32,
+    /// The native chip select asserted by [`SpiController::set_cs`], if any.
+    native_cs: Option<u8>,
+    dma: Option<Dma>,
+}
+
+impl PhytiumSpi {
+    /// Takes the controller SPI`id` and resets it to mode 0 at 1 MHz.
+    pub fn new(id: usize) -> Result<Self, SpiError> {
+        let base = *SPI_BASES.get(id).ok_or(SpiError::InvalidController(id))?;
+        if TAKEN[id].swap(true, Ordering::Acquire) {
+            return Err(SpiError::InUse);
+        }
+        Self::with_base(id, phys_to_virt(PhysAddr::from(base)).as_usize())
+    }
+
+    /// Resets the controller SPI`id` mapped at `base`.
+    fn with_base(id: usize, base: usize) -> Result<Self, SpiError> {
+        let mut spi = Self {
+            id,
+            regs: Regs { base },
+            fifo_depth: 0,
+            ctrlr0: CTRLR0_DFS_8BIT,
+            native_cs: None,
+            dma: None,
+        };
+        spi.regs.write(SSIENR, 0);
+        spi.regs.write(IMR, 0);
+        spi.regs.write(SER, 0);
+        spi.regs.write(CS, 0);
+        spi.fifo_depth = spi.detect_fifo_depth();
+        spi.configure(&SpiConfig::default())?;
+        debug!("SPI{}: FIFO depth {}", id, spi.fifo_depth);
+        Ok(spi)
+    }
+
+    /// Index of the controller.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Moves transfers of at least 64 words by DMA, with `tx` feeding the TX
+    /// FIFO and `rx` draining the RX FIFO.
+    ///
+    /// The buffers of such transfers must be in the linear mapping of
+    /// physical memory.
+    pub fn set_dma(&mut self, tx: Box<dyn DmaChannel>, rx: Box<dyn DmaChannel>) {
+        self.dma = Some(Dma { tx, rx });
+    }
+
+    /// Detaches the DMA channels: all transfers are polled.
+    pub fn clear_dma(&mut self) {
+        self.dma = None;
+    }
+
+    /// The TX FIFO threshold can be set up to the depth of the FIFO.
+    fn detect_fifo_depth(&self) -> u32 {
+        let mut depth = 1;
+        while depth < 256 {
+            self.regs.write(TXFTLR, depth);
+            if self.regs.read(TXFTLR) != depth {
+                break;
+            }
+            depth += 1;
+        }
+        self.regs.write(TXFTLR, 0);
+        depth
+    }
+
+    /// Applies `ctrlr0` with the transfer mode `tmod`, enabling the
+    /// controller.
+    fn set_mode(&mut self, tmod: u32) {
+        let ctrlr0 = (self.ctrlr0 & !CTRLR0_TMOD_MASK) | (tmod << CTRLR0_TMOD_SHIFT);
+        self.regs.write(SSIENR, 0);
+        self.regs.write(CTRLR0, ctrlr0);
+        self.regs.write(SSIENR, 1);
+    }
+
+    fn wait_idle(&self) -> Result<(), SpiError> {
+        let deadline = current_time() + TIMEOUT;
+        while self.regs.read(SR) & (SR_TFE | SR_BUSY) != SR_TFE {
+            if current_time() > deadline {
+                return Err(SpiError::Timeout);
+            }
+            core::hint::spin_loop();
+        }
+        Ok(())
+    }
+
+    fn check_overrun(&self) -> Result<(), SpiError> {
+        if self.regs.read(RISR) & INTR_RXOI != 0 {
+            self.regs.read(ICR);
+            return Err(SpiError::Overrun);
+        }
+        Ok(())
+    }
+
+    /// Clocks `len` words through the FIFOs. `tx(i)` gives word `i` to send
+    /// and `rx(i, word)` takes word `i` received.
+    fn pio(
+        &mut self,
+        len: usize,
+        tx: impl Fn(usize) -> u8,
+        mut rx: impl FnMut(usize, u8),
+    ) -> Result<(), SpiError> {
+        self.set_mode(TMOD_TX_RX);
+        let (mut sent, mut received) = (0, 0);
+        let mut deadline = current_time() + TIMEOUT;
+        while received < len {
+            // Never have more words in flight than the RX FIFO can hold.
+            while sent < len
+                && sent - received < self.fifo_depth as usize
+                && self.regs.read(TXFLR) < self.fifo_depth
+            {
+                self.regs.write(DR, tx(sent) as u32);
+                sent += 1;
+            }
+            if self.regs.read(SR) & SR_RFNE != 0 {
+                rx(received, self.regs.read(DR) as u8);
+                received += 1;
+                deadline = current_time() + TIMEOUT;
+            } else if current_time() > deadline {
+                return Err(SpiError::Timeout);
+            }
+        }
+        self.check_overrun()
+    }
+
+    /// Sends `len` words from `tx`, storing the words received at `rx` if
+    /// any, by DMA.
+    fn dma(&mut self, tx: *const u8, rx: Option<*mut u8>, len: usize) -> Result<(), SpiError> {
+        let dr = SPI_BASES[self.id] + DR;
+        let paddr = |ptr: usize| virt_to_phys(VirtAddr::from(ptr)).as_usize();
+        self.set_mode(if rx.is_some() {
+            TMOD_TX_RX
+        } else {
+            TMOD_TX_ONLY
+        });
+        self.regs.write(DMATDLR, self.fifo_depth / 2);
+        self.regs.write(DMARDLR, 0);
+        let dma = self.dma.as_mut().unwrap();
+        let result = (|| {
+            if let Some(rx) = rx {
+                dma.rx
+                    .start(DmaDirection::DeviceToMemory, paddr(rx as usize), dr, len)?;
+            }
+            dma.tx
+                .start(DmaDirection::MemToDevice, paddr(tx as usize), dr, len)?;
+            self.regs.write(
+                DMACR,
+                DMACR_TDMAE | if rx.is_some() { DMACR_RDMAE } else { 0 },
+            );
+            dma.tx.wait()?;
+            if rx.is_some() {
+                dma.rx.wait()?;
+            }
+            Ok(())
+        })();
+        self.regs.write(DMACR, 0);
+        result?;
+        self.wait_idle()?;
+        self.check_overrun()
+    }
+
+    fn use_dma(&self, len: usize) -> bool {
+        self.dma.is_some() && len >= DMA_MIN_LEN
+    }
+
+    /// Enables the native chip select of the transfer: the asserted one, or
+    /// line 0 for devices without one, as the controller only clocks while a
+    /// slave is selected.
+    fn select_slave(&self) {
+        self.regs.write(SER, 1 << self.native_cs.unwrap_or(0));
+    }
+}
+
+impl Drop for PhytiumSpi {
+    fn drop(&mut self) {
+        self.regs.write(DMACR, 0);
+        self.regs.write(SSIENR, 0);
+        self.regs.write(SER, 0);
+        self.regs.write(CS, 0);
+        TAKEN[self.id].store(false, Ordering::Release);
+    }
+}
+
+impl SpiController for PhytiumSpi {
+    fn configure(&mut self, config: &SpiConfig) -> Result<(), SpiError> {
+        // The divider is even, from 2 to 65534.
+        let freq = config.frequency_hz;
+        let div = match freq {
+            0 => return Err(SpiError::UnsupportedFrequency(freq)),
+            _ => (REF_CLK_HZ.div_ceil(freq).max(2) + 1) & !1,
+        };
+        if div > 0xFFFE {
+            return Err(SpiError::UnsupportedFrequency(freq));
+        }
+        let mut ctrlr0 = CTRLR0_DFS_8BIT;
+        if config.mode.phase == Phase::CaptureOnSecondTransition {
+            ctrlr0 |= CTRLR0_SCPH;
+        }
+        if config.mode.polarity == Polarity::IdleHigh {
+            ctrlr0 |= CTRLR0_SCPOL;
+        }
+        self.ctrlr0 = ctrlr0;
+        self.regs.write(SSIENR, 0);
+        self.regs.write(BAUDR, div);
+        self.set_mode(TMOD_TX_RX);
+        trace!(
+            "SPI{}: {} Hz, CTRLR0 {:#x}",
+            self.id,
+            REF_CLK_HZ / div,
+            ctrlr0
+        );
+        Ok(())
+    }
+
+    fn set_cs(&mut self, cs: u8, active: bool) -> Result<(), SpiError> {
+        if cs >= CS_COUNT {
+            return Err(SpiError::InvalidChipSelect(cs));
+        }
+        if active {
+            self.native_cs = Some(cs);
+            self.regs.write(SER, 1 << cs);
+            self.regs.write(CS, cs_enable(cs));
+        } else {
+            self.native_cs = None;
+            self.regs.write(CS, cs_enable(cs) | cs_level(cs));
+            self.regs.write(CS, 0);
+            self.regs.write(SER, 0);
+        }
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), SpiError> {
+        self.select_slave();
+        let len = read.len().max(write.len());
+        if len == 0 {
+            return Ok(());
+        }
+        if self.use_dma(len) && read.is_empty() {
+            return self.dma(write.as_ptr(), None, len);
+        }
+        if self.use_dma(len) && read.len() == write.len() {
+            return self.dma(write.as_ptr(), Some(read.as_mut_ptr()), len);
+        }
+        self.pio(
+            len,
+            |i| write.get(i).copied().unwrap_or(0),
+            |i, word| {
+                if let Some(slot) = read.get_mut(i) {
+                    *slot = word;
+                }
+            },
+        )
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), SpiError> {
+        self.select_slave();
+        let len = words.len();
+        if self.use_dma(len) {
+            // The RX channel writes each word after the TX channel read it.
+            let ptr = words.as_mut_ptr();
+            return self.dma(ptr, Some(ptr), len);
+        }
+        // Word `i` is sent before it is overwritten by the word received.
+        let ptr = words.as_mut_ptr();
+        self.pio(
+            len,
+            |i| unsafe { ptr.add(i).read() },
+            |i, word| unsafe { ptr.add(i).write(word) },
+        )
+    }
+
+    fn flush(&mut self) -> Result<(), SpiError> {
+        self.wait_idle()
+    }
+
+    fn delay_ns(&mut self, ns: u32) {
+        busy_wait(Duration::from_nanos(ns as u64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::convert::Infallible;
+    use std::rc::Rc;
+
+    use embedded_hal::digital::{ErrorType, OutputPin};
+    use embedded_hal::spi::SpiDevice;
+
+    use super::*;
+    use crate::{ChipSelect, NoPin, SpiConfig, SpiMaster};
+
+    /// Registers backed by memory: `SR` reports an empty TX FIFO and a word
+    /// received, and `DR` reads back the last word written.
+    struct MockRegs(Box<[u32; CS / 4 + 1]>);
+
+    impl MockRegs {
+        fn new() -> Self {
+            let mut regs = Box::new([0; CS / 4 + 1]);
+            regs[SR / 4] = SR_TFE | SR_RFNE;
+            Self(regs)
+        }
+
+        fn base(&self) -> usize {
+            self.0.as_ptr() as usize
+        }
+
+        fn read(&self, offset: usize) -> u32 {
+            unsafe { core::ptr::read_volatile(&self.0[offset / 4]) }
+        }
+    }
+
+    /// A GPIO chip select that records `SER` when it is asserted.
+    struct SerPin {
+        base: usize,
+        ser: Rc<Cell<Option<u32>>>,
+    }
+
+    impl ErrorType for SerPin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for SerPin {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.ser.set(Some(Regs { base: self.base }.read(SER)));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_native_and_gpio_cs() {
+        let regs = MockRegs::new();
+        let bus = SpiMaster::new(PhytiumSpi::with_base(0, regs.base()).unwrap());
+        let ser = Rc::new(Cell::new(None));
+        let pin = SerPin {
+            base: regs.base(),
+            ser: ser.clone(),
+        };
+        let mut native = bus.device(ChipSelect::<NoPin>::Native(2), SpiConfig::default());
+        let mut gpio = bus.device(ChipSelect::Gpio(pin), SpiConfig::default());
+        let mut none = bus.device(ChipSelect::<NoPin>::None, SpiConfig::default());
+
+        native.write(&[1, 2]).unwrap();
+        assert_eq!(regs.read(SER), 0);
+        assert_eq!(regs.read(CS), 0);
+
+        // Line 2 is no longer selected when the GPIO chip select is asserted,
+        // and line 0 clocks the transfer.
+        gpio.write(&[3]).unwrap();
+        assert_eq!(ser.get(), Some(0));
+        assert_eq!(regs.read(SER), 1);
+        assert_eq!(regs.read(DR), 3);
+
+        native.write(&[4]).unwrap();
+        assert_eq!(regs.read(SER), 0);
+        none.write(&[5]).unwrap();
+        assert_eq!(regs.read(SER), 1);
+        assert_eq!(regs.read(DR), 5);
+    }
+}
@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use embedded_hal::digital::{ErrorType, OutputPin};
+use embedded_hal::spi::{Operation, SpiBus, SpiDevice, MODE_3};
+
+use crate::mock::{MockEvent, MockSpi, IDLE_WORD};
+use crate::*;
+
+/// A GPIO pin logging its levels.
+#[derive(Clone, Default)]
+struct LogPin(Rc<RefCell<Vec<bool>>>);
+
+impl ErrorType for LogPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for LogPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.borrow_mut().push(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.borrow_mut().push(true);
+        Ok(())
+    }
+}
+
+fn transfer(mosi: &[u8], miso: &[u8]) -> MockEvent {
+    MockEvent::Transfer {
+        mosi: mosi.to_vec(),
+        miso: miso.to_vec(),
+    }
+}
+
+#[test]
+fn test_loopback_bus() {
+    let mock = MockSpi::loopback();
+    let mut bus = SpiMaster::new(mock.clone());
+
+    let mut buf = [0u8; 4];
+    bus.transfer(&mut buf, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(buf, [1, 2, 3, 4]);
+
+    // Shorter writes are padded with zeros, longer reads are truncated.
+    let mut buf = [0xAAu8; 3];
+    bus.transfer(&mut buf, &[5]).unwrap();
+    assert_eq!(buf, [5, 0, 0]);
+    let mut buf = [0u8; 1];
+    bus.transfer(&mut buf, &[6, 7]).unwrap();
+    assert_eq!(buf, [6]);
+
+    let mut buf = [9, 8, 7];
+    bus.transfer_in_place(&mut buf).unwrap();
+    assert_eq!(buf, [9, 8, 7]);
+    assert_eq!(mock.sent(), [1, 2, 3, 4, 5, 0, 0, 6, 7, 9, 8, 7]);
+}
+
+#[test]
+fn test_device_transaction() {
+    let mock = MockSpi::new();
+    mock.respond(&[0x12, 0x34]);
+    let bus = SpiMaster::new(mock.clone());
+    let mut dev = bus.device(ChipSelect::<NoPin>::Native(1), SpiConfig::default());
+
+    let mut data = [0u8; 3];
+    dev.transaction(&mut [
+        Operation::Write(&[0x80]),
+        Operation::DelayNs(500),
+        Operation::Read(&mut data),
+    ])
+    .unwrap();
+    assert_eq!(data, [0x34, IDLE_WORD, IDLE_WORD]);
+    assert_eq!(
+        mock.take_events(),
+        [
+            MockEvent::Configure(SpiConfig::default()),
+            MockEvent::ChipSelect(1, true),
+            transfer(&[0x80], &[0x12]),
+            MockEvent::Delay(500),
+            transfer(&[0, 0, 0], &[0x34, IDLE_WORD, IDLE_WORD]),
+            MockEvent::ChipSelect(1, false),
+        ]
+    );
+
+    // The bus is not reconfigured for the same settings.
+    dev.write(&[1]).unwrap();
+    assert_eq!(
+        mock.take_events(),
+        [
+            MockEvent::ChipSelect(1, true),
+            transfer(&[1], &[IDLE_WORD]),
+            MockEvent::ChipSelect(1, false),
+        ]
+    );
+}
+
+#[test]
+fn test_shared_bus() {
+    let mock = MockSpi::loopback();
+    let bus = SpiMaster::new(mock.clone());
+    let fast = SpiConfig {
+        mode: MODE_3,
+        frequency_hz: 10_000_000,
+    };
+    let pin = LogPin::default();
+    let mut dev0 = bus.device(ChipSelect::Gpio(pin.clone()), SpiConfig::default());
+    let mut dev1 = bus.device(ChipSelect::<NoPin>::None, fast);
+
+    dev0.write(&[1]).unwrap();
+    dev1.write(&[2]).unwrap();
+    dev0.write(&[3]).unwrap();
+    assert_eq!(
+        mock.take_events(),
+        [
+            MockEvent::Configure(SpiConfig::default()),
+            transfer(&[1], &[1]),
+            MockEvent::Configure(fast),
+            transfer(&[2], &[2]),
+            MockEvent::Configure(SpiConfig::default()),
+            transfer(&[3], &[3]),
+        ]
+    );
+    // The GPIO chip select is active low.
+    assert_eq!(*pin.0.borrow(), [false, true, false, true]);
+}
@@ -11,6 +11,43 @@ use alloc::vec::Vec;
 use axalloc::global_no_cache_allocator;
 use log::debug;
 
+/// Direction of a streaming DMA transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaDirection {
+    /// The device reads the buffer.
+    ToDevice,
+    /// The device writes the buffer.
+    FromDevice,
+    /// The device both reads and writes the buffer.
+    Bidirectional,
+}
+
+/// Hands `len` bytes at `addr` over to the device before a transfer.
+///
+/// Buffers allocated from cacheable memory must be synced with this before
+/// the controller is told about them, and with [`sync_for_cpu`] once the
+/// transfer completed. It is cheap on uncached memory.
+pub fn sync_for_device(addr: usize, len: usize, dir: DmaDirection) {
+    let vaddr = addr.into();
+    match dir {
+        DmaDirection::ToDevice => axhal::arch::clean_dcache_range(vaddr, len),
+        // dirty lines must not be written back over what the device writes
+        DmaDirection::FromDevice => axhal::arch::invalidate_dcache_range(vaddr, len),
+        DmaDirection::Bidirectional => axhal::arch::flush_dcache_range(vaddr, len),
+    }
+}
+
+/// Hands `len` bytes at `addr` back to the CPU after a transfer.
+pub fn sync_for_cpu(addr: usize, len: usize, dir: DmaDirection) {
+    match dir {
+        DmaDirection::ToDevice => {}
+        // lines may have been speculatively fetched during the transfer
+        DmaDirection::FromDevice | DmaDirection::Bidirectional => {
+            axhal::arch::invalidate_dcache_range(addr.into(), len)
+        }
+    }
+}
+
 pub struct DMA<T, A>
 where
     T: ?Sized,
@@ -86,6 +123,16 @@ where
     pub fn addr_len_tuple(&self) -> (usize, usize) {
         (self.addr(), self.length_for_bytes())
     }
+
+    /// See [`sync_for_device`](self::sync_for_device).
+    pub fn sync_for_device(&self, dir: DmaDirection) {
+        self::sync_for_device(self.addr(), self.length_for_bytes(), dir)
+    }
+
+    /// See [`sync_for_cpu`](self::sync_for_cpu).
+    pub fn sync_for_cpu(&self, dir: DmaDirection) {
+        self::sync_for_cpu(self.addr(), self.length_for_bytes(), dir)
+    }
 }
 
 impl<T, A> DMA<[T], A>
@@ -10,6 +10,8 @@ pub enum Error {
     Pip,
     TimeOut,
     DontDoThatOnControlPipe,
+    /// The transfer ring can not take more TRBs until some complete.
+    RingFull,
 }
 
 impl Display for Error {
@@ -20,6 +22,7 @@ impl Display for Error {
             Error::TimeOut => write!(f, "timeout"),
             Error::CMD(cmd) => write!(f, "cmd fail: {:#?}", cmd),
             Error::Pip => write!(f, "piped"),
+            Error::RingFull => write!(f, "ring full, retry later"),
             Error::DontDoThatOnControlPipe => {
                 write!(f, "don't do that on controller pipe! illegal operation!")
             }
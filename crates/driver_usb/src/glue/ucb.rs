@@ -28,6 +28,9 @@ where
 #[derive(Debug)]
 pub enum CompleteCode {
     Event(TransferEventCompleteCode),
+    /// The URB was not queued because the ring was full, submit it again
+    /// later.
+    RingFull,
 }
 
 #[derive(Debug)]
@@ -26,6 +26,8 @@ where
     pub device_out_context_list: Vec<DMA<Device64Byte, O::DMA>>,
     pub device_input_context_list: Vec<DMA<Input64Byte, O::DMA>>,
     pub transfer_rings: Vec<Vec<Ring<O>>>,
+    /// stream context arrays of bulk endpoints with streams, by (slot, dci)
+    pub stream_arrays: BTreeMap<(usize, usize), StreamContextArray<O>>,
 }
 
 impl<O> DeviceContextList<O>
@@ -60,6 +62,7 @@ where
             device_out_context_list: out_context_list,
             device_input_context_list: in_context_list,
             transfer_rings,
+            stream_arrays: BTreeMap::new(),
             config: config.clone(),
         }
     }
@@ -87,6 +90,35 @@ where
             .collect();
 
         self.transfer_rings[slot] = trs;
+        self.stream_arrays.retain(|(s, _), _| *s != slot);
+    }
+
+    /// Allocates `streams` streams of endpoint `dci`, returns the address of
+    /// the stream context array to put in the endpoint context.
+    pub fn new_stream_array(
+        &mut self,
+        slot: usize,
+        dci: usize,
+        max_primary_streams: u8,
+        streams: usize,
+    ) -> u64 {
+        let os = self.config.lock().os.clone();
+        let array = StreamContextArray::new(os, max_primary_streams, streams);
+        let addr = array.register();
+        self.stream_arrays.insert((slot, dci), array);
+        addr
+    }
+
+    pub fn stream_ring_mut(
+        &mut self,
+        slot: usize,
+        dci: usize,
+        stream_id: u16,
+    ) -> Option<&mut Ring<O>> {
+        self.stream_arrays
+            .get_mut(&(slot, dci))?
+            .rings
+            .get_mut((stream_id as usize).checked_sub(1)?)
     }
 }
 
@@ -147,3 +179,64 @@ where
         self.entries.addr()
     }
 }
+
+register_structs! {
+    StreamContext{
+        (0x000 => dequeue_low: ReadWrite<u32>),
+        (0x004 => dequeue_high: ReadWrite<u32>),
+        (0x008 => stopped_edtla: ReadWrite<u32>),
+        (0x00C => _reserved),
+        (0x010 => @END),
+    }
+}
+
+/// Stream Context Type of a primary transfer ring in a linear array.
+const SCT_PRIMARY_TR: u32 = 1;
+
+impl StreamContext {
+    fn set_ring(&mut self, addr: u64, cycle: bool) {
+        let low = (addr as u32 & !0xf) | (SCT_PRIMARY_TR << 1) | cycle as u32;
+        self.dequeue_low.set(low);
+        self.dequeue_high.set((addr >> 32) as u32);
+    }
+}
+
+/// A linear stream context array, one transfer ring per stream.
+///
+/// Stream 0 is reserved, so `rings[i]` is the ring of stream `i + 1`.
+pub struct StreamContextArray<O>
+where
+    O: OSAbstractions,
+{
+    pub contexts: DMA<[StreamContext], O::DMA>,
+    pub rings: Vec<Ring<O>>,
+}
+
+impl<O> StreamContextArray<O>
+where
+    O: OSAbstractions,
+{
+    /// Creates an array of `2^(max_primary_streams + 1)` entries, the value
+    /// of MaxPStreams in the endpoint context, with a ring for each of the
+    /// first `streams` streams. The remaining entries are left invalid.
+    pub fn new(os: O, max_primary_streams: u8, streams: usize) -> Self {
+        let entries = 1usize << (max_primary_streams + 1);
+        let mut contexts: DMA<[StreamContext], O::DMA> = DMA::zeroed(entries, 64, os.dma_alloc());
+        let rings: Vec<Ring<O>> = (0..streams.min(entries - 1))
+            .map(|_| Ring::new(os.clone(), 32, true).unwrap())
+            .collect();
+        for (context, ring) in contexts.iter_mut().skip(1).zip(rings.iter()) {
+            context.set_ring(ring.register(), ring.cycle);
+        }
+        debug!(
+            "new stream context array of {} entries, {} streams",
+            entries,
+            rings.len()
+        );
+        Self { contexts, rings }
+    }
+
+    pub fn register(&self) -> u64 {
+        self.contexts.addr() as u64
+    }
+}
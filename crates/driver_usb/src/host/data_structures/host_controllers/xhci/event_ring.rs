@@ -22,6 +22,11 @@ register_structs! {
     }
 }
 
+/// Default number of event ring segments, each one holding
+/// [`EVENT_RING_SEGMENT_LEN`] TRBs.
+pub const EVENT_RING_SEGMENTS: usize = 4;
+pub const EVENT_RING_SEGMENT_LEN: usize = 256;
+
 pub struct EventRing<O>
 where
     O: OSAbstractions,
@@ -34,22 +39,29 @@ impl<O> EventRing<O>
 where
     O: OSAbstractions,
 {
-    pub fn new(os: O) -> Result<Self> {
+    /// Creates an event ring of `segments` segments, described by an event
+    /// ring segment table of as many entries.
+    pub fn new(os: O, segments: usize) -> Result<Self> {
         let a = os.dma_alloc();
         let mut ring = EventRing {
-            ste: DMA::zeroed(1, 64, a),
-            ring: Ring::new(os, 256, false)?,
+            ste: DMA::zeroed(segments, 64, a),
+            ring: Ring::with_segments(os, EVENT_RING_SEGMENT_LEN, segments, false)?,
         };
         ring.ring.cycle = true;
-        ring.ste[0].addr_low.set(ring.ring.register() as u32);
-        ring.ste[0]
-            .addr_high
-            .set((ring.ring.register() as u64 >> 32) as u32);
-        ring.ste[0].size.set(ring.ring.trbs.len() as u16);
+        for i in 0..segments {
+            let addr = ring.ring.segment_addr(i);
+            ring.ste[i].addr_low.set(addr as u32);
+            ring.ste[i].addr_high.set((addr >> 32) as u32);
+            ring.ste[i].size.set(ring.ring.segment_len() as u16);
+        }
 
         Ok(ring)
     }
 
+    pub fn segment_count(&self) -> usize {
+        self.ring.segment_count()
+    }
+
     /// 完成一次循环返回 true
     pub fn next(&mut self) -> Option<(Allowed, bool)> {
         let (data, flag) = self.ring.current_data();
@@ -1,4 +1,4 @@
-use alloc::{borrow::ToOwned, boxed::Box, sync::Arc, vec, vec::Vec};
+use alloc::{borrow::ToOwned, boxed::Box, format, sync::Arc, vec, vec::Vec};
 use context::{DeviceContextList, ScratchpadBufferArray};
 use core::{
     mem::{self, MaybeUninit},
@@ -30,6 +30,7 @@ use crate::{
     usb::{
         descriptors::{
             desc_configuration,
+            desc_endpoint::Endpoint,
             topological_desc::{
                 TopologicalUSBDescriptorConfiguration, TopologicalUSBDescriptorEndpoint,
                 TopologicalUSBDescriptorFunction,
@@ -48,6 +49,10 @@ use crate::{
 
 use super::Controller;
 
+/// Streams allocated per bulk endpoint are capped to `2^(n + 1) - 1`, each
+/// one has its own transfer ring.
+const MAX_PRIMARY_STREAMS: u8 = 4;
+
 mod context;
 mod event_ring;
 mod ring;
@@ -220,7 +225,8 @@ where
         let mut ir0 = regs.interrupter_register_set.interrupter_mut(0);
         {
             debug!("{TAG} Writing ERSTZ");
-            ir0.erstsz.update_volatile(|r| r.set(1));
+            let erstsz = self.event.segment_count() as u16;
+            ir0.erstsz.update_volatile(|r| r.set(erstsz));
 
             let erdp = self.event.erdp();
             debug!("{TAG} Writing ERDP: {:X}", erdp);
@@ -356,7 +362,7 @@ where
     }
 
     fn post_cmd(&mut self, mut trb: command::Allowed) -> crate::err::Result<CommandCompletion> {
-        let addr = self.cmd.enque_command(trb);
+        let addr = self.cmd.enque_command(trb)?;
 
         self.regs.doorbell.update_volatile_at(0, |r| {
             r.set_doorbell_stream_id(0);
@@ -396,6 +402,7 @@ where
                             c.command_trb_pointer(),
                             c.cycle_bit()
                         );
+                        self.cmd.retire(c.command_trb_pointer());
                         if c.command_trb_pointer() != addr {
                             continue;
                         }
@@ -450,6 +457,21 @@ where
         &mut self.dev_ctx.transfer_rings[device_slot_id][dci as usize - 1]
     }
 
+    /// Moves the dequeue position of the ring `trb_addr` belongs to, the
+    /// endpoint ring or one of its stream rings.
+    fn retire_transfer(&mut self, device_slot_id: usize, dci: u8, trb_addr: u64) {
+        if self.ep_ring_mut(device_slot_id, dci).retire(trb_addr) {
+            return;
+        }
+        if let Some(streams) = self
+            .dev_ctx
+            .stream_arrays
+            .get_mut(&(device_slot_id, dci as usize))
+        {
+            streams.rings.iter_mut().any(|ring| ring.retire(trb_addr));
+        }
+    }
+
     fn update_erdp(&mut self) {
         self.regs
             .interrupter_register_set
@@ -484,6 +506,7 @@ where
                         //     continue;
                         // }
                         trace!("code:{:?},pointer:{:x}", code, c.trb_pointer());
+                        self.retire_transfer(c.slot_id() as _, c.endpoint_id(), c.trb_pointer());
                         if CompletionCode::Success == code || CompletionCode::ShortPacket == code {
                             return Ok(c);
                         }
@@ -551,61 +574,7 @@ where
 
                                         for item in endpoints {
                                             if let TopologicalUSBDescriptorEndpoint::Standard(ep) = item {
-                                                let dci = ep.doorbell_value_aka_dci() as usize;
-                                                let max_packet_size = ep.max_packet_size;
-                                                let ring_addr =
-                                                    self.ep_ring_mut(device_slot_id, dci as _).register();
-
-                                                let input = self.dev_ctx.device_input_context_list
-                                                    [device_slot_id]
-                                                    .deref_mut();
-                                                let control_mut = input.control_mut();
-                                                debug!("init ep {} {:?}", dci, ep.endpoint_type());
-                                                control_mut.set_add_context_flag(dci);
-                                                let ep_mut = input.device_mut().endpoint_mut(dci);
-                                                ep_mut.set_interval(3);
-                                                ep_mut.set_endpoint_type(ep.endpoint_type());
-                                                ep_mut.set_tr_dequeue_pointer(ring_addr);
-                                                ep_mut.set_max_packet_size(max_packet_size);
-                                                ep_mut.set_error_count(3);
-                                                ep_mut.set_dequeue_cycle_state();
-                                                let endpoint_type = ep.endpoint_type();
-                                                match endpoint_type {
-                                                    EndpointType::Control => {}
-                                                    EndpointType::BulkOut | EndpointType::BulkIn => {
-                                                        ep_mut.set_max_burst_size(0);
-                                                        ep_mut.set_max_primary_streams(0);
-                                                    }
-                                                    EndpointType::IsochOut
-                                                    | EndpointType::IsochIn
-                                                    | EndpointType::InterruptOut
-                                                    | EndpointType::InterruptIn => {
-                                                        //init for isoch/interrupt
-                                                        ep_mut.set_max_packet_size(max_packet_size & 0x7ff); //refer xhci page 162
-                                                        ep_mut.set_max_burst_size(
-                                                            ((max_packet_size & 0x1800) >> 11)
-                                                                .try_into()
-                                                                .unwrap(),
-                                                        );
-                                                        ep_mut.set_mult(0); //always 0 for interrupt
-
-                                                        if let EndpointType::IsochOut
-                                                        | EndpointType::IsochIn = endpoint_type
-                                                        {
-                                                            ep_mut.set_error_count(0);
-                                                        }
-
-                                                        ep_mut.set_tr_dequeue_pointer(ring_addr);
-                                                        ep_mut
-                                                        .set_max_endpoint_service_time_interval_payload_low(
-                                                            4,
-                                                        );
-                                                        //best guess?
-                                                    }
-                                                    EndpointType::NotValid => {
-                                                        unreachable!("Not Valid Endpoint should not exist.")
-                                                    }
-                                                }
+                                                self.init_endpoint_context(device_slot_id, ep);
                                             }
                                         }
                                     });
@@ -683,56 +652,7 @@ where
 
                         for item in endpoints {
                             if let TopologicalUSBDescriptorEndpoint::Standard(ep) = item {
-                                let dci = ep.doorbell_value_aka_dci() as usize;
-                                let max_packet_size = ep.max_packet_size;
-                                let ring_addr =
-                                    self.ep_ring_mut(device_slot_id, dci as _).register();
-
-                                let input = self.dev_ctx.device_input_context_list[device_slot_id]
-                                    .deref_mut();
-                                let control_mut = input.control_mut();
-                                debug!("init ep {} {:?}", dci, ep.endpoint_type());
-                                control_mut.set_add_context_flag(dci);
-                                let ep_mut = input.device_mut().endpoint_mut(dci);
-                                ep_mut.set_interval(3);
-                                ep_mut.set_endpoint_type(ep.endpoint_type());
-                                ep_mut.set_tr_dequeue_pointer(ring_addr);
-                                ep_mut.set_max_packet_size(max_packet_size);
-                                ep_mut.set_error_count(3);
-                                ep_mut.set_dequeue_cycle_state();
-                                let endpoint_type = ep.endpoint_type();
-                                match endpoint_type {
-                                    EndpointType::Control => {}
-                                    EndpointType::BulkOut | EndpointType::BulkIn => {
-                                        ep_mut.set_max_burst_size(0);
-                                        ep_mut.set_max_primary_streams(0);
-                                    }
-                                    EndpointType::IsochOut
-                                    | EndpointType::IsochIn
-                                    | EndpointType::InterruptOut
-                                    | EndpointType::InterruptIn => {
-                                        //init for isoch/interrupt
-                                        ep_mut.set_max_packet_size(max_packet_size & 0x7ff); //refer xhci page 162
-                                        ep_mut.set_max_burst_size(
-                                            ((max_packet_size & 0x1800) >> 11).try_into().unwrap(),
-                                        );
-                                        ep_mut.set_mult(0); //always 0 for interrupt
-
-                                        if let EndpointType::IsochOut | EndpointType::IsochIn =
-                                            endpoint_type
-                                        {
-                                            ep_mut.set_error_count(0);
-                                        }
-
-                                        ep_mut.set_tr_dequeue_pointer(ring_addr);
-                                        ep_mut
-                                            .set_max_endpoint_service_time_interval_payload_low(4);
-                                        //best guess?
-                                    }
-                                    EndpointType::NotValid => {
-                                        unreachable!("Not Valid Endpoint should not exist.")
-                                    }
-                                }
+                                self.init_endpoint_context(device_slot_id, ep);
                             }
                         }
 
@@ -770,12 +690,103 @@ where
         )))
     }
 
-    fn prepare_transfer_normal(&mut self, device_slot_id: usize, dci: u8) {
+    /// Fills the input context of endpoint `ep`, honouring its SuperSpeed
+    /// companion descriptor: burst size, Mult and bulk streams.
+    fn init_endpoint_context(&mut self, device_slot_id: usize, ep: &Endpoint) {
+        let dci = ep.doorbell_value_aka_dci() as usize;
+        let endpoint_type = ep.endpoint_type();
+        let ring_addr = self.ep_ring_mut(device_slot_id, dci as _).register();
+        let streams = ep.max_streams().and_then(|streams| {
+            let max_pstreams = self.primary_streams(streams)?;
+            let array_addr = self.dev_ctx.new_stream_array(
+                device_slot_id,
+                dci,
+                max_pstreams,
+                1 << streams,
+            );
+            Some((max_pstreams, array_addr))
+        });
+        let lec = self
+            .regs
+            .capability
+            .hccparams2
+            .read_volatile()
+            .large_esit_payload_capability();
+
+        let input = self.dev_ctx.device_input_context_list[device_slot_id].deref_mut();
+        debug!("init ep {} {:?}", dci, endpoint_type);
+        input.control_mut().set_add_context_flag(dci);
+        let ep_mut = input.device_mut().endpoint_mut(dci);
+        ep_mut.set_interval(3);
+        ep_mut.set_endpoint_type(endpoint_type);
+        ep_mut.set_tr_dequeue_pointer(ring_addr);
+        ep_mut.set_max_packet_size(ep.max_packet_size);
+        ep_mut.set_error_count(3);
+        ep_mut.set_dequeue_cycle_state();
+        match endpoint_type {
+            EndpointType::Control => {}
+            EndpointType::BulkOut | EndpointType::BulkIn => {
+                ep_mut.set_max_burst_size(ep.max_burst());
+                match streams {
+                    Some((max_pstreams, array_addr)) => {
+                        debug!("ep {} with MaxPStreams {}", dci, max_pstreams);
+                        ep_mut.set_max_primary_streams(max_pstreams);
+                        ep_mut.set_linear_stream_array();
+                        // the dequeue cycle state is kept in each stream context
+                        ep_mut.set_tr_dequeue_pointer(array_addr);
+                        ep_mut.clear_dequeue_cycle_state();
+                    }
+                    None => {
+                        ep_mut.set_max_primary_streams(0);
+                    }
+                }
+            }
+            EndpointType::IsochOut
+            | EndpointType::IsochIn
+            | EndpointType::InterruptOut
+            | EndpointType::InterruptIn => {
+                //init for isoch/interrupt
+                ep_mut.set_max_packet_size(ep.packet_size()); //refer xhci page 162
+                ep_mut.set_max_burst_size(ep.max_burst());
+                ep_mut.set_mult(ep.mult(lec));
+
+                if let EndpointType::IsochOut | EndpointType::IsochIn = endpoint_type {
+                    ep_mut.set_error_count(0);
+                }
+
+                ep_mut.set_max_endpoint_service_time_interval_payload_low(
+                    ep.max_esit_payload().min(u16::MAX as u32) as u16,
+                );
+            }
+            EndpointType::NotValid => {
+                unreachable!("Not Valid Endpoint should not exist.")
+            }
+        }
+    }
+
+    /// MaxPStreams to program for an endpoint supporting `2^streams`
+    /// streams, `None` if the controller does not support streams.
+    fn primary_streams(&self, streams: u8) -> Option<u8> {
+        let max_psa_size = self
+            .regs
+            .capability
+            .hccparams1
+            .read_volatile()
+            .max_primary_stream_array_size();
+        // an array of 2^(n + 1) entries holds the 2^n streams and the reserved stream 0
+        (max_psa_size > 0).then(|| streams.min(max_psa_size).min(MAX_PRIMARY_STREAMS))
+    }
+
+    fn prepare_transfer_normal(&mut self, device_slot_id: usize, dci: u8) -> crate::err::Result {
         //in our code , the init state of transfer ring always has ccs = 0, so we use ccs =1 to fill transfer ring
         let mut normal = transfer::Normal::default();
         normal.set_cycle_bit();
         let ring = self.ep_ring_mut(device_slot_id, dci);
-        ring.enque_trbs(vec![normal.into_raw(); 31]) //the 32 is link trb
+        // fill the first segment, the last trb of it is reserved for link
+        ring.enque_trbs(vec![normal.into_raw(); ring.segment_len() - 1])?;
+        // these placeholders complete without any event
+        ring.forget_pending();
+        Ok(())
     }
 }
 
@@ -812,7 +823,18 @@ where
             trace!("new cmd ring");
             let cmd = Ring::new(config.lock().os.clone(), entries_per_page, true).unwrap();
             trace!("new evt ring");
-            let event = EventRing::new(config.lock().os.clone()).unwrap();
+            // the controller supports at most 2^ERST_Max event ring segments
+            let erst_max = 1usize
+                << regs
+                    .capability
+                    .hcsparams2
+                    .read_volatile()
+                    .event_ring_segment_table_max();
+            let event = EventRing::new(
+                config.lock().os.clone(),
+                event_ring::EVENT_RING_SEGMENTS.min(erst_max),
+            )
+            .unwrap();
 
             debug!("{TAG} ring size {}", cmd.len());
 
@@ -941,14 +963,7 @@ where
         }
         trbs.push(status.into());
 
-        let mut trb_pointers = Vec::new();
-
-        {
-            let ring = self.ep_ring_mut(dev_slot_id, 1);
-            for trb in trbs {
-                trb_pointers.push(ring.enque_transfer(trb));
-            }
-        }
+        let trb_pointers = self.ep_ring_mut(dev_slot_id, 1).enque_transfers(trbs)?;
 
         if trb_pointers.len() == 2 {
             trace!(
@@ -1167,7 +1182,7 @@ where
                     .set_interrupter_target(0)
                     .set_interrupt_on_short_packet()
                     .set_interrupt_on_completion(),
-            ));
+            ))?;
         self.regs.doorbell.update_volatile_at(dev_slot_id, |r| {
             r.set_doorbell_target(urb_req.endpoint_id as _);
         });
@@ -1192,11 +1207,89 @@ where
             })?
     }
 
+    fn bulk_transfer(
+        &mut self,
+        dev_slot_id: usize,
+        urb_req: trasnfer::bulk::BulkTransfer,
+    ) -> crate::err::Result<UCB<O>> {
+        let (addr, len) = urb_req.buffer_addr_len;
+        let dci = urb_req.endpoint_id as u8;
+
+        // a TRB moves at most 64KiB and must not cross a 64KiB boundary
+        let mut trbs = Vec::new();
+        let mut offset = 0;
+        loop {
+            let chunk_addr = addr + offset;
+            let chunk = (len - offset).min(0x10000 - (chunk_addr & 0xffff));
+            let mut normal = *Normal::new()
+                .set_data_buffer_pointer(chunk_addr as _)
+                .set_trb_transfer_length(chunk as _)
+                .set_interrupter_target(0)
+                .set_interrupt_on_short_packet();
+            offset += chunk;
+            if offset < len {
+                normal.set_chain_bit();
+            } else {
+                normal.set_interrupt_on_completion();
+            }
+            trbs.push(transfer::Allowed::Normal(normal));
+            if offset >= len {
+                break;
+            }
+        }
+
+        let ring = if urb_req.stream_id == 0 {
+            self.ep_ring_mut(dev_slot_id, dci)
+        } else {
+            self.dev_ctx
+                .stream_ring_mut(dev_slot_id, dci as _, urb_req.stream_id)
+                .ok_or_else(|| {
+                    Error::Param(format!(
+                        "no stream {} on slot {} dci {}",
+                        urb_req.stream_id, dev_slot_id, dci
+                    ))
+                })?
+        };
+        let trb_pointers = ring.enque_transfers(trbs)?;
+        trace!(
+            "[Transfer] >> bulk {} trbs, last@{:#X}",
+            trb_pointers.len(),
+            trb_pointers.last().unwrap()
+        );
+
+        fence(Ordering::Release);
+        self.regs.doorbell.update_volatile_at(dev_slot_id, |r| {
+            r.set_doorbell_target(dci);
+            r.set_doorbell_stream_id(urb_req.stream_id);
+        });
+
+        self.event_busy_wait_transfer(*trb_pointers.last().unwrap() as _)
+            .map(|transfer_event| match transfer_event.completion_code() {
+                Ok(complete) => match complete {
+                    CompletionCode::Success | CompletionCode::ShortPacket => Ok(UCB::new(
+                        CompleteCode::Event(TransferEventCompleteCode::Success),
+                    )),
+                    CompletionCode::BabbleDetectedError => Ok(UCB::new(CompleteCode::Event(
+                        TransferEventCompleteCode::Babble,
+                    ))),
+                    CompletionCode::StallError => Ok(UCB::new(CompleteCode::Event(
+                        TransferEventCompleteCode::Halt,
+                    ))),
+                    other => Ok(UCB::new(CompleteCode::Event(
+                        TransferEventCompleteCode::Unknown(other as u8),
+                    ))),
+                },
+                Err(fail) => Ok(UCB::new(CompleteCode::Event(
+                    TransferEventCompleteCode::Unknown(fail),
+                ))),
+            })?
+    }
+
     fn extra_step(&mut self, dev_slot_id: usize, urb_req: ExtraStep) -> crate::err::Result<UCB<O>> {
         match urb_req {
             ExtraStep::PrepareForTransfer(dci) => {
                 if dci > 1 {
-                    self.prepare_transfer_normal(dev_slot_id, dci as u8);
+                    self.prepare_transfer_normal(dev_slot_id, dci as u8)?;
                     Ok(UCB::<O>::new(CompleteCode::Event(
                         TransferEventCompleteCode::Success,
                     )))
@@ -7,6 +7,7 @@ use crate::abstractions::dma::DMA;
 use crate::abstractions::OSAbstractions;
 use crate::err::*;
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::slice;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -19,124 +20,297 @@ use xhci::ring::trb::event;
 use xhci::ring::trb::transfer;
 use xhci::ring::trb::Link;
 const TRB_LEN: usize = 4;
+/// Upper bound of segments a growable ring may chain together.
+pub const MAX_SEGMENTS: usize = 16;
 pub type TrbData = [u32; TRB_LEN];
 
+/// A TRB ring made of one or more segments.
+///
+/// Producer rings (`link = true`) chain their segments with Link TRBs, the
+/// last one toggling the cycle bit. When a TD does not fit before the oldest
+/// TRB the controller has not completed yet, a new segment is inserted after
+/// the current one, up to [`MAX_SEGMENTS`]; past that [`Error::RingFull`] is
+/// returned and the caller has to retry once some transfers have completed.
+///
+/// Consumer rings (`link = false`, the event ring) walk their segments in
+/// order and flip the cycle bit after the last one.
 pub struct Ring<O: OSAbstractions> {
+    os: O,
     link: bool,
-    pub trbs: DMA<[TrbData], O::DMA>,
+    segments: Vec<DMA<[TrbData], O::DMA>>,
+    segment_len: usize,
+    max_segments: usize,
+    /// segment of the enqueue (producer) or dequeue (consumer) pointer
+    seg: usize,
     pub i: usize,
     pub cycle: bool,
+    /// oldest TRB not completed by the controller yet, producer rings only
+    deq: (usize, usize),
 }
 
 impl<O: OSAbstractions> Ring<O> {
     pub fn new(os: O, len: usize, link: bool) -> Result<Self> {
-        let a = os.dma_alloc();
-        let mut trbs = DMA::new_vec([0; TRB_LEN], len, 64, a);
-        Ok(Self {
-            trbs,
+        Self::with_segments(os, len, 1, link)
+    }
+
+    /// Creates a ring of `segments` segments of `len` TRBs each.
+    pub fn with_segments(os: O, len: usize, segments: usize, link: bool) -> Result<Self> {
+        if len < 2 || segments == 0 {
+            return Err(Error::Param(format!(
+                "ring of {} segments with {} trbs",
+                segments, len
+            )));
+        }
+        let mut ring = Self {
+            os,
+            link,
+            segments: Vec::with_capacity(segments),
+            segment_len: len,
+            max_segments: MAX_SEGMENTS.max(segments),
+            seg: 0,
             i: 0,
             cycle: link,
-            link,
-        })
+            deq: (0, 0),
+        };
+        for _ in 0..segments {
+            let segment = ring.new_segment();
+            ring.segments.push(segment);
+        }
+        Ok(ring)
+    }
+
+    fn new_segment(&self) -> DMA<[TrbData], O::DMA> {
+        // stale TRBs must not look valid to the controller in the current pass
+        let stale = if self.link && !self.cycle { 1 } else { 0 };
+        DMA::new_vec([0, 0, 0, stale], self.segment_len, 64, self.os.dma_alloc())
     }
+
+    /// Total number of TRBs, Link TRBs included.
     pub fn len(&self) -> usize {
-        self.trbs.len()
+        self.segments.len() * self.segment_len
+    }
+
+    pub fn segment_len(&self) -> usize {
+        self.segment_len
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Bus address of the first TRB of segment `idx`.
+    pub fn segment_addr(&self, idx: usize) -> u64 {
+        self.segments[idx][0].as_ptr() as usize as u64
+    }
+
+    pub fn set_max_segments(&mut self, max: usize) {
+        self.max_segments = max.max(self.segments.len());
     }
 
     fn get_trb(&self) -> &TrbData {
-        unsafe { &self.trbs[self.i] }
+        &self.segments[self.seg][self.i]
     }
 
     pub fn register(&self) -> u64 {
         self.get_trb().as_ptr() as usize as u64
     }
 
-    pub fn enque_command(&mut self, mut trb: command::Allowed) -> usize {
+    pub fn enque_command(&mut self, mut trb: command::Allowed) -> Result<usize> {
         if self.cycle {
             trb.set_cycle_bit();
         } else {
             trb.clear_cycle_bit();
         }
-        let addr = self.enque_trb(trb.clone().into_raw());
+        let addr = self.enque_trb(trb.clone().into_raw())?;
         trace!("[CMD] >> {:?} @{:X}", trb, addr);
-        addr
+        Ok(addr)
+    }
+
+    pub fn enque_transfer(&mut self, trb: transfer::Allowed) -> Result<usize> {
+        self.reserve(1)?;
+        Ok(self.push_transfer(trb))
     }
 
-    pub fn enque_transfer(&mut self, mut trb: transfer::Allowed) -> usize {
+    /// Enqueues the TRBs of one TD, either all of them or none.
+    pub fn enque_transfers(&mut self, trbs: Vec<transfer::Allowed>) -> Result<Vec<usize>> {
+        self.reserve(trbs.len())?;
+        Ok(trbs.into_iter().map(|trb| self.push_transfer(trb)).collect())
+    }
+
+    fn push_transfer(&mut self, mut trb: transfer::Allowed) -> usize {
+        // the cycle bit may change when crossing a Link TRB
+        self.cross_link();
         if self.cycle {
             trb.set_cycle_bit();
         } else {
             trb.clear_cycle_bit();
         }
-        let addr = self.enque_trb(trb.clone().into_raw());
-        addr
+        self.push_trb(trb.into_raw())
     }
 
-    pub fn enque_trb(&mut self, mut trb: TrbData) -> usize {
-        self.trbs[self.i].copy_from_slice(&trb);
-        let addr = self.trbs[self.i].as_ptr() as usize;
+    pub fn enque_trb(&mut self, trb: TrbData) -> Result<usize> {
+        self.reserve(1)?;
+        Ok(self.push_trb(trb))
+    }
+
+    pub fn enque_trbs(&mut self, trb: Vec<TrbData>) -> Result<Vec<usize>> {
+        self.reserve(trb.len())?;
+        Ok(trb.into_iter().map(|ele| self.push_trb(ele)).collect())
+    }
+
+    fn push_trb(&mut self, trb: TrbData) -> usize {
+        self.cross_link();
+        let i = self.i;
+        let this_trb = &mut self.segments[self.seg][i];
+        this_trb.copy_from_slice(&trb);
+        let addr = this_trb.as_ptr() as usize;
         trace!(
-            "enqueued {} @{:#X}\n{:x}\n{:x}\n{:x}\n{:x}\n------------------------------------------------",
-            self.i, addr, trb[0], trb[1], trb[2], trb[3]
+            "enqueued {}:{} @{:#X}\n{:x}\n{:x}\n{:x}\n{:x}\n------------------------------------------------",
+            self.seg, i, addr, trb[0], trb[1], trb[2], trb[3]
         );
         self.next_index();
         addr
     }
 
-    pub fn enque_trbs(&mut self, trb: Vec<TrbData>) {
-        for ele in trb {
-            self.enque_trb(ele);
+    /// Number of TRBs that can be enqueued without growing the ring.
+    fn room(&self) -> usize {
+        let usable = self.segment_len - 1;
+        let pending = self.has_pending();
+        let count = self.segments.len();
+        let mut room = usable - self.i;
+        let mut next = (self.seg + 1) % count;
+        // never enter a segment which still holds uncompleted TRBs
+        while next != self.seg && !(pending && next == self.deq.0) {
+            room += usable;
+            next = (next + 1) % count;
+        }
+        room
+    }
+
+    /// Makes sure `n` TRBs fit, inserting segments as needed.
+    pub fn reserve(&mut self, n: usize) -> Result {
+        if !self.link {
+            return Ok(());
+        }
+        while self.room() < n {
+            self.grow()?;
         }
+        Ok(())
+    }
+
+    /// Inserts an empty segment right after the enqueue segment.
+    fn grow(&mut self) -> Result {
+        if self.segments.len() >= self.max_segments {
+            debug!("ring full, {} segments in use", self.segments.len());
+            return Err(Error::RingFull);
+        }
+        let segment = self.new_segment();
+        self.segments.insert(self.seg + 1, segment);
+        if self.deq.0 > self.seg {
+            self.deq.0 += 1;
+        }
+        debug!("ring grown to {} segments", self.segments.len());
+        Ok(())
+    }
+
+    /// Enqueue position, with a filled segment counting as the start of
+    /// the next one.
+    fn enqueue_pos(&self) -> (usize, usize) {
+        if self.link && self.i >= self.segment_len - 1 {
+            ((self.seg + 1) % self.segments.len(), 0)
+        } else {
+            (self.seg, self.i)
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        self.deq != self.enqueue_pos()
+    }
+
+    /// Marks everything up to and including the TRB at `addr` as completed,
+    /// as reported by an event. Returns false if `addr` is not on this ring.
+    pub fn retire(&mut self, addr: u64) -> bool {
+        let trb_size = mem::size_of::<TrbData>() as u64;
+        let found = (0..self.segments.len()).find_map(|idx| {
+            let base = self.segment_addr(idx);
+            let end = base + (self.segment_len as u64) * trb_size;
+            (base..end)
+                .contains(&addr)
+                .then(|| (idx, ((addr - base) / trb_size) as usize))
+        });
+        let Some((seg, i)) = found else {
+            return false;
+        };
+        self.deq = if i + 1 >= self.segment_len - 1 {
+            ((seg + 1) % self.segments.len(), 0)
+        } else {
+            (seg, i + 1)
+        };
+        true
+    }
+
+    /// Treats every TRB enqueued so far as completed, for TRBs the
+    /// controller will not report an event for.
+    pub fn forget_pending(&mut self) {
+        self.deq = self.enqueue_pos();
     }
 
     fn next_index(&mut self) -> usize {
         self.i += 1;
-        let mut need_link = false;
-        let len = self.len();
-
-        // link模式下，最后一个是Link
-        if self.link && self.i >= len - 1 {
-            self.i = 0;
-            need_link = true;
-            trace!("flip and link!")
-        } else if self.i >= len {
+        if !self.link && self.i >= self.segment_len {
             self.i = 0;
+            self.seg = (self.seg + 1) % self.segments.len();
         }
+        self.i
+    }
 
-        if need_link {
+    /// Writes the Link TRB at the end of a filled segment and moves on to
+    /// the next one. Done lazily, right before the next TRB is written, so
+    /// that a segment inserted meanwhile is linked in.
+    fn cross_link(&mut self) {
+        if !self.link || self.i < self.segment_len - 1 {
+            return;
+        }
+        let next = (self.seg + 1) % self.segments.len();
+        let address = self.segment_addr(next);
+        let mut link = Link::new();
+        link.set_ring_segment_pointer(address);
+        if next == 0 {
+            link.set_toggle_cycle();
+            trace!("flip and link!")
+        } else {
             trace!("link!");
-            let address = self.trbs[0].as_ptr() as usize;
-            let mut link = Link::new();
-            link.set_ring_segment_pointer(address as u64)
-                .set_toggle_cycle();
-
-            if self.cycle {
-                link.set_cycle_bit();
-            } else {
-                link.clear_cycle_bit();
-            }
-            let trb = command::Allowed::Link(link);
-            let link_trb = trb.into_raw();
-            let mut this_trb = &mut self.trbs[len - 1];
-            this_trb.copy_from_slice(&link_trb);
+        }
+        if self.cycle {
+            link.set_cycle_bit();
+        } else {
+            link.clear_cycle_bit();
+        }
+        let link_trb = command::Allowed::Link(link).into_raw();
+        let len = self.segment_len;
+        self.segments[self.seg][len - 1].copy_from_slice(&link_trb);
 
+        if next == 0 {
             self.cycle = !self.cycle;
         }
-
-        self.i
+        self.seg = next;
+        self.i = 0;
     }
 
     /// 完成一次循环返回true
     pub fn inc_deque(&mut self) -> bool {
         self.i += 1;
         let mut is_cycle = false;
-        let len = self.len();
         if self.link {
         } else {
-            if self.i >= len {
+            if self.i >= self.segment_len {
                 self.i = 0;
-                self.cycle = !self.cycle;
-                is_cycle = true;
+                self.seg += 1;
+                if self.seg >= self.segments.len() {
+                    self.seg = 0;
+                    self.cycle = !self.cycle;
+                    is_cycle = true;
+                }
             }
         }
 
@@ -144,10 +318,10 @@ impl<O: OSAbstractions> Ring<O> {
     }
 
     pub fn current_data(&mut self) -> (&TrbData, bool) {
-        (&self.trbs[self.i], self.cycle)
+        (&self.segments[self.seg][self.i], self.cycle)
     }
 
     pub fn get_len(&self) -> usize {
-        self.trbs.len()
+        self.len()
     }
 }
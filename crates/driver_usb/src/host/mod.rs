@@ -2,17 +2,31 @@ use alloc::{boxed::Box, collections::binary_heap::Iter, sync::Arc, vec::Vec};
 use data_structures::host_controllers::{xhci::XHCI, Controller, ControllerArc};
 use log::{debug, trace};
 use spinlock::SpinNoIrq;
-use xhci::ring::trb::event;
+use xhci::ring::trb::{event, transfer::Direction};
 
 use crate::{
-    abstractions::PlatformAbstractions,
-    err,
-    glue::{driver_independent_device_instance::DriverIndependentDeviceInstance, ucb::UCB},
-    usb::{self, operation::Configuration, trasnfer::control::ControlTransfer, urb::URB},
+    abstractions::{
+        dma::{self, DmaDirection},
+        PlatformAbstractions,
+    },
+    err::{self, Error},
+    glue::{
+        driver_independent_device_instance::DriverIndependentDeviceInstance,
+        ucb::{CompleteCode, UCB},
+    },
+    usb::{
+        self,
+        operation::Configuration,
+        trasnfer::control::ControlTransfer,
+        urb::{RequestedOperation, URB},
+    },
     USBSystemConfig,
 };
 
 pub mod data_structures;
+pub mod pci;
+#[cfg(feature = "usbmon")]
+pub mod usbmon;
 
 impl<O> USBSystemConfig<O>
 where
@@ -95,12 +109,23 @@ where
     }
 
     pub fn urb_request(&mut self, request: URB<O>) -> crate::err::Result<UCB<O>> {
-        match request.operation {
+        #[cfg(feature = "usbmon")]
+        let traced = usbmon::submit(request.device_slot_id, &request.operation);
+
+        let payload = payload_of(&request.operation);
+        if let Some(((addr, len), dir)) = payload {
+            dma::sync_for_device(addr, len, dir);
+        }
+
+        let result = match request.operation {
             usb::urb::RequestedOperation::Control(control) => {
                 trace!("request transfer!");
                 self.control_transfer(request.device_slot_id, control)
             }
-            usb::urb::RequestedOperation::Bulk => todo!(),
+            usb::urb::RequestedOperation::Bulk(bulk_transfer) => self
+                .controller
+                .lock()
+                .bulk_transfer(request.device_slot_id, bulk_transfer),
             usb::urb::RequestedOperation::Interrupt(interrupt_transfer) => self
                 .controller
                 .lock()
@@ -114,7 +139,16 @@ where
                 .controller
                 .lock()
                 .extra_step(request.device_slot_id, step),
+        };
+
+        if let Some(((addr, len), dir)) = payload {
+            dma::sync_for_cpu(addr, len, dir);
         }
+
+        #[cfg(feature = "usbmon")]
+        usbmon::complete(traced, &result);
+
+        result
     }
 
     pub fn tock(&mut self, todo_list_list: Vec<Vec<URB<O>>>) {
@@ -122,13 +156,46 @@ where
         todo_list_list.iter().for_each(|list| {
             list.iter().for_each(|todo| {
                 //debug!("tock! req: {:#?}", todo.operation);
-                if let Ok(ok) = self.urb_request(todo.clone())
-                    && let Some(sender) = &todo.sender
-                {
+                let ucb = match self.urb_request(todo.clone()) {
+                    Ok(ok) => ok,
+                    Err(Error::RingFull) => UCB::new(CompleteCode::RingFull),
+                    Err(_) => return,
+                };
+                if let Some(sender) = &todo.sender {
                     //debug!("send back!");
-                    sender.lock().receive_complete_event(ok);
+                    sender.lock().receive_complete_event(ucb);
                 };
             })
         })
     }
 }
+
+/// The data buffer of a transfer and the direction it moves in.
+fn payload_of(operation: &RequestedOperation) -> Option<((usize, usize), DmaDirection)> {
+    match operation {
+        RequestedOperation::Control(control) => control.data.map(|buffer| {
+            let dir = match control.request_type.direction {
+                Direction::In => DmaDirection::FromDevice,
+                Direction::Out => DmaDirection::ToDevice,
+            };
+            (buffer, dir)
+        }),
+        RequestedOperation::Interrupt(interrupt) => Some((
+            interrupt.buffer_addr_len,
+            dci_direction(interrupt.endpoint_id),
+        )),
+        RequestedOperation::Bulk(bulk) => {
+            Some((bulk.buffer_addr_len, dci_direction(bulk.endpoint_id)))
+        }
+        _ => None,
+    }
+}
+
+/// Endpoint 0 is dci 1, then odd dci are IN endpoints.
+fn dci_direction(dci: usize) -> DmaDirection {
+    if dci % 2 == 1 {
+        DmaDirection::FromDevice
+    } else {
+        DmaDirection::ToDevice
+    }
+}
@@ -0,0 +1,145 @@
+//! Locating xHCI controllers on the PCI bus, e.g. the VL805 behind the
+//! bcm2711 root complex or `qemu-xhci`.
+
+use axhal::mem::phys_to_virt;
+use driver_pci::{
+    types::{Bar, ConfigCommand, PciHeader},
+    DeviceFunction, DeviceFunctionInfo, PciRoot,
+};
+use log::{debug, info, warn};
+
+use crate::{abstractions::PlatformAbstractions, USBSystemConfig};
+
+const PCI_CLASS_SERIAL_BUS: u8 = 0x0c;
+const PCI_SUBCLASS_USB: u8 = 0x03;
+const PCI_PROG_IF_XHCI: u8 = 0x30;
+
+/// Doorbell of the local APIC, messages are delivered to the BSP.
+#[cfg(target_arch = "x86_64")]
+const MSI_ADDRESS: u64 = 0xfee0_0000;
+
+/// Vector of the xHCI MSI, the first of the vectors that `axhal` leaves to MSIs.
+#[cfg(target_arch = "x86_64")]
+pub const XHCI_MSI_VECTOR: u32 = 0x40;
+
+/// How the interrupt of a PCI function reaches the interrupt controller.
+#[derive(Clone, Copy, Debug)]
+pub enum PciIrqRouting {
+    /// Message signaled interrupt at the given vector.
+    Msi { vector: u32 },
+    /// Legacy interrupt pin (1 = INTA#) and the IRQ number it is wired to.
+    Intx { pin: u8, irq: u32 },
+    /// The function has no interrupt.
+    None,
+}
+
+impl PciIrqRouting {
+    pub fn irq_num(&self) -> Option<u32> {
+        match self {
+            PciIrqRouting::Msi { vector } => Some(*vector),
+            PciIrqRouting::Intx { irq, .. } => Some(*irq),
+            PciIrqRouting::None => None,
+        }
+    }
+}
+
+pub fn is_xhci(info: &DeviceFunctionInfo) -> bool {
+    info.class == PCI_CLASS_SERIAL_BUS
+        && info.subclass == PCI_SUBCLASS_USB
+        && info.prog_if == PCI_PROG_IF_XHCI
+}
+
+impl<O> USBSystemConfig<O>
+where
+    O: PlatformAbstractions,
+{
+    /// Creates the config of the xHCI controller at `bdf`.
+    ///
+    /// BAR0 is used as the register base. Memory decoding and bus mastering
+    /// are enabled, and the interrupt is routed through MSI where the platform
+    /// supports it, INTx otherwise.
+    pub fn from_pci(
+        root: &PciRoot,
+        bdf: DeviceFunction,
+        irq_priority: u32,
+        os_dep: O,
+    ) -> Option<Self> {
+        let mmio_base = match root.bar_info(bdf, 0)? {
+            Bar::Memory64 { address, .. } => address as usize,
+            Bar::Memory32 { address, .. } => address as usize,
+            Bar::Io { .. } => {
+                warn!("xhci {}: BAR0 is of I/O type", bdf);
+                return None;
+            }
+        };
+
+        let header = root.header(bdf)?;
+        header.enable_command([
+            ConfigCommand::MemorySpaceEnable,
+            ConfigCommand::BusMasterEnable,
+        ]);
+        let routing = route_irq(bdf, &header);
+        info!(
+            "xhci {}: registers at {:#x}, irq {:?}",
+            bdf, mmio_base, routing
+        );
+
+        Some(Self::new(
+            phys_to_virt(mmio_base.into()).as_usize(),
+            routing.irq_num().unwrap_or(0),
+            irq_priority,
+            os_dep,
+        ))
+    }
+
+    /// Scans the PCI bus described by the platform config, and creates the
+    /// config of the first xHCI controller found.
+    pub fn probe_pci(irq_priority: u32, os_dep: O) -> Option<Self> {
+        let base_vaddr = phys_to_virt(axconfig::PCI_ECAM_BASE.into());
+        let bar_range = axconfig::PCI_RANGES
+            .get(1)
+            .map_or(0..0, |range| range.0 as u64..range.1 as u64);
+        let root = driver_pci::new_root_complex(base_vaddr.as_usize(), bar_range);
+
+        let bdf = root.enumerate_bus().find_map(|(bdf, info, _)| {
+            debug!("PCI {}: {}", bdf, info);
+            is_xhci(&info).then_some(bdf)
+        })?;
+        Self::from_pci(&root, bdf, irq_priority, os_dep)
+    }
+}
+
+fn route_irq(bdf: DeviceFunction, header: &PciHeader) -> PciIrqRouting {
+    #[cfg(target_arch = "x86_64")]
+    if let Some(msi) = header.msi() {
+        msi.enable(MSI_ADDRESS, XHCI_MSI_VECTOR as u16);
+        header.enable_command([ConfigCommand::InterruptDisable]);
+        return PciIrqRouting::Msi {
+            vector: XHCI_MSI_VECTOR,
+        };
+    }
+
+    let (pin, line) = header.interrupt_pin_and_line();
+    if pin == 0 {
+        return PciIrqRouting::None;
+    }
+    let Some(irq) = intx_irq(bdf, pin, line) else {
+        warn!("xhci {}: INTx line {} is not routable", bdf, line);
+        return PciIrqRouting::None;
+    };
+    header.disable_command([ConfigCommand::InterruptDisable]);
+    PciIrqRouting::Intx { pin, irq }
+}
+
+/// The IO APIC pin was assigned by firmware, and is programmed for the
+/// function.
+#[cfg(target_arch = "x86_64")]
+fn intx_irq(_bdf: DeviceFunction, _pin: u8, line: u8) -> Option<u32> {
+    axhal::irq::route_pci_intx(line as usize).map(|irq| irq as u32)
+}
+
+/// INTA#..INTD# are wired to consecutive IRQs, swizzled by device number.
+#[cfg(not(target_arch = "x86_64"))]
+fn intx_irq(bdf: DeviceFunction, pin: u8, _line: u8) -> Option<u32> {
+    Some((axconfig::PCI_IRQ_BASE + (bdf.device + pin as usize - 1) % 4) as u32)
+}
@@ -0,0 +1,373 @@
+//! usbmon-style URB tracing.
+//!
+//! Every URB that goes through [`USBHostSystem::urb_request`] is recorded at
+//! submission and completion into a ring buffer, in the same shape as the
+//! Linux binary usbmon interface. The records can be exported as a pcap file
+//! of link type `LINKTYPE_USB_LINUX_MMAPPED`, which Wireshark understands.
+//!
+//! Tracing is off until [`start`] is called.
+//!
+//! [`USBHostSystem::urb_request`]: super::USBHostSystem::urb_request
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::time::Duration;
+
+use spinlock::SpinNoIrq;
+use xhci::ring::trb::transfer::Direction;
+
+use crate::{
+    err::{Error, Result},
+    glue::ucb::{CompleteCode, TransferEventCompleteCode, UCB},
+    usb::{trasnfer::control::ControlTransfer, urb::RequestedOperation},
+    PlatformAbstractions,
+};
+
+const LINKTYPE_USB_LINUX_MMAPPED: u32 = 220;
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const USBMON_HEADER_LEN: usize = 64;
+const BUS_NUMBER: u16 = 1;
+
+const EINPROGRESS: i32 = -115;
+const ENOSPC: i32 = -28;
+const EPIPE: i32 = -32;
+const EOVERFLOW: i32 = -75;
+const EPROTO: i32 = -71;
+const ETIMEDOUT: i32 = -110;
+
+/// Event type of a record, as the `type` field of usbmon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EventKind {
+    Submission = b'S',
+    Completion = b'C',
+    Error = b'E',
+}
+
+/// Transfer type of a record, as the `xfer_type` field of usbmon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum XferType {
+    Isochronous = 0,
+    Interrupt = 1,
+    Control = 2,
+    Bulk = 3,
+}
+
+/// One traced URB event.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// Identifies the URB, the same for its submission and completion.
+    pub id: u64,
+    pub kind: EventKind,
+    pub xfer_type: XferType,
+    /// Endpoint number, bit 7 set for IN endpoints.
+    pub epnum: u8,
+    /// Device slot id.
+    pub devnum: u8,
+    pub timestamp: Duration,
+    /// Setup packet, for control submissions only.
+    pub setup: Option<[u8; 8]>,
+    /// 0 or a negative errno, `-EINPROGRESS` for submissions.
+    pub status: i32,
+    /// Length of the transfer buffer.
+    pub length: u32,
+    /// Captured payload, truncated to the snap length.
+    pub data: Vec<u8>,
+}
+
+struct UsbMon {
+    records: VecDeque<Record>,
+    capacity: usize,
+    snap_len: usize,
+    next_id: u64,
+    dropped: usize,
+}
+
+static USBMON: SpinNoIrq<Option<UsbMon>> = SpinNoIrq::new(None);
+
+/// Starts tracing, keeping at most `capacity` records and `snap_len` bytes
+/// of payload per record. Records of a previous session are discarded.
+pub fn start(capacity: usize, snap_len: usize) {
+    *USBMON.lock() = Some(UsbMon {
+        records: VecDeque::with_capacity(capacity),
+        capacity,
+        snap_len,
+        next_id: 0,
+        dropped: 0,
+    });
+}
+
+/// Stops tracing, returning the records collected so far.
+pub fn stop() -> Vec<Record> {
+    USBMON
+        .lock()
+        .take()
+        .map(|mon| mon.records.into())
+        .unwrap_or_default()
+}
+
+pub fn is_enabled() -> bool {
+    USBMON.lock().is_some()
+}
+
+/// Returns a copy of the records currently in the ring buffer.
+pub fn records() -> Vec<Record> {
+    USBMON
+        .lock()
+        .as_ref()
+        .map(|mon| mon.records.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Number of records overwritten because the ring buffer was full.
+pub fn dropped() -> usize {
+    USBMON.lock().as_ref().map_or(0, |mon| mon.dropped)
+}
+
+impl UsbMon {
+    fn push(&mut self, record: Record) {
+        if self.records.len() >= self.capacity {
+            self.records.pop_front();
+            self.dropped += 1;
+        }
+        self.records.push_back(record);
+    }
+}
+
+/// What is remembered about a submitted URB until it completes.
+pub(crate) struct Pending {
+    id: u64,
+    xfer_type: XferType,
+    epnum: u8,
+    devnum: u8,
+    buffer: Option<(usize, usize)>,
+}
+
+impl Pending {
+    fn is_in(&self) -> bool {
+        self.epnum & 0x80 != 0
+    }
+}
+
+/// Records the submission of `operation`, returns `None` if tracing is off
+/// or the operation does not go on the wire.
+pub(crate) fn submit(devnum: usize, operation: &RequestedOperation) -> Option<Pending> {
+    let (xfer_type, epnum, buffer, setup) = match operation {
+        RequestedOperation::Control(control) => (
+            XferType::Control,
+            match control.request_type.direction {
+                Direction::In => 0x80,
+                Direction::Out => 0,
+            },
+            control.data,
+            Some(setup_packet(control)),
+        ),
+        RequestedOperation::Interrupt(interrupt) => (
+            XferType::Interrupt,
+            dci_to_epnum(interrupt.endpoint_id),
+            Some(interrupt.buffer_addr_len),
+            None,
+        ),
+        RequestedOperation::Bulk(bulk) => (
+            XferType::Bulk,
+            dci_to_epnum(bulk.endpoint_id),
+            Some(bulk.buffer_addr_len),
+            None,
+        ),
+        _ => return None,
+    };
+
+    let mut guard = USBMON.lock();
+    let mon = guard.as_mut()?;
+    let pending = Pending {
+        id: mon.next_id,
+        xfer_type,
+        epnum,
+        devnum: devnum as u8,
+        buffer,
+    };
+    mon.next_id += 1;
+
+    let data = match buffer {
+        Some(buffer) if !pending.is_in() => capture(buffer, mon.snap_len),
+        _ => Vec::new(),
+    };
+    mon.push(Record {
+        id: pending.id,
+        kind: EventKind::Submission,
+        xfer_type,
+        epnum,
+        devnum: pending.devnum,
+        timestamp: axhal::time::current_time(),
+        setup,
+        status: EINPROGRESS,
+        length: buffer.map_or(0, |(_, len)| len as u32),
+        data,
+    });
+    Some(pending)
+}
+
+/// Records the completion of a URB returned by [`submit`].
+pub(crate) fn complete<O>(pending: Option<Pending>, result: &Result<UCB<O>>)
+where
+    O: PlatformAbstractions,
+{
+    let Some(pending) = pending else {
+        return;
+    };
+    let mut guard = USBMON.lock();
+    let Some(mon) = guard.as_mut() else {
+        return;
+    };
+
+    let (kind, status) = match result {
+        Ok(ucb) => (EventKind::Completion, ucb_status(ucb)),
+        Err(Error::TimeOut) => (EventKind::Error, ETIMEDOUT),
+        Err(Error::RingFull) => (EventKind::Error, ENOSPC),
+        Err(_) => (EventKind::Error, EPROTO),
+    };
+    let data = match pending.buffer {
+        Some(buffer) if pending.is_in() && status == 0 => capture(buffer, mon.snap_len),
+        _ => Vec::new(),
+    };
+    mon.push(Record {
+        id: pending.id,
+        kind,
+        xfer_type: pending.xfer_type,
+        epnum: pending.epnum,
+        devnum: pending.devnum,
+        timestamp: axhal::time::current_time(),
+        setup: None,
+        status,
+        length: pending.buffer.map_or(0, |(_, len)| len as u32),
+        data,
+    });
+}
+
+fn ucb_status<O>(ucb: &UCB<O>) -> i32
+where
+    O: PlatformAbstractions,
+{
+    match ucb.code {
+        CompleteCode::Event(TransferEventCompleteCode::Success) => 0,
+        CompleteCode::Event(TransferEventCompleteCode::Halt) => EPIPE,
+        CompleteCode::Event(TransferEventCompleteCode::Babble) => EOVERFLOW,
+        CompleteCode::Event(TransferEventCompleteCode::Unknown(_)) => EPROTO,
+        CompleteCode::RingFull => ENOSPC,
+    }
+}
+
+/// Endpoint 0 is dci 1, then OUT/IN endpoints alternate.
+fn dci_to_epnum(dci: usize) -> u8 {
+    let number = (dci / 2) as u8;
+    if dci % 2 == 1 {
+        number | 0x80
+    } else {
+        number
+    }
+}
+
+fn setup_packet(control: &ControlTransfer) -> [u8; 8] {
+    let length = control.data.map_or(0, |(_, len)| len as u16);
+    let mut setup = [0u8; 8];
+    setup[0] = control.request_type.clone().into();
+    setup[1] = control.request.clone() as u8;
+    setup[2..4].copy_from_slice(&control.value.to_le_bytes());
+    setup[4..6].copy_from_slice(&control.index.to_le_bytes());
+    setup[6..8].copy_from_slice(&length.to_le_bytes());
+    setup
+}
+
+fn capture((addr, len): (usize, usize), snap_len: usize) -> Vec<u8> {
+    let len = len.min(snap_len);
+    unsafe { core::slice::from_raw_parts(addr as *const u8, len) }.to_vec()
+}
+
+impl Record {
+    /// Serializes the record as a `usbmon_packet` header followed by the
+    /// captured payload.
+    fn write_usbmon(&self, out: &mut Vec<u8>) {
+        let (flag_setup, setup) = match self.setup {
+            Some(setup) => (0, setup),
+            None => (b'-', [0u8; 8]),
+        };
+        let flag_data = match (self.data.is_empty(), self.epnum & 0x80 != 0) {
+            (false, _) => 0,
+            (true, true) => b'<',
+            (true, false) => b'>',
+        };
+
+        out.extend_from_slice(&self.id.to_le_bytes());
+        out.push(self.kind as u8);
+        out.push(self.xfer_type as u8);
+        out.push(self.epnum);
+        out.push(self.devnum);
+        out.extend_from_slice(&BUS_NUMBER.to_le_bytes());
+        out.push(flag_setup);
+        out.push(flag_data);
+        out.extend_from_slice(&(self.timestamp.as_secs() as i64).to_le_bytes());
+        out.extend_from_slice(&(self.timestamp.subsec_micros() as i32).to_le_bytes());
+        out.extend_from_slice(&self.status.to_le_bytes());
+        out.extend_from_slice(&self.length.to_le_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&setup);
+        // interval, start_frame, xfer_flags, ndesc
+        out.extend_from_slice(&[0u8; 16]);
+        out.extend_from_slice(&self.data);
+    }
+}
+
+/// Writes the traced records as a pcap file to `sink`, in chunks.
+pub fn export_pcap(mut sink: impl FnMut(&[u8])) {
+    let records = records();
+
+    let mut header = Vec::with_capacity(24);
+    header.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&4u16.to_le_bytes());
+    header.extend_from_slice(&0i32.to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&(u16::MAX as u32).to_le_bytes());
+    header.extend_from_slice(&LINKTYPE_USB_LINUX_MMAPPED.to_le_bytes());
+    sink(&header);
+
+    let mut packet = Vec::new();
+    for record in records.iter() {
+        packet.clear();
+        let captured = (USBMON_HEADER_LEN + record.data.len()) as u32;
+        packet.extend_from_slice(&(record.timestamp.as_secs() as u32).to_le_bytes());
+        packet.extend_from_slice(&record.timestamp.subsec_micros().to_le_bytes());
+        packet.extend_from_slice(&captured.to_le_bytes());
+        packet.extend_from_slice(&captured.to_le_bytes());
+        record.write_usbmon(&mut packet);
+        sink(&packet);
+    }
+}
+
+/// Returns the traced records as a pcap file, e.g. to be written to a file.
+pub fn pcap() -> Vec<u8> {
+    let mut out = Vec::new();
+    export_pcap(|chunk| out.extend_from_slice(chunk));
+    out
+}
+
+/// Prints the pcap file to the console as hex lines between two markers,
+/// it can be turned back into a file with `xxd -r -p`.
+pub fn dump_pcap_to_console() {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let console = axhal::console::write_bytes;
+
+    console(b"\n-----BEGIN USBMON PCAP-----\n");
+    let mut column = 0;
+    export_pcap(|chunk| {
+        for byte in chunk {
+            console(&[HEX[(byte >> 4) as usize], HEX[(byte & 0xf) as usize]]);
+            column += 1;
+            if column == 32 {
+                console(b"\n");
+                column = 0;
+            }
+        }
+    });
+    console(b"\n-----END USBMON PCAP-----\n");
+}
@@ -145,9 +145,9 @@ where
                     self.config.lock().os.dma_alloc(),
                 );
 
-                let desc = match (&driver.controller).lock().control_transfer(
+                let desc = match self.host_driver_layer.urb_request(URB::new(
                     driver.slotid,
-                    ControlTransfer {
+                    RequestedOperation::Control(ControlTransfer {
                         request_type: bmRequestType::new(
                             Direction::In,
                             DataTransferType::Standard,
@@ -162,8 +162,8 @@ where
                         .bits(),
                         data: Some(buffer_device.addr_len_tuple()),
                         response:false
-                    },
-                ) {
+                    }),
+                )) {
                     Ok(_) => {
                         let mut parser = RawDescriptorParser::<O>::new(buffer_device);
                         parser.single_state_cycle();
@@ -175,11 +175,10 @@ where
                                 O::PAGE_SIZE,
                                 self.config.lock().os.dma_alloc(),
                             );
-                            (&driver.controller)
-                                .lock()
-                                .control_transfer(
+                            self.host_driver_layer
+                                .urb_request(URB::new(
                                     driver.slotid,
-                                    ControlTransfer {
+                                    RequestedOperation::Control(ControlTransfer {
                                         request_type: bmRequestType::new(
                                             Direction::In,
                                             DataTransferType::Standard,
@@ -194,8 +193,8 @@ where
                                         .bits(),
                                         data: Some(buffer.addr_len_tuple()),
                                         response:false
-                                    },
-                                )
+                                    }),
+                                ))
                                 .inspect(|_| {
                                     parser.append_config(buffer);
                                 });
@@ -1,3 +1,5 @@
+use core::ptr;
+
 use bit_field::BitField;
 use num_traits::FromPrimitive;
 use xhci::context::EndpointHandler;
@@ -19,16 +21,36 @@ pub(crate) struct Endpoint {
     pub(crate) ssc: Option<SuperSpeedCmp>,
 }
 
+/// SuperSpeed endpoint companion descriptor, follows the endpoint
+/// descriptor of SuperSpeed devices.
 #[derive(Clone, Copy, Debug)]
 #[repr(C, packed)]
 pub struct SuperSpeedCmp {
+    len: u8,
     pub kind: u8,
     pub max_burst: u8,
     pub attributes: u8,
     pub bytes_per_interval: u16,
 }
 
+/// Length of the endpoint descriptor on the wire, without the companion.
+const ENDPOINT_DESC_LEN: usize = 7;
+
 impl Endpoint {
+    pub(crate) fn from_slice(raw: &[u8]) -> Self {
+        let mut endpoint = Self::default();
+        // SAFETY: `Endpoint` starts with the fields of the descriptor, the
+        // companion is attached afterwards by the parser.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                raw.as_ptr(),
+                &mut endpoint as *mut Self as *mut u8,
+                raw.len().min(ENDPOINT_DESC_LEN),
+            )
+        };
+        endpoint
+    }
+
     pub fn endpoint_type(&self) -> EndpointType {
         EndpointType::from_u8(if self.attributes == 0 {
             4
@@ -56,48 +78,74 @@ impl Endpoint {
         }
     }
 
+    /// MaxStreams of the companion descriptor, the endpoint supports
+    /// `2^n` streams. `None` for endpoints without streams.
     pub(crate) fn max_streams(&self) -> Option<u8> {
-        //TODO: complete me
-        if self.is_bulk_out() {
-            Some(self.calculate_max_streams())
-        } else {
-            None
+        if !self.is_bulk() {
+            return None;
         }
+        self.ssc
+            .map(|ssc| ssc.attributes.get_bits(0..=4))
+            .filter(|streams| *streams > 0)
     }
 
-    pub(crate) fn is_bulk_out(&self) -> bool {
-        self.endpoint_type() == EndpointType::BulkOut
+    pub(crate) fn is_bulk(&self) -> bool {
+        matches!(
+            self.endpoint_type(),
+            EndpointType::BulkOut | EndpointType::BulkIn
+        )
     }
 
-    pub(crate) fn calculate_max_streams(&self) -> u8 {
-        self.ssc
-            .as_ref()
-            .map(|ssc| {
-                if self.is_bulk_out() {
-                    let raw = ssc.attributes & 0x1F;
-                    raw
-                } else {
-                    0
-                }
-            })
-            .unwrap()
+    pub(crate) fn is_periodic(&self) -> bool {
+        matches!(
+            self.endpoint_type(),
+            EndpointType::IsochOut
+                | EndpointType::IsochIn
+                | EndpointType::InterruptOut
+                | EndpointType::InterruptIn
+        )
     }
 
     pub(crate) fn is_superspeedplus(&self) -> bool {
         false
     }
 
+    /// Packets per burst minus one, from the companion descriptor on
+    /// SuperSpeed, from the additional transactions bits of wMaxPacketSize
+    /// for high-speed periodic endpoints.
+    pub(crate) fn max_burst(&self) -> u8 {
+        match self.ssc {
+            Some(ssc) => ssc.max_burst.min(15),
+            None if self.is_periodic() => self.max_packet_size.get_bits(11..=12) as u8,
+            None => 0,
+        }
+    }
+
+    /// wMaxPacketSize without the additional transactions bits.
+    pub(crate) fn packet_size(&self) -> u16 {
+        self.max_packet_size.get_bits(0..=10)
+    }
+
+    /// Bursts per service interval minus one, for SuperSpeed isochronous
+    /// endpoints. Must be 0 if the controller supports Large ESIT Payloads.
     pub(crate) fn mult(&self, lec: bool) -> u8 {
-        if !lec && self.endpoint_type() == EndpointType::IsochOut {
-            if self.is_superspeedplus() {
-                return 0;
-            }
-            self.ssc
-                .as_ref()
-                .map(|ssc| ssc.attributes & 0x3)
-                .unwrap_or(0)
-        } else {
-            0
+        if lec || self.is_superspeedplus() {
+            return 0;
+        }
+        match self.endpoint_type() {
+            EndpointType::IsochOut | EndpointType::IsochIn => self
+                .ssc
+                .map(|ssc| ssc.attributes.get_bits(0..=1))
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Bytes moved per service interval of a periodic endpoint.
+    pub(crate) fn max_esit_payload(&self) -> u32 {
+        match self.ssc {
+            Some(ssc) => ssc.bytes_per_interval as u32,
+            None => self.packet_size() as u32 * (self.max_burst() as u32 + 1),
         }
     }
 
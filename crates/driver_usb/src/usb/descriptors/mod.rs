@@ -5,7 +5,7 @@ use alloc::{collections, vec, vec::Vec};
 use const_enum::ConstEnum;
 use desc_configuration::Configuration;
 use desc_device::Device;
-use desc_endpoint::Endpoint;
+use desc_endpoint::{Endpoint, SuperSpeedCmp};
 use desc_hid::{HIDDescriptorTypes, Hid};
 use desc_interface::{Interface, InterfaceAssociation};
 use desc_str::Str;
@@ -86,6 +86,7 @@ pub(crate) enum USBDescriptor {
     Interface(Interface),
     InterfaceAssociation(InterfaceAssociation),
     Endpoint(Endpoint),
+    SuperSpeedEndpointCompanion(SuperSpeedCmp),
     Hid(Hid),
     UVCInterface(UVCInterface),
     UVCClassSpecVideoControlInterruptEndpoint(UVCVideoControlInterruptEndpoint),
@@ -161,8 +162,11 @@ impl USBDescriptor {
                         Ok(Self::Interface(unsafe { ptr::read(raw.cast()) }))
                     }
                     USBStandardDescriptorTypes::Endpoint => {
-                        Ok(Self::Endpoint(unsafe { ptr::read(raw.cast()) }))
+                        Ok(Self::Endpoint(Endpoint::from_slice(unsafe { &*raw })))
                     }
+                    USBStandardDescriptorTypes::SuperSpeedEndpointCompanion => Ok(
+                        Self::SuperSpeedEndpointCompanion(unsafe { ptr::read(raw.cast()) }),
+                    ),
                     USBStandardDescriptorTypes::InterfaceAssociation => {
                         Ok(Self::InterfaceAssociation(unsafe { ptr::read(raw.cast()) }))
                     }
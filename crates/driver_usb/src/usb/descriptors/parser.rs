@@ -17,6 +17,7 @@ use crate::{
 
 use super::{
     desc_device::StandardUSBDeviceClassCode,
+    desc_endpoint::SuperSpeedCmp,
     desc_interface::{Interface, InterfaceAssociation},
     desc_uvc::{
         uvc_interfaces::{
@@ -495,6 +496,27 @@ where
         vec
     }
 
+    /// Parses the companion descriptors following an endpoint descriptor of
+    /// a SuperSpeed device, if any.
+    fn parse_superspeed_companion(&mut self) -> Option<SuperSpeedCmp> {
+        if self.peek_std_desc_type() != Some(USBStandardDescriptorTypes::SuperSpeedEndpointCompanion)
+        {
+            return None;
+        }
+        let companion = match self.parse_any_descriptor() {
+            Ok(USBDescriptor::SuperSpeedEndpointCompanion(companion)) => Some(companion),
+            _ => None,
+        };
+        // the SuperSpeedPlus isochronous companion is not used, skip it
+        if self.peek_std_desc_type()
+            == Some(USBStandardDescriptorTypes::SuperSpeedPlusIsochEndpointCompanion)
+        {
+            let _ = self.cut_raw_descriptor();
+        }
+        trace!("parsed superspeed companion:{:?}", companion);
+        companion
+    }
+
     fn parse_endpoints(&mut self) -> Vec<TopologicalUSBDescriptorEndpoint> {
         trace!("parse enedpoints, metadata:{:?}", self.metadata);
         let mut endpoints = Vec::new();
@@ -502,7 +524,8 @@ where
         loop {
             if let Some(USBStandardDescriptorTypes::Endpoint) = self.peek_std_desc_type() {
                 match self.parse_any_descriptor().unwrap() {
-                    USBDescriptor::Endpoint(endpoint) => {
+                    USBDescriptor::Endpoint(mut endpoint) => {
+                        endpoint.ssc = self.parse_superspeed_companion();
                         trace!("parsed endpoint:{:?}", endpoint);
                         endpoints.push(TopologicalUSBDescriptorEndpoint::Standard(endpoint))
                     }
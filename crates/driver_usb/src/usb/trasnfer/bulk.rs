@@ -0,0 +1,7 @@
+#[derive(Debug, Clone)]
+pub struct BulkTransfer {
+    pub endpoint_id: usize,
+    pub buffer_addr_len: (usize, usize),
+    /// Stream to queue the transfer on, 0 for endpoints without streams.
+    pub stream_id: u16,
+}
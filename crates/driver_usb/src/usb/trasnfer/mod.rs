@@ -1,3 +1,4 @@
+pub mod bulk;
 pub mod interrupt;
 pub mod endpoints;
 pub mod control;
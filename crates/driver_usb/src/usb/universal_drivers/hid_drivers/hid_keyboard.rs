@@ -174,6 +174,10 @@ where
             CompleteCode::Event(TransferEventCompleteCode::Babble) => {
                 self.driver_state_machine = HidKeyboardStateMachine::Sending
             }
+            CompleteCode::RingFull => {
+                // not queued, submit the report request again on next tick
+                self.driver_state_machine = HidKeyboardStateMachine::Sending
+            }
             other => panic!("received {:?}", other),
         }
     }
@@ -1,6 +1,6 @@
 const NET_DEV_FEATURES: &[&str] = &["ixgbe", "virtio-net", "phytium"];
 const BLOCK_DEV_FEATURES: &[&str] = &["ramdisk", "bcm2835-sdhci", "virtio-blk"];
-const DISPLAY_DEV_FEATURES: &[&str] = &["virtio-gpu"];
+const DISPLAY_DEV_FEATURES: &[&str] = &["ssd1306", "virtio-gpu"];
 const USB_HOST_DEV_FEATURES: &[&str] = &["phytium-xhci", "vl805"];
 
 fn has_feature(feature: &str) -> bool {
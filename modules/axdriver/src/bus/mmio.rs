@@ -3,23 +3,37 @@ use crate::{prelude::*, AllDevices};
 
 impl AllDevices {
     pub(crate) fn probe_bus_devices(&mut self) {
-        // TODO: parse device tree
         //don't use cfg_if at here ramdomly!!!! might cause lld error
         //waste time: 2days
         #[cfg(feature = "virtio")]
-        for reg in axconfig::VIRTIO_MMIO_REGIONS {
-            for_each_drivers!(type Driver, {
-                if let Some(dev) = Driver::probe_mmio(reg.0, reg.1) {
-                    info!(
-                        "registered a new {:?} device at [PA:{:#x}, PA:{:#x}): {:?}",
-                        dev.device_type(),
-                        reg.0, reg.0 + reg.1,
-                        dev.device_name(),
-                    );
-                    self.add_device(dev);
-                    continue; // skip to the next device
+        if let Some(fdt) = axhal::dtb::get() {
+            // The transports of the device tree take precedence over the
+            // configured ones, so that the image follows the machine.
+            for node in fdt.find_compatible(&["virtio,mmio"]) {
+                for reg in node.reg() {
+                    self.probe_mmio_device(reg.addr, reg.size);
                 }
-            });
+            }
+        } else {
+            for reg in axconfig::VIRTIO_MMIO_REGIONS {
+                self.probe_mmio_device(reg.0, reg.1);
+            }
         }
     }
+
+    #[cfg(feature = "virtio")]
+    fn probe_mmio_device(&mut self, base: usize, size: usize) {
+        for_each_drivers!(type Driver, {
+            if let Some(dev) = Driver::probe_mmio(base, size) {
+                info!(
+                    "registered a new {:?} device at [PA:{:#x}, PA:{:#x}): {:?}",
+                    dev.device_type(),
+                    base, base + size,
+                    dev.device_name(),
+                );
+                self.add_device(dev);
+                return; // skip to the next device
+            }
+        });
+    }
 }
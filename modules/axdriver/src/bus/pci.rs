@@ -5,11 +5,11 @@ use driver_pci::*;
 impl AllDevices {
     pub(crate) fn probe_bus_devices(&mut self) {
         let base_vaddr = phys_to_virt(axconfig::PCI_ECAM_BASE.into());
-        let pci_range = axconfig::PCI_RANGES.get(1).unwrap();
-        let mut root = driver_pci::new_root_complex(
-            base_vaddr.as_usize(),
-            pci_range.0 as u64..pci_range.1 as u64,
-        );
+        // no BAR range on x86, keep the assignment done by firmware
+        let bar_range = axconfig::PCI_RANGES
+            .get(1)
+            .map_or(0..0, |range| range.0 as u64..range.1 as u64);
+        let mut root = driver_pci::new_root_complex(base_vaddr.as_usize(), bar_range);
 
         debug!("probing in pci.rs!");
 
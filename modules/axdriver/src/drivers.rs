@@ -151,6 +151,31 @@ cfg_if::cfg_if! {
     }
 }
 
+cfg_if::cfg_if! {
+    if #[cfg(display_dev = "ssd1306")] {
+        use driver_display::ssd1306::{Ssd1306, SSD1306_ADDRESS};
+        use driver_i2c::{I2cBus, I2cConfig};
+
+        /// MIO controller the OLED panel of the Phytium car is wired to.
+        const SSD1306_MIO: usize = 1;
+
+        pub struct Ssd1306Driver;
+        register_display_driver!(Ssd1306Driver, Ssd1306<I2cBus>);
+
+        impl DriverProbe for Ssd1306Driver {
+            fn probe_global() -> Option<AxDeviceEnum> {
+                let bus = I2cBus::new(SSD1306_MIO, I2cConfig::default())
+                    .map_err(|e| warn!("ssd1306: cannot open MIO{}: {:?}", SSD1306_MIO, e))
+                    .ok()?;
+                Ssd1306::new(bus, SSD1306_ADDRESS)
+                    .map_err(|e| warn!("ssd1306: no panel at {:#04x}: {:?}", SSD1306_ADDRESS, e))
+                    .ok()
+                    .map(AxDeviceEnum::from_display)
+            }
+        }
+    }
+}
+
 // //todo maybe we should re arrange these code
 // //------------------------------------------
 // use axalloc::GlobalNoCacheAllocator;
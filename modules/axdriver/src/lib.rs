@@ -34,6 +34,7 @@
 //! | Block | `virtio-blk` | VirtIO block device |
 //! | Network | `virtio-net` | VirtIO network device |
 //! | Display | `virtio-gpu` | VirtIO graphics device |
+//! | Display | `ssd1306` | SSD1306 I2C OLED panel on the Phytium MIO1 bus |
 //!
 //! # Other Cargo Features
 //!
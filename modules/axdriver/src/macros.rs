@@ -72,5 +72,10 @@ macro_rules! for_each_drivers {
             type $drv_type = crate::drivers::IxgbeDriver;
             $code
         }
+        #[cfg(display_dev = "ssd1306")]
+        {
+            type $drv_type = crate::drivers::Ssd1306Driver;
+            $code
+        }
     }};
 }
@@ -5,6 +5,7 @@ use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
 use capability::{Cap, WithCap};
 use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
@@ -20,6 +21,40 @@ pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
 
+/// Incremented each time a device node becomes readable.
+static READY_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// The tasks blocked in [`wait_readable`].
+#[cfg(feature = "multitask")]
+static READY_WAIT: axtask::WaitQueue = axtask::WaitQueue::new();
+
+/// Returns the sequence number to be passed to [`wait_readable`]. It must be
+/// taken before the read that failed with `WouldBlock`.
+pub fn ready_seq() -> usize {
+    READY_SEQ.load(Ordering::Acquire)
+}
+
+/// Blocks until a device node becomes readable after [`ready_seq`] returned
+/// `seq`. The caller should retry its read then.
+pub fn wait_readable(seq: usize) {
+    let is_ready = || READY_SEQ.load(Ordering::Acquire) != seq;
+    #[cfg(feature = "multitask")]
+    READY_WAIT.wait_until(is_ready);
+    #[cfg(not(feature = "multitask"))]
+    while !is_ready() {
+        core::hint::spin_loop();
+    }
+}
+
+/// Wakes up the tasks blocked in [`wait_readable`]. It is called by the device
+/// nodes whenever data is queued, possibly in IRQ context.
+#[cfg(feature = "input")]
+pub(crate) fn notify_readable() {
+    READY_SEQ.fetch_add(1, Ordering::AcqRel);
+    #[cfg(feature = "multitask")]
+    READY_WAIT.notify_all(false);
+}
+
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
@@ -240,6 +275,15 @@ impl File {
     pub fn get_attr(&self) -> AxResult<FileAttr> {
         self.node.access(Cap::empty())?.get_attr()
     }
+
+    /// Checks whether the file can be read or written without blocking.
+    pub fn poll(&self) -> AxResult<axio::PollState> {
+        let state = self.node.access(Cap::empty())?.poll()?;
+        Ok(axio::PollState {
+            readable: state.readable,
+            writable: state.writable,
+        })
+    }
 }
 
 impl Directory {
@@ -4,19 +4,65 @@ use core::cell::UnsafeCell;
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Dir, File, LossyOemCpConverter, Read, Seek, SeekFrom, TimeProvider, Write};
 
 use crate::dev::Disk;
 
 const BLOCK_SIZE: usize = 512;
 
+/// Provides the wall-clock time for the timestamps of FAT entries.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AxTimeProvider;
+
+impl TimeProvider for AxTimeProvider {
+    fn get_current_date(&self) -> fatfs::Date {
+        self.get_current_date_time().date
+    }
+
+    fn get_current_date_time(&self) -> fatfs::DateTime {
+        use axhal::time::DateTime;
+        // FAT timestamps range from 1980-01-01 to 2107-12-31.
+        const MIN: DateTime = DateTime {
+            year: 1980,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+        const MAX: DateTime = DateTime {
+            year: 2107,
+            month: 12,
+            day: 31,
+            hour: 23,
+            minute: 59,
+            second: 59,
+        };
+        let now = axhal::time::wall_time();
+        let secs = now.as_secs().clamp(
+            MIN.to_unix_timestamp().unwrap(),
+            MAX.to_unix_timestamp().unwrap(),
+        );
+        let dt = DateTime::from_unix_timestamp(secs);
+        fatfs::DateTime::new(
+            fatfs::Date::new(dt.year as u16, dt.month as u16, dt.day as u16),
+            fatfs::Time::new(
+                dt.hour as u16,
+                dt.minute as u16,
+                dt.second as u16,
+                now.subsec_millis() as u16,
+            ),
+        )
+    }
+}
+
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
+    inner: fatfs::FileSystem<Disk, AxTimeProvider, LossyOemCpConverter>,
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
 }
 
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+pub struct FileWrapper<'a>(Mutex<File<'a, Disk, AxTimeProvider, LossyOemCpConverter>>);
+pub struct DirWrapper<'a>(Dir<'a, Disk, AxTimeProvider, LossyOemCpConverter>);
 
 unsafe impl Sync for FatFileSystem {}
 unsafe impl Send for FatFileSystem {}
@@ -30,8 +76,9 @@ impl FatFileSystem {
     pub fn new(mut disk: Disk) -> Self {
         let opts = fatfs::FormatVolumeOptions::new();
         fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
+        let inner =
+            fatfs::FileSystem::new(disk, fatfs::FsOptions::new().time_provider(AxTimeProvider))
+                .expect("failed to initialize FAT filesystem");
         Self {
             inner,
             root_dir: UnsafeCell::new(None),
@@ -40,8 +87,9 @@ impl FatFileSystem {
 
     #[cfg(not(feature = "use-ramdisk"))]
     pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
+        let inner =
+            fatfs::FileSystem::new(disk, fatfs::FsOptions::new().time_provider(AxTimeProvider))
+                .expect("failed to initialize FAT filesystem");
         Self {
             inner,
             root_dir: UnsafeCell::new(None),
@@ -53,11 +101,11 @@ impl FatFileSystem {
         unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
     }
 
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
+    fn new_file(file: File<'_, Disk, AxTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
         Arc::new(FileWrapper(Mutex::new(file)))
     }
 
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
+    fn new_dir(dir: Dir<'_, Disk, AxTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
         Arc::new(DirWrapper(dir))
     }
 }
@@ -8,6 +8,10 @@
 //!    is **enabled** by default.
 //! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
 //!    **enabled** by default.
+//! - `input`: Add `/dev/input/event0` and `/dev/input/mice`, fed by mouse
+//!    events posted on [`ax_event_bus`]. This feature is **disabled** by default.
+//! - `multitask`: Block the tasks waiting for device nodes to become readable,
+//!    instead of spinning. This feature is **disabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
@@ -13,9 +13,45 @@ pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
     devfs.add("null", Arc::new(null));
     devfs.add("zero", Arc::new(zero));
     foo_dir.add("bar", Arc::new(bar));
+    #[cfg(feature = "input")]
+    input_devices(&devfs);
     Arc::new(devfs)
 }
 
+/// Creates `/dev/input/event0` and `/dev/input/mice`, and feeds them with
+/// every [`MouseEvent`](ax_event_bus::events::mouse::MouseEvent) posted on the
+/// event bus.
+#[cfg(feature = "input")]
+fn input_devices(devfs: &fs::devfs::DeviceFileSystem) {
+    use ax_event_bus::events::{mouse::MouseEvent, Propagation};
+    use fs::devfs::{EvdevDev, MiceDev, MouseReport};
+
+    let event0 = Arc::new(EvdevDev::with_notify(crate::fops::notify_readable));
+    let mice = Arc::new(MiceDev::with_notify(crate::fops::notify_readable));
+    let input_dir = devfs.mkdir("input");
+    input_dir.add("event0", event0.clone());
+    input_dir.add("mice", mice.clone());
+
+    // Run before application handlers, which may consume the event.
+    ax_event_bus::subscribe_with_priority::<MouseEvent, _>(
+        i32::MAX,
+        move |event: &mut MouseEvent| {
+            let report = MouseReport {
+                dx: event.dx as i32,
+                dy: event.dy as i32,
+                wheel: event.wheel as i32,
+                left: event.left,
+                right: event.right,
+                middle: event.middle,
+            };
+            event0.report_mouse(axhal::time::current_time(), &report);
+            mice.report_mouse(&report);
+            Propagation::Continue
+        },
+    )
+    .detach();
+}
+
 #[cfg(feature = "ramfs")]
 pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
     Arc::new(fs::ramfs::RamFileSystem::new())
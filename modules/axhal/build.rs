@@ -6,6 +6,8 @@ fn main() {
     if platform != "dummy" {
         gen_linker_script(&arch, platform).unwrap();
     }
+    gen_ksyms().unwrap();
+    println!("cargo:rerun-if-changed=linker.lds.S");
 
     println!("cargo:rustc-cfg=platform=\"{}\"", platform);
     println!("cargo:rustc-cfg=platform_family=\"{}\"", axconfig::FAMILY);
@@ -31,3 +33,18 @@ fn gen_linker_script(arch: &str, platform: &str) -> Result<()> {
     std::fs::write(fname, ld_content)?;
     Ok(())
 }
+
+/// Copies the symbol table given by `AX_KSYMS` to `OUT_DIR`, to be embedded by
+/// `backtrace.rs`. An empty table is used if it does not exist.
+fn gen_ksyms() -> Result<()> {
+    println!("cargo:rerun-if-env-changed=AX_KSYMS");
+    let out_path = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("ksyms.txt");
+    let ksyms = match std::env::var("AX_KSYMS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            std::fs::read(&path).unwrap_or_default()
+        }
+        _ => Vec::new(),
+    };
+    std::fs::write(out_path, ksyms)
+}
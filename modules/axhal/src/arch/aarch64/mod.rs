@@ -35,6 +35,19 @@ pub fn wait_for_irqs() {
     aarch64_cpu::asm::wfi();
 }
 
+/// Enables interrupts and waits for them, without missing an interrupt that
+/// arrives in between.
+///
+/// It must be called with interrupts disabled, so that the caller can check
+/// for pending work before waiting. Interrupts are enabled when it returns.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    // A pending interrupt wakes up `wfi` even if it is masked, and is taken
+    // after being unmasked.
+    aarch64_cpu::asm::wfi();
+    enable_irqs();
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -117,6 +130,64 @@ pub fn flush_dcache_line(vaddr: VirtAddr) {
     unsafe { asm!("dc ivac, {0:x}; dsb sy; isb", in(reg) vaddr.as_usize()) };
 }
 
+/// Returns the size of the smallest data cache line, from `CTR_EL0.DminLine`.
+#[inline]
+pub fn dcache_line_size() -> usize {
+    let ctr: u64;
+    unsafe { asm!("mrs {}, ctr_el0", out(reg) ctr) };
+    4 << ((ctr >> 16) & 0xf)
+}
+
+macro_rules! dcache_op_range {
+    ($op:literal, $start:expr, $end:expr, $line:expr) => {{
+        let mut addr = $start;
+        while addr < $end {
+            unsafe { asm!(concat!("dc ", $op, ", {0:x}"), in(reg) addr) };
+            addr += $line;
+        }
+    }};
+}
+
+/// Writes back the data cache lines covering `[vaddr, vaddr + size)` to the
+/// point of coherency, so that a device sees what the CPU wrote.
+pub fn clean_dcache_range(vaddr: VirtAddr, size: usize) {
+    let line = dcache_line_size();
+    let start = vaddr.as_usize() & !(line - 1);
+    dcache_op_range!("cvac", start, vaddr.as_usize() + size, line);
+    unsafe { asm!("dsb sy") };
+}
+
+/// Discards the data cache lines covering `[vaddr, vaddr + size)`, so that
+/// the CPU sees what a device wrote.
+///
+/// Lines only partly covered by the range are cleaned as well, not to lose
+/// dirty data next to the range.
+pub fn invalidate_dcache_range(vaddr: VirtAddr, size: usize) {
+    let line = dcache_line_size();
+    let mut start = vaddr.as_usize();
+    let mut end = start + size;
+    if start & (line - 1) != 0 {
+        start &= !(line - 1);
+        dcache_op_range!("civac", start, start + line, line);
+        start += line;
+    }
+    if end & (line - 1) != 0 && end > start {
+        end &= !(line - 1);
+        dcache_op_range!("civac", end, end + line, line);
+    }
+    dcache_op_range!("ivac", start, end, line);
+    unsafe { asm!("dsb sy") };
+}
+
+/// Writes back then discards the data cache lines covering
+/// `[vaddr, vaddr + size)`.
+pub fn flush_dcache_range(vaddr: VirtAddr, size: usize) {
+    let line = dcache_line_size();
+    let start = vaddr.as_usize() & !(line - 1);
+    dcache_op_range!("civac", start, vaddr.as_usize() + size, line);
+    unsafe { asm!("dsb sy") };
+}
+
 /// Reads the thread pointer of the current CPU.
 ///
 /// It is used to implement TLS (Thread Local Storage).
@@ -1,12 +1,24 @@
 use core::arch::global_asm;
 
 use aarch64_cpu::registers::{ESR_EL1, FAR_EL1};
+use memory_addr::VirtAddr;
 use tock_registers::interfaces::Readable;
 
 use super::TrapFrame;
+use crate::trap::{
+    handle_alignment_fault_extern, handle_breakpoint_extern, handle_page_fault_extern,
+    handle_undefined_instruction_extern, PageFaultFlags,
+};
 
 global_asm!(include_str!("trap.S"));
 
+/// Write not Read bit of the data abort ISS.
+const ISS_DA_WNR: u64 = 1 << 6;
+/// Cache maintenance bit of the data abort ISS.
+const ISS_DA_CM: u64 = 1 << 8;
+/// Fault status code (DFSC) of an alignment fault.
+const FSC_ALIGNMENT_FAULT: u64 = 0b10_0001;
+
 #[repr(u8)]
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -35,38 +47,76 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
     );
 }
 
+/// Returns whether the fault status code (DFSC/IFSC) of an abort indicates
+/// a translation, access flag or permission fault.
+fn is_page_fault(fsc: u64) -> bool {
+    (0b00_0100..=0b00_1111).contains(&fsc)
+}
+
+fn handle_abort(tf: &mut TrapFrame, iss: u64, is_instr: bool, is_user: bool) {
+    let vaddr = VirtAddr::from(FAR_EL1.get() as usize);
+    let fsc = iss & 0x3f;
+    let access_flags = if is_instr {
+        PageFaultFlags::EXECUTE
+    } else if iss & ISS_DA_WNR != 0 && iss & ISS_DA_CM == 0 {
+        PageFaultFlags::WRITE
+    } else {
+        PageFaultFlags::READ
+    };
+
+    if !is_instr && fsc == FSC_ALIGNMENT_FAULT {
+        if !handle_alignment_fault_extern(tf, vaddr) {
+            panic!(
+                "Unhandled alignment fault @ {:#x}, FAR={:#x}, ISS={:#x}:\n{:#x?}",
+                tf.elr, vaddr, iss, tf,
+            );
+        }
+        return;
+    }
+    if is_page_fault(fsc) && handle_page_fault_extern(vaddr, access_flags, is_user) {
+        return;
+    }
+    if is_user {
+        warn!(
+            "EL0 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}, access_flags={:?}",
+            tf.elr, vaddr, iss, access_flags,
+        );
+    } else {
+        panic!(
+            "EL1 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}, access_flags={:?}:\n{:#x?}",
+            tf.elr, vaddr, iss, access_flags, tf,
+        );
+    }
+}
+
 #[no_mangle]
 fn handle_sync_exception(tf: &mut TrapFrame) {
     let esr = ESR_EL1.extract();
+    let iss = esr.read(ESR_EL1::ISS);
     match esr.read_as_enum(ESR_EL1::EC) {
         Some(ESR_EL1::EC::Value::Brk64) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            debug!("BRK #{:#x} @ {:#x} ", iss, tf.elr);
-            tf.elr += 4;
+            if !handle_breakpoint_extern(tf) {
+                debug!("BRK #{:#x} @ {:#x} ", iss, tf.elr);
+                tf.elr += 4;
+            }
         }
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No supervisor call is supported currently!");
         }
-        Some(ESR_EL1::EC::Value::DataAbortLowerEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            warn!(
-                "EL0 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss
-            );
+        Some(ESR_EL1::EC::Value::DataAbortLowerEL) => handle_abort(tf, iss, false, true),
+        Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => handle_abort(tf, iss, true, true),
+        Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => handle_abort(tf, iss, false, false),
+        Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => handle_abort(tf, iss, true, false),
+        Some(ESR_EL1::EC::Value::PCAlignmentFault) => {
+            let vaddr = VirtAddr::from(FAR_EL1.get() as usize);
+            if !handle_alignment_fault_extern(tf, vaddr) {
+                panic!("Unhandled PC alignment fault @ {:#x}:\n{:#x?}", tf.elr, tf);
+            }
         }
-        Some(ESR_EL1::EC::Value::DataAbortCurrentEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            panic!(
-                "EL1 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}:\n{:#x?}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss,
-                tf,
-            );
+        Some(ESR_EL1::EC::Value::Unknown) => {
+            if !handle_undefined_instruction_extern(tf) {
+                panic!("Undefined instruction @ {:#x}:\n{:#x?}", tf.elr, tf);
+            }
         }
         _ => {
             panic!(
@@ -74,7 +124,7 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
                 tf.elr,
                 esr.get(),
                 esr.read(ESR_EL1::EC),
-                esr.read(ESR_EL1::ISS),
+                iss,
             );
         }
     }
@@ -36,6 +36,19 @@ pub fn wait_for_irqs() {
     unsafe { riscv::asm::wfi() }
 }
 
+/// Enables interrupts and waits for them, without missing an interrupt that
+/// arrives in between.
+///
+/// It must be called with interrupts disabled, so that the caller can check
+/// for pending work before waiting. Interrupts are enabled when it returns.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    // A pending interrupt wakes up `wfi` even if it is masked, and is taken
+    // after being unmasked.
+    unsafe { riscv::asm::wfi() }
+    enable_irqs();
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -86,6 +99,21 @@ pub fn set_trap_vector_base(stvec: usize) {
     unsafe { stvec::write(stvec, stvec::TrapMode::Direct) }
 }
 
+/// Writes back the data cache lines covering the range.
+///
+/// DMA is assumed cache coherent on riscv, so this does nothing.
+#[inline]
+pub fn clean_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+/// Discards the data cache lines covering the range, does nothing.
+#[inline]
+pub fn invalidate_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+/// Writes back then discards the data cache lines covering the range, does
+/// nothing.
+#[inline]
+pub fn flush_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
 /// Reads the thread pointer of the current CPU.
 ///
 /// It is used to implement TLS (Thread Local Storage).
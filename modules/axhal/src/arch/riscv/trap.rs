@@ -1,6 +1,12 @@
+use memory_addr::VirtAddr;
 use riscv::register::scause::{self, Exception as E, Trap};
+use riscv::register::stval;
 
 use super::TrapFrame;
+use crate::trap::{
+    handle_alignment_fault_extern, handle_breakpoint_extern, handle_page_fault_extern,
+    handle_undefined_instruction_extern, PageFaultFlags,
+};
 
 include_asm_marcos!();
 
@@ -9,16 +15,42 @@ core::arch::global_asm!(
     trapframe_size = const core::mem::size_of::<TrapFrame>(),
 );
 
-fn handle_breakpoint(sepc: &mut usize) {
-    debug!("Exception(Breakpoint) @ {:#x} ", sepc);
-    *sepc += 2
+fn handle_breakpoint(tf: &mut TrapFrame) {
+    if !handle_breakpoint_extern(tf) {
+        debug!("Exception(Breakpoint) @ {:#x} ", tf.sepc);
+        tf.sepc += 2
+    }
+}
+
+fn handle_page_fault(tf: &TrapFrame, access_flags: PageFaultFlags, is_user: bool) {
+    let vaddr = VirtAddr::from(stval::read());
+    if !handle_page_fault_extern(vaddr, access_flags, is_user) {
+        panic!(
+            "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x}, access_flags={:?}:\n{:#x?}",
+            if is_user { "User" } else { "Supervisor" },
+            tf.sepc,
+            vaddr,
+            access_flags,
+            tf,
+        );
+    }
 }
 
 #[no_mangle]
-fn riscv_trap_handler(tf: &mut TrapFrame, _from_user: bool) {
+fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
     let scause = scause::read();
     match scause.cause() {
-        Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
+        Trap::Exception(E::Breakpoint) => handle_breakpoint(tf),
+        Trap::Exception(E::LoadPageFault) => handle_page_fault(tf, PageFaultFlags::READ, from_user),
+        Trap::Exception(E::StorePageFault) => {
+            handle_page_fault(tf, PageFaultFlags::WRITE, from_user)
+        }
+        Trap::Exception(E::InstructionPageFault) => {
+            handle_page_fault(tf, PageFaultFlags::EXECUTE, from_user)
+        }
+        Trap::Exception(E::IllegalInstruction) if handle_undefined_instruction_extern(tf) => {}
+        Trap::Exception(E::InstructionMisaligned | E::LoadMisaligned | E::StoreMisaligned)
+            if handle_alignment_fault_extern(tf, VirtAddr::from(stval::read())) => {}
         Trap::Interrupt(_) => crate::trap::handle_irq_extern(scause.bits()),
         _ => {
             panic!(
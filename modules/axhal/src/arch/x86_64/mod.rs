@@ -48,6 +48,21 @@ pub fn wait_for_irqs() {
     }
 }
 
+/// Enables interrupts and waits for them, without missing an interrupt that
+/// arrives in between.
+///
+/// It must be called with interrupts disabled, so that the caller can check
+/// for pending work before waiting. Interrupts are enabled when it returns.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    if cfg!(target_os = "none") {
+        // Interrupts are not recognized until the instruction after `sti`.
+        unsafe { asm!("sti; hlt") }
+    } else {
+        core::hint::spin_loop()
+    }
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -89,6 +104,21 @@ pub fn flush_tlb(vaddr: Option<VirtAddr>) {
     }
 }
 
+/// Writes back the data cache lines covering the range.
+///
+/// DMA is cache coherent on x86_64, so this does nothing.
+#[inline]
+pub fn clean_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+/// Discards the data cache lines covering the range, does nothing.
+#[inline]
+pub fn invalidate_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
+/// Writes back then discards the data cache lines covering the range, does
+/// nothing.
+#[inline]
+pub fn flush_dcache_range(_vaddr: VirtAddr, _size: usize) {}
+
 /// Reads the thread pointer of the current CPU.
 ///
 /// It is used to implement TLS (Thread Local Storage).
@@ -1,34 +1,68 @@
+use memory_addr::VirtAddr;
 use x86::{controlregs::cr2, irq::*};
 
 use super::context::TrapFrame;
+use crate::trap::{
+    handle_alignment_fault_extern, handle_breakpoint_extern, handle_page_fault_extern,
+    handle_undefined_instruction_extern, PageFaultFlags,
+};
 
 core::arch::global_asm!(include_str!("trap.S"));
 
 const IRQ_VECTOR_START: u8 = 0x20;
 const IRQ_VECTOR_END: u8 = 0xff;
 
+/// Page fault error code bits.
+const PF_ERR_WRITE: u64 = 1 << 1;
+const PF_ERR_INSTR: u64 = 1 << 4;
+
+fn handle_page_fault(tf: &TrapFrame) {
+    let vaddr = VirtAddr::from(unsafe { cr2() });
+    let access_flags = if tf.error_code & PF_ERR_INSTR != 0 {
+        PageFaultFlags::EXECUTE
+    } else if tf.error_code & PF_ERR_WRITE != 0 {
+        PageFaultFlags::WRITE
+    } else {
+        PageFaultFlags::READ
+    };
+    if handle_page_fault_extern(vaddr, access_flags, tf.is_user()) {
+        return;
+    }
+    if tf.is_user() {
+        warn!(
+            "User #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}",
+            tf.rip, vaddr, tf.error_code,
+        );
+    } else {
+        panic!(
+            "Kernel #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}:\n{:#x?}",
+            tf.rip, vaddr, tf.error_code, tf,
+        );
+    }
+}
+
 #[no_mangle]
-fn x86_trap_handler(tf: &TrapFrame) {
+fn x86_trap_handler(tf: &mut TrapFrame) {
     match tf.vector as u8 {
-        PAGE_FAULT_VECTOR => {
-            if tf.is_user() {
-                warn!(
-                    "User #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}",
-                    tf.rip,
-                    unsafe { cr2() },
-                    tf.error_code,
-                );
-            } else {
+        PAGE_FAULT_VECTOR => handle_page_fault(tf),
+        BREAKPOINT_VECTOR => {
+            if !handle_breakpoint_extern(tf) {
+                debug!("#BP @ {:#x} ", tf.rip)
+            }
+        }
+        INVALID_OPCODE_VECTOR => {
+            if !handle_undefined_instruction_extern(tf) {
+                panic!("#UD @ {:#x}:\n{:#x?}", tf.rip, tf);
+            }
+        }
+        ALIGNMENT_CHECK_VECTOR => {
+            if !handle_alignment_fault_extern(tf, VirtAddr::from(0)) {
                 panic!(
-                    "Kernel #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}:\n{:#x?}",
-                    tf.rip,
-                    unsafe { cr2() },
-                    tf.error_code,
-                    tf,
+                    "#AC @ {:#x}, error_code={:#x}:\n{:#x?}",
+                    tf.rip, tf.error_code, tf
                 );
             }
         }
-        BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
                 "#GP @ {:#x}, error_code={:#x}:\n{:#x?}",
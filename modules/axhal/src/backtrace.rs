@@ -0,0 +1,308 @@
+//! Frame-pointer based stack unwinding and kernel symbolization.
+//!
+//! The kernel must be built with frame pointers (`-C force-frame-pointers=yes`)
+//! for the unwinding to work. The trap entry code points the frame pointer to
+//! the saved [`TrapFrame`] before calling the trap handler, so the unwinder can
+//! continue from the interrupted context after dumping its registers.
+//!
+//! Symbols are looked up in a table embedded at build time from the file given
+//! by the `AX_KSYMS` environment variable. Each line of the table is a
+//! hexadecimal address followed by a space and the symbol name, sorted by
+//! address (the output format of `nm -n`, without the symbol type).
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::arch::TrapFrame;
+
+/// Maximum number of frames to unwind.
+const MAX_DEPTH: usize = 64;
+
+/// Symbol table, placed at the end of `.rodata` by the linker script so that
+/// the addresses of the code do not depend on it.
+#[used]
+#[link_section = ".ksyms"]
+static KSYMS: [u8; include_bytes!(concat!(env!("OUT_DIR"), "/ksyms.txt")).len()] =
+    *include_bytes!(concat!(env!("OUT_DIR"), "/ksyms.txt"));
+
+extern "C" {
+    fn _stext();
+    fn _etext();
+    fn _sksyms();
+    fn _eksyms();
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "x86_64")] {
+        extern "C" {
+            fn _trap_handlers();
+            fn _trap_handlers_end();
+        }
+
+        fn trap_entry_range() -> (usize, usize) {
+            (_trap_handlers as usize, _trap_handlers_end as usize)
+        }
+
+        #[inline(always)]
+        fn current_fp() -> usize {
+            let fp;
+            unsafe { core::arch::asm!("mov {}, rbp", out(reg) fp) };
+            fp
+        }
+
+        /// The frame record is `[rbp] = prev_rbp, [rbp + 8] = rip`.
+        const fn frame_record_addr(fp: usize) -> usize {
+            fp
+        }
+
+        fn trap_frame_regs(tf: &TrapFrame) -> (usize, usize, bool) {
+            (tf.rbp as usize, tf.rip as usize, tf.is_user())
+        }
+    } else if #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))] {
+        extern "C" {
+            fn trap_vector_base();
+            fn trap_vector_end();
+        }
+
+        fn trap_entry_range() -> (usize, usize) {
+            (trap_vector_base as usize, trap_vector_end as usize)
+        }
+
+        #[inline(always)]
+        fn current_fp() -> usize {
+            let fp;
+            unsafe { core::arch::asm!("mv {}, s0", out(reg) fp) };
+            fp
+        }
+
+        /// The frame record is `[s0 - 16] = prev_s0, [s0 - 8] = ra`.
+        const fn frame_record_addr(fp: usize) -> usize {
+            fp.wrapping_sub(16)
+        }
+
+        fn trap_frame_regs(tf: &TrapFrame) -> (usize, usize, bool) {
+            const SSTATUS_SPP: usize = 1 << 8;
+            (tf.regs.s0, tf.sepc, tf.sstatus & SSTATUS_SPP == 0)
+        }
+    } else if #[cfg(target_arch = "aarch64")] {
+        extern "C" {
+            fn exception_vector_base();
+            fn exception_vector_end();
+        }
+
+        fn trap_entry_range() -> (usize, usize) {
+            (exception_vector_base as usize, exception_vector_end as usize)
+        }
+
+        #[inline(always)]
+        fn current_fp() -> usize {
+            let fp;
+            unsafe { core::arch::asm!("mov {}, x29", out(reg) fp) };
+            fp
+        }
+
+        /// The frame record is `[x29] = prev_x29, [x29 + 8] = x30`.
+        const fn frame_record_addr(fp: usize) -> usize {
+            fp
+        }
+
+        fn trap_frame_regs(tf: &TrapFrame) -> (usize, usize, bool) {
+            const SPSR_M_EL0T: u64 = 0b0000;
+            (tf.r[29] as usize, tf.elr as usize, tf.spsr & 0xf == SPSR_M_EL0T)
+        }
+    }
+}
+
+fn ksyms() -> &'static [u8] {
+    let start = _sksyms as usize;
+    let end = _eksyms as usize;
+    unsafe { core::slice::from_raw_parts(start as *const u8, end - start) }
+}
+
+fn parse_hex(s: &[u8]) -> Option<usize> {
+    if s.is_empty() || s.len() > 2 * core::mem::size_of::<usize>() {
+        return None;
+    }
+    s.iter().try_fold(0usize, |acc, &c| {
+        let digit = (c as char).to_digit(16)?;
+        Some((acc << 4) | digit as usize)
+    })
+}
+
+/// Looks up the symbol containing the given address in the embedded symbol
+/// table.
+///
+/// Returns the symbol name and the offset of `addr` from its start, or `None`
+/// if no symbol is found (e.g., the symbol table is not embedded, or `addr` is
+/// not in the kernel code).
+pub fn symbolize(addr: usize) -> Option<(&'static str, usize)> {
+    if !(_stext as usize.._etext as usize).contains(&addr) {
+        return None;
+    }
+    let mut found = None;
+    for line in ksyms().split(|&c| c == b'\n') {
+        let Some(pos) = line.iter().position(|&c| c == b' ') else {
+            continue;
+        };
+        let Some(sym_addr) = parse_hex(&line[..pos]) else {
+            continue;
+        };
+        if sym_addr > addr {
+            break; // the table is sorted by address
+        }
+        found = Some((&line[pos + 1..], sym_addr));
+    }
+    found.map(|(name, sym_addr)| {
+        (
+            core::str::from_utf8(name).unwrap_or("<invalid>"),
+            addr - sym_addr,
+        )
+    })
+}
+
+/// Whether `size` bytes at `addr` lie in `stack` and are aligned to words.
+fn is_on_stack(stack: &Range<usize>, addr: usize, size: usize) -> bool {
+    addr % core::mem::size_of::<usize>() == 0
+        && addr >= stack.start
+        && addr.checked_add(size).is_some_and(|end| end <= stack.end)
+}
+
+/// Returns the previous frame pointer and the return address saved in the
+/// frame record of `fp`, if the record lies in `stack`.
+fn read_frame_record(stack: &Range<usize>, fp: usize) -> Option<(usize, usize)> {
+    let addr = frame_record_addr(fp);
+    if !is_on_stack(stack, addr, 2 * core::mem::size_of::<usize>()) {
+        return None;
+    }
+    let record = addr as *const usize;
+    Some(unsafe { (record.read(), record.add(1).read()) })
+}
+
+/// Prints a frame. If `pc` is a return address, `pc - 1` is looked up instead
+/// to find the symbol of the call site.
+fn fmt_frame(f: &mut fmt::Formatter, depth: usize, pc: usize, is_ra: bool) -> fmt::Result {
+    write!(f, "  #{:<2} {:#018x}", depth, pc)?;
+    let addr = if is_ra { pc.wrapping_sub(1) } else { pc };
+    match symbolize(addr) {
+        Some((name, offset)) => writeln!(f, " {}+{:#x}", name, offset),
+        None => writeln!(f, " <unknown>"),
+    }
+}
+
+/// Prints a trap frame with its registers, and then the interrupted PC.
+fn fmt_trap_frame(
+    f: &mut fmt::Formatter,
+    depth: usize,
+    addr: usize,
+    tf: &TrapFrame,
+) -> fmt::Result {
+    writeln!(f, "  -- trap frame at {:#x} --", addr)?;
+    writeln!(f, "{:#x?}", tf)?;
+    fmt_frame(f, depth, trap_frame_regs(tf).1, false)
+}
+
+/// A frame found by [`unwind`].
+enum Frame<'a> {
+    /// A function frame, with the return address to its caller.
+    Return(usize),
+    /// A trap frame at the given address, saved by the trap entry.
+    Trap(usize, &'a TrapFrame),
+}
+
+/// Walks the frames starting from the frame pointer `fp`, and calls `f` on
+/// each of them until it returns `false`.
+///
+/// Only the stack of the current CPU is read, see [`current_stack`]. Traps
+/// from the kernel save their frame on that stack as well. Unwinding stops at
+/// the outermost frame, a frame pointer out of the stack, or a trap frame
+/// from user space. Returns `true` if it stopped because of [`MAX_DEPTH`]
+/// instead.
+///
+/// [`current_stack`]: crate::cpu::current_stack
+fn unwind(mut fp: usize, mut f: impl FnMut(Frame) -> bool) -> bool {
+    let stack = crate::cpu::current_stack();
+    let (trap_start, trap_end) = trap_entry_range();
+    for _ in 0..MAX_DEPTH {
+        let Some((prev_fp, ra)) = read_frame_record(&stack, fp) else {
+            return false;
+        };
+        if ra == 0 {
+            return false;
+        }
+        if (trap_start..trap_end).contains(&ra) {
+            // Called from the trap entry, `prev_fp` points to the trap frame.
+            if !is_on_stack(&stack, prev_fp, core::mem::size_of::<TrapFrame>()) {
+                return false;
+            }
+            let tf = unsafe { &*(prev_fp as *const TrapFrame) };
+            let (tf_fp, _, from_user) = trap_frame_regs(tf);
+            if !f(Frame::Trap(prev_fp, tf)) || from_user {
+                return false;
+            }
+            fp = tf_fp;
+        } else {
+            if !f(Frame::Return(ra)) || prev_fp <= fp {
+                return false; // the stack grows downwards
+            }
+            fp = prev_fp;
+        }
+    }
+    true
+}
+
+/// Calls `f` on the PC of the code interrupted by the innermost trap being
+/// handled on the current CPU, and then on the call sites in its callers,
+/// until `f` returns `false`.
+///
+/// This is meant to be called from a trap handler, e.g., by a sampling
+/// profiler in an interrupt handler. Nothing is reported if the current CPU
+/// is not handling a trap. Return addresses are adjusted to point into the
+/// call instructions, so every reported PC can be symbolized directly.
+pub fn unwind_trapped(mut f: impl FnMut(usize) -> bool) {
+    let mut trapped = false;
+    unwind(current_fp(), |frame| match frame {
+        Frame::Trap(_, tf) => {
+            trapped = true;
+            f(trap_frame_regs(tf).1)
+        }
+        Frame::Return(ra) if trapped => f(ra - 1),
+        Frame::Return(_) => true,
+    });
+}
+
+/// A stack backtrace of the current CPU, unwound lazily when formatted.
+///
+/// Unwinding stops at the outermost frame, a frame pointer out of the stack
+/// of the current CPU, or a trap frame from user space.
+pub struct Backtrace {
+    fp: usize,
+}
+
+impl Backtrace {
+    /// Captures the frame pointer of the caller.
+    #[inline(always)]
+    pub fn capture() -> Self {
+        Self { fp: current_fp() }
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Backtrace:")?;
+        let mut depth = 0;
+        let mut res = Ok(());
+        let truncated = unwind(self.fp, |frame| {
+            res = match frame {
+                Frame::Return(ra) => fmt_frame(f, depth, ra, true),
+                Frame::Trap(addr, tf) => fmt_trap_frame(f, depth, addr, tf),
+            };
+            depth += 1;
+            res.is_ok()
+        });
+        res?;
+        if truncated {
+            writeln!(f, "  ...")?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 //! CPU-related operations.
 
+use core::ops::Range;
+
 #[percpu::def_percpu]
 static CPU_ID: usize = 0;
 
@@ -9,6 +11,17 @@ static IS_BSP: bool = false;
 #[percpu::def_percpu]
 static CURRENT_TASK_PTR: usize = 0;
 
+#[percpu::def_percpu]
+static CURRENT_STACK_BOTTOM: usize = 0;
+
+#[percpu::def_percpu]
+static CURRENT_STACK_TOP: usize = 0;
+
+extern "C" {
+    fn boot_stack();
+    fn boot_stack_top();
+}
+
 /// Returns the ID of the current CPU.
 #[inline]
 pub fn this_cpu_id() -> usize {
@@ -73,6 +86,31 @@ pub unsafe fn set_current_task_ptr<T>(ptr: *const T) {
     }
 }
 
+/// Returns the bounds of the stack of the code running on the current CPU.
+///
+/// That is the region of the boot stacks until a task switch sets the stack
+/// of the new task with [`set_current_stack`].
+pub fn current_stack() -> Range<usize> {
+    let _guard = kernel_guard::IrqSave::new();
+    unsafe { CURRENT_STACK_BOTTOM.read_current_raw()..CURRENT_STACK_TOP.read_current_raw() }
+}
+
+/// Sets the bounds of the stack of the code running on the current CPU.
+///
+/// # Safety
+///
+/// The whole `stack` must be mapped, and must contain the current stack
+/// pointer once the caller switches to the new stack.
+pub unsafe fn set_current_stack(stack: Range<usize>) {
+    let _guard = kernel_guard::IrqSave::new();
+    CURRENT_STACK_BOTTOM.write_current_raw(stack.start);
+    CURRENT_STACK_TOP.write_current_raw(stack.end);
+}
+
+fn boot_stacks() -> Range<usize> {
+    boot_stack as usize..boot_stack_top as usize
+}
+
 #[allow(dead_code)]
 pub(crate) fn init_primary(cpu_id: usize) {
     percpu::init(axconfig::SMP);
@@ -80,7 +118,10 @@ pub(crate) fn init_primary(cpu_id: usize) {
     unsafe {
         CPU_ID.write_current_raw(cpu_id);
         IS_BSP.write_current_raw(true);
+        set_current_stack(boot_stacks());
     }
+    #[cfg(feature = "pmu")]
+    crate::pmu::init_percpu();
 }
 
 #[allow(dead_code)]
@@ -89,5 +130,8 @@ pub(crate) fn init_secondary(cpu_id: usize) {
     unsafe {
         CPU_ID.write_current_raw(cpu_id);
         IS_BSP.write_current_raw(false);
+        set_current_stack(boot_stacks());
     }
+    #[cfg(feature = "pmu")]
+    crate::pmu::init_percpu();
 }
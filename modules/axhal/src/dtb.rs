@@ -0,0 +1,829 @@
+//! Flattened device tree (FDT) parsing.
+//!
+//! The boot code of the QEMU `virt` platforms hands the device tree blob
+//! over to [`init`]. [`get`] then gives access to the parsed [`Fdt`], which
+//! describes the RAM banks, reserved memory, interrupt controller, timer
+//! frequency, `/chosen` parameters and devices of the machine.
+//!
+//! The parser works in place on the blob and never allocates. Addresses in
+//! `reg` properties are read with the `#address-cells` and `#size-cells` of
+//! the parent node, but are not translated through the `ranges` of buses.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use lazy_init::LazyInit;
+
+use crate::mem::{phys_to_virt, PhysAddr};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+/// Oldest format version with all the header fields we need.
+const FDT_MIN_VERSION: u32 = 17;
+
+// Structure block tokens.
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Deepest node nesting followed by [`Fdt::all_nodes`].
+const MAX_DEPTH: usize = 16;
+
+/// Errors of device tree parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdtError {
+    /// The blob does not start with the FDT magic number.
+    BadMagic,
+    /// The format version of the blob is not supported.
+    BadVersion(u32),
+    /// A block of the blob lies out of its bounds.
+    Truncated,
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn be64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+const fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// Reads a number of `cells` 32-bit cells, keeping the low 64 bits.
+fn read_cells(data: &[u8], cells: u32) -> u64 {
+    data.chunks_exact(4).take(cells as usize).fold(0, |acc, c| {
+        (acc << 32) | u32::from_be_bytes(c.try_into().unwrap()) as u64
+    })
+}
+
+/// A device tree blob.
+#[derive(Clone, Copy)]
+pub struct Fdt<'a> {
+    structs: &'a [u8],
+    strings: &'a [u8],
+    mem_rsv: &'a [u8],
+    total_size: usize,
+    boot_cpu: u32,
+}
+
+enum Token<'a> {
+    BeginNode(&'a str),
+    EndNode,
+    Prop(Property<'a>),
+    End,
+}
+
+impl<'a> Fdt<'a> {
+    /// Parses the header of the blob in `data`.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, FdtError> {
+        let header = |field: usize| be32(data, field * 4).ok_or(FdtError::Truncated);
+        if header(0)? != FDT_MAGIC {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = header(1)? as usize;
+        let version = header(5)?;
+        if version < FDT_MIN_VERSION || header(6)? > FDT_MIN_VERSION {
+            return Err(FdtError::BadVersion(version));
+        }
+        let data = data.get(..total_size).ok_or(FdtError::Truncated)?;
+        let block = |offset: u32, size: u32| {
+            let (offset, size) = (offset as usize, size as usize);
+            data.get(offset..offset + size).ok_or(FdtError::Truncated)
+        };
+        let off_mem_rsv = header(4)?;
+        Ok(Self {
+            structs: block(header(2)?, header(9)?)?,
+            strings: block(header(3)?, header(8)?)?,
+            mem_rsv: block(off_mem_rsv, (total_size as u32).saturating_sub(off_mem_rsv))?,
+            total_size,
+            boot_cpu: header(7)?,
+        })
+    }
+
+    /// Parses the blob at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a readable blob, or to at least 8 readable bytes
+    /// not starting with the FDT magic number.
+    pub unsafe fn from_ptr(ptr: *const u8) -> Result<Self, FdtError> {
+        let header = core::slice::from_raw_parts(ptr, 8);
+        if be32(header, 0) != Some(FDT_MAGIC) {
+            return Err(FdtError::BadMagic);
+        }
+        let total_size = be32(header, 4).unwrap() as usize;
+        Self::from_bytes(core::slice::from_raw_parts(ptr, total_size))
+    }
+
+    /// Size of the blob in bytes.
+    pub fn total_size(&self) -> usize {
+        self.total_size
+    }
+
+    /// Physical ID of the boot CPU.
+    pub fn boot_cpu_id(&self) -> u32 {
+        self.boot_cpu
+    }
+
+    fn string(&self, offset: u32) -> Option<&'a str> {
+        let bytes = self.strings.get(offset as usize..)?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+        core::str::from_utf8(&bytes[..len]).ok()
+    }
+
+    /// Reads the token at `offset` of the structure block, skipping `NOP`s.
+    /// Returns the token and the offset of the next one.
+    fn token(&self, mut offset: usize) -> Option<(Token<'a>, usize)> {
+        loop {
+            let tag = be32(self.structs, offset)?;
+            offset += 4;
+            match tag {
+                FDT_BEGIN_NODE => {
+                    let bytes = self.structs.get(offset..)?;
+                    let len = bytes.iter().position(|&b| b == 0)?;
+                    let name = core::str::from_utf8(&bytes[..len]).ok()?;
+                    return Some((Token::BeginNode(name), align4(offset + len + 1)));
+                }
+                FDT_END_NODE => return Some((Token::EndNode, offset)),
+                FDT_PROP => {
+                    let len = be32(self.structs, offset)? as usize;
+                    let name = self.string(be32(self.structs, offset + 4)?)?;
+                    let value = self.structs.get(offset + 8..offset + 8 + len)?;
+                    let next = align4(offset + 8 + len);
+                    return Some((Token::Prop(Property { name, value }), next));
+                }
+                FDT_NOP => continue,
+                FDT_END => return Some((Token::End, offset)),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Returns the offset following the end of the node whose properties
+    /// start at `offset`.
+    fn skip_node(&self, mut offset: usize) -> Option<usize> {
+        let mut depth = 1;
+        loop {
+            let (token, next) = self.token(offset)?;
+            match token {
+                Token::BeginNode(_) => depth += 1,
+                Token::EndNode => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(next);
+                    }
+                }
+                Token::Prop(_) => {}
+                Token::End => return None,
+            }
+            offset = next;
+        }
+    }
+
+    /// The root node.
+    pub fn root(&self) -> Option<Node<'a>> {
+        match self.token(0)? {
+            (Token::BeginNode(name), props) => Some(Node {
+                fdt: *self,
+                name,
+                props,
+                parent_cells: Cells::DEFAULT,
+            }),
+            _ => None,
+        }
+    }
+
+    /// All nodes, depth first.
+    pub fn all_nodes(&self) -> AllNodes<'a> {
+        AllNodes {
+            fdt: *self,
+            offset: 0,
+            depth: 0,
+            cells: [Cells::DEFAULT; MAX_DEPTH + 1],
+        }
+    }
+
+    /// The node at `path`, such as `/chosen` or `/soc/serial@2800d000`.
+    ///
+    /// A path component without unit address also matches a node with one.
+    pub fn find_node(&self, path: &str) -> Option<Node<'a>> {
+        path.split('/')
+            .filter(|c| !c.is_empty())
+            .try_fold(self.root()?, |node, component| {
+                node.children().find(|child| {
+                    let name = child.name();
+                    name == component
+                        || (!component.contains('@') && child.base_name() == component)
+                })
+            })
+    }
+
+    /// The available nodes compatible with any of `compatible`.
+    pub fn find_compatible<'b>(
+        &self,
+        compatible: &'b [&'b str],
+    ) -> impl Iterator<Item = Node<'a>> + 'b
+    where
+        'a: 'b,
+    {
+        self.all_nodes()
+            .filter(|node| compatible.iter().any(|c| node.is_compatible(c)))
+            .filter(|node| node.is_available())
+    }
+
+    /// The node with the given `phandle`.
+    pub fn find_phandle(&self, phandle: u32) -> Option<Node<'a>> {
+        self.all_nodes()
+            .find(|node| node.phandle() == Some(phandle))
+    }
+
+    /// Ranges of the memory reservation block.
+    pub fn mem_reservations(&self) -> impl Iterator<Item = RegEntry> + 'a {
+        let mem_rsv = self.mem_rsv;
+        (0..)
+            .map_while(move |i| {
+                let addr = be64(mem_rsv, i * 16)?;
+                let size = be64(mem_rsv, i * 16 + 8)?;
+                Some((addr, size))
+            })
+            .take_while(|&(addr, size)| addr != 0 || size != 0)
+            .filter_map(|(addr, size)| RegEntry::new(addr, size))
+    }
+
+    /// RAM banks, from the `memory` nodes.
+    pub fn memory_banks(&self) -> impl Iterator<Item = RegEntry> + 'a {
+        self.all_nodes()
+            .filter(|node| {
+                node.property("device_type").and_then(|p| p.as_str()) == Some("memory")
+                    && node.is_available()
+            })
+            .flat_map(|node| node.reg())
+    }
+
+    /// Memory not to be used by the kernel: the memory reservation block and
+    /// the static regions under `/reserved-memory`.
+    pub fn reserved_memory(&self) -> impl Iterator<Item = RegEntry> + 'a {
+        let nodes = self.find_node("/reserved-memory").into_iter();
+        let regions = nodes.flat_map(|node| node.children().flat_map(|child| child.reg()));
+        self.mem_reservations().chain(regions)
+    }
+
+    /// The `/chosen` node.
+    pub fn chosen(&self) -> Option<Node<'a>> {
+        self.find_node("/chosen")
+    }
+
+    /// The kernel command line, from `/chosen/bootargs`.
+    pub fn bootargs(&self) -> Option<&'a str> {
+        self.chosen()?.property("bootargs")?.as_str()
+    }
+
+    /// The path of the console device, from `/chosen/stdout-path`, without
+    /// its options.
+    pub fn stdout_path(&self) -> Option<&'a str> {
+        let path = self.chosen()?.property("stdout-path")?.as_str()?;
+        path.split(':').next()
+    }
+
+    /// The root interrupt controller: the `interrupt-parent` of the root node,
+    /// or else the first interrupt controller.
+    pub fn interrupt_controller(&self) -> Option<Node<'a>> {
+        let parent = self.root()?.property("interrupt-parent");
+        if let Some(node) = parent.and_then(|p| self.find_phandle(p.as_u32()?)) {
+            return Some(node);
+        }
+        self.all_nodes()
+            .find(|node| node.property("interrupt-controller").is_some())
+    }
+
+    /// Frequency of the system timer in Hz, if the device tree specifies it.
+    ///
+    /// That is the `clock-frequency` of an ARM architected timer, which
+    /// overrides the frequency set by the firmware, or the
+    /// `timebase-frequency` of the CPUs on RISC-V.
+    pub fn timer_frequency(&self) -> Option<u64> {
+        let timers = self.find_compatible(&["arm,armv8-timer", "arm,armv7-timer"]);
+        let freq = timers
+            .filter_map(|node| node.property("clock-frequency"))
+            .next()
+            .or_else(|| self.find_node("/cpus")?.property("timebase-frequency"));
+        freq?.as_u64()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Cells {
+    addr: u32,
+    size: u32,
+}
+
+impl Cells {
+    /// Values of a node without `#address-cells` and `#size-cells`.
+    const DEFAULT: Self = Self { addr: 2, size: 1 };
+}
+
+/// A node of the device tree.
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    fdt: Fdt<'a>,
+    name: &'a str,
+    /// Offset of the first property.
+    props: usize,
+    /// Cells of the `reg` entries, from the parent node.
+    parent_cells: Cells,
+}
+
+impl<'a> Node<'a> {
+    /// Name of the node, with its unit address.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Name of the node, without its unit address.
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap()
+    }
+
+    /// The properties of the node.
+    pub fn properties(&self) -> impl Iterator<Item = Property<'a>> {
+        let fdt = self.fdt;
+        let mut offset = self.props;
+        core::iter::from_fn(move || match fdt.token(offset)? {
+            (Token::Prop(prop), next) => {
+                offset = next;
+                Some(prop)
+            }
+            _ => None,
+        })
+    }
+
+    /// The property called `name`.
+    pub fn property(&self, name: &str) -> Option<Property<'a>> {
+        self.properties().find(|p| p.name == name)
+    }
+
+    /// The children of the node.
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> {
+        let fdt = self.fdt;
+        let cells = self.cells();
+        let mut offset = self.after_properties();
+        core::iter::from_fn(move || match fdt.token(offset)? {
+            (Token::BeginNode(name), props) => {
+                offset = fdt.skip_node(props)?;
+                Some(Node {
+                    fdt,
+                    name,
+                    props,
+                    parent_cells: cells,
+                })
+            }
+            _ => None,
+        })
+    }
+
+    /// Offset of the token following the properties.
+    fn after_properties(&self) -> usize {
+        let mut offset = self.props;
+        while let Some((Token::Prop(_), next)) = self.fdt.token(offset) {
+            offset = next;
+        }
+        offset
+    }
+
+    /// Cells of the `reg` entries of the children.
+    fn cells(&self) -> Cells {
+        let cells = |name, default| {
+            self.property(name)
+                .and_then(|p| p.as_u32())
+                .unwrap_or(default)
+        };
+        Cells {
+            addr: cells("#address-cells", Cells::DEFAULT.addr),
+            size: cells("#size-cells", Cells::DEFAULT.size),
+        }
+    }
+
+    /// The `reg` entries of the node.
+    ///
+    /// Empty if the parent has more than 2 address or size cells. Entries
+    /// that do not fit in the address space are skipped.
+    pub fn reg(&self) -> impl Iterator<Item = RegEntry> + 'a {
+        let Cells { addr, size } = self.parent_cells;
+        let value = match self.property("reg") {
+            Some(p) if addr <= 2 && size <= 2 && addr + size > 0 => p.value,
+            _ => &[],
+        };
+        let entry_len = (addr + size) as usize * 4;
+        value
+            .chunks_exact(entry_len.max(4))
+            .filter_map(move |entry| {
+                let (a, s) = entry.split_at(addr as usize * 4);
+                RegEntry::new(read_cells(a, addr), read_cells(s, size))
+            })
+    }
+
+    /// The strings of the `compatible` property.
+    pub fn compatible(&self) -> impl Iterator<Item = &'a str> {
+        self.property("compatible")
+            .into_iter()
+            .flat_map(|p| p.as_str_list())
+    }
+
+    /// Whether `compatible` is one of the strings of the `compatible`
+    /// property.
+    pub fn is_compatible(&self, compatible: &str) -> bool {
+        self.compatible().any(|c| c == compatible)
+    }
+
+    /// Whether the device is enabled: the `status` property is absent, or is
+    /// `okay`.
+    pub fn is_available(&self) -> bool {
+        match self.property("status").and_then(|p| p.as_str()) {
+            None | Some("okay") | Some("ok") => true,
+            Some(_) => false,
+        }
+    }
+
+    /// The phandle of the node.
+    pub fn phandle(&self) -> Option<u32> {
+        self.property("phandle")
+            .or_else(|| self.property("linux,phandle"))
+            .and_then(|p| p.as_u32())
+    }
+
+    /// The cells of the `interrupts` property, to be read with the
+    /// `#interrupt-cells` of the interrupt parent.
+    pub fn interrupts(&self) -> impl Iterator<Item = u32> + 'a {
+        self.property("interrupts")
+            .into_iter()
+            .flat_map(|p| p.as_cells())
+    }
+}
+
+/// Iterator over all the nodes of a device tree, see [`Fdt::all_nodes`].
+pub struct AllNodes<'a> {
+    fdt: Fdt<'a>,
+    offset: usize,
+    depth: usize,
+    /// Cells defined by each node on the path to the current one.
+    cells: [Cells; MAX_DEPTH + 1],
+}
+
+impl<'a> Iterator for AllNodes<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        loop {
+            let (token, next) = self.fdt.token(self.offset)?;
+            self.offset = next;
+            match token {
+                Token::BeginNode(name) => {
+                    let node = Node {
+                        fdt: self.fdt,
+                        name,
+                        props: next,
+                        parent_cells: self.cells[self.depth.min(MAX_DEPTH)],
+                    };
+                    self.depth += 1;
+                    if self.depth <= MAX_DEPTH {
+                        self.cells[self.depth] = node.cells();
+                    }
+                    return Some(node);
+                }
+                Token::EndNode => self.depth = self.depth.saturating_sub(1),
+                Token::Prop(_) => {}
+                Token::End => return None,
+            }
+        }
+    }
+}
+
+/// A property of a node.
+#[derive(Debug, Clone, Copy)]
+pub struct Property<'a> {
+    pub name: &'a str,
+    pub value: &'a [u8],
+}
+
+impl<'a> Property<'a> {
+    /// The value as one cell.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.value.len() {
+            4 => be32(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// The value as one or two cells.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => be32(self.value, 0).map(u64::from),
+            8 => be64(self.value, 0),
+            _ => None,
+        }
+    }
+
+    /// The value as a string.
+    pub fn as_str(&self) -> Option<&'a str> {
+        let bytes = self.value.strip_suffix(&[0]).unwrap_or(self.value);
+        core::str::from_utf8(bytes).ok()
+    }
+
+    /// The value as a list of strings.
+    pub fn as_str_list(&self) -> impl Iterator<Item = &'a str> {
+        self.value
+            .split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    /// The value as a list of cells.
+    pub fn as_cells(&self) -> impl Iterator<Item = u32> + 'a {
+        self.value
+            .chunks_exact(4)
+            .map(|c| u32::from_be_bytes(c.try_into().unwrap()))
+    }
+}
+
+/// An entry of a `reg` property or of the memory reservation block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegEntry {
+    pub addr: usize,
+    pub size: usize,
+}
+
+impl RegEntry {
+    /// The entry for `[addr, addr + size)`, if that range fits in the address
+    /// space.
+    fn new(addr: u64, size: u64) -> Option<Self> {
+        let addr = usize::try_from(addr).ok()?;
+        let size = usize::try_from(size).ok()?;
+        addr.checked_add(size)?;
+        Some(Self { addr, size })
+    }
+}
+
+static FDT: LazyInit<Fdt<'static>> = LazyInit::new();
+static FDT_PADDR: AtomicUsize = AtomicUsize::new(0);
+
+/// Parses the device tree blob at physical address `dtb`.
+///
+/// Must be called once, after the `.bss` section is cleared and while the
+/// blob is mapped at its linear-mapping address. Does nothing if there is
+/// no valid blob at `dtb`.
+pub(crate) fn init(dtb: usize) {
+    if dtb == 0 {
+        return;
+    }
+    let ptr = phys_to_virt(PhysAddr::from(dtb)).as_ptr();
+    if let Ok(fdt) = unsafe { Fdt::from_ptr(ptr) } {
+        FDT.init_by(fdt);
+        FDT_PADDR.store(dtb, Ordering::Release);
+    }
+}
+
+/// The device tree passed by the boot loader, if any.
+pub fn get() -> Option<&'static Fdt<'static>> {
+    FDT.try_get()
+}
+
+/// Physical address and size of the device tree blob.
+pub fn blob_region() -> Option<(PhysAddr, usize)> {
+    let fdt = get()?;
+    Some((FDT_PADDR.load(Ordering::Acquire).into(), fdt.total_size()))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Builds device tree blobs.
+    #[derive(Default)]
+    struct Builder {
+        structs: Vec<u8>,
+        strings: Vec<u8>,
+        mem_rsv: Vec<(u64, u64)>,
+    }
+
+    impl Builder {
+        fn word(&mut self, word: u32) -> &mut Self {
+            self.structs.extend(word.to_be_bytes());
+            self
+        }
+
+        fn pad(&mut self) -> &mut Self {
+            self.structs.resize(align4(self.structs.len()), 0);
+            self
+        }
+
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.word(FDT_BEGIN_NODE);
+            self.structs.extend(name.as_bytes());
+            self.structs.push(0);
+            self.pad()
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.word(FDT_END_NODE)
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let name_offset = self.strings.len() as u32;
+            self.strings.extend(name.as_bytes());
+            self.strings.push(0);
+            self.word(FDT_PROP)
+                .word(value.len() as u32)
+                .word(name_offset);
+            self.structs.extend(value);
+            self.pad()
+        }
+
+        fn cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+            let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+            self.prop(name, &value)
+        }
+
+        fn reserve(&mut self, addr: u64, size: u64) -> &mut Self {
+            self.mem_rsv.push((addr, size));
+            self
+        }
+
+        fn finish(&mut self) -> Vec<u8> {
+            self.word(FDT_END);
+            let mut mem_rsv = Vec::new();
+            for &(addr, size) in self.mem_rsv.iter().chain([&(0, 0)]) {
+                mem_rsv.extend(addr.to_be_bytes());
+                mem_rsv.extend(size.to_be_bytes());
+            }
+            let off_mem_rsv = 40;
+            let off_structs = off_mem_rsv + mem_rsv.len();
+            let off_strings = off_structs + self.structs.len();
+            let total_size = off_strings + self.strings.len();
+            let header = [
+                FDT_MAGIC,
+                total_size as u32,
+                off_structs as u32,
+                off_strings as u32,
+                off_mem_rsv as u32,
+                FDT_MIN_VERSION,
+                16,
+                1,
+                self.strings.len() as u32,
+                self.structs.len() as u32,
+            ];
+            let mut blob: Vec<u8> = header.iter().flat_map(|w| w.to_be_bytes()).collect();
+            blob.extend(mem_rsv);
+            blob.extend(&self.structs);
+            blob.extend(&self.strings);
+            blob
+        }
+    }
+
+    fn machine() -> Vec<u8> {
+        Builder::default()
+            .reserve(0x8800_0000, 0x1000)
+            .begin("")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .begin("chosen")
+            .prop("bootargs", b"console=ttyS0\0")
+            .prop("stdout-path", b"/soc/serial@1000:115200\0")
+            .end()
+            .begin("cpus")
+            .cells("#address-cells", &[1])
+            .cells("#size-cells", &[0])
+            .cells("timebase-frequency", &[10_000_000])
+            .begin("cpu@0")
+            .cells("reg", &[0])
+            .end()
+            .end()
+            .begin("memory@80000000")
+            .prop("device_type", b"memory\0")
+            .cells("reg", &[0, 0x8000_0000, 0, 0x800_0000])
+            .end()
+            .begin("reserved-memory")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .begin("firmware@80000000")
+            .cells("reg", &[0, 0x8000_0000, 0, 0x2_0000])
+            .end()
+            .end()
+            .begin("soc")
+            .cells("#address-cells", &[1])
+            .cells("#size-cells", &[1])
+            .begin("serial@1000")
+            .prop("compatible", b"ns16550a\0")
+            .cells("reg", &[0x1000, 0x100])
+            .end()
+            .begin("serial@2000")
+            .prop("compatible", b"ns16550a\0")
+            .prop("status", b"disabled\0")
+            .cells("reg", &[0x2000, 0x100])
+            .end()
+            .begin("bus")
+            .cells("#address-cells", &[3])
+            .begin("device@0")
+            .cells("reg", &[0, 0, 0x1000, 0x100])
+            .end()
+            .end()
+            .end()
+            .begin("overflow")
+            .cells(
+                "reg",
+                &[0xffff_ffff, 0xffff_f000, 0, 0x2000, 0, 0x3000, 0, 0x1000],
+            )
+            .end()
+            .end()
+            .finish()
+    }
+
+    fn regs(node: Node) -> Vec<(usize, usize)> {
+        node.reg().map(|r| (r.addr, r.size)).collect()
+    }
+
+    #[test]
+    fn header() {
+        let mut blob = machine();
+        let fdt = Fdt::from_bytes(&blob).unwrap();
+        assert_eq!(fdt.total_size(), blob.len());
+        assert_eq!(fdt.boot_cpu_id(), 1);
+        assert!(matches!(
+            Fdt::from_bytes(&blob[..blob.len() - 1]),
+            Err(FdtError::Truncated)
+        ));
+        blob[23] = 16;
+        assert!(matches!(
+            Fdt::from_bytes(&blob),
+            Err(FdtError::BadVersion(16))
+        ));
+        blob[0] = 0;
+        assert!(matches!(Fdt::from_bytes(&blob), Err(FdtError::BadMagic)));
+    }
+
+    #[test]
+    fn nodes() {
+        let blob = machine();
+        let fdt = Fdt::from_bytes(&blob).unwrap();
+        let names: Vec<_> = fdt.root().unwrap().children().map(|n| n.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "chosen",
+                "cpus",
+                "memory@80000000",
+                "reserved-memory",
+                "soc",
+                "overflow"
+            ]
+        );
+        assert_eq!(fdt.all_nodes().count(), 13);
+        assert_eq!(fdt.find_node("/memory").unwrap().name(), "memory@80000000");
+        assert!(fdt.find_node("/soc/serial@3000").is_none());
+
+        let serials: Vec<_> = fdt.find_compatible(&["ns16550a"]).collect();
+        assert_eq!(serials.len(), 1);
+        assert_eq!(serials[0].name(), "serial@1000");
+        assert_eq!(regs(serials[0]), [(0x1000, 0x100)]);
+    }
+
+    #[test]
+    fn chosen_and_timer() {
+        let blob = machine();
+        let fdt = Fdt::from_bytes(&blob).unwrap();
+        assert_eq!(fdt.bootargs(), Some("console=ttyS0"));
+        assert_eq!(fdt.stdout_path(), Some("/soc/serial@1000"));
+        assert_eq!(fdt.timer_frequency(), Some(10_000_000));
+    }
+
+    #[test]
+    fn memory() {
+        let blob = machine();
+        let fdt = Fdt::from_bytes(&blob).unwrap();
+        let banks: Vec<_> = fdt.memory_banks().map(|r| (r.addr, r.size)).collect();
+        assert_eq!(banks, [(0x8000_0000, 0x800_0000)]);
+        let reserved: Vec<_> = fdt.reserved_memory().map(|r| (r.addr, r.size)).collect();
+        assert_eq!(reserved, [(0x8800_0000, 0x1000), (0x8000_0000, 0x2_0000)]);
+    }
+
+    #[test]
+    fn invalid_reg() {
+        let blob = machine();
+        let fdt = Fdt::from_bytes(&blob).unwrap();
+        // More than 2 address cells.
+        assert!(regs(fdt.find_node("/soc/bus/device").unwrap()).is_empty());
+        // The first entry ends past the address space.
+        assert_eq!(
+            regs(fdt.find_node("/overflow").unwrap()),
+            [(0x3000, 0x1000)]
+        );
+    }
+}
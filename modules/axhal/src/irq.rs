@@ -6,6 +6,13 @@ use crate::platform::irq::MAX_IRQ_COUNT;
 
 pub use crate::platform::irq::{dispatch_irq, register_handler, set_enable};
 
+// The IPI is handled as a normal IRQ, and should be enabled on each CPU.
+#[cfg(feature = "smp")]
+pub use crate::platform::irq::{send_ipi, IPI_IRQ_NUM};
+
+#[cfg(all(target_arch = "x86_64", platform_family = "x86-pc"))]
+pub use crate::platform::irq::route_pci_intx;
+
 /// The type if an IRQ handler.
 pub type IrqHandler = handler_table::Handler;
 
@@ -20,6 +20,9 @@
 //! - `fp_simd`: Enable floating-point and SIMD support.
 //! - `paging`: Enable page table manipulation.
 //! - `irq`: Enable interrupt handling support.
+//! - `rtc`: Read the wall-clock time from the real-time clock of the platform.
+//! - `backtrace`: Enable stack unwinding and symbolization of kernel addresses.
+//! - `pmu`: Enable access to the hardware performance counters.
 //!
 //! [ArceOS]: https://github.com/rcore-os/arceos
 //! [cargo test]: https://doc.rust-lang.org/cargo/guide/tests.html
@@ -38,7 +41,9 @@ mod platform;
 
 pub mod arch;
 pub mod cpu;
+pub mod dtb;
 pub mod mem;
+pub mod power;
 pub mod time;
 pub mod trap;
 
@@ -51,6 +56,12 @@ pub mod irq;
 #[cfg(feature = "paging")]
 pub mod paging;
 
+#[cfg(feature = "backtrace")]
+pub mod backtrace;
+
+#[cfg(feature = "pmu")]
+pub mod pmu;
+
 /// Console input and output.
 pub mod console {
     pub use super::platform::console::*;
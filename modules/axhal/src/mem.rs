@@ -1,10 +1,14 @@
 //! Physical memory management.
 
 use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 #[doc(no_inline)]
 pub use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
+#[cfg(feature = "paging")]
+use crate::paging::{kernel_page_table, MappingFlags, PagingError, PagingResult};
+
 bitflags::bitflags! {
     /// The flags of a physical memory region.
     pub struct MemRegionFlags: usize {
@@ -72,7 +76,9 @@ pub const fn phys_to_virt(paddr: PhysAddr) -> VirtAddr {
 
 /// Returns an iterator over all physical memory regions.
 pub fn memory_regions() -> impl Iterator<Item = MemRegion> {
-    kernel_image_regions().chain(crate::platform::mem::platform_regions())
+    kernel_image_regions()
+        .chain(dtb_regions())
+        .chain(crate::platform::mem::platform_regions())
 }
 
 /// Returns the memory regions of the kernel image (code and data sections).
@@ -112,32 +118,149 @@ fn kernel_image_regions() -> impl Iterator<Item = MemRegion> {
     .into_iter()
 }
 
-/// Returns the default MMIO memory regions (from [`axconfig::MMIO_REGIONS`]).
+/// Returns the memory region of the device tree blob, if any, which must stay
+/// mapped for [`crate::dtb::get`].
+fn dtb_regions() -> impl Iterator<Item = MemRegion> {
+    crate::dtb::blob_region()
+        .map(|(paddr, size)| {
+            let start = paddr.align_down_4k();
+            MemRegion {
+                paddr: start,
+                size: memory_addr::align_up_4k(paddr.as_usize() + size) - start.as_usize(),
+                flags: MemRegionFlags::RESERVED | MemRegionFlags::READ,
+                name: "device tree blob",
+            }
+        })
+        .into_iter()
+}
+
+/// Maximum number of MMIO or free memory regions.
+const MAX_REGIONS: usize = 32;
+
+/// Disjoint physical address ranges, with a name each.
+struct Ranges {
+    ranges: [(usize, usize, &'static str); MAX_REGIONS],
+    len: usize,
+}
+
+impl Ranges {
+    const fn new() -> Self {
+        Self {
+            ranges: [(0, 0, ""); MAX_REGIONS],
+            len: 0,
+        }
+    }
+
+    fn insert(&mut self, start: usize, end: usize, name: &'static str) {
+        if start >= end {
+            return;
+        }
+        if self.len == MAX_REGIONS {
+            warn!(
+                "too many memory regions, dropping {:?} [{:#x}, {:#x})",
+                name, start, end
+            );
+            return;
+        }
+        self.ranges[self.len] = (start, end, name);
+        self.len += 1;
+    }
+
+    /// Removes `start..end` from the ranges, splitting them as needed.
+    fn remove(&mut self, start: usize, end: usize) {
+        let old = core::mem::replace(self, Self::new());
+        for &(s, e, name) in &old.ranges[..old.len] {
+            if e <= start || s >= end {
+                self.insert(s, e, name);
+            } else {
+                self.insert(s, start, name);
+                self.insert(end, e, name);
+            }
+        }
+    }
+
+    fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.ranges[..self.len]
+            .iter()
+            .any(|&(s, e, _)| s < end && start < e)
+    }
+
+    fn into_regions(self, flags: MemRegionFlags) -> impl Iterator<Item = MemRegion> {
+        (0..self.len).map(move |i| {
+            let (start, end, name) = self.ranges[i];
+            MemRegion {
+                paddr: start.into(),
+                size: end - start,
+                flags,
+                name,
+            }
+        })
+    }
+}
+
+/// Returns the default MMIO memory regions: those of [`axconfig::MMIO_REGIONS`],
+/// and those of the top-level devices of the device tree outside of them.
 #[allow(dead_code)]
 pub(crate) fn default_mmio_regions() -> impl Iterator<Item = MemRegion> {
-    axconfig::MMIO_REGIONS.iter().map(|reg| MemRegion {
-        paddr: reg.0.into(),
-        size: reg.1,
-        flags: MemRegionFlags::RESERVED
-            | MemRegionFlags::DEVICE
-            | MemRegionFlags::READ
-            | MemRegionFlags::WRITE,
-        name: "mmio",
-    })
+    let flags = MemRegionFlags::RESERVED
+        | MemRegionFlags::DEVICE
+        | MemRegionFlags::READ
+        | MemRegionFlags::WRITE;
+    let mut config = Ranges::new();
+    for reg in axconfig::MMIO_REGIONS {
+        config.insert(reg.0, reg.0 + reg.1, "mmio");
+    }
+    let mut found = Ranges::new();
+    if let Some(fdt) = crate::dtb::get() {
+        let mut ram = Ranges::new();
+        for bank in fdt.memory_banks() {
+            ram.insert(bank.addr, bank.addr + bank.size, "");
+        }
+        let devices = fdt.root().into_iter().flat_map(|root| root.children());
+        for node in devices.filter(|node| node.is_available()) {
+            for reg in node.reg() {
+                let start = memory_addr::align_down_4k(reg.addr);
+                let end = memory_addr::align_up_4k(reg.addr + reg.size);
+                if ![&config, &found, &ram]
+                    .iter()
+                    .any(|r| r.overlaps(start, end))
+                {
+                    found.insert(start, end, node.name());
+                }
+            }
+        }
+    }
+    config.into_regions(flags).chain(found.into_regions(flags))
 }
 
-/// Returns the default free memory regions (kernel image end to physical memory end).
+/// Returns the default free memory regions: the RAM banks of the device tree
+/// if any, or else up to [`axconfig::PHYS_MEMORY_END`], after the kernel image.
+///
+/// Reserved memory and the device tree blob are left out.
 #[allow(dead_code)]
 pub(crate) fn default_free_regions() -> impl Iterator<Item = MemRegion> {
     let start = VirtAddr::from(_ekernel as usize + axconfig::NOCACHE_MEMORY_SIZE).align_up_4k();
-    let start = virt_to_phys(start);
-    let end = PhysAddr::from(axconfig::PHYS_MEMORY_END);
-    core::iter::once(MemRegion {
-        paddr: start,
-        size: end.as_usize() - start.as_usize(),
-        flags: MemRegionFlags::FREE | MemRegionFlags::READ | MemRegionFlags::WRITE,
-        name: "free memory",
-    })
+    let start = virt_to_phys(start).as_usize();
+    let mut free = Ranges::new();
+    match crate::dtb::get() {
+        Some(fdt) => {
+            for bank in fdt.memory_banks() {
+                let end = memory_addr::align_down_4k(bank.addr + bank.size);
+                free.insert(memory_addr::align_up_4k(bank.addr), end, "free memory");
+            }
+            free.remove(0, start);
+            for r in fdt.reserved_memory() {
+                let end = memory_addr::align_up_4k(r.addr + r.size);
+                free.remove(memory_addr::align_down_4k(r.addr), end);
+            }
+            if let Some((paddr, size)) = crate::dtb::blob_region() {
+                let end = memory_addr::align_up_4k(paddr.as_usize() + size);
+                free.remove(paddr.align_down_4k().as_usize(), end);
+            }
+        }
+        None => free.insert(start, axconfig::PHYS_MEMORY_END, "free memory"),
+    }
+    free.into_regions(MemRegionFlags::FREE | MemRegionFlags::READ | MemRegionFlags::WRITE)
 }
 
 /// Returns the default free memory regions (kernel image end to physical memory end).
@@ -154,6 +277,166 @@ pub(crate) fn default_nocache_regions() -> impl Iterator<Item = MemRegion> {
     })
 }
 
+/// Maps the device memory `[paddr, paddr + size)` into the kernel page table
+/// at runtime, and returns the virtual address of `paddr`.
+///
+/// The memory is mapped at its linear mapping address (see [`phys_to_virt`]),
+/// so it can be accessed as if it were listed in the `mmio-regions` of the
+/// platform configuration. `flags` usually contains [`MappingFlags::DEVICE`]
+/// for device registers, or [`MappingFlags::UNCACHED`] for memory such as
+/// framebuffers. Pages of the memory regions mapped at boot (see
+/// [`memory_regions`]) are left untouched, but the other pages must not be
+/// mapped yet, e.g., by an overlapping `ioremap`. Nothing is mapped on error.
+///
+/// It can be used only after the kernel page table is set by the runtime.
+#[cfg(feature = "paging")]
+pub fn ioremap(paddr: PhysAddr, size: usize, flags: MappingFlags) -> PagingResult<VirtAddr> {
+    let (start, end) = page_range(paddr, size)?;
+    let mut pt = kernel_page_table().ok_or(PagingError::NotMapped)?.lock();
+
+    // Check all the pages first, so that nothing is left mapped on error.
+    let mut page = start;
+    while page < end {
+        match pt.query(phys_to_virt(page.into())) {
+            Ok((target, _, _)) if target.as_usize() == page && is_boot_mapped(page) => {}
+            Ok(_) => return Err(PagingError::AlreadyMapped),
+            Err(_) => {}
+        }
+        page += PAGE_SIZE_4K;
+    }
+
+    // Map each run of unmapped pages. Huge pages are not used, since the run may
+    // share a page table with existing mappings.
+    let mut run_start = start;
+    for page in (start..end).step_by(PAGE_SIZE_4K) {
+        if pt.query(phys_to_virt(page.into())).is_ok() {
+            ioremap_run(&mut pt, start, run_start, page, flags)?;
+            run_start = page + PAGE_SIZE_4K;
+        }
+    }
+    ioremap_run(&mut pt, start, run_start, end, flags)?;
+    crate::arch::flush_tlb(None);
+    debug!("ioremap: [PA:{:#x}, PA:{:#x}) {:?}", start, end, flags);
+    Ok(phys_to_virt(paddr))
+}
+
+/// Maps the run `[run_start, run_end)` of unmapped pages for [`ioremap`],
+/// or unmaps all the pages mapped from `start` on error.
+#[cfg(feature = "paging")]
+fn ioremap_run(
+    pt: &mut crate::paging::PageTable,
+    start: usize,
+    run_start: usize,
+    run_end: usize,
+    flags: MappingFlags,
+) -> PagingResult {
+    if run_start < run_end {
+        let res = pt.map_region(
+            phys_to_virt(run_start.into()),
+            run_start.into(),
+            run_end - run_start,
+            flags,
+            false,
+        );
+        if res.is_err() {
+            unmap_pages(pt, start, run_end);
+        }
+        res?;
+    }
+    Ok(())
+}
+
+/// Unmaps the device memory mapped by [`ioremap`].
+///
+/// `vaddr` and `size` should be the same as the returned address and the `size`
+/// argument of [`ioremap`]. Pages of the memory regions mapped at boot (see
+/// [`memory_regions`]) are kept. Nothing is unmapped if any other page is not
+/// mapped. The TLB entries of the other CPUs are flushed with the function
+/// registered by [`register_tlb_shootdown`].
+#[cfg(feature = "paging")]
+pub fn iounmap(vaddr: VirtAddr, size: usize) -> PagingResult {
+    let (start, end) = page_range(virt_to_phys(vaddr), size)?;
+    let mut pt = kernel_page_table().ok_or(PagingError::NotMapped)?.lock();
+    let mut page = start;
+    while page < end {
+        if !is_boot_mapped(page) {
+            match pt.query(phys_to_virt(page.into())) {
+                Ok((target, _, _)) if target.as_usize() == page => {}
+                _ => return Err(PagingError::NotMapped),
+            }
+        }
+        page += PAGE_SIZE_4K;
+    }
+    unmap_pages(&mut pt, start, end);
+    // Other CPUs may wait for the page table with IRQs disabled, release it
+    // before interrupting them.
+    drop(pt);
+    tlb_shootdown();
+    debug!("iounmap: [PA:{:#x}, PA:{:#x})", start, end);
+    Ok(())
+}
+
+/// Returns the page-aligned bounds of `[paddr, paddr + size)`.
+#[cfg(feature = "paging")]
+fn page_range(paddr: PhysAddr, size: usize) -> PagingResult<(usize, usize)> {
+    let end = paddr
+        .as_usize()
+        .checked_add(size)
+        .and_then(|end| end.checked_add(PAGE_SIZE_4K - 1))
+        .ok_or(PagingError::NotAligned)?;
+    Ok((paddr.align_down_4k().as_usize(), end & !(PAGE_SIZE_4K - 1)))
+}
+
+/// Whether the physical page at `paddr` is in the memory regions mapped at
+/// boot.
+#[cfg(feature = "paging")]
+fn is_boot_mapped(paddr: usize) -> bool {
+    memory_regions().any(|r| r.paddr.as_usize() <= paddr && paddr - r.paddr.as_usize() < r.size)
+}
+
+/// Unmaps the pages of `[start, end)` mapped by [`ioremap`], and flushes
+/// their TLB entries on the current CPU.
+#[cfg(feature = "paging")]
+fn unmap_pages(pt: &mut crate::paging::PageTable, start: usize, end: usize) {
+    for page in (start..end).step_by(PAGE_SIZE_4K) {
+        if !is_boot_mapped(page) {
+            let vaddr = phys_to_virt(page.into());
+            if pt.unmap(vaddr).is_ok() {
+                crate::arch::flush_tlb(Some(vaddr));
+            }
+        }
+    }
+}
+
+/// Flushes the TLB of the other CPUs, see [`register_tlb_shootdown`].
+#[cfg(feature = "paging")]
+static TLB_SHOOTDOWN: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers the function flushing the whole TLB of the other online CPUs,
+/// called by [`iounmap`] after removing mappings.
+///
+/// The runtime registers one sending inter-processor interrupts when SMP is
+/// enabled. Returns `false` if one is already registered.
+#[cfg(feature = "paging")]
+pub fn register_tlb_shootdown(shootdown: fn()) -> bool {
+    TLB_SHOOTDOWN
+        .compare_exchange(0, shootdown as usize, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+}
+
+#[cfg(feature = "paging")]
+fn tlb_shootdown() {
+    // The TLB invalidations are broadcast to all the CPUs on AArch64.
+    if cfg!(target_arch = "aarch64") {
+        return;
+    }
+    let shootdown = TLB_SHOOTDOWN.load(Ordering::Acquire);
+    if shootdown != 0 {
+        let shootdown: fn() = unsafe { core::mem::transmute(shootdown) };
+        shootdown();
+    }
+}
+
 /// Fills the `.bss` section with zeros.
 #[allow(dead_code)]
 pub(crate) fn clear_bss() {
@@ -1,7 +1,9 @@
 //! Page table manipulation.
 
 use axalloc::global_allocator;
+use lazy_init::LazyInit;
 use page_table::PagingIf;
+use spinlock::SpinNoIrq;
 
 use crate::mem::{phys_to_virt, virt_to_phys, MemRegionFlags, PhysAddr, VirtAddr, PAGE_SIZE_4K};
 
@@ -64,3 +66,21 @@ cfg_if::cfg_if! {
         pub type PageTable = page_table::aarch64::A64PageTable<PagingIfImpl>;
     }
 }
+
+/// The kernel page table, shared by all CPUs.
+static KERNEL_PAGE_TABLE: LazyInit<SpinNoIrq<PageTable>> = LazyInit::new();
+
+/// Sets the kernel page table, which is created by the runtime on the primary
+/// CPU after the memory allocator is initialized.
+///
+/// # Panics
+///
+/// Panics if the kernel page table is already set.
+pub fn set_kernel_page_table(page_table: PageTable) {
+    KERNEL_PAGE_TABLE.init_by(SpinNoIrq::new(page_table));
+}
+
+/// Returns the kernel page table, or `None` if it is not set yet.
+pub fn kernel_page_table() -> Option<&'static SpinNoIrq<PageTable>> {
+    KERNEL_PAGE_TABLE.try_get()
+}
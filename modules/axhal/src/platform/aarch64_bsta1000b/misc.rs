@@ -1,5 +1,13 @@
 pub use crate::platform::aarch64_common::psci::system_off as terminate;
 
+/// Reboots the whole system, including all CPUs.
+pub fn reboot() -> ! {
+    do_reset();
+    loop {
+        crate::arch::halt();
+    }
+}
+
 use crate::mem::phys_to_virt;
 use crate::time::{busy_wait, Duration};
 use core::ptr::{read_volatile, write_volatile};
@@ -35,7 +43,6 @@ pub fn reset_cpu() {
 }
 
 /// reboot system
-#[allow(dead_code)]
 pub fn do_reset() {
     axlog::ax_println!("resetting ...\n");
 
@@ -38,6 +38,34 @@ unsafe fn switch_to_el1() {
         // Disable EL1 timer traps and the timer offset.
         CNTHCTL_EL2.modify(CNTHCTL_EL2::EL1PCEN::SET + CNTHCTL_EL2::EL1PCTEN::SET);
         CNTVOFF_EL2.set(0);
+        // Let EL1 use the system register interface of GICv3, if present.
+        core::arch::asm!(
+            "
+            mrs     x8, id_aa64pfr0_el1
+            ubfx    x8, x8, #24, #4         // GIC field
+            cbz     x8, 1f
+            mrs     x8, S3_4_C12_C9_5       // ICC_SRE_EL2
+            orr     x8, x8, #0x9            // SRE | Enable
+            msr     S3_4_C12_C9_5, x8
+            isb
+        1:",
+            out("x8") _,
+        );
+        // Let EL1 use all the PMU counters without traps, if present.
+        core::arch::asm!(
+            "
+            mrs     x8, id_aa64dfr0_el1
+            ubfx    x8, x8, #8, #4          // PMUVer field
+            cbz     x8, 1f
+            cmp     x8, #0xf
+            b.eq    1f
+            mrs     x8, pmcr_el0
+            ubfx    x8, x8, #11, #5         // N field
+            msr     mdcr_el2, x8            // HPMN = N
+            isb
+        1:",
+            out("x8") _,
+        );
         // Set EL1 to 64bit.
         HCR_EL2.write(HCR_EL2::RW::EL1IsAarch64);
         // Set the return address and exception level.
@@ -101,8 +129,6 @@ unsafe fn init_boot_page_table() {
     crate::platform::mem::init_boot_page_table(&mut BOOT_PT_L0, &mut BOOT_PT_L1);
 }
 
-
-
 #[naked]
 #[no_mangle]
 #[link_section = ".text.boot"]
@@ -42,8 +42,12 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
 }
 
 /// Early stage initialization: stores the timer frequency.
+///
+/// A frequency given by the device tree overrides the one set by the
+/// firmware in `CNTFRQ_EL0`.
 pub(crate) fn init_early() {
-    let freq = CNTFRQ_EL0.get();
+    let fdt_freq = crate::dtb::get().and_then(|fdt| fdt.timer_frequency());
+    let freq = fdt_freq.unwrap_or_else(|| CNTFRQ_EL0.get());
     unsafe {
         CNTPCT_TO_NANOS_RATIO = Ratio::new(crate::time::NANOS_PER_SEC as u32, freq as u32);
         NANOS_TO_CNTPCT_RATIO = CNTPCT_TO_NANOS_RATIO.inverse();
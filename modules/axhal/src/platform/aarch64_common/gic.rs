@@ -1,6 +1,7 @@
 use crate::{irq::IrqHandler, mem::phys_to_virt};
-use arm_gic::gic_v2::{GicCpuInterface, GicDistributor};
-use arm_gic::{translate_irq, InterruptType};
+use arm_gic::{gic_v2, gic_v3};
+use arm_gic::{translate_irq, InterruptType, SPI_RANGE};
+use lazy_init::LazyInit;
 use memory_addr::PhysAddr;
 use spinlock::SpinNoIrq;
 
@@ -13,19 +14,124 @@ pub const TIMER_IRQ_NUM: usize = translate_irq(14, InterruptType::PPI).unwrap();
 /// The UART IRQ number.
 pub const UART_IRQ_NUM: usize = translate_irq(axconfig::UART_IRQ, InterruptType::SPI).unwrap();
 
+/// The IRQ number of the inter-processor interrupt.
+pub const IPI_IRQ_NUM: usize = translate_irq(1, InterruptType::SGI).unwrap();
+
+/// Device tree compatible strings of the ARMv8 PMU.
+const PMU_COMPATIBLE: &[&str] = &[
+    "arm,armv8-pmuv3",
+    "arm,cortex-a53-pmu",
+    "arm,cortex-a55-pmu",
+    "arm,cortex-a57-pmu",
+    "arm,cortex-a72-pmu",
+    "arm,cortex-a76-pmu",
+];
+
+/// The PPI of the PMU overflow interrupt recommended by the Arm Base System
+/// Architecture, used if the device tree does not describe the PMU.
+const DEFAULT_PMU_PPI: usize = 7;
+
 const GICD_BASE: PhysAddr = PhysAddr::from(axconfig::GICD_PADDR);
 const GICC_BASE: PhysAddr = PhysAddr::from(axconfig::GICC_PADDR);
+const GICR_BASE: PhysAddr = PhysAddr::from(axconfig::GICR_PADDR);
+
+/// Device tree compatible strings of GICv2.
+const GICV2_COMPATIBLE: &[&str] = &["arm,gic-400", "arm,cortex-a15-gic", "arm,cortex-a9-gic"];
+/// Device tree compatible strings of GICv3.
+const GICV3_COMPATIBLE: &[&str] = &["arm,gic-v3"];
+
+enum Gic {
+    V2 {
+        gicd: SpinNoIrq<gic_v2::GicDistributor>,
+        // per-CPU, no lock
+        gicc: gic_v2::GicCpuInterface,
+    },
+    V3 {
+        gicd: SpinNoIrq<gic_v3::GicDistributor>,
+        /// Base address of the redistributors of all CPUs.
+        gicr_base: usize,
+    },
+}
+
+static GIC: LazyInit<Gic> = LazyInit::new();
+
+/// Returns the GIC version and the bases of its GICD and GICC (GICv2) or
+/// GICR (GICv3), from the device tree or else from the platform
+/// configuration.
+fn gic_config() -> (usize, PhysAddr, PhysAddr) {
+    let gic = crate::dtb::get().and_then(|fdt| fdt.interrupt_controller());
+    let fdt_config = gic.and_then(|gic| {
+        let compatible = |list: &[&str]| list.iter().any(|c| gic.is_compatible(c));
+        let version = if compatible(GICV3_COMPATIBLE) {
+            3
+        } else if compatible(GICV2_COMPATIBLE) {
+            2
+        } else {
+            return None;
+        };
+        let mut reg = gic.reg();
+        Some((version, reg.next()?.addr.into(), reg.next()?.addr.into()))
+    });
+    fdt_config.unwrap_or(match axconfig::GIC_VERSION {
+        3 => (3, GICD_BASE, GICR_BASE),
+        _ => (2, GICD_BASE, GICC_BASE),
+    })
+}
 
-static GICD: SpinNoIrq<GicDistributor> =
-    SpinNoIrq::new(GicDistributor::new(phys_to_virt(GICD_BASE).as_mut_ptr()));
+/// Returns the IRQ number of the PMU overflow interrupt of the current CPU.
+///
+/// The device tree describes it as either a PPI, or an SPI for each CPU.
+pub fn pmu_irq_num() -> usize {
+    let fdt_irq = || {
+        let pmu = crate::dtb::get()?.find_compatible(PMU_COMPATIBLE).next()?;
+        // <type number flags> for each interrupt
+        let mut cells = pmu.interrupts();
+        let mut next_irq = move || {
+            let (ty, num, _flags) = (cells.next()?, cells.next()?, cells.next()?);
+            Some((ty, num))
+        };
+        let (ty, num) = next_irq()?;
+        if ty == 1 {
+            return translate_irq(num as _, InterruptType::PPI);
+        }
+        let (_, num) = (0..crate::cpu::this_cpu_id()).try_fold((ty, num), |_, _| next_irq())?;
+        translate_irq(num as _, InterruptType::SPI)
+    };
+    fdt_irq().unwrap_or(translate_irq(DEFAULT_PMU_PPI, InterruptType::PPI).unwrap())
+}
 
-// per-CPU, no lock
-static GICC: GicCpuInterface = GicCpuInterface::new(phys_to_virt(GICC_BASE).as_mut_ptr());
+/// Returns the redistributor of the current CPU.
+fn current_gicr(gicr_base: usize) -> gic_v3::GicRedistributor {
+    unsafe { gic_v3::GicRedistributor::current(gicr_base as *mut u8) }
+        .expect("no GICv3 redistributor for the current CPU")
+}
 
 /// Enables or disables the given IRQ.
 pub fn set_enable(irq_num: usize, enabled: bool) {
     trace!("GICD set enable: {} {}", irq_num, enabled);
-    GICD.lock().set_enable(irq_num as _, enabled);
+    match &*GIC {
+        Gic::V2 { gicd, .. } => gicd.lock().set_enable(irq_num as _, enabled),
+        // SGIs and PPIs are enabled in the redistributor of each CPU.
+        Gic::V3 { gicr_base, .. } if irq_num < SPI_RANGE.start => {
+            current_gicr(*gicr_base).set_enable(irq_num, enabled)
+        }
+        Gic::V3 { gicd, .. } => gicd.lock().set_enable(irq_num, enabled),
+    }
+}
+
+/// Sends the inter-processor interrupt to the given CPU.
+///
+/// The CPU ID is its `MPIDR_EL1` affinity (see `_start`), which is also taken
+/// as the CPU interface number for GICv2.
+#[cfg(feature = "smp")]
+pub fn send_ipi(cpu_id: usize) {
+    let sgi = IPI_IRQ_NUM;
+    match &*GIC {
+        Gic::V2 { gicd, .. } => gicd.lock().send_sgi(gic_v2::SgiTarget::Cpu(cpu_id), sgi),
+        Gic::V3 { .. } => {
+            gic_v3::GicCpuInterface::new().send_sgi(gic_v3::SgiTarget::Cpu(cpu_id as _), sgi)
+        }
+    }
 }
 
 /// Registers an IRQ handler for the given IRQ.
@@ -43,18 +149,54 @@ pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
 /// up in the IRQ handler table and calls the corresponding handler. If
 /// necessary, it also acknowledges the interrupt controller after handling.
 pub fn dispatch_irq(_unused: usize) {
-    GICC.handle_irq(|irq_num| crate::irq::dispatch_irq_common(irq_num as _));
+    let dispatch = |irq_num: u32| crate::irq::dispatch_irq_common(irq_num as _);
+    match &*GIC {
+        Gic::V2 { gicc, .. } => gicc.handle_irq(dispatch),
+        Gic::V3 { .. } => gic_v3::GicCpuInterface::new().handle_irq(dispatch),
+    }
+}
+
+/// Initializes the per-CPU parts of the GIC on the current CPU.
+fn init_percpu() {
+    match &*GIC {
+        Gic::V2 { gicc, .. } => gicc.init(),
+        Gic::V3 { gicr_base, .. } => {
+            current_gicr(*gicr_base).init();
+            gic_v3::GicCpuInterface::new().init();
+        }
+    }
 }
 
-/// Initializes GICD, GICC on the primary CPU.
+/// Initializes GICD, GICC (GICR and the CPU interface for GICv3) on the
+/// primary CPU.
 pub(crate) fn init_primary() {
-    info!("Initialize GICv2...");
-    GICD.lock().init();
-    GICC.init();
+    let (version, gicd_base, cpu_base) = gic_config();
+    info!(
+        "Initialize GICv{} at {:#x}, {:#x}...",
+        version, gicd_base, cpu_base
+    );
+    let gicd_ptr = phys_to_virt(gicd_base).as_mut_ptr();
+    let gic = if version == 3 {
+        let mut gicd = gic_v3::GicDistributor::new(gicd_ptr);
+        gicd.init();
+        Gic::V3 {
+            gicd: SpinNoIrq::new(gicd),
+            gicr_base: phys_to_virt(cpu_base).as_usize(),
+        }
+    } else {
+        let mut gicd = gic_v2::GicDistributor::new(gicd_ptr);
+        gicd.init();
+        Gic::V2 {
+            gicd: SpinNoIrq::new(gicd),
+            gicc: gic_v2::GicCpuInterface::new(phys_to_virt(cpu_base).as_mut_ptr()),
+        }
+    };
+    GIC.init_by(gic);
+    init_percpu();
 }
 
-/// Initializes GICC on secondary CPUs.
+/// Initializes GICC (GICR and the CPU interface for GICv3) on secondary CPUs.
 #[cfg(feature = "smp")]
 pub(crate) fn init_secondary() {
-    GICC.init();
+    init_percpu();
 }
@@ -12,3 +12,6 @@ pub mod gic;
 
 #[cfg(not(platform_family = "aarch64-bsta1000b"))]
 pub mod pl011;
+
+#[cfg(all(feature = "rtc", platform_family = "aarch64-qemu-virt"))]
+pub mod pl031;
@@ -0,0 +1,40 @@
+//! PL031 RTC.
+
+use arm_pl031::pl031::Pl031Rtc;
+use lazy_init::LazyInit;
+use memory_addr::PhysAddr;
+use spinlock::SpinNoIrq;
+
+use crate::mem::phys_to_virt;
+use crate::time::Rtc;
+
+const RTC_BASE: PhysAddr = PhysAddr::from(axconfig::RTC_PADDR);
+
+struct Pl031(SpinNoIrq<Pl031Rtc>);
+
+impl Rtc for Pl031 {
+    fn get_unix_timestamp(&self) -> u64 {
+        self.0.lock().get_unix_timestamp() as u64
+    }
+
+    fn set_unix_timestamp(&self, secs: u64) {
+        // The counter wraps in 2106.
+        self.0.lock().set_unix_timestamp(secs as u32);
+    }
+}
+
+static RTC: LazyInit<Pl031> = LazyInit::new();
+
+/// Initializes the RTC and sets the wall-clock time from it.
+///
+/// The base address is taken from the device tree if it describes a PL031.
+pub fn init() {
+    let base = crate::dtb::get()
+        .and_then(|fdt| fdt.find_compatible(&["arm,pl031"]).next())
+        .and_then(|node| node.reg().next())
+        .map_or(RTC_BASE, |reg| reg.addr.into());
+    let mut rtc = Pl031Rtc::new(phys_to_virt(base).as_mut_ptr());
+    rtc.init();
+    RTC.init_by(Pl031(SpinNoIrq::new(rtc)));
+    crate::time::register_rtc(&*RTC);
+}
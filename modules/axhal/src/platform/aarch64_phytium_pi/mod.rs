@@ -18,12 +18,9 @@ pub mod time {
 }
 
 pub mod misc {
-    pub fn terminate() -> ! {
-        info!("Shutting down...");
-        loop {
-            crate::arch::halt();
-        }
-    }
+    pub use crate::platform::aarch64_common::psci::{
+        system_off as terminate, system_reset as reboot,
+    };
 }
 
 extern "C" {
@@ -18,10 +18,13 @@ pub(crate) unsafe fn init_boot_page_table(
         MappingFlags::READ | MappingFlags::WRITE | MappingFlags::DEVICE,
         true,
     );
-    // 0x0000_4000_0000..0x0000_8000_0000, 1G block, normal memory
-    boot_pt_l1[1] = A64PTE::new_page(
-        PhysAddr::from(0x4000_0000),
-        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE,
-        true,
-    );
+    // 0x0000_4000_0000..0x0004_0000_0000, 1G blocks, normal memory
+    // (the RAM size is only known from the device tree, map up to 15G)
+    for (i, pte) in boot_pt_l1.iter_mut().enumerate().take(16).skip(1) {
+        *pte = A64PTE::new_page(
+            PhysAddr::from(i << 30),
+            MappingFlags::READ | MappingFlags::WRITE | MappingFlags::EXECUTE,
+            true,
+        );
+    }
 }
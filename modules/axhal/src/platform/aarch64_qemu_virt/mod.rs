@@ -17,7 +17,9 @@ pub mod time {
 }
 
 pub mod misc {
-    pub use crate::platform::aarch64_common::psci::system_off as terminate;
+    pub use crate::platform::aarch64_common::psci::{
+        system_off as terminate, system_reset as reboot,
+    };
 }
 
 extern "C" {
@@ -32,6 +34,7 @@ pub(crate) unsafe extern "C" fn rust_entry(cpu_id: usize, dtb: usize) {
     crate::arch::set_exception_vector_base(exception_vector_base as usize);
     crate::arch::write_page_table_root0(0.into()); // disable low address access
     crate::cpu::init_primary(cpu_id);
+    crate::dtb::init(dtb);
     super::aarch64_common::pl011::init_early();
     super::aarch64_common::generic_timer::init_early();
     rust_main(cpu_id, dtb);
@@ -53,6 +56,8 @@ pub fn platform_init() {
     super::aarch64_common::gic::init_primary();
     super::aarch64_common::generic_timer::init_percpu();
     super::aarch64_common::pl011::init();
+    #[cfg(feature = "rtc")]
+    super::aarch64_common::pl031::init();
 }
 
 /// Initializes the platform devices for secondary CPUs.
@@ -8,3 +8,16 @@ pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     let entry = virt_to_phys(VirtAddr::from(_start_secondary as usize));
     crate::platform::aarch64_common::psci::cpu_on(cpu_id, entry.as_usize(), stack_top.as_usize());
 }
+
+/// Whether CPUs can be stopped and started again.
+pub const CPU_HOTPLUG: bool = true;
+
+/// Powers off the current CPU. It can be started again by
+/// [`start_secondary_cpu`].
+pub fn stop_current_cpu() -> ! {
+    crate::platform::aarch64_common::psci::cpu_off();
+    warn!("It should be powered off!");
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -17,12 +17,38 @@ pub mod time {
 }
 
 pub mod misc {
+    use crate::mem::phys_to_virt;
+
+    const PM_BASE: usize = 0xFE10_0000;
+    const PM_RSTC: usize = 0x1c;
+    const PM_WDOG: usize = 0x24;
+    const PM_PASSWORD: u32 = 0x5a00_0000;
+    const PM_RSTC_WRCFG_CLR: u32 = 0xffff_ffcf;
+    const PM_RSTC_WRCFG_FULL_RESET: u32 = 0x20;
+
     pub fn terminate() -> ! {
         info!("Shutting down...");
         loop {
             crate::arch::halt();
         }
     }
+
+    /// Reboots the whole system by the watchdog of the power management block.
+    pub fn reboot() -> ! {
+        info!("Rebooting...");
+        let base = phys_to_virt(PM_BASE.into()).as_usize();
+        unsafe {
+            let rstc = (base + PM_RSTC) as *mut u32;
+            let wdog = (base + PM_WDOG) as *mut u32;
+            wdog.write_volatile(PM_PASSWORD | 10); // expires in about 150us
+            let val = rstc.read_volatile() & PM_RSTC_WRCFG_CLR;
+            rstc.write_volatile(PM_PASSWORD | val | PM_RSTC_WRCFG_FULL_RESET);
+        }
+        warn!("It should reboot!");
+        loop {
+            crate::arch::halt();
+        }
+    }
 }
 
 extern "C" {
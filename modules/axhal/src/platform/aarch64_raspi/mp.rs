@@ -47,3 +47,16 @@ pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     }
     aarch64_cpu::asm::sev();
 }
+
+/// CPUs released from the spin table cannot be stopped and started again.
+pub const CPU_HOTPLUG: bool = false;
+
+/// Parks the current CPU.
+///
+/// A CPU released from the spin table cannot be powered off, nor be started
+/// again by [`start_secondary_cpu`].
+pub fn stop_current_cpu() -> ! {
+    loop {
+        crate::arch::halt();
+    }
+}
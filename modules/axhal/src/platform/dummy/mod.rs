@@ -18,12 +18,25 @@ pub mod misc {
     pub fn terminate() -> ! {
         unimplemented!()
     }
+
+    /// Reboots the whole system, including all CPUs.
+    pub fn reboot() -> ! {
+        unimplemented!()
+    }
 }
 
 #[cfg(feature = "smp")]
 pub mod mp {
     /// Starts the given secondary CPU with its boot stack.
     pub fn start_secondary_cpu(cpu_id: usize, stack_top: crate::mem::PhysAddr) {}
+
+    /// Whether CPUs can be stopped and started again.
+    pub const CPU_HOTPLUG: bool = false;
+
+    /// Powers off the current CPU.
+    pub fn stop_current_cpu() -> ! {
+        unimplemented!()
+    }
 }
 
 pub mod mem {
@@ -63,9 +76,21 @@ pub mod irq {
     /// The timer IRQ number.
     pub const TIMER_IRQ_NUM: usize = 0;
 
+    /// The IRQ number of the inter-processor interrupt.
+    pub const IPI_IRQ_NUM: usize = 0;
+
+    /// Returns the IRQ number of the PMU overflow interrupt.
+    pub fn pmu_irq_num() -> usize {
+        0
+    }
+
     /// Enables or disables the given IRQ.
     pub fn set_enable(irq_num: usize, enabled: bool) {}
 
+    /// Sends the inter-processor interrupt to the given CPU.
+    #[cfg(feature = "smp")]
+    pub fn send_ipi(cpu_id: usize) {}
+
     /// Registers an IRQ handler for the given IRQ.
     pub fn register_handler(irq_num: usize, handler: crate::irq::IrqHandler) -> bool {
         false
@@ -8,11 +8,23 @@ static mut BOOT_STACK: [u8; TASK_STACK_SIZE] = [0; TASK_STACK_SIZE];
 #[link_section = ".data.boot_page_table"]
 static mut BOOT_PT_SV39: [u64; 512] = [0; 512];
 
-unsafe fn init_boot_page_table() {
+unsafe fn init_boot_page_table(dtb: usize) {
     // 0x8000_0000..0xc000_0000, VRWX_GAD, 1G block
     BOOT_PT_SV39[2] = (0x80000 << 10) | 0xef;
     // 0xffff_ffc0_8000_0000..0xffff_ffc0_c000_0000, VRWX_GAD, 1G block
     BOOT_PT_SV39[0x102] = (0x80000 << 10) | 0xef;
+    // QEMU places the device tree blob at the end of the RAM, which can be
+    // above the first 1G. Map the 1G blocks holding it, VRW_GAD.
+    let header = dtb as *const u32;
+    if dtb != 0 && u32::from_be(header.read_volatile()) == 0xd00d_feed {
+        let size = u32::from_be(header.add(1).read_volatile()) as usize;
+        for block in dtb >> 30..=(dtb + size.max(1) - 1) >> 30 {
+            let vpn2 = ((PHYS_VIRT_OFFSET + (block << 30)) >> 30) & 0x1ff;
+            if BOOT_PT_SV39[vpn2] == 0 {
+                BOOT_PT_SV39[vpn2] = ((block << 18) << 10) as u64 | 0xe7;
+            }
+        }
+    }
 }
 
 unsafe fn init_mmu() {
@@ -36,6 +48,7 @@ unsafe extern "C" fn _start() -> ! {
         li      t0, {boot_stack_size}
         add     sp, sp, t0              // setup boot stack
 
+        mv      a0, s1
         call    {init_boot_page_table}
         call    {init_mmu}              // setup boot page table and enabel MMU
 
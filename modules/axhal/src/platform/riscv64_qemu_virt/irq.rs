@@ -8,7 +8,6 @@ use riscv::register::sie;
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
 
 /// Supervisor software interrupt in `scause`
-#[allow(unused)]
 pub(super) const S_SOFT: usize = INTC_IRQ_BASE + 1;
 
 /// Supervisor timer interrupt in `scause`
@@ -17,18 +16,43 @@ pub(super) const S_TIMER: usize = INTC_IRQ_BASE + 5;
 /// Supervisor external interrupt in `scause`
 pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
 
+/// Local counter overflow interrupt in `scause` (the Sscofpmf extension)
+pub(super) const S_LCOF: usize = INTC_IRQ_BASE + 13;
+
 static TIMER_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
 
+static LCOF_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
+
+static SOFT_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
+
 /// The maximum number of IRQs.
 pub const MAX_IRQ_COUNT: usize = 1024;
 
 /// The timer IRQ number (supervisor timer interrupt in `scause`).
 pub const TIMER_IRQ_NUM: usize = S_TIMER;
 
+/// The IRQ number of the inter-processor interrupt (supervisor software
+/// interrupt in `scause`).
+pub const IPI_IRQ_NUM: usize = S_SOFT;
+
+/// Returns the IRQ number of the PMU overflow interrupt (local counter
+/// overflow interrupt in `scause`).
+pub fn pmu_irq_num() -> usize {
+    S_LCOF
+}
+
 macro_rules! with_cause {
-    ($cause: expr, @TIMER => $timer_op: expr, @EXT => $ext_op: expr $(,)?) => {
+    (
+        $cause: expr,
+        @SOFT => $soft_op: expr,
+        @TIMER => $timer_op: expr,
+        @LCOF => $lcof_op: expr,
+        @EXT => $ext_op: expr $(,)?
+    ) => {
         match $cause {
+            S_SOFT => $soft_op,
             S_TIMER => $timer_op,
+            S_LCOF => $lcof_op,
             S_EXT => $ext_op,
             _ => panic!("invalid trap cause: {:#x}", $cause),
         }
@@ -36,12 +60,28 @@ macro_rules! with_cause {
 }
 
 /// Enables or disables the given IRQ.
-pub fn set_enable(scause: usize, _enabled: bool) {
+pub fn set_enable(scause: usize, enabled: bool) {
     if scause == S_EXT {
         // TODO: set enable in PLIC
+    } else if scause == S_LCOF {
+        // `sie.LCOFIE`, not supported by the `riscv` crate
+        let bit = 1usize << (S_LCOF & !INTC_IRQ_BASE);
+        unsafe {
+            if enabled {
+                core::arch::asm!("csrs sie, {}", in(reg) bit);
+            } else {
+                core::arch::asm!("csrc sie, {}", in(reg) bit);
+            }
+        }
     }
 }
 
+/// Sends the inter-processor interrupt to the given hart.
+#[cfg(feature = "smp")]
+pub fn send_ipi(hartid: usize) {
+    sbi_rt::send_ipi(1, hartid);
+}
+
 /// Registers an IRQ handler for the given IRQ.
 ///
 /// It also enables the IRQ if the registration succeeds. It returns `false` if
@@ -49,12 +89,25 @@ pub fn set_enable(scause: usize, _enabled: bool) {
 pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
     with_cause!(
         scause,
+        @SOFT => if !SOFT_HANDLER.is_init() {
+            SOFT_HANDLER.init_by(handler);
+            true
+        } else {
+            false
+        },
         @TIMER => if !TIMER_HANDLER.is_init() {
             TIMER_HANDLER.init_by(handler);
             true
         } else {
             false
         },
+        @LCOF => if !LCOF_HANDLER.is_init() {
+            LCOF_HANDLER.init_by(handler);
+            set_enable(S_LCOF, true);
+            true
+        } else {
+            false
+        },
         @EXT => crate::irq::register_handler_common(scause & !INTC_IRQ_BASE, handler),
     )
 }
@@ -67,10 +120,21 @@ pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
 pub fn dispatch_irq(scause: usize) {
     with_cause!(
         scause,
+        @SOFT => {
+            trace!("IRQ: IPI");
+            // Clear `sip.SSIP`, which is set by the SBI on each IPI.
+            let bit = 1usize << (S_SOFT & !INTC_IRQ_BASE);
+            unsafe { core::arch::asm!("csrc sip, {}", in(reg) bit) };
+            SOFT_HANDLER();
+        },
         @TIMER => {
             trace!("IRQ: timer");
             TIMER_HANDLER();
         },
+        @LCOF => {
+            trace!("IRQ: counter overflow");
+            LCOF_HANDLER();
+        },
         @EXT => crate::irq::dispatch_irq_common(0), // TODO: get IRQ number from PLIC
     );
 }
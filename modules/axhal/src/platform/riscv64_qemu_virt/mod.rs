@@ -22,6 +22,8 @@ unsafe extern "C" fn rust_entry(cpu_id: usize, dtb: usize) {
     crate::mem::clear_bss();
     crate::cpu::init_primary(cpu_id);
     crate::arch::set_trap_vector_base(trap_vector_base as usize);
+    crate::dtb::init(dtb); // the blob is mapped by `init_boot_page_table`
+    self::time::init_early();
     rust_main(cpu_id, dtb);
 }
 
@@ -12,3 +12,15 @@ pub fn start_secondary_cpu(hartid: usize, stack_top: PhysAddr) {
     let entry = virt_to_phys(VirtAddr::from(_start_secondary as usize));
     sbi_rt::hart_start(hartid, entry.as_usize(), stack_top.as_usize());
 }
+
+/// Whether CPUs can be stopped and started again.
+pub const CPU_HOTPLUG: bool = true;
+
+/// Stops the current hart. It can be started again by [`start_secondary_cpu`].
+pub fn stop_current_cpu() -> ! {
+    sbi_rt::hart_stop();
+    warn!("It should be stopped!");
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -1,6 +1,8 @@
+use ratio::Ratio;
 use riscv::register::time;
 
-const NANOS_PER_TICK: u64 = crate::time::NANOS_PER_SEC / axconfig::TIMER_FREQUENCY as u64;
+static mut TICKS_TO_NANOS_RATIO: Ratio = Ratio::zero();
+static mut NANOS_TO_TICKS_RATIO: Ratio = Ratio::zero();
 
 /// Returns the current clock time in hardware ticks.
 #[inline]
@@ -10,14 +12,14 @@ pub fn current_ticks() -> u64 {
 
 /// Converts hardware ticks to nanoseconds.
 #[inline]
-pub const fn ticks_to_nanos(ticks: u64) -> u64 {
-    ticks * NANOS_PER_TICK
+pub fn ticks_to_nanos(ticks: u64) -> u64 {
+    unsafe { TICKS_TO_NANOS_RATIO.mul_trunc(ticks) }
 }
 
 /// Converts nanoseconds to hardware ticks.
 #[inline]
-pub const fn nanos_to_ticks(nanos: u64) -> u64 {
-    nanos / NANOS_PER_TICK
+pub fn nanos_to_ticks(nanos: u64) -> u64 {
+    unsafe { NANOS_TO_TICKS_RATIO.mul_trunc(nanos) }
 }
 
 /// Set a one-shot timer.
@@ -28,6 +30,19 @@ pub fn set_oneshot_timer(deadline_ns: u64) {
     sbi_rt::set_timer(nanos_to_ticks(deadline_ns));
 }
 
+/// Early stage initialization: stores the timer frequency.
+///
+/// The `timebase-frequency` of the device tree overrides the one of the
+/// platform configuration.
+pub(super) fn init_early() {
+    let fdt_freq = crate::dtb::get().and_then(|fdt| fdt.timer_frequency());
+    let freq = fdt_freq.unwrap_or(axconfig::TIMER_FREQUENCY as u64);
+    unsafe {
+        TICKS_TO_NANOS_RATIO = Ratio::new(crate::time::NANOS_PER_SEC as u32, freq as u32);
+        NANOS_TO_TICKS_RATIO = TICKS_TO_NANOS_RATIO.inverse();
+    }
+}
+
 pub(super) fn init_percpu() {
     #[cfg(feature = "irq")]
     sbi_rt::set_timer(0);
@@ -3,7 +3,7 @@
 use lazy_init::LazyInit;
 use memory_addr::PhysAddr;
 use spinlock::SpinNoIrq;
-use x2apic::ioapic::IoApic;
+use x2apic::ioapic::{IoApic, IrqFlags, IrqMode, RedirectionTableEntry};
 use x2apic::lapic::{xapic_base, LocalApic, LocalApicBuilder};
 use x86_64::instructions::port::Port;
 
@@ -11,9 +11,15 @@ use self::vectors::*;
 use crate::mem::phys_to_virt;
 
 pub(super) mod vectors {
+    /// Vector of IO APIC pin 0, the other pins follow.
+    pub const IO_APIC_VECTOR_BASE: u8 = 0x20;
+    /// Vectors from here up to the local APIC vectors are allocated for MSIs.
+    pub const MSI_VECTOR_BASE: u8 = 0x40;
     pub const APIC_TIMER_VECTOR: u8 = 0xf0;
     pub const APIC_SPURIOUS_VECTOR: u8 = 0xf1;
     pub const APIC_ERROR_VECTOR: u8 = 0xf2;
+    pub const APIC_PMU_VECTOR: u8 = 0xf3;
+    pub const APIC_IPI_VECTOR: u8 = 0xf4;
 }
 
 /// The maximum number of IRQs.
@@ -22,6 +28,14 @@ pub const MAX_IRQ_COUNT: usize = 256;
 /// The timer IRQ number.
 pub const TIMER_IRQ_NUM: usize = APIC_TIMER_VECTOR as usize;
 
+/// The IRQ number of the inter-processor interrupt.
+pub const IPI_IRQ_NUM: usize = APIC_IPI_VECTOR as usize;
+
+/// Returns the IRQ number of the PMU overflow interrupt.
+pub fn pmu_irq_num() -> usize {
+    APIC_PMU_VECTOR as usize
+}
+
 const IO_APIC_BASE: PhysAddr = PhysAddr::from(0xFEC0_0000);
 
 static mut LOCAL_APIC: Option<LocalApic> = None;
@@ -31,18 +45,67 @@ static IO_APIC: LazyInit<SpinNoIrq<IoApic>> = LazyInit::new();
 /// Enables or disables the given IRQ.
 #[cfg(feature = "irq")]
 pub fn set_enable(vector: usize, enabled: bool) {
-    // should not affect LAPIC interrupts
-    if vector < APIC_TIMER_VECTOR as _ {
+    // should not affect LAPIC interrupts, nor MSIs which are masked in the
+    // MSI capability of the device
+    if let Some(pin) = io_apic_pin(vector) {
         unsafe {
             if enabled {
-                IO_APIC.lock().enable_irq(vector as u8);
+                IO_APIC.lock().enable_irq(pin);
             } else {
-                IO_APIC.lock().disable_irq(vector as u8);
+                IO_APIC.lock().disable_irq(pin);
             }
         }
+    } else if vector == APIC_PMU_VECTOR as _ {
+        set_pmu_lvt_enable(enabled);
     }
 }
 
+/// Configures IO APIC pin `gsi` for the INTx interrupt of a PCI function:
+/// level-triggered, active-low, and delivered to the BSP.
+///
+/// The pin stays masked until a handler is registered for the returned IRQ
+/// number. Returns `None` if the IO APIC has no such pin.
+#[cfg(feature = "irq")]
+pub fn route_pci_intx(gsi: usize) -> Option<usize> {
+    let vector = IO_APIC_VECTOR_BASE as usize + gsi;
+    let pin = io_apic_pin(vector)?;
+    let flags = IrqFlags::LEVEL_TRIGGERED | IrqFlags::LOW_ACTIVE;
+    unsafe {
+        IO_APIC
+            .lock()
+            .set_table_entry(pin, redirection_entry(pin, flags))
+    };
+    Some(vector)
+}
+
+/// Redirection of IO APIC pin `pin`, masked, to its vector on the BSP (APIC
+/// ID 0).
+fn redirection_entry(pin: u8, flags: IrqFlags) -> RedirectionTableEntry {
+    let mut entry = RedirectionTableEntry::default();
+    entry.set_mode(IrqMode::Fixed);
+    entry.set_flags(flags | IrqFlags::MASKED);
+    entry.set_vector(IO_APIC_VECTOR_BASE + pin);
+    entry.set_dest(0);
+    entry
+}
+
+/// The IO APIC pin delivered at `vector`, if any.
+fn io_apic_pin(vector: usize) -> Option<u8> {
+    let pin = vector.checked_sub(IO_APIC_VECTOR_BASE as usize)?;
+    if vector >= MSI_VECTOR_BASE as usize
+        || pin > unsafe { IO_APIC.lock().max_table_entry() } as usize
+    {
+        return None;
+    }
+    Some(pin as u8)
+}
+
+/// Sends the inter-processor interrupt to the CPU with the given APIC ID.
+#[cfg(all(feature = "irq", feature = "smp"))]
+pub fn send_ipi(cpu_id: usize) {
+    unsafe { local_apic().send_ipi(APIC_IPI_VECTOR, raw_apic_id(cpu_id as u8)) };
+}
+
 /// Registers an IRQ handler for the given IRQ.
 ///
 /// It also enables the IRQ if the registration succeeds. It returns `false` if
@@ -60,9 +123,32 @@ pub fn register_handler(vector: usize, handler: crate::irq::IrqHandler) -> bool
 #[cfg(feature = "irq")]
 pub fn dispatch_irq(vector: usize) {
     crate::irq::dispatch_irq_common(vector);
+    if vector == APIC_PMU_VECTOR as _ {
+        // The CPU masks the performance counter LVT entry on each interrupt.
+        set_pmu_lvt_enable(true);
+    }
     unsafe { local_apic().end_of_interrupt() };
 }
 
+/// Masks or unmasks the performance counter interrupt of the current CPU, in
+/// the LVT entry that is not managed by the `x2apic` crate.
+fn set_pmu_lvt_enable(enabled: bool) {
+    const X2APIC_LVT_PMI_MSR: u32 = 0x834;
+    const XAPIC_LVT_PMI_OFFSET: usize = 0x340;
+    const LVT_MASKED: u32 = 1 << 16;
+
+    let value = APIC_PMU_VECTOR as u32 | if enabled { 0 } else { LVT_MASKED };
+    unsafe {
+        if IS_X2APIC {
+            x86::msr::wrmsr(X2APIC_LVT_PMI_MSR, value as u64);
+        } else {
+            let base = phys_to_virt(PhysAddr::from(xapic_base() as usize));
+            let lvt = (base.as_usize() + XAPIC_LVT_PMI_OFFSET) as *mut u32;
+            lvt.write_volatile(value);
+        }
+    }
+}
+
 pub(super) fn local_apic<'a>() -> &'a mut LocalApic {
     // It's safe as LAPIC is per-cpu.
     unsafe { LOCAL_APIC.as_mut().unwrap() }
@@ -114,7 +200,12 @@ pub(super) fn init_primary() {
     }
 
     info!("Initialize IO APIC...");
-    let io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
+    let mut io_apic = unsafe { IoApic::new(phys_to_virt(IO_APIC_BASE).as_usize() as u64) };
+    // Pins are edge-triggered and active-high, as ISA interrupts, unless
+    // routed to a PCI function.
+    for pin in 0..=unsafe { io_apic.max_table_entry() } {
+        unsafe { io_apic.set_table_entry(pin, redirection_entry(pin, IrqFlags::empty())) };
+    }
     IO_APIC.init_by(SpinNoIrq::new(io_apic));
 }
 
@@ -1,5 +1,7 @@
 use x86_64::instructions::port::PortWriteOnly;
 
+use crate::time::{busy_wait, Duration};
+
 /// Shutdown the whole system (in QEMU), including all CPUs.
 ///
 /// See <https://wiki.osdev.org/Shutdown> for more information.
@@ -25,3 +27,21 @@ pub fn terminate() -> ! {
         crate::arch::halt();
     }
 }
+
+/// Reboots the whole system, including all CPUs.
+///
+/// It writes the reset control register (port `0xCF9`) first, which is the
+/// ACPI reset register of most PC chipsets including the QEMU Q35 machine, and
+/// then falls back to pulsing the reset line by the keyboard controller.
+pub fn reboot() -> ! {
+    info!("Rebooting...");
+    unsafe {
+        PortWriteOnly::new(0xcf9).write(0x06u8); // full reset
+        busy_wait(Duration::from_millis(50));
+        PortWriteOnly::new(0x64).write(0xfeu8);
+    }
+    warn!("It should reboot!");
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -3,6 +3,9 @@ mod boot;
 mod dtables;
 mod uart16550;
 
+#[cfg(feature = "rtc")]
+mod rtc;
+
 pub mod mem;
 pub mod misc;
 pub mod time;
@@ -58,6 +61,8 @@ unsafe extern "C" fn rust_entry_secondary(magic: usize) {
 pub fn platform_init() {
     self::apic::init_primary();
     self::time::init_primary();
+    #[cfg(feature = "rtc")]
+    self::rtc::init();
 }
 
 /// Initializes the platform devices for secondary CPUs.
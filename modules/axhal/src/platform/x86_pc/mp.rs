@@ -43,3 +43,14 @@ pub fn start_secondary_cpu(apic_id: usize, stack_top: PhysAddr) {
     busy_wait(Duration::from_micros(200)); // 200us
     unsafe { lapic.send_sipi(START_PAGE_IDX, apic_id) };
 }
+
+/// Whether CPUs can be stopped and started again.
+pub const CPU_HOTPLUG: bool = true;
+
+/// Halts the current CPU with interrupts disabled. It can be started again by
+/// [`start_secondary_cpu`], as the INIT IPI resets the CPU.
+pub fn stop_current_cpu() -> ! {
+    loop {
+        crate::arch::halt();
+    }
+}
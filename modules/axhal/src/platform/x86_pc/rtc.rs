@@ -0,0 +1,171 @@
+//! CMOS real-time clock (MC146818 compatible).
+
+use spinlock::SpinNoIrq;
+use x86_64::instructions::port::{Port, PortWriteOnly};
+
+use crate::time::{DateTime, Rtc};
+
+const CMOS_SECOND: u8 = 0x00;
+const CMOS_MINUTE: u8 = 0x02;
+const CMOS_HOUR: u8 = 0x04;
+const CMOS_DAY: u8 = 0x07;
+const CMOS_MONTH: u8 = 0x08;
+const CMOS_YEAR: u8 = 0x09;
+const CMOS_STATUS_A: u8 = 0x0A;
+const CMOS_STATUS_B: u8 = 0x0B;
+/// Century register, at the location given by the ACPI FADT of most PCs.
+const CMOS_CENTURY: u8 = 0x32;
+
+/// Set while the RTC updates its registers.
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+/// Stops the updates while the registers are written.
+const STATUS_B_SET: u8 = 1 << 7;
+/// Values are binary instead of BCD.
+const STATUS_B_BINARY: u8 = 1 << 2;
+/// Hours are in 24-hour format instead of 12-hour format.
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+/// Set in the hour register for PM hours in the 12-hour format.
+const HOUR_PM: u8 = 1 << 7;
+
+/// Disables NMIs while a register is selected.
+const NMI_DISABLE: u8 = 1 << 7;
+
+struct Cmos {
+    index: PortWriteOnly<u8>,
+    data: Port<u8>,
+}
+
+impl Cmos {
+    const fn new() -> Self {
+        Self {
+            index: PortWriteOnly::new(0x70),
+            data: Port::new(0x71),
+        }
+    }
+
+    fn read(&mut self, reg: u8) -> u8 {
+        unsafe {
+            self.index.write(NMI_DISABLE | reg);
+            self.data.read()
+        }
+    }
+
+    fn write(&mut self, reg: u8, value: u8) {
+        unsafe {
+            self.index.write(NMI_DISABLE | reg);
+            self.data.write(value);
+        }
+    }
+
+    /// Reads the date and time registers, in their raw format.
+    fn read_raw(&mut self) -> [u8; 7] {
+        while self.read(CMOS_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0 {
+            core::hint::spin_loop();
+        }
+        [
+            CMOS_SECOND,
+            CMOS_MINUTE,
+            CMOS_HOUR,
+            CMOS_DAY,
+            CMOS_MONTH,
+            CMOS_YEAR,
+            CMOS_CENTURY,
+        ]
+        .map(|reg| self.read(reg))
+    }
+
+    fn read_date_time(&mut self) -> DateTime {
+        // Read until two reads agree, so that no update happened in between.
+        let mut raw = self.read_raw();
+        loop {
+            let again = self.read_raw();
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+        let [second, minute, hour, day, month, year, century] = raw;
+
+        let status_b = self.read(CMOS_STATUS_B);
+        let decode = |v: u8| {
+            if status_b & STATUS_B_BINARY != 0 {
+                v
+            } else {
+                (v >> 4) * 10 + (v & 0xf)
+            }
+        };
+        let mut hour24 = decode(hour & !HOUR_PM);
+        if status_b & STATUS_B_24_HOUR == 0 {
+            hour24 %= 12;
+            if hour & HOUR_PM != 0 {
+                hour24 += 12;
+            }
+        }
+        let century = match decode(century) {
+            c @ 19..=99 => c as u32,
+            _ => 20,
+        };
+        DateTime {
+            year: century * 100 + decode(year) as u32,
+            month: decode(month),
+            day: decode(day),
+            hour: hour24,
+            minute: decode(minute),
+            second: decode(second),
+        }
+    }
+
+    fn write_date_time(&mut self, dt: &DateTime) {
+        let status_b = self.read(CMOS_STATUS_B);
+        let encode = |v: u8| {
+            if status_b & STATUS_B_BINARY != 0 {
+                v
+            } else {
+                ((v / 10) << 4) | (v % 10)
+            }
+        };
+        let hour = if status_b & STATUS_B_24_HOUR != 0 {
+            encode(dt.hour)
+        } else {
+            let hour12 = match dt.hour % 12 {
+                0 => 12,
+                h => h,
+            };
+            encode(hour12) | if dt.hour >= 12 { HOUR_PM } else { 0 }
+        };
+
+        self.write(CMOS_STATUS_B, status_b | STATUS_B_SET);
+        self.write(CMOS_SECOND, encode(dt.second));
+        self.write(CMOS_MINUTE, encode(dt.minute));
+        self.write(CMOS_HOUR, hour);
+        self.write(CMOS_DAY, encode(dt.day));
+        self.write(CMOS_MONTH, encode(dt.month));
+        self.write(CMOS_YEAR, encode((dt.year % 100) as u8));
+        self.write(CMOS_CENTURY, encode((dt.year / 100) as u8));
+        self.write(CMOS_STATUS_B, status_b & !STATUS_B_SET);
+    }
+}
+
+struct CmosRtc(SpinNoIrq<Cmos>);
+
+impl Rtc for CmosRtc {
+    fn get_unix_timestamp(&self) -> u64 {
+        let dt = self.0.lock().read_date_time();
+        dt.to_unix_timestamp().unwrap_or_else(|| {
+            warn!("invalid RTC date {:?}", dt);
+            0
+        })
+    }
+
+    fn set_unix_timestamp(&self, secs: u64) {
+        let dt = DateTime::from_unix_timestamp(secs);
+        self.0.lock().write_date_time(&dt);
+    }
+}
+
+static RTC: CmosRtc = CmosRtc(SpinNoIrq::new(Cmos::new()));
+
+/// Sets the wall-clock time from the CMOS RTC.
+pub(super) fn init() {
+    crate::time::register_rtc(&RTC);
+}
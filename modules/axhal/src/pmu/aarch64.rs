@@ -0,0 +1,194 @@
+//! ARMv8 PMU (PMUv3).
+
+use core::arch::asm;
+
+use super::{PmuError, PmuEvent, PmuResult};
+
+/// PMCR_EL0: enable all counters.
+const PMCR_E: u64 = 1 << 0;
+/// PMCR_EL0: reset all event counters.
+const PMCR_P: u64 = 1 << 1;
+/// PMCR_EL0: reset the cycle counter.
+const PMCR_C: u64 = 1 << 2;
+/// PMCR_EL0: the cycle counter overflows at 64 bits.
+const PMCR_LC: u64 = 1 << 6;
+/// PMCR_EL0: the number of event counters.
+const PMCR_N_SHIFT: u64 = 11;
+const PMCR_N_MASK: u64 = 0x1f;
+
+/// The bit of the cycle counter in the enable, interrupt and overflow
+/// registers.
+const CYCLE_COUNTER_BIT: u64 = 1 << 31;
+
+// Common architectural and microarchitectural events.
+const SW_INCR: u64 = 0x00;
+const INST_RETIRED: u64 = 0x08;
+const BR_MIS_PRED: u64 = 0x10;
+const CPU_CYCLES: u64 = 0x11;
+const CHAIN: u64 = 0x1e;
+const BR_RETIRED: u64 = 0x21;
+const LL_CACHE_RD: u64 = 0x36;
+const LL_CACHE_MISS_RD: u64 = 0x37;
+
+macro_rules! read_sysreg {
+    ($reg:literal) => {{
+        let value: u64;
+        unsafe { asm!(concat!("mrs {}, ", $reg), out(reg) value) };
+        value
+    }};
+}
+
+macro_rules! write_sysreg {
+    ($reg:literal, $value:expr) => {
+        unsafe { asm!(concat!("msr ", $reg, ", {}"), in(reg) $value as u64) }
+    };
+}
+
+fn has_pmu() -> bool {
+    // ID_AA64DFR0_EL1.PMUVer: 0 = not implemented, 0xf = IMPLEMENTATION DEFINED
+    let pmuver = (read_sysreg!("id_aa64dfr0_el1") >> 8) & 0xf;
+    pmuver != 0 && pmuver != 0xf
+}
+
+/// Returns the number of event counters implemented.
+fn num_event_counters() -> usize {
+    if !has_pmu() {
+        return 0;
+    }
+    ((read_sysreg!("pmcr_el0") >> PMCR_N_SHIFT) & PMCR_N_MASK) as usize
+}
+
+/// Returns the even-odd pair of event counters chained to count retired
+/// instructions, or `None` if there are less than two event counters.
+fn inst_counters() -> Option<usize> {
+    Some(num_event_counters().checked_sub(2)? & !1)
+}
+
+/// Maps a general-purpose counter to an event counter, skipping the pair for
+/// retired instructions.
+fn hw_counter(idx: usize) -> usize {
+    match inst_counters() {
+        Some(inst) if idx >= inst => idx + 2,
+        _ => idx,
+    }
+}
+
+/// Runs `f` with the event counter `hw_idx` selected by PMSELR_EL0, so that it
+/// can be accessed by PMXEVTYPER_EL0 and PMXEVCNTR_EL0.
+fn with_selected<T>(hw_idx: usize, f: impl FnOnce() -> T) -> T {
+    // PMSELR_EL0 is shared with the overflow interrupt handler.
+    let _guard = kernel_guard::IrqSave::new();
+    write_sysreg!("pmselr_el0", hw_idx);
+    unsafe { asm!("isb") };
+    f()
+}
+
+fn event_type(event: PmuEvent) -> PmuResult<u64> {
+    Ok(match event {
+        PmuEvent::CpuCycles => CPU_CYCLES,
+        PmuEvent::Instructions => INST_RETIRED,
+        PmuEvent::CacheReferences => LL_CACHE_RD,
+        PmuEvent::CacheMisses => LL_CACHE_MISS_RD,
+        PmuEvent::BranchInstructions => BR_RETIRED,
+        PmuEvent::BranchMisses => BR_MIS_PRED,
+        PmuEvent::Raw(ty) if ty <= 0xffff && ty != CHAIN && ty != SW_INCR => ty,
+        PmuEvent::Raw(_) => return Err(PmuError::EventNotSupported),
+    })
+}
+
+pub fn num_counters() -> usize {
+    num_event_counters().saturating_sub(2)
+}
+
+pub fn read_cycles() -> PmuResult<u64> {
+    if !has_pmu() {
+        return Err(PmuError::NotSupported);
+    }
+    Ok(read_sysreg!("pmccntr_el0"))
+}
+
+pub fn read_instructions() -> PmuResult<u64> {
+    let inst = inst_counters().ok_or(PmuError::NotSupported)?;
+    let read = |hw_idx| with_selected(hw_idx, || read_sysreg!("pmxevcntr_el0"));
+    // Read the high half again in case the low half wrapped in between.
+    loop {
+        let high = read(inst + 1);
+        let low = read(inst);
+        if read(inst + 1) == high {
+            return Ok(high << 32 | low);
+        }
+    }
+}
+
+/// Event counters are 32-bit.
+pub fn max_period(_idx: usize) -> u64 {
+    u32::MAX as u64
+}
+
+pub fn start_counter(idx: usize, event: PmuEvent, period: u64) -> PmuResult {
+    let ty = event_type(event)?;
+    let hw_idx = hw_counter(idx);
+    let bit = 1u64 << hw_idx;
+    with_selected(hw_idx, || {
+        // Count at EL0 and EL1.
+        write_sysreg!("pmxevtyper_el0", ty);
+        write_sysreg!("pmxevcntr_el0", (period as u32).wrapping_neg());
+    });
+    write_sysreg!("pmovsclr_el0", bit);
+    if period != 0 {
+        write_sysreg!("pmintenset_el1", bit);
+    }
+    write_sysreg!("pmcntenset_el0", bit);
+    Ok(())
+}
+
+pub fn stop_counter(idx: usize) {
+    let bit = 1u64 << hw_counter(idx);
+    write_sysreg!("pmcntenclr_el0", bit);
+    write_sysreg!("pmintenclr_el1", bit);
+    write_sysreg!("pmovsclr_el0", bit);
+}
+
+pub fn read_counter(idx: usize) -> u64 {
+    with_selected(hw_counter(idx), || read_sysreg!("pmxevcntr_el0"))
+}
+
+pub fn take_overflow() -> u64 {
+    if !has_pmu() {
+        return 0;
+    }
+    let status = read_sysreg!("pmovsclr_el0");
+    write_sysreg!("pmovsclr_el0", status);
+    // Map the event counters back to the general-purpose counters.
+    let status = status & !CYCLE_COUNTER_BIT;
+    match inst_counters() {
+        Some(inst) => status & ((1 << inst) - 1) | (status >> (inst + 2)) << inst,
+        None => status,
+    }
+}
+
+pub fn restart_period(idx: usize, period: u64) {
+    with_selected(hw_counter(idx), || {
+        write_sysreg!("pmxevcntr_el0", (period as u32).wrapping_neg())
+    });
+}
+
+pub fn init_percpu() {
+    if !has_pmu() {
+        return;
+    }
+    write_sysreg!("pmcntenclr_el0", u32::MAX);
+    write_sysreg!("pmintenclr_el1", u32::MAX);
+    write_sysreg!("pmovsclr_el0", u32::MAX);
+    // Count cycles at EL0 and EL1.
+    write_sysreg!("pmccfiltr_el0", 0);
+    write_sysreg!("pmcr_el0", PMCR_E | PMCR_P | PMCR_C | PMCR_LC);
+
+    let mut enabled = CYCLE_COUNTER_BIT;
+    if let Some(inst) = inst_counters() {
+        with_selected(inst, || write_sysreg!("pmxevtyper_el0", INST_RETIRED));
+        with_selected(inst + 1, || write_sysreg!("pmxevtyper_el0", CHAIN));
+        enabled |= 0b11 << inst;
+    }
+    write_sysreg!("pmcntenset_el0", enabled);
+}
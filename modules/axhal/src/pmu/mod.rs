@@ -0,0 +1,167 @@
+//! Hardware performance counters of the current CPU.
+//!
+//! Each CPU has its own performance monitoring unit (PMU), so all functions in
+//! this module act on the PMU of the calling CPU. Callers that must stay on
+//! one CPU across several calls should disable preemption.
+//!
+//! The cycle and retired-instruction counters keep running since boot, and are
+//! read by [`read_cycles`] and [`read_instructions`]. In addition, there are
+//! [`num_counters`] general-purpose counters, which are programmed by
+//! [`start_counter`] to count a [`PmuEvent`]. A counter started with a
+//! sampling period raises the PMU overflow interrupt ([`irq_num`]) every
+//! `period` events. The handler of the interrupt must call
+//! [`handle_overflow`], which acknowledges the overflow and starts the next
+//! period.
+//!
+//! Supported PMUs:
+//!
+//! - AArch64: the ARMv8 PMU (PMUv3). A pair of event counters is chained to
+//!   count retired instructions, and the others are the general-purpose
+//!   counters.
+//! - RISC-V: the `cycle`, `instret` and `hpmcounter` CSRs, programmed through
+//!   the SBI PMU extension. Overflow interrupts require the Sscofpmf extension.
+//! - x86_64: the architectural performance monitoring of Intel CPUs (version 2
+//!   or later), with the fixed-function counters for cycles and instructions.
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "aarch64")] {
+        mod aarch64;
+        use aarch64 as arch;
+    } else if #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))] {
+        mod riscv;
+        use riscv as arch;
+    } else if #[cfg(target_arch = "x86_64")] {
+        mod x86_64;
+        use self::x86_64 as arch;
+    }
+}
+
+/// The maximum number of general-purpose counters supported.
+const MAX_COUNTERS: usize = 32;
+
+/// Sampling periods of the general-purpose counters, 0 if not sampling.
+#[percpu::def_percpu]
+static PERIODS: [u64; MAX_COUNTERS] = [0; MAX_COUNTERS];
+
+/// Events that can be counted by the general-purpose counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmuEvent {
+    /// CPU cycles.
+    CpuCycles,
+    /// Retired instructions.
+    Instructions,
+    /// Accesses to the last level (or a shared) cache.
+    CacheReferences,
+    /// Misses of the last level (or a shared) cache.
+    CacheMisses,
+    /// Retired branch instructions.
+    BranchInstructions,
+    /// Mispredicted branch instructions.
+    BranchMisses,
+    /// A raw event number of the PMU: the event type of ARMv8, the event
+    /// data of the SBI raw event on RISC-V, or the event select and unit mask
+    /// (bits 15:0 of `IA32_PERFEVTSELx`) on x86.
+    Raw(u64),
+}
+
+/// Errors of the PMU operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmuError {
+    /// The CPU has no usable PMU, or the operation is not supported by it.
+    NotSupported,
+    /// The counter index is out of range.
+    InvalidCounter,
+    /// The event cannot be counted by the counter.
+    EventNotSupported,
+    /// The sampling period is zero or too large for the counter.
+    InvalidPeriod,
+}
+
+/// A [`Result`] type with [`PmuError`] as the error type.
+pub type PmuResult<T = ()> = Result<T, PmuError>;
+
+/// Returns the number of general-purpose counters, or 0 if the CPU has no
+/// usable PMU.
+pub fn num_counters() -> usize {
+    arch::num_counters().min(MAX_COUNTERS)
+}
+
+/// Reads the cycle counter.
+pub fn read_cycles() -> PmuResult<u64> {
+    arch::read_cycles()
+}
+
+/// Reads the retired-instruction counter.
+pub fn read_instructions() -> PmuResult<u64> {
+    arch::read_instructions()
+}
+
+/// Programs the general-purpose counter `idx` to count `event` from zero.
+///
+/// If `period` is given, the counter raises the overflow interrupt after
+/// every `period` events instead (see [`handle_overflow`]). The maximum period
+/// depends on the width of the counter, and is at least `2^31`.
+pub fn start_counter(idx: usize, event: PmuEvent, period: Option<u64>) -> PmuResult {
+    if idx >= num_counters() {
+        return Err(PmuError::InvalidCounter);
+    }
+    let period = match period {
+        Some(p) if p == 0 || p > arch::max_period(idx) => return Err(PmuError::InvalidPeriod),
+        Some(p) => p,
+        None => 0,
+    };
+    stop_counter(idx)?;
+    arch::start_counter(idx, event, period)?;
+    PERIODS.with_current(|periods| periods[idx] = period);
+    Ok(())
+}
+
+/// Stops the general-purpose counter `idx`.
+pub fn stop_counter(idx: usize) -> PmuResult {
+    if idx >= num_counters() {
+        return Err(PmuError::InvalidCounter);
+    }
+    PERIODS.with_current(|periods| periods[idx] = 0);
+    arch::stop_counter(idx);
+    Ok(())
+}
+
+/// Reads the general-purpose counter `idx`.
+///
+/// For a sampling counter, the value is the number of events counted in the
+/// current period plus an architecture-specific offset.
+pub fn read_counter(idx: usize) -> PmuResult<u64> {
+    if idx >= num_counters() {
+        return Err(PmuError::InvalidCounter);
+    }
+    Ok(arch::read_counter(idx))
+}
+
+/// Handles the PMU overflow interrupt on the current CPU.
+///
+/// It acknowledges the overflows, and restarts the period of each sampling
+/// counter that has overflowed. Returns the bitmap of the general-purpose
+/// counters that have overflowed.
+pub fn handle_overflow() -> u64 {
+    let overflowed = arch::take_overflow() & ((1u64 << num_counters()) - 1);
+    PERIODS.with_current(|periods| {
+        for (idx, &period) in periods.iter().enumerate() {
+            if overflowed & (1 << idx) != 0 && period != 0 {
+                arch::restart_period(idx, period);
+            }
+        }
+    });
+    overflowed
+}
+
+/// Returns the IRQ number of the PMU overflow interrupt of the current CPU.
+#[cfg(feature = "irq")]
+pub fn irq_num() -> usize {
+    crate::platform::irq::pmu_irq_num()
+}
+
+/// Initializes the PMU of the current CPU, and starts the cycle and
+/// retired-instruction counters.
+pub(crate) fn init_percpu() {
+    arch::init_percpu();
+}
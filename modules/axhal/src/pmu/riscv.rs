@@ -0,0 +1,236 @@
+//! RISC-V performance counters, programmed through the SBI PMU extension.
+
+use core::arch::asm;
+
+use lazy_init::LazyInit;
+
+use super::{PmuError, PmuEvent, PmuResult};
+
+const EID_BASE: usize = 0x10;
+const FID_PROBE_EXTENSION: usize = 3;
+
+const EID_PMU: usize = 0x504d55;
+const FID_NUM_COUNTERS: usize = 0;
+const FID_COUNTER_GET_INFO: usize = 1;
+const FID_COUNTER_CONFIG_MATCHING: usize = 2;
+const FID_COUNTER_START: usize = 3;
+const FID_COUNTER_STOP: usize = 4;
+
+const START_SET_INIT_VALUE: usize = 1 << 0;
+const STOP_RESET: usize = 1 << 0;
+
+/// Event index of the SBI hardware raw events (type 2), whose event data is
+/// passed separately.
+const HW_RAW_EVENT: usize = 2 << 16;
+
+/// The first and last `hpmcounter` CSRs.
+const CSR_HPMCOUNTER3: usize = 0xc03;
+const CSR_HPMCOUNTER31: usize = 0xc1f;
+
+/// Bit of the local counter overflow interrupt in `sip` and `sie`.
+const SIP_LCOFIP: usize = 1 << 13;
+
+/// A hardware counter reported by the SBI.
+#[derive(Clone, Copy, Default)]
+struct HpmCounter {
+    /// Index of the counter in the SBI PMU extension.
+    sbi_idx: usize,
+    /// The `hpmcounter` CSR to read the counter.
+    csr: usize,
+    /// Width of the counter in bits.
+    width: u32,
+}
+
+/// The `hpmcounter`s of all harts, which are assumed to be the same.
+struct HpmCounters {
+    counters: [HpmCounter; 29],
+    len: usize,
+}
+
+static HPM_COUNTERS: LazyInit<HpmCounters> = LazyInit::new();
+
+fn sbi_call(eid: usize, fid: usize, args: [usize; 5]) -> Result<usize, isize> {
+    let (error, value): (isize, usize);
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("a0") args[0] => error,
+            inlateout("a1") args[1] => value,
+            in("a2") args[2],
+            in("a3") args[3],
+            in("a4") args[4],
+            in("a6") fid,
+            in("a7") eid,
+        );
+    }
+    if error == 0 {
+        Ok(value)
+    } else {
+        Err(error)
+    }
+}
+
+/// Reads an `hpmcounter` CSR, whose number must be encoded in the instruction.
+fn read_hpmcounter(csr: usize) -> u64 {
+    macro_rules! read_csr {
+        ($($csr:literal)*) => {
+            match csr {
+                $($csr => {
+                    let value: usize;
+                    unsafe { asm!(concat!("csrr {}, ", stringify!($csr)), out(reg) value) };
+                    value as u64
+                })*
+                _ => 0,
+            }
+        };
+    }
+    read_csr!(
+        0xc03 0xc04 0xc05 0xc06 0xc07 0xc08 0xc09 0xc0a 0xc0b 0xc0c 0xc0d 0xc0e 0xc0f 0xc10 0xc11
+        0xc12 0xc13 0xc14 0xc15 0xc16 0xc17 0xc18 0xc19 0xc1a 0xc1b 0xc1c 0xc1d 0xc1e 0xc1f
+    )
+}
+
+/// Whether the harts support the Sscofpmf extension, which adds the overflow
+/// interrupts, from the ISA string in the device tree.
+fn has_sscofpmf() -> bool {
+    let Some(fdt) = crate::dtb::get() else {
+        return false;
+    };
+    fdt.all_nodes().any(|node| {
+        node.property("riscv,isa")
+            .and_then(|isa| isa.as_str())
+            .is_some_and(|isa| isa.split('_').any(|ext| ext == "sscofpmf"))
+            || node
+                .property("riscv,isa-extensions")
+                .is_some_and(|exts| exts.as_str_list().any(|ext| ext == "sscofpmf"))
+    })
+}
+
+fn probe_counters() -> HpmCounters {
+    let mut hpm = HpmCounters {
+        counters: [HpmCounter::default(); 29],
+        len: 0,
+    };
+    if sbi_call(EID_BASE, FID_PROBE_EXTENSION, [EID_PMU, 0, 0, 0, 0]) != Ok(1) {
+        return hpm;
+    }
+    let num = sbi_call(EID_PMU, FID_NUM_COUNTERS, [0; 5]).unwrap_or(0);
+    for sbi_idx in 0..num {
+        let Ok(info) = sbi_call(EID_PMU, FID_COUNTER_GET_INFO, [sbi_idx, 0, 0, 0, 0]) else {
+            continue;
+        };
+        // bits 11:0: CSR number, bits 17:12: width - 1, MSB: firmware counter
+        let csr = info & 0xfff;
+        let is_firmware = info >> (usize::BITS - 1) != 0;
+        if !is_firmware && (CSR_HPMCOUNTER3..=CSR_HPMCOUNTER31).contains(&csr) {
+            hpm.counters[hpm.len] = HpmCounter {
+                sbi_idx,
+                csr,
+                width: ((info >> 12) & 0x3f) as u32 + 1,
+            };
+            hpm.len += 1;
+        }
+    }
+    hpm
+}
+
+fn counter(idx: usize) -> &'static HpmCounter {
+    &HPM_COUNTERS.counters[idx]
+}
+
+/// Returns the SBI event index and event data of the event. The hardware
+/// general events (type 0) have an event index of their event code.
+fn event_idx(event: PmuEvent) -> (usize, u64) {
+    match event {
+        PmuEvent::CpuCycles => (1, 0),
+        PmuEvent::Instructions => (2, 0),
+        PmuEvent::CacheReferences => (3, 0),
+        PmuEvent::CacheMisses => (4, 0),
+        PmuEvent::BranchInstructions => (5, 0),
+        PmuEvent::BranchMisses => (6, 0),
+        PmuEvent::Raw(data) => (HW_RAW_EVENT, data),
+    }
+}
+
+/// Starts the counter from `period` events before it overflows.
+fn start_with_period(counter: &HpmCounter, period: u64) -> PmuResult {
+    let init_value = period.wrapping_neg() & (u64::MAX >> (64 - counter.width));
+    let args = [counter.sbi_idx, 1, START_SET_INIT_VALUE, init_value as _, 0];
+    sbi_call(EID_PMU, FID_COUNTER_START, args).map_err(|_| PmuError::NotSupported)?;
+    Ok(())
+}
+
+pub fn num_counters() -> usize {
+    HPM_COUNTERS.try_get().map_or(0, |hpm| hpm.len)
+}
+
+pub fn read_cycles() -> PmuResult<u64> {
+    let value: usize;
+    unsafe { asm!("rdcycle {}", out(reg) value) };
+    Ok(value as u64)
+}
+
+pub fn read_instructions() -> PmuResult<u64> {
+    let value: usize;
+    unsafe { asm!("rdinstret {}", out(reg) value) };
+    Ok(value as u64)
+}
+
+pub fn max_period(idx: usize) -> u64 {
+    u64::MAX >> (64 - counter(idx).width)
+}
+
+pub fn start_counter(idx: usize, event: PmuEvent, period: u64) -> PmuResult {
+    if period != 0 && !has_sscofpmf() {
+        return Err(PmuError::NotSupported);
+    }
+    let counter = counter(idx);
+    let (event_idx, event_data) = event_idx(event);
+    // Only match this counter.
+    let args = [counter.sbi_idx, 1, 0, event_idx, event_data as _];
+    sbi_call(EID_PMU, FID_COUNTER_CONFIG_MATCHING, args)
+        .map_err(|_| PmuError::EventNotSupported)?;
+    start_with_period(counter, period)
+}
+
+pub fn stop_counter(idx: usize) {
+    let args = [counter(idx).sbi_idx, 1, STOP_RESET, 0, 0];
+    // Fails if the counter is not started, which is fine.
+    let _ = sbi_call(EID_PMU, FID_COUNTER_STOP, args);
+}
+
+pub fn read_counter(idx: usize) -> u64 {
+    read_hpmcounter(counter(idx).csr)
+}
+
+/// Only called on overflow interrupts, so the Sscofpmf extension is present.
+pub fn take_overflow() -> u64 {
+    if num_counters() == 0 {
+        return 0;
+    }
+    // `scountovf` has a bit for each `hpmcounter` CSR.
+    let status: usize;
+    unsafe {
+        asm!("csrr {}, 0xda0", out(reg) status);
+        asm!("csrc sip, {}", in(reg) SIP_LCOFIP);
+    }
+    let hpm = &*HPM_COUNTERS;
+    hpm.counters[..hpm.len]
+        .iter()
+        .enumerate()
+        .filter(|(_, counter)| status & (1 << (counter.csr & 0x1f)) != 0)
+        .fold(0, |bits, (idx, _)| bits | 1 << idx)
+}
+
+pub fn restart_period(idx: usize, period: u64) {
+    // The overflow flag is cleared by the SBI when the counter is started.
+    let counter = counter(idx);
+    let _ = sbi_call(EID_PMU, FID_COUNTER_STOP, [counter.sbi_idx, 1, 0, 0, 0]);
+    let _ = start_with_period(counter, period);
+}
+
+pub fn init_percpu() {
+    if !HPM_COUNTERS.is_init() {
+        HPM_COUNTERS.init_by(probe_counters());
+    }
+}
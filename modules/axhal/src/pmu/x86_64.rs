@@ -0,0 +1,188 @@
+//! x86 architectural performance monitoring.
+
+use core::arch::x86_64::__cpuid;
+
+use lazy_init::LazyInit;
+use x86::msr::{rdmsr, wrmsr};
+
+use super::{PmuError, PmuEvent, PmuResult};
+
+const IA32_PMC0: u32 = 0xc1;
+const IA32_PERFEVTSEL0: u32 = 0x186;
+/// Fixed-function counter 0: INST_RETIRED.ANY.
+const IA32_FIXED_CTR0: u32 = 0x309;
+/// Fixed-function counter 1: CPU_CLK_UNHALTED.CORE.
+const IA32_FIXED_CTR1: u32 = 0x30a;
+const IA32_FIXED_CTR_CTRL: u32 = 0x38d;
+const IA32_PERF_GLOBAL_STATUS: u32 = 0x38e;
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38f;
+const IA32_PERF_GLOBAL_OVF_CTRL: u32 = 0x390;
+
+const PERFEVTSEL_USR: u64 = 1 << 16;
+const PERFEVTSEL_OS: u64 = 1 << 17;
+const PERFEVTSEL_INT: u64 = 1 << 20;
+const PERFEVTSEL_EN: u64 = 1 << 22;
+
+/// Enables the fixed-function counters 0 and 1 in ring 0 and ring 3.
+const FIXED_CTR_CTRL_EN01: u64 = 0x33;
+/// The bits of the fixed-function counters 0 and 1 in the global registers.
+const GLOBAL_FIXED_CTR01: u64 = 0b11 << 32;
+
+/// The PMU capabilities reported by CPUID leaf 0xA.
+struct PmuInfo {
+    num_counters: usize,
+    num_fixed_counters: usize,
+    /// Bitmap of the architectural events that are not available.
+    unavailable_events: u32,
+}
+
+static PMU_INFO: LazyInit<PmuInfo> = LazyInit::new();
+
+fn probe() -> PmuInfo {
+    let mut info = PmuInfo {
+        num_counters: 0,
+        num_fixed_counters: 0,
+        unavailable_events: 0,
+    };
+    if unsafe { __cpuid(0) }.eax < 0xa {
+        return info;
+    }
+    let leaf = unsafe { __cpuid(0xa) };
+    // Version 2 added the fixed-function counters and the global registers.
+    if leaf.eax & 0xff >= 2 {
+        info.num_counters = ((leaf.eax >> 8) & 0xff) as usize;
+        info.num_fixed_counters = (leaf.edx & 0x1f) as usize;
+        info.unavailable_events = leaf.ebx;
+    }
+    info
+}
+
+fn info() -> &'static PmuInfo {
+    &PMU_INFO
+}
+
+fn has_fixed_counters() -> bool {
+    info().num_fixed_counters >= 2
+}
+
+/// Returns the event select and unit mask of the event.
+fn event_select(event: PmuEvent) -> PmuResult<u64> {
+    // (bit in CPUID.0AH:EBX, event select | unit mask << 8)
+    let (bit, code) = match event {
+        PmuEvent::CpuCycles => (0, 0x003c),
+        PmuEvent::Instructions => (1, 0x00c0),
+        PmuEvent::CacheReferences => (3, 0x4f2e),
+        PmuEvent::CacheMisses => (4, 0x412e),
+        PmuEvent::BranchInstructions => (5, 0x00c4),
+        PmuEvent::BranchMisses => (6, 0x00c5),
+        PmuEvent::Raw(code) if code <= 0xffff => return Ok(code),
+        PmuEvent::Raw(_) => return Err(PmuError::EventNotSupported),
+    };
+    if info().unavailable_events & (1 << bit) != 0 {
+        return Err(PmuError::EventNotSupported);
+    }
+    Ok(code)
+}
+
+/// Writes the start value of a period. Writes to `IA32_PMCx` set the low 32
+/// bits and sign-extend them.
+fn write_period(idx: usize, period: u64) {
+    unsafe {
+        wrmsr(
+            IA32_PMC0 + idx as u32,
+            (period as u32).wrapping_neg() as u64,
+        )
+    };
+}
+
+pub fn num_counters() -> usize {
+    PMU_INFO.try_get().map_or(0, |info| info.num_counters)
+}
+
+pub fn read_cycles() -> PmuResult<u64> {
+    if !has_fixed_counters() {
+        return Err(PmuError::NotSupported);
+    }
+    Ok(unsafe { rdmsr(IA32_FIXED_CTR1) })
+}
+
+pub fn read_instructions() -> PmuResult<u64> {
+    if !has_fixed_counters() {
+        return Err(PmuError::NotSupported);
+    }
+    Ok(unsafe { rdmsr(IA32_FIXED_CTR0) })
+}
+
+/// A period must be representable as a negative 32-bit value.
+pub fn max_period(_idx: usize) -> u64 {
+    1 << 31
+}
+
+pub fn start_counter(idx: usize, event: PmuEvent, period: u64) -> PmuResult {
+    let mut evtsel = event_select(event)? | PERFEVTSEL_USR | PERFEVTSEL_OS | PERFEVTSEL_EN;
+    if period != 0 {
+        evtsel |= PERFEVTSEL_INT;
+    }
+    unsafe {
+        write_period(idx, period);
+        wrmsr(IA32_PERF_GLOBAL_OVF_CTRL, 1 << idx);
+        wrmsr(IA32_PERFEVTSEL0 + idx as u32, evtsel);
+        wrmsr(
+            IA32_PERF_GLOBAL_CTRL,
+            rdmsr(IA32_PERF_GLOBAL_CTRL) | 1 << idx,
+        );
+    }
+    Ok(())
+}
+
+pub fn stop_counter(idx: usize) {
+    unsafe {
+        wrmsr(
+            IA32_PERF_GLOBAL_CTRL,
+            rdmsr(IA32_PERF_GLOBAL_CTRL) & !(1 << idx),
+        );
+        wrmsr(IA32_PERFEVTSEL0 + idx as u32, 0);
+        wrmsr(IA32_PERF_GLOBAL_OVF_CTRL, 1 << idx);
+    }
+}
+
+pub fn read_counter(idx: usize) -> u64 {
+    unsafe { rdmsr(IA32_PMC0 + idx as u32) }
+}
+
+pub fn take_overflow() -> u64 {
+    if num_counters() == 0 {
+        return 0;
+    }
+    unsafe {
+        let status = rdmsr(IA32_PERF_GLOBAL_STATUS);
+        wrmsr(IA32_PERF_GLOBAL_OVF_CTRL, status);
+        status
+    }
+}
+
+pub fn restart_period(idx: usize, period: u64) {
+    write_period(idx, period);
+}
+
+pub fn init_percpu() {
+    if !PMU_INFO.is_init() {
+        PMU_INFO.init_by(probe());
+    }
+    if num_counters() == 0 {
+        return;
+    }
+    unsafe {
+        wrmsr(IA32_PERF_GLOBAL_CTRL, 0);
+        for idx in 0..num_counters() as u32 {
+            wrmsr(IA32_PERFEVTSEL0 + idx, 0);
+        }
+        wrmsr(IA32_PERF_GLOBAL_OVF_CTRL, rdmsr(IA32_PERF_GLOBAL_STATUS));
+        if has_fixed_counters() {
+            wrmsr(IA32_FIXED_CTR0, 0);
+            wrmsr(IA32_FIXED_CTR1, 0);
+            wrmsr(IA32_FIXED_CTR_CTRL, FIXED_CTR_CTRL_EN01);
+            wrmsr(IA32_PERF_GLOBAL_CTRL, GLOBAL_FIXED_CTR01);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! System and CPU power management.
+
+#[cfg(feature = "smp")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The bitmap of CPUs that have finished going offline.
+#[cfg(feature = "smp")]
+static DEAD_CPUS: AtomicUsize = AtomicUsize::new(0);
+
+/// Shutdown the whole system, including all CPUs.
+pub fn shutdown() -> ! {
+    crate::platform::misc::terminate()
+}
+
+/// Reboots the whole system, including all CPUs.
+pub fn reboot() -> ! {
+    crate::platform::misc::reboot()
+}
+
+/// Powers off the current CPU.
+///
+/// The caller is responsible for moving all work away from the CPU, and for
+/// disabling interrupts before the call. Other CPUs learn that the CPU has
+/// stopped touching memory by [`is_cpu_dead`], and can bring it online again
+/// by [`cpu_online`].
+///
+/// Must not be called if [`cpu_hotplug_supported`] returns `false`.
+#[cfg(feature = "smp")]
+pub fn cpu_offline() -> ! {
+    let cpu_id = crate::cpu::this_cpu_id();
+    info!("CPU {} is going offline.", cpu_id);
+    // The last store of this CPU, nothing may be touched after it.
+    DEAD_CPUS.fetch_or(1 << cpu_id, Ordering::Release);
+    crate::platform::mp::stop_current_cpu()
+}
+
+/// Whether the given CPU has finished going offline by [`cpu_offline`], and
+/// has not been brought online again.
+#[cfg(feature = "smp")]
+pub fn is_cpu_dead(cpu_id: usize) -> bool {
+    DEAD_CPUS.load(Ordering::Acquire) & (1 << cpu_id) != 0
+}
+
+/// Whether CPUs can be powered off by [`cpu_offline`] and started again by
+/// [`cpu_online`] on this platform.
+///
+/// It is not supported on the platforms that start secondary CPUs from a
+/// spin table, which cannot take the CPUs back.
+#[cfg(feature = "smp")]
+pub fn cpu_hotplug_supported() -> bool {
+    crate::platform::mp::CPU_HOTPLUG
+}
+
+/// Starts the given CPU, which has been powered off by [`cpu_offline`], with
+/// the boot stack whose top is `stack_top`.
+///
+/// Just like booting a secondary CPU, the CPU starts executing from the
+/// bootstrapping code of the platform, and enters the runtime again.
+#[cfg(feature = "smp")]
+pub fn cpu_online(cpu_id: usize, stack_top: crate::mem::PhysAddr) {
+    DEAD_CPUS.fetch_and(!(1 << cpu_id), Ordering::Relaxed);
+    crate::platform::mp::start_secondary_cpu(cpu_id, stack_top)
+}
@@ -1,6 +1,13 @@
 //! Time-related operations.
+//!
+//! Besides the monotonic clock since boot, it keeps the wall-clock time as an
+//! offset from the monotonic clock. The offset is read from the [`Rtc`]
+//! registered by the platform (if any), and updated by [`set_wall_time`].
+
+use core::sync::atomic::{AtomicU64, Ordering};
 
 pub use core::time::Duration;
+use lazy_init::LazyInit;
 
 /// A measurement of the system clock.
 ///
@@ -46,3 +53,219 @@ pub fn busy_wait_until(deadline: TimeValue) {
         core::hint::spin_loop();
     }
 }
+
+/// A real-time clock, which keeps the wall-clock time across reboots.
+pub trait Rtc: Send + Sync {
+    /// Returns the seconds since the Unix epoch (1970-01-01 00:00:00 UTC).
+    fn get_unix_timestamp(&self) -> u64;
+
+    /// Sets the seconds since the Unix epoch.
+    fn set_unix_timestamp(&self, secs: u64);
+}
+
+static RTC: LazyInit<&'static dyn Rtc> = LazyInit::new();
+
+/// Wall-clock time at the monotonic time zero, in nanoseconds since the Unix
+/// epoch.
+static EPOCH_OFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Registers the RTC of the system and sets the wall-clock time from it.
+///
+/// It should be called only once.
+pub fn register_rtc(rtc: &'static dyn Rtc) {
+    let now = rtc.get_unix_timestamp() * NANOS_PER_SEC;
+    RTC.init_by(rtc);
+    EPOCH_OFFSET_NANOS.store(now.saturating_sub(current_time_nanos()), Ordering::Release);
+}
+
+/// Returns the wall-clock time at the monotonic time zero, in nanoseconds
+/// since the Unix epoch.
+pub fn epoch_offset_nanos() -> u64 {
+    EPOCH_OFFSET_NANOS.load(Ordering::Acquire)
+}
+
+/// Returns the wall-clock time in nanoseconds since the Unix epoch.
+///
+/// Without an RTC, the wall-clock time starts at the epoch on boot.
+pub fn wall_time_nanos() -> u64 {
+    current_time_nanos() + epoch_offset_nanos()
+}
+
+/// Returns the wall-clock time since the Unix epoch in [`TimeValue`].
+pub fn wall_time() -> TimeValue {
+    TimeValue::from_nanos(wall_time_nanos())
+}
+
+/// Sets the wall-clock time, as a duration since the Unix epoch.
+///
+/// The RTC is also updated, if there is one.
+pub fn set_wall_time(time: TimeValue) {
+    let nanos = time.as_nanos() as u64;
+    EPOCH_OFFSET_NANOS.store(
+        nanos.saturating_sub(current_time_nanos()),
+        Ordering::Release,
+    );
+    if let Some(rtc) = RTC.try_get() {
+        rtc.set_unix_timestamp(time.as_secs());
+    }
+}
+
+/// A calendar date and time in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u32,
+    /// Month of the year, from 1 to 12.
+    pub month: u8,
+    /// Day of the month, from 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Converts seconds since the Unix epoch to a date and time.
+    pub const fn from_unix_timestamp(secs: u64) -> Self {
+        let days = secs / 86400;
+        let secs = secs % 86400;
+        // Days since 0000-03-01, in 400-year eras (proleptic Gregorian).
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let doe = days % 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = era * 400 + yoe + if month <= 2 { 1 } else { 0 };
+        Self {
+            year: year as u32,
+            month: month as u8,
+            day: (doy - (153 * mp + 2) / 5 + 1) as u8,
+            hour: (secs / 3600) as u8,
+            minute: (secs / 60 % 60) as u8,
+            second: (secs % 60) as u8,
+        }
+    }
+
+    /// Converts the date and time to seconds since the Unix epoch.
+    ///
+    /// Returns `None` if a field is out of range, or if the date is before
+    /// the epoch.
+    pub const fn to_unix_timestamp(&self) -> Option<u64> {
+        if self.year < 1970
+            || self.month < 1
+            || self.month > 12
+            || self.day < 1
+            || self.day > self.days_in_month()
+            || self.hour > 23
+            || self.minute > 59
+            || self.second > 59
+        {
+            return None;
+        }
+        let month = self.month as u64;
+        let year = self.year as u64 - if month <= 2 { 1 } else { 0 };
+        let era = year / 400;
+        let yoe = year % 400;
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + self.day as u64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+        Some(days * 86400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64)
+    }
+
+    const fn days_in_month(&self) -> u8 {
+        let leap = self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0);
+        match self.month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn date(year: u32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    /// Checks the conversions both ways.
+    fn assert_converts(time: DateTime, secs: u64) {
+        assert_eq!(time.to_unix_timestamp(), Some(secs), "{:?}", time);
+        assert_eq!(DateTime::from_unix_timestamp(secs), time);
+    }
+
+    #[test]
+    fn test_epoch() {
+        assert_converts(date(1970, 1, 1, 0, 0, 0), 0);
+        assert_converts(date(1970, 1, 1, 0, 0, 1), 1);
+        assert_converts(date(2038, 1, 19, 3, 14, 8), 1 << 31);
+        assert_eq!(date(1969, 12, 31, 23, 59, 59).to_unix_timestamp(), None);
+    }
+
+    #[test]
+    fn test_leap_days() {
+        assert_converts(date(2000, 2, 29, 0, 0, 0), 951_782_400);
+        assert_converts(date(2000, 3, 1, 0, 0, 0), 951_782_400 + 86400);
+        assert_converts(date(2024, 2, 29, 12, 0, 0), 1_709_208_000);
+        // 2100 is divisible by 100 but not by 400: not a leap year.
+        assert_eq!(date(2100, 2, 29, 0, 0, 0).to_unix_timestamp(), None);
+        assert_converts(date(2100, 2, 28, 0, 0, 0), 4_107_456_000);
+        assert_converts(date(2100, 3, 1, 0, 0, 0), 4_107_456_000 + 86400);
+        assert_eq!(date(2023, 2, 29, 0, 0, 0).to_unix_timestamp(), None);
+    }
+
+    #[test]
+    fn test_end_of_month_and_year() {
+        assert_converts(date(2023, 4, 30, 23, 59, 59), 1_682_899_199);
+        assert_converts(date(2023, 5, 1, 0, 0, 0), 1_682_899_200);
+        assert_eq!(date(2023, 4, 31, 0, 0, 0).to_unix_timestamp(), None);
+        assert_converts(date(2023, 1, 31, 0, 0, 0), 1_675_123_200);
+        assert_converts(date(1999, 12, 31, 23, 59, 59), 946_684_799);
+        assert_converts(date(2000, 1, 1, 0, 0, 0), 946_684_800);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let valid = date(2023, 6, 15, 12, 30, 30);
+        assert!(valid.to_unix_timestamp().is_some());
+        for time in [
+            DateTime { month: 0, ..valid },
+            DateTime { month: 13, ..valid },
+            DateTime { day: 0, ..valid },
+            DateTime { day: 31, ..valid },
+            DateTime { hour: 24, ..valid },
+            DateTime {
+                minute: 60,
+                ..valid
+            },
+            DateTime {
+                second: 60,
+                ..valid
+            },
+        ] {
+            assert_eq!(time.to_unix_timestamp(), None, "{:?}", time);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Every day from the epoch to 2400, at a different time of day.
+        for day in 0..157_000u64 {
+            let secs = day * 86400 + day * 7919 % 86400;
+            let time = DateTime::from_unix_timestamp(secs);
+            assert_eq!(time.to_unix_timestamp(), Some(secs), "{:?}", time);
+        }
+    }
+}
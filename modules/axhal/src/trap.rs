@@ -1,19 +1,55 @@
 //! Trap handling.
 
 use crate_interface::{call_interface, def_interface};
+use memory_addr::VirtAddr;
+
+use crate::arch::TrapFrame;
+
+pub use page_table_entry::MappingFlags as PageFaultFlags;
 
 /// Trap handler interface.
 ///
 /// This trait is defined with the [`#[def_interface]`][1] attribute. Users
 /// should implement it with [`#[impl_interface]`][2] in any other crate.
 ///
+/// The exception hooks return `true` if the exception has been handled, in
+/// which case the trapped context resumes (from the possibly modified
+/// [`TrapFrame`]). Otherwise the default action is taken, which is usually a
+/// panic.
+///
 /// [1]: crate_interface::def_interface
 /// [2]: crate_interface::impl_interface
 #[def_interface]
 pub trait TrapHandler {
     /// Handles interrupt requests for the given IRQ number.
     fn handle_irq(irq_num: usize);
-    // more e.g.: handle_page_fault();
+
+    /// Handles page faults.
+    ///
+    /// `vaddr` is the faulting virtual address, and `access_flags` contains
+    /// one of [`READ`], [`WRITE`] or [`EXECUTE`], indicating the type of the
+    /// faulting access. If handled, the faulting instruction is re-executed.
+    ///
+    /// [`READ`]: PageFaultFlags::READ
+    /// [`WRITE`]: PageFaultFlags::WRITE
+    /// [`EXECUTE`]: PageFaultFlags::EXECUTE
+    fn handle_page_fault(vaddr: VirtAddr, access_flags: PageFaultFlags, is_user: bool) -> bool;
+
+    /// Handles breakpoint exceptions.
+    ///
+    /// If handled, the handler is responsible for setting the program counter
+    /// in `tf` to where the execution continues. Otherwise, the breakpoint
+    /// instruction is skipped.
+    fn handle_breakpoint(tf: &mut TrapFrame) -> bool;
+
+    /// Handles undefined (illegal) instruction exceptions.
+    fn handle_undefined_instruction(tf: &mut TrapFrame) -> bool;
+
+    /// Handles alignment faults.
+    ///
+    /// `vaddr` is the misaligned address. It is zero on x86_64, where the
+    /// address is not reported by the CPU.
+    fn handle_alignment_fault(tf: &mut TrapFrame, vaddr: VirtAddr) -> bool;
 }
 
 /// Call the external IRQ handler.
@@ -21,3 +57,31 @@ pub trait TrapHandler {
 pub(crate) fn handle_irq_extern(irq_num: usize) {
     call_interface!(TrapHandler::handle_irq, irq_num);
 }
+
+/// Call the external page fault handler.
+#[allow(dead_code)]
+pub(crate) fn handle_page_fault_extern(
+    vaddr: VirtAddr,
+    access_flags: PageFaultFlags,
+    is_user: bool,
+) -> bool {
+    call_interface!(TrapHandler::handle_page_fault, vaddr, access_flags, is_user)
+}
+
+/// Call the external breakpoint handler.
+#[allow(dead_code)]
+pub(crate) fn handle_breakpoint_extern(tf: &mut TrapFrame) -> bool {
+    call_interface!(TrapHandler::handle_breakpoint, tf)
+}
+
+/// Call the external undefined instruction handler.
+#[allow(dead_code)]
+pub(crate) fn handle_undefined_instruction_extern(tf: &mut TrapFrame) -> bool {
+    call_interface!(TrapHandler::handle_undefined_instruction, tf)
+}
+
+/// Call the external alignment fault handler.
+#[allow(dead_code)]
+pub(crate) fn handle_alignment_fault_extern(tf: &mut TrapFrame, vaddr: VirtAddr) -> bool {
+    call_interface!(TrapHandler::handle_alignment_fault, tf, vaddr)
+}
@@ -3,5 +3,7 @@ use core::panic::PanicInfo;
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     error!("{}", info);
+    #[cfg(feature = "backtrace")]
+    ax_print!("{}", axhal::backtrace::Backtrace::capture());
     axhal::misc::terminate()
 }
@@ -13,6 +13,9 @@
 //! - `fs`: Enable filesystem support.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
+//! - `backtrace`: Print a symbolized stack backtrace on panic.
+//! - `profiler`: Enable the sampling profiler based on the hardware performance
+//!   counters.
 //!
 //! All the features are optional and disabled by default.
 
@@ -25,9 +28,12 @@
 #[macro_use]
 extern crate axlog;
 
+#[cfg(any(feature = "alloc", test))]
+extern crate alloc;
+
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
-mod trap;
+pub mod trap;
 
 #[cfg(feature = "smp")]
 mod mp;
@@ -35,6 +41,14 @@ mod mp;
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
 
+#[cfg(all(feature = "smp", feature = "multitask"))]
+pub use self::mp::cpu_online;
+
+#[cfg(feature = "profiler")]
+pub mod profiler;
+#[cfg(any(feature = "profiler", test))]
+mod profiler_report;
+
 const LOGO: &str = r#"
        d8888                            .d88888b.   .d8888b.
       d88888                           d88P" "Y88b d88P  Y88b
@@ -142,6 +156,13 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     unsafe { put_debug_paged3() }
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", cpu_id, dtb);
+    if let Some(fdt) = axhal::dtb::get() {
+        info!(
+            "Device tree: {} bytes, bootargs = {:?}",
+            fdt.total_size(),
+            fdt.bootargs().unwrap_or("")
+        );
+    }
 
     info!("Found physcial memory regions:");
     unsafe { put_debug_paged3() }
@@ -173,6 +194,17 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     #[cfg(feature = "multitask")]
     axtask::init_scheduler();
 
+    #[cfg(all(
+        feature = "paging",
+        feature = "smp",
+        feature = "multitask",
+        feature = "irq"
+    ))]
+    axhal::mem::register_tlb_shootdown(|| {
+        // The caller may have migrated since its local flush, flush all CPUs.
+        axtask::smp_call_function(usize::MAX, || axhal::arch::flush_tlb(None));
+    });
+
     #[cfg(any(feature = "fs", feature = "net", feature = "display", feature = "usb"))]
     {
         #[allow(unused_variables)]
@@ -277,13 +309,10 @@ fn init_allocator_no_cache() {
 #[cfg(feature = "paging")]
 fn remap_kernel_memory() -> Result<(), axhal::paging::PagingError> {
     use axhal::mem::{memory_regions, phys_to_virt, VirtAddr};
-    use axhal::paging::PageTable;
-    use lazy_init::LazyInit;
-
-    static KERNEL_PAGE_TABLE: LazyInit<PageTable> = LazyInit::new();
+    use axhal::paging::{kernel_page_table, set_kernel_page_table, PageTable};
 
     if axhal::cpu::this_cpu_is_bsp() {
-        let mut kernel_page_table = PageTable::try_new()?;
+        let mut page_table = PageTable::try_new()?;
         for r in memory_regions() {
             // mailbox 需要物理地址和虚拟地址一致
             let vaddr = if r.name == "nocache memory" {
@@ -291,12 +320,13 @@ fn remap_kernel_memory() -> Result<(), axhal::paging::PagingError> {
             } else {
                 phys_to_virt(r.paddr)
             };
-            kernel_page_table.map_region(vaddr, r.paddr, r.size, r.flags.into(), true)?;
+            page_table.map_region(vaddr, r.paddr, r.size, r.flags.into(), true)?;
         }
-        KERNEL_PAGE_TABLE.init_by(kernel_page_table);
+        set_kernel_page_table(page_table);
     }
 
-    unsafe { axhal::arch::write_page_table_root(KERNEL_PAGE_TABLE.root_paddr()) };
+    let root_paddr = kernel_page_table().unwrap().lock().root_paddr();
+    unsafe { axhal::arch::write_page_table_root(root_paddr) };
     Ok(())
 }
 
@@ -324,10 +354,15 @@ fn init_interrupt() {
 
     axhal::irq::register_handler(TIMER_IRQ_NUM, || {
         update_timer();
+        #[cfg(feature = "profiler")]
+        profiler::on_timer_tick();
         #[cfg(feature = "multitask")]
         axtask::on_timer_tick();
     });
 
+    #[cfg(feature = "profiler")]
+    profiler::init();
+
     // Enable IRQs before starting app
     axhal::arch::enable_irqs();
 }
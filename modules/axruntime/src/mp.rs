@@ -1,5 +1,5 @@
 use axconfig::{SMP, TASK_STACK_SIZE};
-use axhal::mem::{virt_to_phys, VirtAddr};
+use axhal::mem::{virt_to_phys, PhysAddr, VirtAddr};
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 #[link_section = ".bss.stack"]
@@ -7,13 +7,20 @@ static mut SECONDARY_BOOT_STACK: [[u8; TASK_STACK_SIZE]; SMP - 1] = [[0; TASK_ST
 
 static ENTERED_CPUS: AtomicUsize = AtomicUsize::new(1);
 
+static PRIMARY_CPU_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn secondary_boot_stack_top(logic_cpu_id: usize) -> PhysAddr {
+    virt_to_phys(VirtAddr::from(unsafe {
+        SECONDARY_BOOT_STACK[logic_cpu_id].as_ptr_range().end as usize
+    }))
+}
+
 pub fn start_secondary_cpus(primary_cpu_id: usize) {
+    PRIMARY_CPU_ID.store(primary_cpu_id, Ordering::Relaxed);
     let mut logic_cpu_id = 0;
     for i in 0..SMP {
         if i != primary_cpu_id {
-            let stack_top = virt_to_phys(VirtAddr::from(unsafe {
-                SECONDARY_BOOT_STACK[logic_cpu_id].as_ptr_range().end as usize
-            }));
+            let stack_top = secondary_boot_stack_top(logic_cpu_id);
 
             debug!("starting CPU {}...", i);
             axhal::mp::start_secondary_cpu(i, stack_top);
@@ -26,6 +33,38 @@ pub fn start_secondary_cpus(primary_cpu_id: usize) {
     }
 }
 
+/// Brings the given CPU online again, after it is taken offline by
+/// [`axtask::cpu_offline`].
+///
+/// Returns `false` if the CPU does not exist, has not finished going offline,
+/// or does not come online within a second.
+#[cfg(feature = "multitask")]
+pub fn cpu_online(cpu_id: usize) -> bool {
+    if cpu_id >= SMP || !axhal::power::is_cpu_dead(cpu_id) {
+        return false;
+    }
+    // The primary CPU never goes offline.
+    let primary_cpu_id = PRIMARY_CPU_ID.load(Ordering::Relaxed);
+    let logic_cpu_id = if cpu_id < primary_cpu_id {
+        cpu_id
+    } else {
+        cpu_id - 1
+    };
+
+    debug!("bringing CPU {} online...", cpu_id);
+    axhal::power::cpu_online(cpu_id, secondary_boot_stack_top(logic_cpu_id));
+
+    let deadline = axhal::time::current_time() + core::time::Duration::from_secs(1);
+    while !axtask::is_cpu_online(cpu_id) {
+        if axhal::time::current_time() > deadline {
+            warn!("CPU {} did not come online", cpu_id);
+            return false;
+        }
+        axtask::yield_now();
+    }
+    true
+}
+
 /// The main entry point of the ArceOS runtime for secondary CPUs.
 ///
 /// It is called from the bootstrapping code in [axhal].
@@ -43,7 +82,10 @@ pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
     axtask::init_scheduler_secondary();
 
     info!("Secondary CPU {:x} init OK.", cpu_id);
-    super::INITED_CPUS.fetch_add(1, Ordering::Relaxed);
+    // Already counted if the CPU is brought online again.
+    if !super::is_init_ok() {
+        super::INITED_CPUS.fetch_add(1, Ordering::Relaxed);
+    }
 
     while !super::is_init_ok() {
         core::hint::spin_loop();
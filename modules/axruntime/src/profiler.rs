@@ -0,0 +1,217 @@
+//! Sampling profiler based on the hardware performance counters.
+//!
+//! While the profiler is running, the first general-purpose counter of each
+//! CPU counts CPU cycles, and raises the PMU overflow interrupt every `period`
+//! cycles. The interrupt handler records the interrupted PC, the call stack
+//! and the current task into a preallocated buffer. The samples are reported
+//! as PC histograms per task, or as folded stacks (a `task;outer;...;inner
+//! count` line for each distinct stack) to be rendered by flamegraph tools.
+//!
+//! Call stacks are unwound with frame pointers, which the build scripts force
+//! when the `profiler` feature is selected in `FEATURES`. The unwinder stops
+//! at frame pointers outside the stack of the current task. The PCs are
+//! symbolized with the symbol table embedded in the kernel if it is built with
+//! `BACKTRACE=y`, or reported as raw addresses otherwise.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use axhal::pmu::{self, PmuEvent, PmuResult};
+use spinlock::SpinNoIrq;
+
+use crate::profiler_report::{write_folded, write_histograms, SampleRef};
+
+/// The general-purpose counter used for sampling.
+const SAMPLING_COUNTER: usize = 0;
+/// The maximum number of PCs in the call stack of a sample.
+const MAX_STACK_DEPTH: usize = 32;
+/// Task names longer than this are truncated.
+const MAX_NAME_LEN: usize = 32;
+
+struct Sample {
+    task_id: u64,
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+    /// The interrupted PC, followed by the call sites of its callers.
+    pcs: [usize; MAX_STACK_DEPTH],
+    depth: usize,
+}
+
+struct Samples {
+    /// Preallocated, never grows in the interrupt handler.
+    samples: Vec<Sample>,
+    /// Number of samples dropped because the buffer is full.
+    dropped: usize,
+}
+
+static SAMPLES: SpinNoIrq<Samples> = SpinNoIrq::new(Samples {
+    samples: Vec::new(),
+    dropped: 0,
+});
+
+/// Incremented when the profiler is started (to an odd value) or stopped (to
+/// an even value), so that each CPU can update its counter on the next timer
+/// tick.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+static PERIOD: AtomicU64 = AtomicU64::new(0);
+
+/// The generation that the counter of the CPU is set up for.
+#[percpu::def_percpu]
+static CPU_GENERATION: usize = 0;
+
+impl Sample {
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("?")
+    }
+
+    fn as_ref(&self) -> SampleRef<'_> {
+        SampleRef {
+            task_id: self.task_id,
+            name: self.name(),
+            stack: &self.pcs[..self.depth],
+        }
+    }
+}
+
+/// Sets up the sampling counter of the current CPU according to the
+/// generation `gen`.
+fn update_counter(gen: usize) -> PmuResult {
+    unsafe { CPU_GENERATION.write_current_raw(gen) };
+    if gen % 2 == 0 {
+        return pmu::stop_counter(SAMPLING_COUNTER);
+    }
+    let period = PERIOD.load(Ordering::Acquire);
+    pmu::start_counter(SAMPLING_COUNTER, PmuEvent::CpuCycles, Some(period))?;
+    // Also enables the per-CPU interrupt on the secondary CPUs.
+    axhal::irq::set_enable(pmu::irq_num(), true);
+    Ok(())
+}
+
+fn current_task(sample: &mut Sample) {
+    #[cfg(feature = "multitask")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        let name = curr.name().as_bytes();
+        sample.task_id = curr.id().as_u64();
+        sample.name_len = name.len().min(MAX_NAME_LEN);
+        sample.name[..sample.name_len].copy_from_slice(&name[..sample.name_len]);
+        return;
+    }
+    sample.task_id = 0;
+    sample.name_len = 4;
+    sample.name[..4].copy_from_slice(b"main");
+}
+
+/// Handles the PMU overflow interrupt.
+fn on_overflow() {
+    if pmu::handle_overflow() & (1 << SAMPLING_COUNTER) == 0 {
+        return;
+    }
+    let mut sample = Sample {
+        task_id: 0,
+        name: [0; MAX_NAME_LEN],
+        name_len: 0,
+        pcs: [0; MAX_STACK_DEPTH],
+        depth: 0,
+    };
+    current_task(&mut sample);
+    axhal::backtrace::unwind_trapped(|pc| {
+        sample.pcs[sample.depth] = pc;
+        sample.depth += 1;
+        sample.depth < MAX_STACK_DEPTH
+    });
+
+    let mut samples = SAMPLES.lock();
+    if samples.samples.len() < samples.samples.capacity() {
+        samples.samples.push(sample);
+    } else {
+        samples.dropped += 1;
+    }
+}
+
+/// Updates the sampling counter of the current CPU if the profiler has been
+/// started or stopped. Called on each timer tick.
+pub(crate) fn on_timer_tick() {
+    let gen = GENERATION.load(Ordering::Acquire);
+    if unsafe { CPU_GENERATION.read_current_raw() } != gen {
+        // Errors are reported by `start` on the CPU that starts the profiler.
+        let _ = update_counter(gen);
+    }
+}
+
+pub(crate) fn init() {
+    if pmu::num_counters() > SAMPLING_COUNTER {
+        axhal::irq::register_handler(pmu::irq_num(), on_overflow);
+    }
+}
+
+/// Starts sampling every `period` CPU cycles on all CPUs, keeping at most
+/// `max_samples` samples. The samples of the previous run are discarded.
+///
+/// The current CPU starts sampling immediately, and the other CPUs start on
+/// their next timer tick. Returns an error if the PMU of the current CPU does
+/// not support sampling.
+pub fn start(period: u64, max_samples: usize) -> PmuResult {
+    let mut samples = Vec::new();
+    samples.reserve_exact(max_samples);
+    stop();
+    *SAMPLES.lock() = Samples {
+        samples,
+        dropped: 0,
+    };
+    PERIOD.store(period, Ordering::Release);
+    let gen = GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+    update_counter(gen).inspect_err(|_| {
+        GENERATION.fetch_add(1, Ordering::AcqRel);
+        let _ = pmu::stop_counter(SAMPLING_COUNTER);
+    })
+}
+
+/// Stops sampling on all CPUs. The samples are kept to be reported.
+///
+/// The current CPU stops sampling immediately, and the other CPUs stop on
+/// their next timer tick.
+pub fn stop() {
+    let gen = GENERATION.load(Ordering::Acquire);
+    if gen % 2 == 1
+        && GENERATION
+            .compare_exchange(gen, gen + 1, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    {
+        let _guard = kernel_guard::NoPreemptIrqSave::new();
+        let _ = update_counter(gen + 1);
+    }
+}
+
+/// Takes the samples out of the buffer, to format them without holding the
+/// lock. The buffer is given back afterwards to be reused.
+fn with_samples<T>(f: impl FnOnce(&[Sample], usize) -> T) -> T {
+    let (samples, dropped) = {
+        let mut guard = SAMPLES.lock();
+        let dropped = guard.dropped;
+        (core::mem::take(&mut guard.samples), dropped)
+    };
+    let ret = f(&samples, dropped);
+    // Samples are dropped while the buffer is taken.
+    SAMPLES.lock().samples = samples;
+    ret
+}
+
+/// Writes a histogram of the sampled PCs of each task, most frequent first.
+pub fn report(w: &mut impl Write) -> fmt::Result {
+    with_samples(|samples, dropped| {
+        let samples = samples.iter().map(Sample::as_ref);
+        write_histograms(w, samples, dropped, axhal::backtrace::symbolize)
+    })
+}
+
+/// Writes the sampled call stacks in the folded format: a line of
+/// `task;outer;...;inner count` for each distinct stack of function names,
+/// with the task as the root frame.
+pub fn report_folded(w: &mut impl Write) -> fmt::Result {
+    with_samples(|samples, _| {
+        let samples = samples.iter().map(Sample::as_ref);
+        write_folded(w, samples, axhal::backtrace::symbolize)
+    })
+}
@@ -0,0 +1,194 @@
+//! Aggregation and formatting of the samples of the [profiler], free of the
+//! hardware so that it can be tested on the host.
+//!
+//! [profiler]: crate::profiler
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::fmt::{self, Write};
+
+/// The maximum number of PCs shown in the histogram of each task.
+const MAX_HISTOGRAM_ENTRIES: usize = 20;
+
+/// A sample as reported.
+pub(crate) struct SampleRef<'a> {
+    pub task_id: u64,
+    pub name: &'a str,
+    /// The interrupted PC, followed by the call sites of its callers.
+    pub stack: &'a [usize],
+}
+
+/// Writes a histogram of the sampled PCs of each task, most frequent first.
+///
+/// `symbolize` gives the function name of a PC and the offset in it.
+pub(crate) fn write_histograms<'a>(
+    w: &mut impl Write,
+    samples: impl IntoIterator<Item = SampleRef<'a>>,
+    dropped: usize,
+    symbolize: impl Fn(usize) -> Option<(&'static str, usize)>,
+) -> fmt::Result {
+    // task ID -> (task name, PC -> count)
+    let mut tasks = BTreeMap::<u64, (&str, BTreeMap<usize, usize>)>::new();
+    let mut total_samples = 0;
+    for sample in samples {
+        total_samples += 1;
+        let Some(&pc) = sample.stack.first() else {
+            continue;
+        };
+        let (_, pcs) = tasks
+            .entry(sample.task_id)
+            .or_insert_with(|| (sample.name, BTreeMap::new()));
+        *pcs.entry(pc).or_default() += 1;
+    }
+
+    writeln!(w, "{} samples, {} dropped", total_samples, dropped)?;
+    for (task_id, (name, pcs)) in tasks {
+        let total: usize = pcs.values().sum();
+        writeln!(w, "task {} ({}): {} samples", task_id, name, total)?;
+        let mut pcs: Vec<_> = pcs.into_iter().collect();
+        pcs.sort_by_key(|&(_, count)| Reverse(count));
+        for &(pc, count) in pcs.iter().take(MAX_HISTOGRAM_ENTRIES) {
+            let permille = count * 1000 / total;
+            let (int, frac) = (permille / 10, permille % 10);
+            write!(w, "  {:>8} {:>3}.{}%  {:#018x}", count, int, frac, pc)?;
+            match symbolize(pc) {
+                Some((name, offset)) => writeln!(w, " {}+{:#x}", name, offset)?,
+                None => writeln!(w)?,
+            }
+        }
+        if pcs.len() > MAX_HISTOGRAM_ENTRIES {
+            writeln!(w, "  ... {} more", pcs.len() - MAX_HISTOGRAM_ENTRIES)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the sampled call stacks in the folded format: a line of
+/// `task;outer;...;inner count` for each distinct stack of function names,
+/// with the task as the root frame.
+///
+/// `symbolize` gives the function name of a PC and the offset in it. PCs
+/// without a name are written as addresses.
+pub(crate) fn write_folded<'a>(
+    w: &mut impl Write,
+    samples: impl IntoIterator<Item = SampleRef<'a>>,
+    symbolize: impl Fn(usize) -> Option<(&'static str, usize)>,
+) -> fmt::Result {
+    let mut stacks = BTreeMap::<String, usize>::new();
+    for sample in samples.into_iter().filter(|s| !s.stack.is_empty()) {
+        let mut stack = String::new();
+        match sample.name {
+            "" => write!(stack, "task-{}", sample.task_id)?,
+            name => write!(stack, "{}-{}", name, sample.task_id)?,
+        }
+        for &pc in sample.stack.iter().rev() {
+            stack.push(';');
+            match symbolize(pc) {
+                Some((name, _)) => write_folded_frame(&mut stack, name)?,
+                None => write!(stack, "{:#x}", pc)?,
+            }
+        }
+        *stacks.entry(stack).or_default() += 1;
+    }
+    for (stack, count) in stacks {
+        writeln!(w, "{} {}", stack, count)?;
+    }
+    Ok(())
+}
+
+/// Writes a function name as a frame of a folded stack, where `;` is the
+/// frame separator.
+fn write_folded_frame(w: &mut impl Write, name: &str) -> fmt::Result {
+    name.split(';').enumerate().try_for_each(|(i, part)| {
+        if i > 0 {
+            w.write_char(':')?;
+        }
+        w.write_str(part)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(task_id: u64, name: &'static str, stack: &'static [usize]) -> SampleRef<'static> {
+        SampleRef {
+            task_id,
+            name,
+            stack,
+        }
+    }
+
+    fn symbolize(pc: usize) -> Option<(&'static str, usize)> {
+        match pc {
+            0x1000..=0x1fff => Some(("main", pc - 0x1000)),
+            0x2000..=0x2fff => Some(("<T as Trait>::run;weird", pc - 0x2000)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_histogram_ordering() {
+        let samples = [
+            sample(2, "worker", &[0x2010]),
+            sample(1, "main", &[0x1008, 0x1100]),
+            sample(1, "main", &[0x1004]),
+            sample(1, "main", &[0x1008]),
+            sample(1, "main", &[0x9000]),
+            sample(1, "main", &[0x1008]),
+            sample(1, "main", &[0x9000]),
+            // Samples without a PC are counted, but not shown.
+            sample(3, "idle", &[]),
+        ];
+        let mut out = String::new();
+        write_histograms(&mut out, samples, 5, symbolize).unwrap();
+        // Tasks by ID, PCs by count then by address.
+        let expected = "\
+8 samples, 5 dropped
+task 1 (main): 6 samples
+         3  50.0%  0x0000000000001008 main+0x8
+         2  33.3%  0x0000000000009000
+         1  16.6%  0x0000000000001004 main+0x4
+task 2 (worker): 1 samples
+         1 100.0%  0x0000000000002010 <T as Trait>::run;weird+0x10
+";
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_histogram_truncation() {
+        let stacks: Vec<[usize; 1]> = (0..MAX_HISTOGRAM_ENTRIES + 3).map(|i| [i]).collect();
+        let samples = stacks.iter().map(|stack| SampleRef {
+            task_id: 1,
+            name: "main",
+            stack,
+        });
+        let mut out = String::new();
+        write_histograms(&mut out, samples, 0, |_| None).unwrap();
+        assert_eq!(out.lines().count(), 2 + MAX_HISTOGRAM_ENTRIES + 1);
+        assert!(out.ends_with("  ... 3 more\n"));
+    }
+
+    #[test]
+    fn test_folded_lines() {
+        let samples = [
+            sample(1, "main", &[0x1008, 0x1100]),
+            sample(1, "main", &[0x1010, 0x1100]),
+            sample(7, "", &[0x2000, 0x9000]),
+            sample(1, "main", &[0x1100]),
+            sample(3, "idle", &[]),
+        ];
+        let mut out = String::new();
+        write_folded(&mut out, samples, symbolize).unwrap();
+        // The root frame comes first, and the frames of a function are
+        // merged whatever the offset. `;` in names is not a separator.
+        let expected = "\
+main-1;main 1
+main-1;main;main 2
+task-7;0x9000;<T as Trait>::run:weird 1
+";
+        assert_eq!(out, expected);
+    }
+}
@@ -1,3 +1,95 @@
+//! Trap handling, and hooks for the exceptions.
+//!
+//! The runtime implements the [`axhal::trap::TrapHandler`] interface, which
+//! can only be implemented once. Other modules handle exceptions by
+//! registering hooks here instead, such as a page fault handler for demand
+//! paging or a breakpoint handler for a debugger. Exceptions without a hook
+//! get the default handling of `axhal`.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axhal::arch::TrapFrame;
+use axhal::mem::VirtAddr;
+use axhal::trap::PageFaultFlags;
+
+/// A page fault hook, see [`axhal::trap::TrapHandler::handle_page_fault`].
+pub type PageFaultHandler = fn(VirtAddr, PageFaultFlags, bool) -> bool;
+
+/// A breakpoint hook, see [`axhal::trap::TrapHandler::handle_breakpoint`].
+pub type BreakpointHandler = fn(&mut TrapFrame) -> bool;
+
+/// An undefined instruction hook, see
+/// [`axhal::trap::TrapHandler::handle_undefined_instruction`].
+pub type UndefinedInstructionHandler = fn(&mut TrapFrame) -> bool;
+
+/// An alignment fault hook, see
+/// [`axhal::trap::TrapHandler::handle_alignment_fault`].
+pub type AlignmentFaultHandler = fn(&mut TrapFrame, VirtAddr) -> bool;
+
+/// A hook of function pointer type `F`, which can be registered once.
+struct Hook<F> {
+    handler: AtomicUsize,
+    _phantom: PhantomData<F>,
+}
+
+impl<F: Copy> Hook<F> {
+    const fn new() -> Self {
+        assert!(core::mem::size_of::<F>() == core::mem::size_of::<usize>());
+        Self {
+            handler: AtomicUsize::new(0),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn register(&self, handler: F) -> bool {
+        let handler = unsafe { core::mem::transmute_copy::<F, usize>(&handler) };
+        self.handler
+            .compare_exchange(0, handler, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    fn get(&self) -> Option<F> {
+        match self.handler.load(Ordering::Acquire) {
+            0 => None,
+            handler => Some(unsafe { core::mem::transmute_copy::<usize, F>(&handler) }),
+        }
+    }
+}
+
+static PAGE_FAULT_HOOK: Hook<PageFaultHandler> = Hook::new();
+static BREAKPOINT_HOOK: Hook<BreakpointHandler> = Hook::new();
+static UNDEFINED_INSTRUCTION_HOOK: Hook<UndefinedInstructionHandler> = Hook::new();
+static ALIGNMENT_FAULT_HOOK: Hook<AlignmentFaultHandler> = Hook::new();
+
+/// Registers the page fault hook.
+///
+/// Returns `false` if a hook is already registered.
+pub fn register_page_fault_handler(handler: PageFaultHandler) -> bool {
+    PAGE_FAULT_HOOK.register(handler)
+}
+
+/// Registers the breakpoint hook.
+///
+/// Returns `false` if a hook is already registered.
+pub fn register_breakpoint_handler(handler: BreakpointHandler) -> bool {
+    BREAKPOINT_HOOK.register(handler)
+}
+
+/// Registers the undefined instruction hook.
+///
+/// Returns `false` if a hook is already registered.
+pub fn register_undefined_instruction_handler(handler: UndefinedInstructionHandler) -> bool {
+    UNDEFINED_INSTRUCTION_HOOK.register(handler)
+}
+
+/// Registers the alignment fault hook.
+///
+/// Returns `false` if a hook is already registered.
+pub fn register_alignment_fault_handler(handler: AlignmentFaultHandler) -> bool {
+    ALIGNMENT_FAULT_HOOK.register(handler)
+}
+
 struct TrapHandlerImpl;
 
 #[crate_interface::impl_interface]
@@ -10,4 +102,26 @@ impl axhal::trap::TrapHandler for TrapHandlerImpl {
             drop(guard); // rescheduling may occur when preemption is re-enabled.
         }
     }
+
+    fn handle_page_fault(vaddr: VirtAddr, access_flags: PageFaultFlags, is_user: bool) -> bool {
+        PAGE_FAULT_HOOK
+            .get()
+            .is_some_and(|handler| handler(vaddr, access_flags, is_user))
+    }
+
+    fn handle_breakpoint(tf: &mut TrapFrame) -> bool {
+        BREAKPOINT_HOOK.get().is_some_and(|handler| handler(tf))
+    }
+
+    fn handle_undefined_instruction(tf: &mut TrapFrame) -> bool {
+        UNDEFINED_INSTRUCTION_HOOK
+            .get()
+            .is_some_and(|handler| handler(tf))
+    }
+
+    fn handle_alignment_fault(tf: &mut TrapFrame, vaddr: VirtAddr) -> bool {
+        ALIGNMENT_FAULT_HOOK
+            .get()
+            .is_some_and(|handler| handler(tf, vaddr))
+    }
 }
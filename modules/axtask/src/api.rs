@@ -9,6 +9,9 @@ pub use crate::task::{CurrentTask, TaskId, TaskInner};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::wait_queue::WaitQueue;
 
+#[cfg(all(feature = "smp", feature = "irq"))]
+pub use crate::ipi::{smp_call_function, smp_call_function_async, SmpCallHandle};
+
 /// The reference type of a task.
 pub type AxTaskRef = Arc<AxTask>;
 
@@ -68,6 +71,8 @@ pub fn init_scheduler() {
     crate::run_queue::init();
     #[cfg(feature = "irq")]
     crate::timers::init();
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    crate::ipi::init();
 
     info!("  use {} scheduler.", Scheduler::scheduler_name());
 }
@@ -75,6 +80,8 @@ pub fn init_scheduler() {
 /// Initializes the task scheduler for secondary CPUs.
 pub fn init_scheduler_secondary() {
     crate::run_queue::init_secondary();
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    crate::ipi::init_secondary();
 }
 
 /// Handles periodic timer ticks for the task manager.
@@ -153,14 +160,48 @@ pub fn exit(exit_code: i32) -> ! {
     RUN_QUEUE.lock().exit_current(exit_code)
 }
 
+/// Takes the given CPU offline, and waits until it is powered off.
+///
+/// The task running on the CPU is put back to the run queue at its next
+/// reschedule point (requested by an IPI if the `irq` feature is enabled), and
+/// continues on other CPUs. After that, the CPU powers
+/// itself off in its idle task, and this function returns once the CPU has
+/// acknowledged it (see [`axhal::power::is_cpu_dead`]).
+///
+/// Returns `false` if the platform does not support taking CPUs offline, the
+/// CPU does not exist, is the primary CPU, or is already offline or going
+/// offline.
+#[cfg(feature = "smp")]
+pub fn cpu_offline(cpu_id: usize) -> bool {
+    if !axhal::power::cpu_hotplug_supported() || !RUN_QUEUE.lock().request_cpu_offline(cpu_id) {
+        return false;
+    }
+    #[cfg(feature = "irq")]
+    crate::ipi::send_resched(cpu_id);
+    while !axhal::power::is_cpu_dead(cpu_id) {
+        yield_now();
+    }
+    true
+}
+
+/// Returns whether the given CPU is online, i.e., it is able to run tasks.
+#[cfg(feature = "smp")]
+pub fn is_cpu_online(cpu_id: usize) -> bool {
+    RUN_QUEUE.lock().is_cpu_online(cpu_id)
+}
+
 /// The idle task routine.
 ///
-/// It runs an infinite loop that keeps calling [`yield_now()`].
+/// It runs an infinite loop that keeps calling [`yield_now()`], and waits for
+/// IRQs while no task is ready to run. If the CPU is requested to go offline
+/// (see [`cpu_offline`]), it powers off the CPU.
 pub fn run_idle() -> ! {
     loop {
         yield_now();
+        #[cfg(feature = "smp")]
+        crate::run_queue::offline_if_requested();
         debug!("idle task: waiting for IRQs...");
         #[cfg(feature = "irq")]
-        axhal::arch::wait_for_irqs();
+        crate::run_queue::wait_for_resched();
     }
 }
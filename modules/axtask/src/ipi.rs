@@ -0,0 +1,150 @@
+//! Inter-processor interrupts: cross-CPU function calls and remote
+//! rescheduling.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axhal::irq::{send_ipi, IPI_IRQ_NUM};
+use spinlock::SpinNoIrq;
+
+use crate::RUN_QUEUE;
+
+/// A function to be run on a set of CPUs.
+struct SmpCall {
+    func: Box<dyn Fn() + Send + Sync>,
+    /// The number of CPUs that have not returned from `func`.
+    pending: AtomicUsize,
+}
+
+type CallQueue = SpinNoIrq<VecDeque<Arc<SmpCall>>>;
+
+/// The calls to be run on each CPU, indexed by CPU ID.
+static CALL_QUEUES: [CallQueue; axconfig::SMP] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: CallQueue = SpinNoIrq::new(VecDeque::new());
+    [EMPTY; axconfig::SMP]
+};
+
+/// Bitmap of CPUs requested to reschedule.
+static RESCHED_CPUS: AtomicUsize = AtomicUsize::new(0);
+
+impl SmpCall {
+    fn run(&self) {
+        (self.func)();
+        self.pending.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// The handle of a cross-CPU function call started by
+/// [`smp_call_function_async`].
+pub struct SmpCallHandle(Arc<SmpCall>);
+
+impl SmpCallHandle {
+    /// Whether all the target CPUs have returned from the function.
+    pub fn is_done(&self) -> bool {
+        self.0.pending.load(Ordering::Acquire) == 0
+    }
+
+    /// Waits until all the target CPUs have returned from the function.
+    ///
+    /// Calls to the current CPU are run while waiting, so that two CPUs calling
+    /// each other with IRQs disabled do not deadlock.
+    pub fn wait(self) {
+        while !self.is_done() {
+            let _guard = kernel_guard::NoPreemptIrqSave::new();
+            run_pending_calls();
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Runs the calls queued for the current CPU. IRQs must be disabled.
+pub(crate) fn run_pending_calls() {
+    let queue = &CALL_QUEUES[axhal::cpu::this_cpu_id()];
+    loop {
+        // Do not hold the lock while running the call.
+        let Some(call) = queue.lock().pop_front() else {
+            break;
+        };
+        call.run();
+    }
+}
+
+/// Requests the given CPU to reschedule. An idle CPU is woken up to pick up
+/// the ready tasks.
+pub(crate) fn send_resched(cpu_id: usize) {
+    RESCHED_CPUS.fetch_or(1 << cpu_id, Ordering::AcqRel);
+    send_ipi(cpu_id);
+}
+
+fn handle_ipi() {
+    run_pending_calls();
+    let bit = 1 << axhal::cpu::this_cpu_id();
+    if RESCHED_CPUS.fetch_and(!bit, Ordering::AcqRel) & bit != 0 {
+        // Rescheduling occurs when preemption is re-enabled after the IRQ
+        // handler, or when the idle task wakes up.
+        #[cfg(feature = "preempt")]
+        crate::current().set_preempt_pending(true);
+    }
+}
+
+/// Runs `f` on each online CPU in `cpu_mask` (a bitmap of CPU IDs), and waits
+/// until all of them return.
+///
+/// See [`smp_call_function_async`] for details.
+pub fn smp_call_function<F>(cpu_mask: usize, f: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    smp_call_function_async(cpu_mask, f).wait();
+}
+
+/// Runs `f` on each online CPU in `cpu_mask` (a bitmap of CPU IDs), and
+/// returns a handle to wait for the completion.
+///
+/// `f` is run in the IPI handler of the other CPUs, with IRQs disabled, so it
+/// must not block. If the current CPU is in `cpu_mask`, `f` is run on it
+/// before returning, also with IRQs disabled.
+pub fn smp_call_function_async<F>(cpu_mask: usize, f: F) -> SmpCallHandle
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let call = Arc::new(SmpCall {
+        func: Box::new(f),
+        pending: AtomicUsize::new(0),
+    });
+    let _guard = kernel_guard::NoPreempt::new();
+    let this_cpu_id = axhal::cpu::this_cpu_id();
+    {
+        // Hold the run queue lock so that the CPUs do not go offline before
+        // they receive the call. A CPU runs its remaining calls when it goes
+        // offline.
+        let rq = RUN_QUEUE.lock();
+        let is_target = |&cpu_id: &usize| cpu_mask & (1 << cpu_id) != 0 && rq.is_cpu_online(cpu_id);
+        let num_targets = (0..axconfig::SMP).filter(is_target).count();
+        call.pending.store(num_targets, Ordering::Release);
+        for cpu_id in (0..axconfig::SMP).filter(is_target) {
+            if cpu_id != this_cpu_id {
+                CALL_QUEUES[cpu_id].lock().push_back(call.clone());
+                send_ipi(cpu_id);
+            }
+        }
+    }
+    if cpu_mask & (1 << this_cpu_id) != 0 {
+        let _guard = kernel_guard::IrqSave::new();
+        call.run();
+    }
+    SmpCallHandle(call)
+}
+
+pub(crate) fn init() {
+    axhal::irq::register_handler(IPI_IRQ_NUM, handle_ipi);
+}
+
+pub(crate) fn init_secondary() {
+    axhal::irq::set_enable(IPI_IRQ_NUM, true);
+    // The IPIs sent before the CPU is started may be lost.
+    run_pending_calls();
+}
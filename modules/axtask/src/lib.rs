@@ -13,6 +13,10 @@
 //!    APIs can be used, such as [`sleep`], [`sleep_until`], and
 //!    [`WaitQueue::wait_timeout`].
 //! - `preempt`: Enable preemptive scheduling.
+//! - `smp`: Enable SMP (symmetric multiprocessing) support, including taking
+//!   CPUs offline (see [`cpu_offline`]). With `irq`, it also enables waking up
+//!   idle CPUs by IPIs when tasks become ready, and cross-CPU function calls
+//!   (see [`smp_call_function`]).
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -42,6 +46,8 @@ cfg_if::cfg_if! {
 
         #[cfg(feature = "irq")]
         mod timers;
+        #[cfg(all(feature = "smp", feature = "irq"))]
+        mod ipi;
 
         #[doc(cfg(feature = "multitask"))]
         pub use self::api::*;
@@ -20,26 +20,52 @@ static IDLE_TASK: LazyInit<AxTaskRef> = LazyInit::new();
 
 pub(crate) struct AxRunQueue {
     scheduler: Scheduler,
+    /// The primary CPU, which never goes offline.
+    #[cfg(feature = "smp")]
+    primary_cpu_id: usize,
+    /// Bitmap of CPUs requested to go offline.
+    #[cfg(feature = "smp")]
+    offline_pending_cpus: usize,
+    /// Bitmap of offline CPUs.
+    #[cfg(feature = "smp")]
+    offline_cpus: usize,
+    /// Bitmap of CPUs running their idle tasks.
+    idle_cpus: usize,
 }
 
+#[cfg(feature = "smp")]
+const _: () = assert!(axconfig::SMP <= usize::BITS as usize);
+
 impl AxRunQueue {
     pub fn new() -> SpinNoIrq<Self> {
         let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
         let mut scheduler = Scheduler::new();
         scheduler.add_task(gc_task);
-        SpinNoIrq::new(Self { scheduler })
+        SpinNoIrq::new(Self {
+            scheduler,
+            #[cfg(feature = "smp")]
+            primary_cpu_id: axhal::cpu::this_cpu_id(),
+            #[cfg(feature = "smp")]
+            offline_pending_cpus: 0,
+            #[cfg(feature = "smp")]
+            offline_cpus: 0,
+            idle_cpus: 0,
+        })
     }
 
     pub fn add_task(&mut self, task: AxTaskRef) {
         debug!("task spawn: {}", task.id_name());
         assert!(task.is_ready());
         self.scheduler.add_task(task);
+        self.wake_idle_cpu();
     }
 
     #[cfg(feature = "irq")]
     pub fn scheduler_timer_tick(&mut self) {
         let curr = crate::current();
-        if !curr.is_idle() && self.scheduler.task_tick(curr.as_task_ref()) {
+        if !curr.is_idle()
+            && (self.scheduler.task_tick(curr.as_task_ref()) || self.this_cpu_going_offline())
+        {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
@@ -99,6 +125,29 @@ impl AxRunQueue {
         unreachable!("task exited!");
     }
 
+    /// Requests the given CPU to go offline.
+    ///
+    /// Returns `false` if the CPU does not exist, is the primary CPU, or is
+    /// already offline or going offline.
+    #[cfg(feature = "smp")]
+    pub fn request_cpu_offline(&mut self, cpu_id: usize) -> bool {
+        let bit = 1 << cpu_id;
+        if cpu_id >= axconfig::SMP
+            || cpu_id == self.primary_cpu_id
+            || (self.offline_cpus | self.offline_pending_cpus) & bit != 0
+        {
+            return false;
+        }
+        debug!("CPU {} is requested to go offline", cpu_id);
+        self.offline_pending_cpus |= bit;
+        true
+    }
+
+    #[cfg(feature = "smp")]
+    pub fn is_cpu_online(&self, cpu_id: usize) -> bool {
+        cpu_id < axconfig::SMP && self.offline_cpus & (1 << cpu_id) == 0
+    }
+
     pub fn block_current<F>(&mut self, wait_queue_push: F)
     where
         F: FnOnce(AxTaskRef),
@@ -122,6 +171,7 @@ impl AxRunQueue {
         if task.is_blocked() {
             task.set_state(TaskState::Ready);
             self.scheduler.add_task(task); // TODO: priority
+            self.wake_idle_cpu();
             if resched {
                 #[cfg(feature = "preempt")]
                 crate::current().set_preempt_pending(true);
@@ -156,13 +206,70 @@ impl AxRunQueue {
                 self.scheduler.put_prev_task(prev.clone(), preempt);
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
+        // If the CPU is going offline, leave all ready tasks (including the
+        // previous one) to other CPUs, and switch to the idle task to power off.
+        let next = if self.this_cpu_going_offline() {
+            // Let an idle CPU run the previous task.
+            if prev.is_ready() && !prev.is_idle() {
+                self.wake_idle_cpu();
+            }
+            None
+        } else {
+            self.scheduler.pick_next_task()
+        };
+        let next = next.unwrap_or_else(|| unsafe {
             // Safety: IRQs must be disabled at this time.
             IDLE_TASK.current_ref_raw().get_unchecked().clone()
         });
+        let bit = 1 << axhal::cpu::this_cpu_id();
+        if next.is_idle() {
+            self.idle_cpus |= bit;
+        } else {
+            self.idle_cpus &= !bit;
+        }
         self.switch_to(prev, next);
     }
 
+    /// Wakes up an idle CPU by an IPI, to run the tasks that just became ready.
+    ///
+    /// If the current CPU is idle, i.e., the tasks become ready in an IRQ
+    /// handler that interrupts its idle task, it runs them by itself.
+    fn wake_idle_cpu(&mut self) {
+        let this = 1 << axhal::cpu::this_cpu_id();
+        if self.idle_cpus & this != 0 {
+            // Stops the idle task from waiting for IRQs (see `wait_for_resched`).
+            self.idle_cpus &= !this;
+        } else {
+            #[cfg(all(feature = "smp", feature = "irq"))]
+            {
+                let others = self.idle_cpus & !this;
+                if others != 0 {
+                    let cpu_id = others.trailing_zeros() as usize;
+                    // Wake up another CPU for the next ready task, unless the
+                    // woken CPU finds no task to run and becomes idle again.
+                    self.idle_cpus &= !(1 << cpu_id);
+                    crate::ipi::send_resched(cpu_id);
+                }
+            }
+        }
+    }
+
+    /// Whether the current CPU has nothing to do since it switched to its idle
+    /// task: no task became ready for it, and it is not going offline.
+    #[cfg(feature = "irq")]
+    fn this_cpu_idle(&self) -> bool {
+        self.idle_cpus & (1 << axhal::cpu::this_cpu_id()) != 0 && !self.this_cpu_going_offline()
+    }
+
+    fn this_cpu_going_offline(&self) -> bool {
+        #[cfg(feature = "smp")]
+        {
+            self.offline_pending_cpus & (1 << axhal::cpu::this_cpu_id()) != 0
+        }
+        #[cfg(not(feature = "smp"))]
+        false
+    }
+
     fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
         trace!(
             "context switch: {} -> {}",
@@ -226,8 +333,62 @@ pub(crate) fn init() {
 }
 
 pub(crate) fn init_secondary() {
-    let idle_task = TaskInner::new_init("idle".into());
+    // Reuse the idle task if the CPU is brought online again.
+    let idle_task = IDLE_TASK
+        .with_current(|i| i.try_get().cloned())
+        .unwrap_or_else(|| {
+            let idle_task = TaskInner::new_init("idle".into());
+            IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
+            idle_task
+        });
     idle_task.set_state(TaskState::Running);
-    IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
+    #[cfg(feature = "smp")]
+    {
+        let bit = 1 << axhal::cpu::this_cpu_id();
+        let mut rq = RUN_QUEUE.lock();
+        rq.offline_cpus &= !bit;
+        rq.idle_cpus |= bit;
+    }
     unsafe { CurrentTask::init_current(idle_task) }
 }
+
+/// Waits for IRQs in the idle task, unless the current CPU has been requested
+/// to reschedule since it switched to the idle task.
+///
+/// IRQs are disabled while checking the run queue, so that a wakeup after the
+/// check (e.g., an IPI) is left pending to end the wait, instead of being
+/// handled before the wait begins.
+#[cfg(feature = "irq")]
+pub(crate) fn wait_for_resched() {
+    axhal::arch::disable_irqs();
+    if RUN_QUEUE.lock().this_cpu_idle() {
+        axhal::arch::enable_irqs_and_wait();
+    } else {
+        axhal::arch::enable_irqs();
+    }
+}
+
+/// Powers off the current CPU if it is requested to go offline. It must be
+/// called in the idle task.
+#[cfg(feature = "smp")]
+pub(crate) fn offline_if_requested() {
+    let bit = 1 << axhal::cpu::this_cpu_id();
+    let mut rq = RUN_QUEUE.lock();
+    if rq.offline_pending_cpus & bit == 0 {
+        return;
+    }
+    rq.offline_pending_cpus &= !bit;
+    rq.offline_cpus |= bit;
+    rq.idle_cpus &= !bit;
+    drop(rq);
+
+    axhal::arch::disable_irqs();
+    // No more calls are sent to this CPU once it is marked offline.
+    #[cfg(feature = "irq")]
+    crate::ipi::run_pending_calls();
+    // The reference is taken again by `init_secondary()` when the CPU is
+    // brought online, as the current task pointer does not survive the power
+    // cycle on some architectures.
+    unsafe { CurrentTask::clear_current() };
+    axhal::power::cpu_offline();
+}
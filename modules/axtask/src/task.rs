@@ -1,5 +1,5 @@
 use alloc::{boxed::Box, string::String, sync::Arc};
-use core::ops::Deref;
+use core::ops::{Deref, Range};
 use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 
@@ -51,6 +51,8 @@ pub struct TaskInner {
     wait_for_exit: WaitQueue,
 
     kstack: Option<TaskStack>,
+    /// Bounds of the kernel stack, which init tasks do not allocate.
+    stack: Range<usize>,
     ctx: UnsafeCell<TaskContext>,
 
     #[cfg(feature = "tls")]
@@ -131,6 +133,7 @@ impl TaskInner {
             exit_code: AtomicI32::new(0),
             wait_for_exit: WaitQueue::new(),
             kstack: None,
+            stack: 0..0,
             ctx: UnsafeCell::new(TaskContext::new()),
             #[cfg(feature = "tls")]
             tls: TlsArea::alloc(),
@@ -153,6 +156,7 @@ impl TaskInner {
 
         t.entry = Some(Box::into_raw(Box::new(entry)));
         t.ctx.get_mut().init(task_entry as usize, kstack.top(), tls);
+        t.stack = kstack.bottom().as_usize()..kstack.top().as_usize();
         t.kstack = Some(kstack);
         if t.name == "idle" {
             t.is_idle = true;
@@ -171,6 +175,7 @@ impl TaskInner {
     pub(crate) fn new_init(name: String) -> AxTaskRef {
         let mut t = Self::new_common(TaskId::new(), name);
         t.is_init = true;
+        t.stack = axhal::cpu::current_stack();
         if t.name == "idle" {
             t.is_idle = true;
         }
@@ -313,6 +318,10 @@ impl TaskStack {
         }
     }
 
+    pub fn bottom(&self) -> VirtAddr {
+        VirtAddr::from(self.ptr.as_ptr() as usize)
+    }
+
     pub const fn top(&self) -> VirtAddr {
         unsafe { core::mem::transmute(self.ptr.as_ptr().add(self.layout.size())) }
     }
@@ -363,9 +372,17 @@ impl CurrentTask {
         axhal::cpu::set_current_task_ptr(ptr);
     }
 
+    pub(crate) unsafe fn clear_current() {
+        if let Some(Self(arc)) = Self::try_get() {
+            ManuallyDrop::into_inner(arc);
+        }
+        axhal::cpu::set_current_task_ptr(core::ptr::null::<super::AxTask>());
+    }
+
     pub(crate) unsafe fn set_current(prev: Self, next: AxTaskRef) {
         let Self(arc) = prev;
         ManuallyDrop::into_inner(arc); // `call Arc::drop()` to decrease prev task reference count.
+        axhal::cpu::set_current_stack(next.stack.clone());
         let ptr = Arc::into_raw(next);
         axhal::cpu::set_current_task_ptr(ptr);
     }
@@ -1,7 +1,7 @@
 # Main building script
 
-include scripts/make/cargo.mk
 include scripts/make/features.mk
+include scripts/make/cargo.mk
 
 ifeq ($(APP_TYPE), c)
   include scripts/make/build_c.mk
@@ -11,6 +11,20 @@ else
   rust_elf := $(rust_target_dir)/$(rust_package)
 endif
 
+ifeq ($(BACKTRACE), y)
+  # Symbol table embedded into the kernel image (see `axhal::backtrace`)
+  ksyms := $(OUT_DIR)/$(APP_NAME)_$(PLATFORM_NAME).ksyms
+  export AX_KSYMS := $(abspath $(ksyms))
+endif
+
+# Extract function symbols of `$(OUT_ELF)` into `$(ksyms)`, which is only
+# updated if the symbols are changed, to avoid unnecessary rebuilds. The image
+# needs to be rebuilt once more after that to embed the symbol table.
+define gen_ksyms
+  @$(NM) -n --defined-only --demangle $(OUT_ELF) | sed -n 's/^\([0-9a-f]*\) [tTwW] \(.*\)$$/\1 \2/p' | sed 's/::h[0-9a-f]\{16\}$$//' > $(ksyms).tmp
+  @if cmp -s $(ksyms).tmp $(ksyms); then rm $(ksyms).tmp; else mv $(ksyms).tmp $(ksyms); fi
+endef
+
 ifneq ($(filter $(MAKECMDGOALS),doc doc_check_missing),)  # run `cargo doc`
   $(if $(V), $(info RUSTDOCFLAGS: "$(RUSTDOCFLAGS)"))
   export RUSTDOCFLAGS
@@ -36,6 +50,11 @@ _cargo_build:
 ifeq ($(APP_TYPE), rust)
 	$(call cargo_build,--manifest-path $(APP)/Cargo.toml,$(AX_FEAT) $(LIB_FEAT) $(APP_FEAT))
 	@cp $(rust_elf) $(OUT_ELF)
+  ifeq ($(BACKTRACE), y)
+	$(call gen_ksyms)
+	$(call cargo_build,--manifest-path $(APP)/Cargo.toml,$(AX_FEAT) $(LIB_FEAT) $(APP_FEAT))
+	@cp $(rust_elf) $(OUT_ELF)
+  endif
 else ifeq ($(APP_TYPE), c)
 	$(call cargo_build,-p axlibc,$(AX_FEAT) $(LIB_FEAT))
 endif
@@ -25,6 +25,10 @@ ifeq ($(MODE), release)
   CFLAGS += -O3
 endif
 
+ifeq ($(FRAME_POINTERS), y)
+  CFLAGS += -fno-omit-frame-pointer
+endif
+
 ifeq ($(ARCH), x86_64)
   LDFLAGS += --no-relax
 else ifeq ($(ARCH), riscv64)
@@ -71,6 +75,11 @@ $(APP)/%.o: $(APP)/%.c $(ulib_hdr)
 $(OUT_ELF): $(c_lib) $(rust_lib) $(libgcc) $(app-objs)
 	@printf "    $(CYAN_C)Linking$(END_C) $(OUT_ELF)\n"
 	$(call run_cmd,$(LD),$(LDFLAGS) $^ -o $@)
+ifeq ($(BACKTRACE), y)
+	$(call gen_ksyms)
+	$(call cargo_build,-p axlibc,$(AX_FEAT) $(LIB_FEAT))
+	$(call run_cmd,$(LD),$(LDFLAGS) $^ -o $@)
+endif
 
 $(APP)/axbuild.mk: ;
 
@@ -23,6 +23,10 @@ ifeq ($(ARCH), x86_64)
   RUSTFLAGS += -C link-arg=--no-relax
 endif
 
+ifeq ($(FRAME_POINTERS), y)
+  RUSTFLAGS += -C force-frame-pointers=yes
+endif
+
 ifeq ($(MAKECMDGOALS), doc_check_missing)
   RUSTDOCFLAGS += -D missing-docs
 endif
@@ -10,6 +10,8 @@
 #   - `AX_FEAT`: features to be enabled for ArceOS modules (crate `axfeat`).
 #   - `LIB_FEAT`: features to be enabled for the user library (crate `axstd`, `axlibc`).
 #   - `APP_FEAT`: features to be enabled for the Rust app.
+#   - `FRAME_POINTERS`: whether to build with frame pointers for stack
+#     unwinding: y, n
 
 ifeq ($(APP_TYPE),c)
   ax_feat_prefix := axfeat/
@@ -48,6 +50,17 @@ ifeq ($(BUS),pci)
   ax_feat += bus-pci
 endif
 
+ifeq ($(BACKTRACE),y)
+  ax_feat += backtrace
+endif
+
+# The profiler unwinds the sampled call stacks with frame pointers
+ifneq ($(filter y,$(BACKTRACE))$(filter profiler,$(FEATURES)),)
+  FRAME_POINTERS := y
+else
+  FRAME_POINTERS := n
+endif
+
 ifeq ($(shell test $(SMP) -gt 1; echo $$?),0)
   lib_feat += smp
 endif
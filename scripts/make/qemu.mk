@@ -28,7 +28,7 @@ qemu_args-y := -m 2G -smp $(SMP) $(qemu_args-$(ARCH))
 else
 qemu_args-aarch64 := \
   -cpu cortex-a72 \
-  -machine virt \
+  -machine virt,gic-version=$(GIC_VERSION) \
   -kernel $(OUT_BIN)
 qemu_args-y := -m 128M -smp $(SMP) $(qemu_args-$(ARCH))
 endif
@@ -63,6 +63,11 @@ ifeq ($(NET_DUMP), y)
   qemu_args-$(NET) += -object filter-dump,id=dump0,netdev=net0,file=netdump.pcap
 endif
 
+qemu_args-$(USB) += \
+  -device qemu-xhci,id=xhci \
+  -device usb-mouse,bus=xhci.0 \
+  -device usb-kbd,bus=xhci.0
+
 qemu_args-$(GRAPHIC) += \
   -device virtio-gpu-$(vdev-suffix) -vga none \
   -serial mon:stdio
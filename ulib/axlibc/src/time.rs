@@ -1,14 +1,23 @@
-use arceos_posix_api::{sys_clock_gettime, sys_nanosleep};
+use arceos_posix_api::{sys_clock_gettime, sys_clock_settime, sys_nanosleep};
 use core::ffi::c_int;
 
 use crate::{ctypes, utils::e};
 
-/// Get clock time since booting
+/// Get clock time
 #[no_mangle]
 pub unsafe extern "C" fn clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespec) -> c_int {
     e(sys_clock_gettime(clk, ts))
 }
 
+/// Set clock time
+#[no_mangle]
+pub unsafe extern "C" fn clock_settime(
+    clk: ctypes::clockid_t,
+    ts: *const ctypes::timespec,
+) -> c_int {
+    e(sys_clock_settime(clk, ts))
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno